@@ -0,0 +1,24 @@
+// Records the commit being built, for GET /api/admin/info, as GIT_HASH. Builds
+// outside a git checkout, such as in the Docker image, can set GIT_HASH
+// themselves, or go without.
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    if env::var_os("GIT_HASH").is_some() {
+        return;
+    }
+    let output = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output();
+    if let Ok(output) = output {
+        if output.status.success() {
+            let hash = String::from_utf8_lossy(&output.stdout);
+            println!("cargo:rustc-env=GIT_HASH={}", hash.trim());
+        }
+    }
+}
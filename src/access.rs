@@ -0,0 +1,370 @@
+// Who may use the admin API: admin keys and API tokens from the config, each
+// with a scope; keys have the admin scope. A read token can look at every
+// dataset's admin endpoints without changing anything, a write token can also
+// change one dataset, and an admin token can do anything, including adding and
+// removing datasets. The admin pages themselves stay open, as browsers can't
+// send a token to load them; the page asks for one and sends it with its calls.
+// Before any of that, the admin router can be limited to some IP addresses.
+//
+// The middleware in front of the admin router sorts requests by their path, so
+// every admin route, including those of datasets added later, is covered by the
+// same check. JWTs from an identity provider can stand in for tokens, when
+// `[jwt]` is set up; their roles give them a scope (see jwt.rs). With no keys,
+// tokens or provider configured, the admin API is closed. Everything else stays
+// open.
+
+use crate::concurrency::Limited;
+use crate::{accesslog, jwt, CONFIG};
+use axum::extract::connect_info::Connected;
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{header, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use axum::serve::IncomingStream;
+use std::fmt;
+use std::net::IpAddr;
+use tokio::net::{TcpListener, UnixListener};
+
+/// What a token allows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    Read,
+    // Reads, and changes to this dataset; `None` is the main dataset.
+    Write(Option<String>),
+    Admin,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    // Names the token's holder in the audit log, e.g. "uk-curator".
+    pub label: String,
+    pub secret: String,
+    pub scope: Scope,
+}
+
+// The part of the admin API a request is for.
+#[derive(Debug, PartialEq)]
+enum Target {
+    // One dataset's admin endpoints; `None` is the main dataset.
+    Dataset(Option<String>),
+    // Adding, renaming and removing datasets.
+    Datasets,
+    // The whole server: its build, state and configuration.
+    Server,
+}
+
+// Which admin target `path` is for; `None` for the rest of the API.
+fn target(path: &str) -> Option<Target> {
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["api", "admin", "datasets", ..] => Some(Target::Datasets),
+        ["api", "admin", "info"] => Some(Target::Server),
+        ["api", "admin", ..] => Some(Target::Dataset(None)),
+        ["api", name, "admin", ..] => Some(Target::Dataset(Some(name.to_string()))),
+        _ => None,
+    }
+}
+
+impl Scope {
+    // Whether a `method` request for `target` is allowed.
+    fn allows(&self, method: &Method, target: &Target) -> bool {
+        let reading = method == Method::GET || method == Method::HEAD;
+        match (self, target) {
+            (Scope::Admin, _) => true,
+            (Scope::Read, Target::Dataset(_)) => reading,
+            (Scope::Write(_), Target::Dataset(_)) if reading => true,
+            (Scope::Write(own), Target::Dataset(dataset)) => own == dataset,
+            (_, Target::Datasets | Target::Server) => false,
+        }
+    }
+}
+
+tokio::task_local! {
+    // The label of the key or token the request being handled was made with.
+    static ACTOR: Option<String>;
+}
+
+/// Who is making the request being handled, for the audit log: the label of
+/// their key or token, or `None` outside an admin request.
+pub fn actor() -> Option<String> {
+    ACTOR.try_with(Clone::clone).ok().flatten()
+}
+
+/// Middleware letting admin requests through only with a key or token whose
+/// scope allows them.
+pub async fn authorize(request: Request, next: Next) -> Response {
+    let Some(target) = target(request.uri().path()) else {
+        return next.run(request).await;
+    };
+    if CONFIG.tokens.is_empty() && CONFIG.jwt.is_none() {
+        return unauthorized(
+            "The admin API is closed: set admin.keys, BANDS_ADMIN_KEYS, [tokens.<label>] or [jwt] to open it.",
+        );
+    }
+
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let Some(presented) = presented else {
+        return unauthorized("This needs an API key: send Authorization: Bearer <key>.");
+    };
+    // Every token is compared, so the time taken doesn't tell which one matched.
+    let token = CONFIG.tokens.iter().fold(None, |found, token| {
+        let matches = same(token.secret.as_bytes(), presented.trim().as_bytes());
+        if matches {
+            Some(token)
+        } else {
+            found
+        }
+    });
+    let (label, scopes) = match (token, &CONFIG.jwt) {
+        (Some(token), _) => (token.label.clone(), vec![token.scope.clone()]),
+        (None, Some(jwt)) if jwt::looks_like_jwt(presented.trim()) => {
+            match jwt::authenticate(presented.trim(), jwt).await {
+                Ok(holder) => (holder.label, holder.scopes),
+                Err(reason) => {
+                    return unauthorized(&format!("The JWT isn't valid: {}.", reason));
+                }
+            }
+        }
+        (None, _) => return unauthorized("The API key isn't valid."),
+    };
+    if !scopes
+        .iter()
+        .any(|scope| scope.allows(request.method(), &target))
+    {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": format!("The token {} isn't allowed to do this.", label),
+            })),
+        )
+            .into_response();
+    }
+    let mut response = ACTOR.scope(Some(label.clone()), next.run(request)).await;
+    response.extensions_mut().insert(accesslog::Actor(label));
+    response
+}
+
+// --- IP Allowlist ---
+
+/// A range of IP addresses, such as 10.0.0.0/8; a single address is a range of
+/// one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl Cidr {
+    pub fn parse(text: &str) -> Result<Cidr, String> {
+        let invalid = || format!("\"{}\" isn't an IP address or range like 10.0.0.0/8", text);
+        let (address, prefix) = match text.split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix)),
+            None => (text, None),
+        };
+        let network: IpAddr = address.parse().map_err(|_| invalid())?;
+        let bits = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|p| *p <= bits)
+                .ok_or_else(invalid)?,
+            None => bits,
+        };
+        Ok(Cidr { network, prefix })
+    }
+
+    pub fn contains(&self, address: IpAddr) -> bool {
+        // Clients reaching an IPv6 socket over IPv4 show up as ::ffff:a.b.c.d.
+        match (self.network, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Who a connection is from: its IP address, or `None` over a Unix socket.
+#[derive(Debug, Clone, Copy)]
+pub struct Peer(pub Option<IpAddr>);
+
+impl Connected<IncomingStream<'_, Limited<TcpListener>>> for Peer {
+    fn connect_info(stream: IncomingStream<'_, Limited<TcpListener>>) -> Self {
+        Peer(Some(stream.remote_addr().ip()))
+    }
+}
+
+impl Connected<IncomingStream<'_, Limited<UnixListener>>> for Peer {
+    fn connect_info(_: IncomingStream<'_, Limited<UnixListener>>) -> Self {
+        Peer(None)
+    }
+}
+
+// Where a request comes from: the peer, unless that's a trusted proxy, in which
+// case the last address in X-Forwarded-For that isn't one. Only local processes
+// can connect over a Unix socket, so those peers count as trusted proxies. The
+// header is read from the right, as each proxy appends to it; an entry that
+// doesn't parse, or a header naming nothing but proxies, gives `None` rather
+// than the proxy's own address, which is likely to be allowed.
+fn client(peer: Option<IpAddr>, request: &Request, proxies: &[Cidr]) -> Option<IpAddr> {
+    let trusted = |address: &IpAddr| proxies.iter().any(|p| p.contains(*address));
+    if let Some(peer) = peer.filter(|peer| !trusted(peer)) {
+        return Some(peer);
+    }
+    let values: Vec<&str> = request
+        .headers()
+        .get_all("x-forwarded-for")
+        .iter()
+        .map(|value| value.to_str().ok())
+        .collect::<Option<_>>()?;
+    for entry in values.iter().rev().flat_map(|value| value.rsplit(',')) {
+        let address: IpAddr = entry.trim().parse().ok()?;
+        if !trusted(&address) {
+            return Some(address);
+        }
+    }
+    None
+}
+
+/// The address `request` comes from, looking past trusted proxies; `None` when
+/// that's not known, as over a Unix socket with no X-Forwarded-For, or when the
+/// header a trusted proxy sent can't be read.
+pub fn client_address(request: &Request) -> Option<IpAddr> {
+    request
+        .extensions()
+        .get::<ConnectInfo<Peer>>()
+        .and_then(|ConnectInfo(Peer(peer))| client(*peer, request, &CONFIG.trusted_proxies))
+}
+
+/// Middleware letting requests through only from the addresses the config
+/// allows, when it lists any.
+pub async fn allow_ips(request: Request, next: Next) -> Response {
+    if CONFIG.allowed_ips.is_empty() {
+        return next.run(request).await;
+    }
+    let allowed = client_address(&request).is_some_and(|client| {
+        CONFIG
+            .allowed_ips
+            .iter()
+            .any(|range| range.contains(client))
+    });
+    if !allowed {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": "The admin API can't be used from this address.",
+            })),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+fn unauthorized(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({ "error": message })),
+    )
+        .into_response()
+}
+
+/// Compares in time that depends only on the lengths, not on where they differ.
+pub fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_allow_their_own_datasets() {
+        assert_eq!(target("/api/search"), None);
+        assert_eq!(target("/api/searches/abc"), None);
+        assert_eq!(target("/admin"), None);
+        assert_eq!(target("/api/admin/audit"), Some(Target::Dataset(None)));
+        assert_eq!(target("/api/admin/datasets/uk"), Some(Target::Datasets));
+        assert_eq!(target("/api/admin/info"), Some(Target::Server));
+        let uk = Target::Dataset(Some("uk".to_string()));
+        assert_eq!(target("/api/uk/admin/reload"), Some(uk));
+        let uk = Target::Dataset(Some("uk".to_string()));
+
+        let write_uk = Scope::Write(Some("uk".to_string()));
+        assert!(write_uk.allows(&Method::POST, &uk));
+        assert!(write_uk.allows(&Method::GET, &Target::Dataset(None)));
+        assert!(!write_uk.allows(&Method::POST, &Target::Dataset(None)));
+        assert!(!write_uk.allows(&Method::POST, &Target::Datasets));
+        assert!(Scope::Read.allows(&Method::GET, &uk));
+        assert!(!Scope::Read.allows(&Method::DELETE, &uk));
+        assert!(Scope::Admin.allows(&Method::DELETE, &Target::Datasets));
+        assert!(!Scope::Read.allows(&Method::GET, &Target::Server));
+        assert!(Scope::Admin.allows(&Method::GET, &Target::Server));
+
+        assert!(same(b"secret", b"secret"));
+        assert!(!same(b"secret", b"secreT") && !same(b"secret", b"secrets"));
+    }
+
+    #[test]
+    fn ip_ranges_contain_their_addresses() {
+        let private = Cidr::parse("10.0.0.0/8").unwrap();
+        assert!(private.contains("10.1.2.3".parse().unwrap()));
+        assert!(private.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!private.contains("11.0.0.1".parse().unwrap()));
+        let one = Cidr::parse("192.168.1.5").unwrap();
+        assert!(one.contains("192.168.1.5".parse().unwrap()));
+        assert!(!one.contains("192.168.1.6".parse().unwrap()));
+        assert!(Cidr::parse("0.0.0.0/0")
+            .unwrap()
+            .contains("8.8.8.8".parse().unwrap()));
+        assert!(Cidr::parse("fd00::/8")
+            .unwrap()
+            .contains("fd12::1".parse().unwrap()));
+        assert!(!Cidr::parse("::1")
+            .unwrap()
+            .contains("127.0.0.1".parse().unwrap()));
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("localhost").is_err());
+    }
+
+    #[test]
+    fn forwarded_addresses_skip_trusted_proxies() {
+        let proxies = [Cidr::parse("10.0.0.0/8").unwrap()];
+        let proxy = "10.0.0.1".parse().ok();
+        let forwarded = |header: &str| {
+            let request = Request::builder()
+                .header("x-forwarded-for", header)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            client(proxy, &request, &proxies)
+        };
+        let client_ip = "203.0.113.7".parse().ok();
+        assert_eq!(forwarded("198.51.100.1, 203.0.113.7, 10.0.0.2"), client_ip);
+        // Anything left of an address a trusted proxy vouches for is the
+        // client's own claim, and ignored.
+        assert_eq!(forwarded("x, 203.0.113.7"), client_ip);
+        assert_eq!(forwarded("203.0.113.7, x"), None);
+        assert_eq!(forwarded("10.0.0.3"), None);
+        let direct = Request::builder()
+            .header("x-forwarded-for", "10.0.0.3")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(client(client_ip, &direct, &proxies), client_ip);
+    }
+}
@@ -0,0 +1,286 @@
+// The access log: one JSON object per request, appended to `access_log.file`,
+// apart from the server's own log, so it can answer questions like who
+// downloaded the dataset without a log collector. Each line has when the
+// request came in, the client's address, what it asked for, the status, how
+// many bytes the body had and how long it took, and for admin requests the
+// key or token used.
+//
+// The file is started afresh once it reaches `access_log.max_bytes`, and each
+// day if `access_log.daily` is on: the old one is renamed with the time it was
+// started afresh, as `access.log.20250601T000000Z`, and only the newest
+// `access_log.keep` of those are kept.
+
+use crate::requestid::RequestId;
+use crate::{access, clock, CONFIG};
+use axum::body::HttpBody;
+use axum::extract::{OriginalUri, Request};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+static WRITER: Mutex<Option<Writer>> = Mutex::new(None);
+
+/// The label of the key or token an admin request was made with, which the
+/// access check leaves on its response for this log.
+#[derive(Debug, Clone)]
+pub struct Actor(pub String);
+
+#[derive(Serialize)]
+struct Line<'a> {
+    at: String,
+    client: Option<String>,
+    method: &'a str,
+    path: &'a str,
+    status: u16,
+    // `None` for bodies streamed without a known length.
+    bytes: Option<u64>,
+    ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actor: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_agent: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    referer: Option<&'a str>,
+    request_id: Option<&'a str>,
+}
+
+/// Middleware writing a line to the access log for each request, when there
+/// is one.
+pub async fn record(request: Request, next: Next) -> Response {
+    if CONFIG.access_log.file.is_none() {
+        return next.run(request).await;
+    }
+    let started = SystemTime::now();
+    let timer = Instant::now();
+    let (method, path, client, user_agent, referer, request_id) = {
+        let header = |name| {
+            let value = request.headers().get(name)?.to_str().ok()?;
+            Some(value.to_string())
+        };
+        // Under `server.base_path`, the path as the client sent it, with the base.
+        let path = match request.extensions().get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.to_string(),
+            None => request.uri().to_string(),
+        };
+        (
+            request.method().to_string(),
+            path,
+            access::client_address(&request).map(|ip| ip.to_string()),
+            header(header::USER_AGENT),
+            header(header::REFERER),
+            request
+                .extensions()
+                .get::<RequestId>()
+                .map(|id| id.0.clone()),
+        )
+    };
+
+    let response = next.run(request).await;
+    let line = Line {
+        at: clock::rfc3339(started),
+        client,
+        method: &method,
+        path: &path,
+        status: response.status().as_u16(),
+        bytes: response.body().size_hint().exact(),
+        ms: timer.elapsed().as_millis() as u64,
+        actor: response.extensions().get::<Actor>().map(|a| a.0.as_str()),
+        user_agent: user_agent.as_deref(),
+        referer: referer.as_deref(),
+        request_id: request_id.as_deref(),
+    };
+    let line = serde_json::to_string(&line).expect("access log lines serialize");
+    write(&line);
+    response
+}
+
+fn write(line: &str) {
+    let Some(path) = &CONFIG.access_log.file else {
+        return;
+    };
+    let mut writer = WRITER.lock().unwrap();
+    // A reload can point the log somewhere else.
+    if writer.as_ref().is_none_or(|w| &w.path != path) {
+        *writer = Some(Writer::new(path.clone()));
+    }
+    let writer = writer.as_mut().expect("set just above");
+    let settings = &CONFIG.access_log;
+    if let Err(e) = writer.write(
+        line,
+        settings.max_bytes,
+        settings.daily,
+        settings.keep,
+        SystemTime::now(),
+    ) {
+        tracing::warn!(
+            "->> Could not write to the access log {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+// The log file being written, opened when it's first needed.
+struct Writer {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+    // The day the file was started on, for daily rotation.
+    day: (i64, i64, i64),
+}
+
+impl Writer {
+    fn new(path: PathBuf) -> Writer {
+        Writer {
+            path,
+            file: None,
+            size: 0,
+            day: (0, 0, 0),
+        }
+    }
+
+    // Appends `line`, first starting the file afresh if it's full or from an
+    // earlier day. A `max_bytes` of 0 never rotates by size.
+    fn write(
+        &mut self,
+        line: &str,
+        max_bytes: u64,
+        daily: bool,
+        keep: usize,
+        now: SystemTime,
+    ) -> std::io::Result<()> {
+        if self.file.is_none() {
+            self.open(now)?;
+        }
+        let length = line.len() as u64 + 1;
+        let full = max_bytes > 0 && self.size > 0 && self.size + length > max_bytes;
+        if full || (daily && day(now) != self.day) {
+            self.rotate(keep, now)?;
+        }
+        let file = self.file.as_mut().expect("opened above");
+        writeln!(file, "{}", line)?;
+        self.size += length;
+        Ok(())
+    }
+
+    fn open(&mut self, now: SystemTime) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let metadata = file.metadata()?;
+        self.size = metadata.len();
+        // A file kept from before a restart counts from when it was last written.
+        self.day = day(metadata.modified().unwrap_or(now));
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn rotate(&mut self, keep: usize, now: SystemTime) -> std::io::Result<()> {
+        self.file = None;
+        let stamped = format!("{}.{}", self.path.display(), clock::basic(now));
+        // Files started afresh within the same second are numbered after the first.
+        let rotated = (1..)
+            .map(|n| match n {
+                1 => PathBuf::from(&stamped),
+                n => PathBuf::from(format!("{}.{}", stamped, n)),
+            })
+            .find(|path| !path.exists())
+            .expect("some number is free");
+        fs::rename(&self.path, &rotated)?;
+        prune(&self.path, keep);
+        self.open(now)?;
+        // A new file is from today, whatever the clock on the file says.
+        self.day = day(now);
+        Ok(())
+    }
+}
+
+fn day(time: SystemTime) -> (i64, i64, i64) {
+    let (year, month, day, ..) = clock::utc(time);
+    (year, month, day)
+}
+
+// Removes the oldest files rotated from `path` beyond the newest `keep`, going
+// by the time and number at the end of their names.
+fn prune(path: &Path, keep: usize) {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut rotated: Vec<(String, u32, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let stamp = name.strip_prefix(&prefix)?;
+            let (time, number) = stamp.split_once('.').unwrap_or((stamp, "1"));
+            let number = number.parse().ok()?;
+            (time.len() == 16 && time.ends_with('Z'))
+                .then(|| (time.to_string(), number, entry.path()))
+        })
+        .collect();
+    rotated.sort();
+    let excess = rotated.len().saturating_sub(keep);
+    for (_, _, old) in &rotated[..excess] {
+        if let Err(e) = fs::remove_file(old) {
+            tracing::warn!(
+                "->> Could not remove the old access log {}: {}",
+                old.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn files_rotate_by_size_and_day_keeping_the_newest() {
+        let dir = std::env::temp_dir().join(format!("bands-access-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("access.log");
+        let mut writer = Writer::new(path.clone());
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_748_736_000);
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+
+        // Two lines of 10 bytes fit in 20; the third starts a new file.
+        for i in 0..3 {
+            writer.write("123456789", 20, false, 5, at(i)).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "123456789\n");
+        let rotated = dir.join("access.log.20250601T000002Z");
+        assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 2);
+
+        // The next day starts one too, and only the newest is kept.
+        // Within the same second, the next one gets a number.
+        writer.write("123456789", 20, false, 5, at(2)).unwrap();
+        writer.write("123456789", 20, false, 5, at(2)).unwrap();
+        let numbered = dir.join("access.log.20250601T000002Z.2");
+        assert_eq!(fs::read_to_string(&numbered).unwrap().lines().count(), 2);
+        assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 2);
+
+        writer.write("123456789", 0, true, 1, at(86_400)).unwrap();
+        assert!(!rotated.exists() && !numbered.exists());
+        assert!(dir.join("access.log.20250602T000000Z").exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+// The admin page at `/admin` (and `/admin/<name>` for other datasets), for
+// curators who'd rather not use curl: forms to find, correct, remove and add
+// performances, upload a new dataset, and undo changes or go back to a
+// snapshot. It's rendered on the server with what it needs to start from, and
+// the forms call the admin API from the browser.
+
+use crate::snapshot::SnapshotInfo;
+use crate::state::AppState;
+
+/// The admin page for the data in `state`, listing `snapshots`, with forms that
+/// call the API at `api`, such as "/api".
+pub fn page(state: &AppState, snapshots: &[SnapshotInfo], api: &str) -> String {
+    let info = state.dataset_info();
+    let summary = format!(
+        "{} festivals, {} editions, {} performances by {} artists",
+        info.festivals, info.editions, info.performances, info.artists
+    );
+    let editions: String = state
+        .festivals
+        .iter()
+        .flat_map(|festival| {
+            festival.editions.iter().map(move |edition| {
+                format!(
+                    r#"<option value="{year}" data-festival="{festival}">{festival} {year}</option>"#,
+                    festival = escape(&festival.name),
+                    year = edition.year,
+                )
+            })
+        })
+        .collect();
+    let snapshots: String = if snapshots.is_empty() {
+        r#"<li class="text-gray-400">No snapshots yet.</li>"#.to_string()
+    } else {
+        snapshots
+            .iter()
+            .map(|snapshot| {
+                format!(
+                    r#"<li class="flex items-center justify-between gap-4"><span>{name} <span class="text-gray-400">({taken_at}, {kib} KiB)</span></span><button type="button" data-snapshot="{name}" class="restore bg-gray-700 hover:bg-gray-600 rounded-md py-1 px-3">Restore</button></li>"#,
+                    name = escape(&snapshot.name),
+                    taken_at = escape(&snapshot.taken_at),
+                    kib = snapshot.bytes.div_ceil(1024),
+                )
+            })
+            .collect()
+    };
+
+    ADMIN_PAGE
+        .replace("{{source}}", &escape(info.source.unwrap_or("no data")))
+        .replace("{{loaded_at}}", &escape(&info.loaded_at))
+        .replace("{{content_hash}}", &info.content_hash)
+        .replace("{{summary}}", &summary)
+        .replace("{{editions}}", &editions)
+        .replace("{{snapshots}}", &snapshots)
+        // Inside a script, where entities aren't decoded; dataset names need no escaping.
+        .replace("{{api}}", api)
+}
+
+// Makes `text` safe to put in HTML, inside elements or quoted attributes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const ADMIN_PAGE: &str = r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Festival Artist Explorer - Admin</title>
+    <script src="https://cdn.tailwindcss.com"></script>
+</head>
+<body class="bg-gray-900 text-gray-200 font-sans">
+    <div class="container mx-auto p-4 md:p-8 max-w-4xl">
+
+        <header class="mb-8">
+            <h1 class="text-3xl md:text-4xl font-bold text-white mb-2">Dataset admin</h1>
+            <p class="text-gray-400">Serving <span class="text-gray-200">{{source}}</span>, loaded {{loaded_at}}: {{summary}}. <span class="font-mono text-sm">{{content_hash}}</span></p>
+            <p class="text-gray-400 mt-2">Changes made here are served straight away. They're written to the data source only when it's a database; otherwise take a snapshot to keep them.</p>
+            <label class="block mt-4 text-sm text-gray-400">Admin key or API token
+                <input type="password" id="token-input" autocomplete="off" class="mt-1 w-full bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+            </label>
+        </header>
+
+        <div id="status" class="hidden mb-8 rounded-md p-4"></div>
+
+        <main class="space-y-12">
+
+            <!-- Edit Section -->
+            <section id="edit">
+                <h2 class="text-2xl font-semibold mb-4 text-teal-400 border-b-2 border-gray-700 pb-2">Find a performance</h2>
+                <div class="bg-gray-800 rounded-xl shadow-lg p-6">
+                    <form id="find-form" class="flex gap-4">
+                        <input type="text" id="find-input" placeholder="Artist name..." class="flex-grow bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+                        <button type="submit" class="bg-teal-600 hover:bg-teal-700 text-white font-bold py-2 px-6 rounded-md">Find</button>
+                    </form>
+                    <ul id="find-results" class="mt-4 space-y-2"></ul>
+                </div>
+            </section>
+
+            <!-- Add Section -->
+            <section id="add">
+                <h2 class="text-2xl font-semibold mb-4 text-purple-400 border-b-2 border-gray-700 pb-2">Add a performance</h2>
+                <form id="add-form" class="bg-gray-800 rounded-xl shadow-lg p-6 grid grid-cols-1 sm:grid-cols-2 gap-4">
+                    <label class="flex flex-col">Edition
+                        <select id="add-edition" class="bg-gray-700 border border-gray-600 text-white rounded-md p-2">{{editions}}</select>
+                    </label>
+                    <label class="flex flex-col">Artist
+                        <input type="text" id="add-name" required class="bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+                    </label>
+                    <label class="flex flex-col">Genres (separated by commas)
+                        <input type="text" id="add-genres" class="bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+                    </label>
+                    <label class="flex flex-col">Country (two letters, such as NL)
+                        <input type="text" id="add-country" maxlength="2" class="bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+                    </label>
+                    <label class="flex flex-col">Stage
+                        <input type="text" id="add-stage" class="bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+                    </label>
+                    <label class="flex flex-col">Day
+                        <input type="text" id="add-day" placeholder="saturday" class="bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+                    </label>
+                    <label class="flex flex-col">Start time
+                        <input type="text" id="add-start-time" placeholder="21:30" class="bg-gray-700 border border-gray-600 text-white rounded-md p-2">
+                    </label>
+                    <label class="flex items-center gap-2 sm:mt-6">
+                        <input type="checkbox" id="add-headliner"> Headliner
+                    </label>
+                    <button type="submit" class="sm:col-span-2 bg-purple-600 hover:bg-purple-700 text-white font-bold py-2 px-6 rounded-md">Add</button>
+                </form>
+            </section>
+
+            <!-- Upload Section -->
+            <section id="upload">
+                <h2 class="text-2xl font-semibold mb-4 text-amber-400 border-b-2 border-gray-700 pb-2">Upload a dataset</h2>
+                <div class="bg-gray-800 rounded-xl shadow-lg p-6 space-y-4">
+                    <p class="text-gray-400">A JSON, CSV, YAML or TOML data file. Check it and compare it with the data being served before publishing it in its place.</p>
+                    <input type="file" id="upload-file" accept=".json,.csv,.yaml,.yml,.toml">
+                    <div class="flex flex-wrap gap-4">
+                        <button type="button" id="validate-btn" class="bg-gray-700 hover:bg-gray-600 py-2 px-6 rounded-md">Check</button>
+                        <button type="button" id="diff-btn" class="bg-gray-700 hover:bg-gray-600 py-2 px-6 rounded-md">Compare</button>
+                        <button type="button" id="publish-btn" class="bg-amber-600 hover:bg-amber-700 text-white font-bold py-2 px-6 rounded-md">Publish</button>
+                    </div>
+                    <pre id="upload-result" class="hidden bg-gray-900 rounded-md p-4 text-sm overflow-x-auto"></pre>
+                </div>
+            </section>
+
+            <!-- History Section -->
+            <section id="history">
+                <h2 class="text-2xl font-semibold mb-4 text-rose-400 border-b-2 border-gray-700 pb-2">Snapshots and undo</h2>
+                <div class="bg-gray-800 rounded-xl shadow-lg p-6 space-y-4">
+                    <div class="flex flex-wrap gap-4">
+                        <button type="button" id="snapshot-btn" class="bg-rose-600 hover:bg-rose-700 text-white font-bold py-2 px-6 rounded-md">Take a snapshot</button>
+                        <button type="button" id="undo-btn" class="bg-gray-700 hover:bg-gray-600 py-2 px-6 rounded-md">Undo the last change</button>
+                    </div>
+                    <ul id="snapshot-list" class="space-y-2">{{snapshots}}</ul>
+                </div>
+            </section>
+        </main>
+    </div>
+
+    <script>
+        // Where this dataset's API is: /api for the main dataset, /api/<name> for others.
+        const api = '{{api}}';
+        const statusBox = document.getElementById('status');
+
+        function showStatus(message, ok) {
+            statusBox.textContent = message;
+            statusBox.className = 'mb-8 rounded-md p-4 ' + (ok ? 'bg-green-900 text-green-100' : 'bg-red-900 text-red-100');
+            window.scrollTo({ top: 0, behavior: 'smooth' });
+        }
+
+        // The token is kept for the browser tab, so it survives the page reloading itself.
+        const tokenInput = document.getElementById('token-input');
+        tokenInput.value = sessionStorage.getItem('admin-token') || '';
+        tokenInput.addEventListener('change', () => sessionStorage.setItem('admin-token', tokenInput.value.trim()));
+
+        // Calls the admin API, returning the parsed body; failures throw their error message.
+        async function call(method, url, body, contentType) {
+            const options = { method, headers: {} };
+            const token = tokenInput.value.trim();
+            if (token) {
+                options.headers['Authorization'] = `Bearer ${token}`;
+            }
+            if (body !== undefined) {
+                options.body = contentType ? body : JSON.stringify(body);
+                options.headers['Content-Type'] = contentType || 'application/json';
+            }
+            const response = await fetch(url, options);
+            const text = await response.text();
+            const parsed = text ? JSON.parse(text) : null;
+            if (!response.ok) {
+                throw new Error((parsed && parsed.error) || `${response.status} ${response.statusText}`);
+            }
+            return parsed;
+        }
+
+        // Changes that replace the data are shown by reloading the page with a message.
+        function reloadWith(message) {
+            sessionStorage.setItem('admin-status', message);
+            window.location.reload();
+        }
+        const pending = sessionStorage.getItem('admin-status');
+        if (pending) {
+            sessionStorage.removeItem('admin-status');
+            showStatus(pending, true);
+        }
+
+        // --- Find, correct and remove ---
+        const findResults = document.getElementById('find-results');
+
+        function renderPerformance(performance) {
+            const item = document.createElement('li');
+            item.className = 'flex flex-wrap items-center gap-2';
+            const name = document.createElement('input');
+            name.value = performance.name;
+            name.className = 'flex-grow bg-gray-700 border border-gray-600 text-white rounded-md p-1';
+            const year = document.createElement('input');
+            year.type = 'number';
+            year.value = performance.year;
+            year.className = 'w-24 bg-gray-700 border border-gray-600 text-white rounded-md p-1';
+            const festival = document.createElement('span');
+            festival.textContent = performance.festival;
+            festival.className = 'text-gray-400';
+            const save = document.createElement('button');
+            save.textContent = 'Save';
+            save.className = 'bg-teal-600 hover:bg-teal-700 rounded-md py-1 px-3';
+            save.addEventListener('click', async () => {
+                try {
+                    const corrected = await call('PATCH', `${api}/admin/performances/${performance.id}`, {
+                        name: name.value,
+                        year: Number(year.value),
+                    });
+                    item.replaceWith(renderPerformance(corrected || performance));
+                    showStatus(`Saved ${name.value} at ${performance.festival} ${year.value}.`, true);
+                } catch (error) {
+                    showStatus(error.message, false);
+                }
+            });
+            const remove = document.createElement('button');
+            remove.textContent = 'Remove';
+            remove.className = 'bg-red-700 hover:bg-red-800 rounded-md py-1 px-3';
+            remove.addEventListener('click', async () => {
+                if (!confirm(`Remove ${performance.name} from ${performance.festival} ${performance.year}?`)) return;
+                try {
+                    await call('DELETE', `${api}/admin/performances/${performance.id}`);
+                    item.remove();
+                    showStatus(`Removed ${performance.name} from ${performance.festival} ${performance.year}.`, true);
+                } catch (error) {
+                    showStatus(error.message, false);
+                }
+            });
+            item.append(name, festival, year, save, remove);
+            return item;
+        }
+
+        document.getElementById('find-form').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            const query = document.getElementById('find-input').value.trim();
+            if (!query) return;
+            try {
+                const found = await call('GET', `${api}/search?q=${encodeURIComponent(query)}`);
+                findResults.replaceChildren(...found.results.map(renderPerformance));
+                if (found.results.length === 0) {
+                    findResults.textContent = 'No performances found.';
+                }
+            } catch (error) {
+                showStatus(error.message, false);
+            }
+        });
+
+        // --- Add ---
+        document.getElementById('add-form').addEventListener('submit', async (e) => {
+            e.preventDefault();
+            const edition = document.getElementById('add-edition').selectedOptions[0];
+            if (!edition) return showStatus('There are no editions to add to.', false);
+            const text = (id) => document.getElementById(id).value.trim() || undefined;
+            const addition = {
+                festival: edition.dataset.festival,
+                year: Number(edition.value),
+                name: document.getElementById('add-name').value,
+                genres: (text('add-genres') || '').split(',').map((g) => g.trim()).filter((g) => g),
+                country: text('add-country'),
+                stage: text('add-stage'),
+                day: text('add-day'),
+                start_time: text('add-start-time'),
+                headliner: document.getElementById('add-headliner').checked,
+            };
+            try {
+                const added = await call('POST', `${api}/admin/performances`, addition);
+                e.target.reset();
+                showStatus(`Added ${added.name} to ${added.festival} ${added.year}.`, true);
+            } catch (error) {
+                showStatus(error.message, false);
+            }
+        });
+
+        // --- Upload ---
+        const uploadResult = document.getElementById('upload-result');
+        const contentTypes = { json: 'application/json', csv: 'text/csv', yaml: 'application/yaml', yml: 'application/yaml', toml: 'application/toml' };
+
+        async function sendUpload(method, url) {
+            const file = document.getElementById('upload-file').files[0];
+            if (!file) throw new Error('Choose a file to upload first.');
+            const extension = file.name.split('.').pop().toLowerCase();
+            const contentType = contentTypes[extension];
+            if (!contentType) throw new Error('Upload a .json, .csv, .yaml or .toml file.');
+            return call(method, url, file, contentType);
+        }
+
+        function showResult(result) {
+            uploadResult.textContent = JSON.stringify(result, null, 2);
+            uploadResult.classList.remove('hidden');
+        }
+
+        document.getElementById('validate-btn').addEventListener('click', async () => {
+            try {
+                const result = await sendUpload('POST', `${api}/admin/dataset/validate`);
+                showResult(result);
+                showStatus(result.accepted ? 'The file can be published.' : 'The file would be refused; see the problems below.', result.accepted);
+            } catch (error) {
+                showStatus(error.message, false);
+            }
+        });
+        document.getElementById('diff-btn').addEventListener('click', async () => {
+            try {
+                showResult(await sendUpload('POST', `${api}/admin/dataset/diff`));
+            } catch (error) {
+                showStatus(error.message, false);
+            }
+        });
+        document.getElementById('publish-btn').addEventListener('click', async () => {
+            if (!confirm('Serve this file in place of the current data?')) return;
+            try {
+                await sendUpload('PUT', `${api}/admin/dataset`);
+                reloadWith('The uploaded file is now being served.');
+            } catch (error) {
+                showStatus(error.message, false);
+            }
+        });
+
+        // --- Snapshots and undo ---
+        document.getElementById('snapshot-btn').addEventListener('click', async () => {
+            try {
+                const snapshot = await call('POST', `${api}/admin/snapshot`);
+                reloadWith(`Saved snapshot ${snapshot.name}.`);
+            } catch (error) {
+                showStatus(error.message, false);
+            }
+        });
+        document.getElementById('undo-btn').addEventListener('click', async () => {
+            if (!confirm('Serve the data as it was before the last change?')) return;
+            try {
+                await call('POST', `${api}/admin/rollback`, { steps: 1 });
+                reloadWith('The last change was undone.');
+            } catch (error) {
+                showStatus(error.message, false);
+            }
+        });
+        document.querySelectorAll('.restore').forEach((button) => {
+            button.addEventListener('click', async () => {
+                const name = button.dataset.snapshot;
+                if (!confirm(`Serve snapshot ${name} in place of the current data?`)) return;
+                try {
+                    await call('POST', `${api}/admin/snapshots/${encodeURIComponent(name)}/restore`);
+                    reloadWith(`Restored snapshot ${name}.`);
+                } catch (error) {
+                    showStatus(error.message, false);
+                }
+            });
+        });
+    </script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, BandData, Festival, FestivalYear};
+    use crate::schema;
+
+    #[test]
+    fn renders_the_data_escaped() {
+        let data = BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![Festival {
+                name: "Rock <Werchter>".to_string(),
+                years: vec![FestivalYear {
+                    year: 2008,
+                    artists: vec![Act::named("Muse".to_string())],
+                    ..FestivalYear::default()
+                }],
+                ..Festival::default()
+            }],
+        };
+        let snapshot = SnapshotInfo {
+            name: "bands-20240301T120000Z.json".to_string(),
+            taken_at: "2024-03-01T12:00:00Z".to_string(),
+            bytes: 2000,
+        };
+        let page = page(&AppState::new(data, 0), &[snapshot], "/api/uk-festivals");
+        assert!(page.contains(
+            r#"<option value="2008" data-festival="Rock &lt;Werchter&gt;">Rock &lt;Werchter&gt; 2008</option>"#
+        ));
+        assert!(page.contains("1 festivals, 1 editions, 1 performances by 1 artists"));
+        assert!(page.contains(r#"data-snapshot="bands-20240301T120000Z.json""#));
+        assert!(page.contains("const api = '/api/uk-festivals';"));
+        assert!(!page.contains("{{"));
+        assert_eq!(escape(r#"AC/DC & "Guns""#), "AC/DC &amp; &quot;Guns&quot;");
+    }
+}
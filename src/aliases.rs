@@ -0,0 +1,156 @@
+// Canonical names for artists the data spells in more than one way, from an
+// optional aliases file listing each canonical name with its variants:
+//
+//     { "Florence + the Machine": ["Florence and the Machine", "Florence & The Machine"] }
+//
+// Names are matched by their search key, so a variant also covers its other
+// capitalisations and accents, and so does the canonical name itself. Every
+// performance of a variant is renamed while loading, before the data is checked
+// and indexed, so search, browsing and statistics all see a single artist.
+
+use crate::data::{BandData, RawFile};
+use crate::nfc::nfc;
+use crate::search;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub struct Aliases {
+    // Search key of every variant (and canonical name) to the canonical name.
+    canonical: HashMap<String, String>,
+}
+
+/// Reads the aliases file at `path`, if one is configured.
+pub fn read(path: Option<&Path>) -> Result<Option<RawFile>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(Some(RawFile {
+        origin: path.display().to_string(),
+        bytes,
+    }))
+}
+
+impl Aliases {
+    /// Parses what `read` returned; no file means no aliases.
+    pub fn parse(file: Option<&RawFile>) -> Result<Self, String> {
+        let Some(file) = file else {
+            return Ok(Aliases::default());
+        };
+        let entries: Vec<(String, Vec<String>)> =
+            serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(&file.bytes)
+                .and_then(|map| {
+                    map.into_iter()
+                        .map(|(name, variants)| Ok((name, serde_json::from_value(variants)?)))
+                        .collect()
+                })
+                .map_err(|e| format!("Failed to parse {}: {}", file.origin, e))?;
+
+        let mut aliases = Aliases::default();
+        for (name, variants) in entries {
+            let name = nfc(&name.split_whitespace().collect::<Vec<_>>().join(" ")).into_owned();
+            if name.is_empty() {
+                return Err(format!("{}: a canonical name is empty", file.origin));
+            }
+            for variant in std::iter::once(&name).chain(&variants) {
+                let key = search::normalize(variant);
+                if key.is_empty() {
+                    return Err(format!(
+                        "{}: \"{}\" has an empty variant",
+                        file.origin, name
+                    ));
+                }
+                match aliases.canonical.get(&key) {
+                    Some(other) if *other != name => {
+                        return Err(format!(
+                            "{}: \"{}\" is listed for both \"{}\" and \"{}\"",
+                            file.origin, variant, other, name
+                        ));
+                    }
+                    _ => {
+                        aliases.canonical.insert(key, name.clone());
+                    }
+                }
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// Replaces every variant in `data` with its canonical name.
+    pub fn apply(&self, data: &mut BandData) {
+        if self.canonical.is_empty() {
+            return;
+        }
+        let artists = data
+            .festivals
+            .iter_mut()
+            .flat_map(|festival| &mut festival.years)
+            .flat_map(|edition| &mut edition.artists)
+            .map(|act| &mut act.name);
+        for artist in artists {
+            if let Some(name) = self.canonical.get(&search::normalize(artist)) {
+                if artist != name {
+                    artist.clone_from(name);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, Festival, FestivalYear};
+    use crate::schema;
+
+    fn aliases(json: &str) -> Result<Aliases, String> {
+        Aliases::parse(Some(&RawFile {
+            origin: "aliases.json".to_string(),
+            bytes: json.as_bytes().to_vec(),
+        }))
+    }
+
+    #[test]
+    fn renames_variants_to_the_canonical_name() {
+        let aliases =
+            aliases(r#"{ "Florence + the Machine": ["Florence and the Machine"] }"#).unwrap();
+        let mut data = BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![Festival {
+                name: "Pinkpop".to_string(),
+                years: vec![FestivalYear {
+                    year: 2010,
+                    artists: ["florence AND the machine", "Florence + The Machine", "Muse"]
+                        .map(|name| Act::named(name.to_string()))
+                        .to_vec(),
+                    ..FestivalYear::default()
+                }],
+                ..Festival::default()
+            }],
+        };
+
+        aliases.apply(&mut data);
+        let names: Vec<&str> = data.festivals[0].years[0]
+            .artists
+            .iter()
+            .map(|act| act.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["Florence + the Machine", "Florence + the Machine", "Muse"]
+        );
+    }
+
+    #[test]
+    fn rejects_a_variant_listed_twice() {
+        let error =
+            aliases(r#"{ "Florence + the Machine": ["florence"], "Florence": [] }"#).unwrap_err();
+        assert_eq!(
+            error,
+            "aliases.json: \"florence\" is listed for both \"Florence\" and \"Florence + the Machine\""
+        );
+        assert!(aliases(r#"{ "dEUS": "Deus" }"#).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+// The distinct artists of a snapshot: every normalized name once, in key
+// order, with how it's displayed, a slug for URLs and where its performances
+// are. It's built once when the data loads, and everything that lists or counts
+// artists rather than performances goes by it: the search index, autocomplete,
+// browsing, the statistics, the quality report and random picks of distinct
+// artists.
+
+use crate::layout::Performances;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// One distinct artist, going by the search key.
+#[derive(Debug)]
+pub struct Artist {
+    // The normalized name everything matches against.
+    pub key: String,
+    // How the first performance under the key spells the name, shared with it.
+    pub name: Arc<str>,
+    // The key with dashes for spaces, as in "kings-of-leon".
+    pub slug: String,
+    // Positions in the list of performances, in order.
+    pub performances: Vec<u32>,
+}
+
+impl Artist {
+    /// How many performances the artist has in the data.
+    pub fn appearances(&self) -> usize {
+        self.performances.len()
+    }
+}
+
+/// Every distinct artist of a snapshot, sorted by key; the ids the search index
+/// and the rest use are positions in it.
+#[derive(Debug, Default)]
+pub struct Artists {
+    list: Vec<Artist>,
+    // The artist of each performance, by its position.
+    of: Vec<u32>,
+}
+
+impl Artists {
+    pub fn new(performances: &Performances) -> Self {
+        let mut by_key: BTreeMap<&str, Artist> = BTreeMap::new();
+        for performance in performances.iter() {
+            let key = performance.search_key();
+            by_key
+                .entry(key)
+                .or_insert_with(|| Artist {
+                    key: key.to_string(),
+                    name: performance.shared_name(),
+                    slug: key.replace(' ', "-"),
+                    performances: Vec::new(),
+                })
+                .performances
+                .push(performance.position());
+        }
+        let list: Vec<Artist> = by_key.into_values().collect();
+        let mut of = vec![0; performances.len()];
+        for (id, artist) in list.iter().enumerate() {
+            for &position in &artist.performances {
+                of[position as usize] = id as u32;
+            }
+        }
+        Artists { list, of }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn get(&self, id: u32) -> &Artist {
+        &self.list[id as usize]
+    }
+
+    /// The artists in key order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Artist> {
+        self.list.iter()
+    }
+
+    /// The artists in key order, to binary search.
+    pub fn as_slice(&self) -> &[Artist] {
+        &self.list
+    }
+
+    /// The id of the artist of the performance at `position`.
+    pub fn of(&self, position: u32) -> u32 {
+        self.of[position as usize]
+    }
+
+    /// About how many bytes the list takes on the heap, with the names counted
+    /// with the performances they're shared with.
+    pub fn bytes(&self) -> usize {
+        let artists: usize = self
+            .list
+            .iter()
+            .map(|a| {
+                size_of::<Artist>()
+                    + a.key.capacity()
+                    + a.slug.capacity()
+                    + a.performances.capacity() * 4
+            })
+            .sum();
+        artists + self.of.capacity() * 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{Act, BandData, Festival, FestivalYear};
+    use crate::state::AppState;
+
+    #[test]
+    fn artists_are_listed_once_with_their_performances() {
+        let edition = |year: u16, names: &[&str]| FestivalYear {
+            year,
+            artists: names
+                .iter()
+                .map(|name| Act::named(name.to_string()))
+                .collect(),
+            ..FestivalYear::default()
+        };
+        let data = BandData {
+            schema_version: crate::schema::CURRENT,
+            festivals: vec![Festival {
+                name: "Pinkpop".to_string(),
+                years: vec![
+                    edition(2008, &["Kings of Leon", "Editors"]),
+                    edition(2011, &["Kings Of Leon"]),
+                ],
+                ..Festival::default()
+            }],
+        };
+        let state = AppState::new(data, 0);
+        let artists = &state.artists;
+        let listed: Vec<(&str, &str, usize)> = artists
+            .iter()
+            .map(|a| (&*a.name, a.slug.as_str(), a.appearances()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("Editors", "editors", 1),
+                ("Kings of Leon", "kings-of-leon", 2)
+            ]
+        );
+        assert_eq!(artists.get(artists.of(2)).key, "kings of leon");
+        assert_eq!(artists.of(1), 0);
+    }
+}
@@ -0,0 +1,140 @@
+// The audit log: a record of every change made through the admin API, with
+// who made it, when, and what it changed. Entries are appended to a file as
+// one JSON object per line and never rewritten, so the history survives
+// restarts and can be read with any tool that reads JSON lines. Every dataset
+// writes to the same file, tagging its entries with its name.
+
+use crate::{access, clock};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Entry {
+    // RFC 3339, in UTC.
+    pub at: String,
+    // The dataset changed; `None` for the main one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset: Option<String>,
+    // The label of the API key or token the change was made with; `None` in
+    // entries from before the admin API needed one.
+    pub actor: Option<String>,
+    // What kind of change it was, such as "upload" or "delete".
+    pub action: String,
+    // The content hash of the dataset served before and after, as /api/dataset
+    // gives it; the same for changes that leave the data alone.
+    pub dataset_before: String,
+    pub dataset_after: String,
+    // What changed, in a form that depends on the action.
+    pub details: Value,
+}
+
+impl Entry {
+    /// An entry for a change made now, by whoever is making the request being
+    /// handled.
+    pub fn new(action: &str, (before, after): (u64, u64), details: Value) -> Self {
+        Entry {
+            at: clock::rfc3339(SystemTime::now()),
+            dataset: None,
+            actor: access::actor(),
+            action: action.to_string(),
+            dataset_before: format!("{:016x}", before),
+            dataset_after: format!("{:016x}", after),
+            details,
+        }
+    }
+}
+
+// Keeps lines written at the same time, by any dataset's log, from interleaving.
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// One dataset's view of the audit log.
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+    path: PathBuf,
+    dataset: Option<String>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, dataset: Option<String>) -> Self {
+        AuditLog { path, dataset }
+    }
+
+    /// Appends `entry` to the log, as a change to this log's dataset. The change
+    /// it describes has been made by then, so a failure is logged rather than
+    /// undoing it.
+    pub fn record(&self, mut entry: Entry) {
+        entry.dataset.clone_from(&self.dataset);
+        let _guard = WRITING.lock().unwrap();
+        let line = serde_json::to_string(&entry).expect("entries serialize");
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        match written {
+            Ok(()) => tracing::info!("->> Audit: {} by {}.", entry.action, actor(&entry)),
+            Err(e) => tracing::warn!(
+                "->> Could not write to the audit log {}: {} (the entry was: {})",
+                self.path.display(),
+                e,
+                line
+            ),
+        }
+    }
+
+    /// The most recent `limit` entries for this log's dataset, newest first.
+    /// Lines that can't be read as entries are skipped.
+    pub fn recent(&self, limit: usize) -> Result<Vec<Entry>, String> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.path.display(), e)),
+        };
+        let mut entries: Vec<Entry> = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<Entry>(&line).ok())
+            .filter(|entry| entry.dataset == self.dataset)
+            .collect();
+        entries.reverse();
+        entries.truncate(limit);
+        Ok(entries)
+    }
+}
+
+fn actor(entry: &Entry) -> &str {
+    entry.actor.as_deref().unwrap_or("an unknown actor")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn appends_entries_and_reads_the_newest_first() {
+        let path = std::env::temp_dir().join(format!("bands-audit-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let log = AuditLog::new(path.clone(), None);
+        let other = AuditLog::new(path.clone(), Some("uk-festivals".to_string()));
+        assert!(log.recent(10).unwrap().is_empty());
+
+        log.record(Entry::new("upload", (1, 2), json!({ "added": 3 })));
+        other.record(Entry::new("reload", (5, 6), json!({})));
+        log.record(Entry::new("delete", (2, 3), json!({ "id": "abc" })));
+        let entries = log.recent(10).unwrap();
+        let actions: Vec<&str> = entries.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["delete", "upload"]);
+        assert_eq!(entries[1].dataset_after, "0000000000000002");
+        assert_eq!(log.recent(1).unwrap().len(), 1);
+        let entries = other.recent(10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].dataset.as_deref(), Some("uk-festivals"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+// Limits on request bodies. An uploaded dataset can be large, up to
+// `limits.max_upload_bytes`, so it's read as it arrives: one over the limit is
+// refused as soon as that's clear, rather than once it's all in memory. Every
+// other body is a small JSON document, held to `limits.max_body_bytes`. Either
+// way, a body that's too large gets a 413 with a JSON error.
+
+use crate::{data, CONFIG};
+use axum::body::HttpBody;
+use axum::extract::{FromRequest, Request};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use std::future::poll_fn;
+use std::pin::Pin;
+
+/// A dataset in a request's body, named so that its `Content-Type` picks the
+/// format it's read in.
+pub struct Upload(pub data::RawFile);
+
+impl<S: Send + Sync> FromRequest<S> for Upload {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, _: &S) -> Result<Self, Response> {
+        let headers = request.headers();
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        let Some(origin) = data::upload_name(content_type) else {
+            return Err(unsupported());
+        };
+        let limit = CONFIG.limits.max_upload_bytes;
+        let declared = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+        if declared.is_some_and(|length| length > limit) {
+            return Err(too_large(limit));
+        }
+
+        let mut body = request.into_body();
+        let mut bytes = Vec::with_capacity(declared.unwrap_or(0));
+        while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+            let frame = frame.map_err(|e| unreadable(&e.to_string()))?;
+            if let Ok(data) = frame.into_data() {
+                if bytes.len() + data.len() > limit {
+                    return Err(too_large(limit));
+                }
+                bytes.extend_from_slice(&data);
+            }
+        }
+        Ok(Upload(data::RawFile {
+            origin: origin.to_string(),
+            bytes,
+        }))
+    }
+}
+
+/// Middleware giving the 413s of bodies over `limits.max_body_bytes` the same
+/// JSON error as uploads over theirs.
+pub async fn explain_too_large(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE && !json {
+        return too_large(CONFIG.limits.max_body_bytes);
+    }
+    response
+}
+
+fn too_large(limit: usize) -> Response {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(serde_json::json!({
+            "error": format!("The request body is larger than the limit of {} bytes.", limit),
+        })),
+    )
+        .into_response()
+}
+
+fn unsupported() -> Response {
+    (
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Json(serde_json::json!({
+            "error": "Send the data as application/json, text/csv, application/yaml or application/toml.",
+        })),
+    )
+        .into_response()
+}
+
+fn unreadable(message: &str) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "error": format!("The request body couldn't be read: {}", message),
+        })),
+    )
+        .into_response()
+}
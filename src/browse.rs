@@ -0,0 +1,205 @@
+// Alphabetical browsing: distinct artists grouped by the first letter of their
+// name, for an A-Z view.
+//
+// Letters come from the normalized search key, so "Ólafur Arnalds" files under O
+// and punctuation never decides the section. A leading "The" is skipped the way
+// festival apps do, putting "The Killers" under K. Names that don't start with a
+// Latin letter (mostly digits, as in "2manydjs") share the "#" section.
+//
+// The genres in the data are listed here too, with how many artists have each.
+
+use crate::artists::{self, Artists};
+use crate::filter::Filters;
+use crate::layout::{Performance, Performances};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// The section for everything that doesn't start with A-Z.
+pub const OTHER_SECTION: char = '#';
+
+/// The part of a normalized name key that decides its place in the A-Z list.
+pub fn sort_key(key: &str) -> &str {
+    key.strip_prefix("the ").unwrap_or(key)
+}
+
+/// The A-Z section a normalized name key belongs to.
+fn section(key: &str) -> char {
+    match sort_key(key).chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => c.to_ascii_uppercase(),
+        _ => OTHER_SECTION,
+    }
+}
+
+/// Which artists to list: a section, a name prefix, or everyone.
+pub enum Selection {
+    All,
+    Section(char),
+    // Normalized, so it compares directly against name keys.
+    Prefix(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct Appearance<'a> {
+    pub festival: &'a str,
+    pub year: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub day: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub headliner: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+}
+
+impl<'a> Appearance<'a> {
+    pub fn of(performance: Performance<'a>) -> Self {
+        Appearance {
+            festival: performance.festival(),
+            year: performance.year(),
+            stage: performance.stage(),
+            day: performance.day(),
+            start_time: performance.start_time(),
+            headliner: performance.headliner(),
+            cancelled: performance.cancelled(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Artist<'a> {
+    pub name: &'a str,
+    pub slug: &'a str,
+    // Performances in the whole dataset, filtered or not.
+    pub appearances: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<&'a str>,
+    // Oldest first.
+    pub performances: Vec<Appearance<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GenreCount<'a> {
+    pub genre: &'a str,
+    // Distinct artists with the genre, and their performances.
+    pub artists: usize,
+    pub performances: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LetterCount {
+    pub letter: char,
+    pub count: usize,
+}
+
+// Each distinct artist with its performances that pass `filters`; artists left
+// without any are skipped.
+fn artists_passing<'a>(
+    artists: &'a Artists,
+    performances: &'a Performances,
+    filters: &'a Filters,
+) -> impl Iterator<Item = (&'a artists::Artist, Vec<Performance<'a>>)> {
+    artists.iter().filter_map(move |artist| {
+        let passing: Vec<Performance> = artist
+            .performances
+            .iter()
+            .map(|&id| performances.get(id))
+            .filter(|p| filters.matches(*p))
+            .collect();
+        (!passing.is_empty()).then_some((artist, passing))
+    })
+}
+
+/// Distinct artists matching `selection`, in A-Z order. A prefix matches the name
+/// with or without its leading "The".
+pub fn artists<'a>(
+    artists: &'a Artists,
+    performances: &'a Performances,
+    selection: &Selection,
+    filters: &'a Filters,
+) -> Vec<Artist<'a>> {
+    let mut selected: Vec<_> = artists_passing(artists, performances, filters)
+        .filter(|(artist, _)| match selection {
+            Selection::All => true,
+            Selection::Section(letter) => section(&artist.key) == *letter,
+            Selection::Prefix(prefix) => {
+                artist.key.starts_with(prefix.as_str())
+                    || sort_key(&artist.key).starts_with(prefix.as_str())
+            }
+        })
+        .collect();
+    // Ties (a name with and without "The") fall back to the full key.
+    selected.sort_by(|(a, _), (b, _)| {
+        sort_key(&a.key)
+            .cmp(sort_key(&b.key))
+            .then(a.key.cmp(&b.key))
+    });
+
+    selected
+        .into_iter()
+        .map(|(artist, mut passing)| {
+            passing.sort_by(|a, b| a.year().cmp(&b.year()).then(a.festival().cmp(b.festival())));
+            Artist {
+                name: &artist.name,
+                slug: &artist.slug,
+                appearances: artist.appearances(),
+                country: passing.iter().find_map(|p| p.country()),
+                performances: passing.into_iter().map(Appearance::of).collect(),
+            }
+        })
+        .collect()
+}
+
+/// How many artists each section holds: every letter A-Z, even empty ones, so
+/// clients can render a fixed index, followed by "#".
+pub fn letters(
+    artists: &Artists,
+    performances: &Performances,
+    filters: &Filters,
+) -> Vec<LetterCount> {
+    let mut counts: Vec<LetterCount> = ('A'..='Z')
+        .chain([OTHER_SECTION])
+        .map(|letter| LetterCount { letter, count: 0 })
+        .collect();
+    for (artist, _) in artists_passing(artists, performances, filters) {
+        let slot = match section(&artist.key) {
+            OTHER_SECTION => counts.len() - 1,
+            letter => (letter as u8 - b'A') as usize,
+        };
+        counts[slot].count += 1;
+    }
+    counts
+}
+
+/// Every genre on one of `performances`, alphabetically, with how many artists
+/// and performances have it. Genres differing only in case are counted as one,
+/// under the spelling that appears first.
+pub fn genres<'a>(
+    artists: &Artists,
+    performances: impl Iterator<Item = Performance<'a>>,
+) -> Vec<GenreCount<'a>> {
+    let mut counts: Vec<GenreCount> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut counted_artists: HashSet<(usize, u32)> = HashSet::new();
+
+    for performance in performances {
+        for genre in performance.genres() {
+            let position = *positions.entry(genre.to_lowercase()).or_insert_with(|| {
+                counts.push(GenreCount {
+                    genre,
+                    artists: 0,
+                    performances: 0,
+                });
+                counts.len() - 1
+            });
+            counts[position].performances += 1;
+            if counted_artists.insert((position, artists.of(performance.position()))) {
+                counts[position].artists += 1;
+            }
+        }
+    }
+    counts.sort_by_cached_key(|count| count.genre.to_lowercase());
+    counts
+}
@@ -0,0 +1,166 @@
+// A least-recently-used cache, for rendered responses and sessions.
+//
+// Entries remember when they were last touched, and every touch is also queued
+// in order. Once the cache is full, the queue is read from the front until it
+// reaches an entry whose last touch that was, the stalest; touches superseded by
+// later ones are skipped on the way. The queue is compacted whenever it grows to
+// several times the number of entries, so every operation is O(1) amortized,
+// however many entries there are.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Mutex;
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+struct Inner<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    // Every touch, oldest first; one is current while it matches its entry's
+    // `last_used`.
+    touches: VecDeque<(u64, K)>,
+    // Bumped on every access, so a larger `last_used` means more recent.
+    clock: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> Inner<K, V> {
+    fn touch(&mut self, key: &K) -> u64 {
+        self.clock += 1;
+        self.touches.push_back((self.clock, key.clone()));
+        if self.touches.len() > 4 * self.entries.len() + 16 {
+            let entries = &self.entries;
+            self.touches
+                .retain(|(at, key)| entries.get(key).is_some_and(|e| e.last_used == *at));
+        }
+        self.clock
+    }
+
+    fn evict_stalest(&mut self) {
+        while let Some((at, key)) = self.touches.pop_front() {
+            if self.entries.get(&key).is_some_and(|e| e.last_used == at) {
+                self.entries.remove(&key);
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    inner: Mutex<Inner<K, V>>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                touches: VecDeque::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Returns a copy of the cached value and marks it as recently used.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.entries.contains_key(key) {
+            return None;
+        }
+        let now = inner.touch(key);
+        let entry = inner.entries.get_mut(key)?;
+        entry.last_used = now;
+        Some(entry.value.clone())
+    }
+
+    /// Stores `value`, evicting the least recently used entry if the cache is full.
+    pub fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+            inner.evict_stalest();
+        }
+        let now = inner.touch(&key);
+        inner.entries.insert(
+            key,
+            Entry {
+                value,
+                last_used: now,
+            },
+        );
+    }
+
+    /// Drops the entries whose key `keep` refuses, and says how many.
+    pub fn retain(&self, mut keep: impl FnMut(&K) -> bool) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.entries.len();
+        inner.entries.retain(|key, _| keep(key));
+        before - inner.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        // Touching "a" makes "b" the stalest.
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn replacing_a_key_does_not_evict() {
+        let cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("a", 10);
+
+        assert_eq!(cache.get(&"a"), Some(10));
+        assert_eq!(cache.get(&"b"), Some(2));
+    }
+
+    #[test]
+    fn retain_drops_refused_keys() {
+        let cache = LruCache::new(3);
+        cache.insert("apple", 1);
+        cache.insert("avocado", 2);
+        cache.insert("banana", 3);
+
+        assert_eq!(cache.retain(|key| !key.starts_with('a')), 2);
+        assert_eq!(cache.get(&"apple"), None);
+        assert_eq!(cache.get(&"banana"), Some(3));
+    }
+
+    #[test]
+    fn touches_are_compacted_as_entries_are_used() {
+        let cache = LruCache::new(3);
+        for n in 0..3 {
+            cache.insert(n, n);
+        }
+        for _ in 0..1000 {
+            assert_eq!(cache.get(&0), Some(0));
+            assert_eq!(cache.get(&2), Some(2));
+        }
+        assert!(cache.inner.lock().unwrap().touches.len() <= 4 * 3 + 17);
+        cache.insert(3, 3);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&0), Some(0));
+    }
+}
@@ -0,0 +1,77 @@
+// Cache-Control headers, from the `[cache_control]` table, so how long browsers
+// and CDNs keep responses can be tuned per kind of route: exports of a whole
+// dataset change only with the data and can be kept long, random bands should
+// never be kept, and the rest of the public API is somewhere in between. Only
+// successful and 304 responses get one; errors aren't worth keeping.
+
+use crate::{ratelimit, CONFIG};
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// Middleware giving public responses their group's Cache-Control.
+pub async fn public(request: Request, next: Next) -> Response {
+    let policy = match Group::of(request.uri().path()) {
+        Group::Reads => &CONFIG.cache_control.reads,
+        Group::Exports => &CONFIG.cache_control.exports,
+        Group::Random => &CONFIG.cache_control.random,
+    };
+    apply(policy.as_deref(), next.run(request).await)
+}
+
+/// Middleware giving admin responses `cache_control.admin`.
+pub async fn admin(request: Request, next: Next) -> Response {
+    apply(
+        CONFIG.cache_control.admin.as_deref(),
+        next.run(request).await,
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum Group {
+    Reads,
+    Exports,
+    Random,
+}
+
+impl Group {
+    // The group a public request for `path` is in.
+    fn of(path: &str) -> Group {
+        if ratelimit::is_export(path) {
+            Group::Exports
+        } else if path.ends_with("/random-bands") {
+            Group::Random
+        } else {
+            Group::Reads
+        }
+    }
+}
+
+// A handler that sets its own Cache-Control keeps it.
+fn apply(policy: Option<&str>, mut response: Response) -> Response {
+    let cacheable = response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED;
+    if let Some(policy) = policy.filter(|_| cacheable) {
+        let value = HeaderValue::from_str(policy).expect("checked when the config was read");
+        response
+            .headers_mut()
+            .entry(header::CACHE_CONTROL)
+            .or_insert(value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_fall_in_groups_for_every_dataset() {
+        assert_eq!(Group::of("/api/all-bands"), Group::Exports);
+        assert_eq!(Group::of("/api/uk-festivals/all-bands"), Group::Exports);
+        assert_eq!(Group::of("/api/random-bands"), Group::Random);
+        assert_eq!(Group::of("/api/uk-festivals/random-bands"), Group::Random);
+        assert_eq!(Group::of("/api/search"), Group::Reads);
+        assert_eq!(Group::of("/"), Group::Reads);
+    }
+}
@@ -0,0 +1,180 @@
+// Compressed responses, for clients that say they take them in
+// Accept-Encoding. The API's JSON repeats the same keys for every record, so it
+// shrinks to a fraction of its size; an export of the whole dataset most of all.
+// Bodies smaller than `compression.min_bytes` aren't worth it and go as they
+// are, as do errors, so a JSON error can still be given its request ID, and
+// streamed bodies, which would have to be gathered in memory first.
+
+use crate::{deflate, CONFIG};
+use axum::body::{self, Body, HttpBody};
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// A way of compressing response bodies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    // zlib's format, which is what HTTP calls "deflate".
+    Deflate,
+}
+
+impl Encoding {
+    pub fn parse(text: &str) -> Option<Encoding> {
+        match text {
+            "gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Encoding::Gzip => deflate::gzip(data),
+            Encoding::Deflate => deflate::zlib(data),
+        }
+    }
+}
+
+/// Middleware compressing responses in the best of the configured encodings
+/// the client accepts.
+pub async fn compress(request: Request, next: Next) -> Response {
+    let encoding = choose(&CONFIG.compression, request.headers());
+    let head = request.method() == Method::HEAD;
+    let mut response = next.run(request).await;
+    if !compressible(&response) {
+        return response;
+    }
+    // Caches have to keep the compressed and plain bodies apart.
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    let Some(encoding) = encoding.filter(|_| !head) else {
+        return response;
+    };
+
+    let min_bytes = CONFIG.compression_min_bytes;
+    let (mut parts, body) = response.into_parts();
+    let bytes = match body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("->> Couldn't read a response to compress it: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    if bytes.len() < min_bytes {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    // Large bodies take a while, so they're compressed off the async threads.
+    let compressed = tokio::task::spawn_blocking(move || encoding.compress(&bytes))
+        .await
+        .expect("compressing doesn't panic");
+    let headers = &mut parts.headers;
+    headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.name()),
+    );
+    headers.remove(header::CONTENT_LENGTH);
+    // A strong ETag names these exact bytes, so the compressed body gets its own.
+    if let Some(etag) = headers.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+        if let Some(tag) = etag.strip_suffix('"').filter(|_| !etag.starts_with("W/")) {
+            let etag = format!("{}-{}\"", tag, encoding.name());
+            headers.insert(
+                header::ETAG,
+                HeaderValue::from_str(&etag).expect("ETags stay visible ASCII"),
+            );
+        }
+    }
+    Response::from_parts(parts, Body::from(compressed))
+}
+
+// Whether `response` is a successful one with a body of text, of a known length.
+fn compressible(response: &Response) -> bool {
+    let headers = response.headers();
+    let text = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|kind| {
+            kind.starts_with("text/")
+                || ["json", "javascript", "xml", "yaml", "toml"]
+                    .iter()
+                    .any(|format| kind.contains(format))
+        });
+    response.status().is_success()
+        && text
+        && !headers.contains_key(header::CONTENT_ENCODING)
+        && response.body().size_hint().exact().is_some()
+}
+
+// The encoding in `offered` the client likes best, going by the quality values
+// in its Accept-Encoding; the earlier is taken when it likes several as much.
+fn choose(offered: &[Encoding], headers: &HeaderMap) -> Option<Encoding> {
+    let accepted: Vec<(String, f32)> = headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let name = parts.next()?.trim().to_ascii_lowercase();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse().ok())
+                .unwrap_or(1.0);
+            Some((name, quality))
+        })
+        .collect();
+    let quality = |name: &str| {
+        let named = accepted.iter().find(|(n, _)| n == name);
+        let any = accepted.iter().find(|(n, _)| n == "*");
+        named.or(any).map_or(0.0, |(_, q)| *q)
+    };
+    let mut best = None;
+    for &encoding in offered {
+        let q = quality(encoding.name());
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepting(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_ENCODING, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn the_clients_favourite_offered_encoding_is_chosen() {
+        let both = [Encoding::Gzip, Encoding::Deflate];
+        assert_eq!(
+            choose(&both, &accepting("gzip, deflate, br")),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            choose(&both, &accepting("gzip;q=0.5, deflate")),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(choose(&both, &accepting("br, zstd")), None);
+        assert_eq!(
+            choose(&both, &accepting("*;q=0.1, gzip;q=0")),
+            Some(Encoding::Deflate)
+        );
+        assert_eq!(choose(&[Encoding::Deflate], &accepting("gzip")), None);
+        assert_eq!(choose(&[], &accepting("gzip")), None);
+        assert_eq!(choose(&both, &HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,247 @@
+// Caps on how much the server works on at once, whoever it's for: how many
+// requests are in flight, how many of those are downloads of /api/all-bands,
+// and how many connections are open. Rate limits hold back each client; these
+// keep many clients together from using up the server's memory.
+//
+// A request over the in-flight limits isn't queued but shed straight away with
+// a 503 and Retry-After. A request counts until its response has been sent, as
+// an export's body is what takes the memory. Connections over the limit wait in
+// the listen backlog until one closes.
+
+use crate::{ratelimit, CONFIG};
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::Request;
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use axum::serve::Listener;
+use http_body::{Frame, SizeHint};
+use once_cell::sync::Lazy;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// One permit per request or connection that may be open at once; `None` when
+// the limit is 0, which turns it off. They are sized at startup.
+static IN_FLIGHT: Lazy<Option<Arc<Semaphore>>> =
+    Lazy::new(|| semaphore(CONFIG.limits.max_in_flight));
+static EXPORTS: Lazy<Option<Arc<Semaphore>>> =
+    Lazy::new(|| semaphore(CONFIG.limits.max_exports_in_flight));
+static CONNECTIONS: Lazy<Option<Arc<Semaphore>>> =
+    Lazy::new(|| semaphore(CONFIG.limits.max_connections));
+
+fn semaphore(limit: usize) -> Option<Arc<Semaphore>> {
+    (limit > 0).then(|| Arc::new(Semaphore::new(limit)))
+}
+
+// A permit from `semaphore` if one is free right away; `Err` if none is.
+fn try_take(semaphore: &Option<Arc<Semaphore>>) -> Result<Option<OwnedSemaphorePermit>, ()> {
+    match semaphore {
+        Some(semaphore) => semaphore
+            .clone()
+            .try_acquire_owned()
+            .map(Some)
+            .map_err(|_| ()),
+        None => Ok(None),
+    }
+}
+
+/// Middleware shedding requests beyond the in-flight limits with a 503.
+pub async fn shed_load(request: Request, next: Next) -> Response {
+    let Ok(request_permit) = try_take(&IN_FLIGHT) else {
+        return overloaded("The server is busy; try again in a moment.");
+    };
+    let export_permit = if ratelimit::is_export(request.uri().path()) {
+        let Ok(permit) = try_take(&EXPORTS) else {
+            return overloaded("Too many downloads at once; try again in a moment.");
+        };
+        permit
+    } else {
+        None
+    };
+    let response = next.run(request).await;
+    let permits = [request_permit, export_permit]
+        .into_iter()
+        .flatten()
+        .collect();
+    response.map(|body| {
+        Body::new(Held {
+            inner: body,
+            rest: Bytes::new(),
+            _permits: permits,
+        })
+    })
+}
+
+fn overloaded(message: &str) -> Response {
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "error": message })),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert("retry-after", HeaderValue::from(1));
+    response
+}
+
+// A response body holding its request's permits until its last piece has been
+// taken. Large frames are handed out in pieces, so hyper only takes the last one
+// once most of the body has been written, rather than as soon as it's built.
+struct Held {
+    inner: Body,
+    rest: Bytes,
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+const PIECE: usize = 64 * 1024;
+
+impl HttpBody for Held {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let this = self.get_mut();
+        if this.rest.is_empty() {
+            match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => this.rest = data,
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                done => return Poll::Ready(done),
+            }
+        }
+        let piece = this.rest.split_to(this.rest.len().min(PIECE));
+        Poll::Ready(Some(Ok(Frame::data(piece))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.rest.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let inner = self.inner.size_hint();
+        let rest = self.rest.len() as u64;
+        let mut hint = SizeHint::new();
+        if let Some(upper) = inner.upper() {
+            hint.set_upper(upper + rest);
+        }
+        hint.set_lower(inner.lower() + rest);
+        hint
+    }
+}
+
+/// A connection that holds its permit until it's closed.
+pub struct Counted<T> {
+    inner: T,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Counted<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Counted<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// A listener that stops accepting connections while `limits.max_connections`
+/// are open, across all listeners.
+pub struct Limited<L>(pub L);
+
+impl<L: Listener> Listener for Limited<L> {
+    type Io = Counted<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        let permit = match &*CONNECTIONS {
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("the semaphore is never closed"),
+            ),
+            None => None,
+        };
+        let (io, addr) = self.0.accept().await;
+        let io = Counted {
+            inner: io,
+            _permit: permit,
+        };
+        (io, addr)
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.0.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+
+    #[test]
+    fn permits_are_held_until_the_last_piece_is_taken() {
+        let semaphore = semaphore(1);
+        let permit = try_take(&semaphore).unwrap();
+        assert!(permit.is_some());
+        assert!(try_take(&semaphore).is_err());
+        assert!(try_take(&None).unwrap().is_none());
+
+        let mut body = Held {
+            inner: Body::from(vec![b'x'; PIECE + 1]),
+            rest: Bytes::new(),
+            _permits: permit.into_iter().collect(),
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut sizes = Vec::new();
+        while let Poll::Ready(Some(Ok(frame))) = Pin::new(&mut body).poll_frame(&mut cx) {
+            sizes.push(frame.into_data().unwrap().len());
+        }
+        assert_eq!(sizes, [PIECE, 1]);
+        assert!(body.is_end_stream());
+        assert!(try_take(&semaphore).is_err());
+        drop(body);
+        assert!(try_take(&semaphore).unwrap().is_some());
+    }
+}
@@ -0,0 +1,143 @@
+// Conditional requests for responses that only change with the data: they carry
+// an ETag made from the dataset's content hash and the request's path and
+// query, and a Last-Modified of when the data was loaded. A request whose
+// If-None-Match names the tag, or, without one, whose If-Modified-Since is no
+// earlier than the load, gets a 304 with no body instead. Clients that keep a
+// copy, like the UI with the whole dataset, then only download it again once
+// the data has changed.
+
+use crate::clock;
+use crate::state::AppState;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `render`'s response with its ETag and Last-Modified, or a 304 if the client
+/// has it already.
+pub fn respond(
+    state: &AppState,
+    uri: &Uri,
+    headers: &HeaderMap,
+    render: impl FnOnce() -> Response,
+) -> Response {
+    let etag = etag(state.content_hash, uri);
+    let validators = [
+        (
+            header::ETAG,
+            HeaderValue::from_str(&etag).expect("ETags are hex digits"),
+        ),
+        (
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&clock::http_date(state.loaded_at))
+                .expect("dates are visible ASCII"),
+        ),
+    ];
+    // If-None-Match is the more exact of the two, so If-Modified-Since only
+    // counts without it.
+    let fresh = if headers.contains_key(header::IF_NONE_MATCH) {
+        has(headers, &etag)
+    } else {
+        unmodified_since(headers, state.loaded_at)
+    };
+    if fresh {
+        return (StatusCode::NOT_MODIFIED, validators).into_response();
+    }
+    let mut response = render();
+    if response.status().is_success() {
+        response.headers_mut().extend(validators);
+    }
+    response
+}
+
+fn etag(content_hash: u64, uri: &Uri) -> String {
+    let mut hasher = DefaultHasher::new();
+    uri.path().hash(&mut hasher);
+    uri.query().hash(&mut hasher);
+    format!("\"{:016x}-{:016x}\"", content_hash, hasher.finish())
+}
+
+// Whether If-None-Match names `etag`, or a compressed body with it, on which
+// it gets a suffix such as "-gzip". Weak tags count too, as they do for this
+// header.
+fn has(headers: &HeaderMap, etag: &str) -> bool {
+    let ours = etag.trim_matches('"');
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| {
+            let tag = tag.strip_prefix("W/").unwrap_or(tag).trim_matches('"');
+            tag == "*"
+                || tag
+                    .strip_prefix(ours)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+        })
+}
+
+// Whether If-Modified-Since is no earlier than `loaded_at`, to the second, which
+// is as precise as the dates are. A date that doesn't parse is ignored.
+fn unmodified_since(headers: &HeaderMap, loaded_at: SystemTime) -> bool {
+    let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(clock::parse_http_date)
+        .is_some_and(|since| seconds(loaded_at) <= seconds(since))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn etags_follow_the_data_and_the_query() {
+        let uri: Uri = "/api/artists?starts_with=a".parse().unwrap();
+        let etag = etag(0xc808_5579_69a3_e991, &uri);
+        assert!(etag.starts_with("\"c808557969a3e991-"));
+        assert_ne!(etag, super::etag(1, &uri));
+        assert_ne!(
+            etag,
+            super::etag(0xc808_5579_69a3_e991, &"/api/artists".parse().unwrap())
+        );
+
+        let asking = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, value.parse().unwrap());
+            headers
+        };
+        let ours = etag.trim_matches('"');
+        assert!(has(&asking(&etag), &etag));
+        assert!(has(&asking(&format!("\"x\", W/\"{}-gzip\"", ours)), &etag));
+        assert!(has(&asking("*"), &etag));
+        assert!(!has(&asking(&format!("\"{}0\"", ours)), &etag));
+        assert!(!has(&HeaderMap::new(), &etag));
+    }
+
+    #[test]
+    fn if_modified_since_compares_whole_seconds() {
+        let since = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_MODIFIED_SINCE, value.parse().unwrap());
+            headers
+        };
+        let loaded_at = UNIX_EPOCH + Duration::from_millis(784_111_777_250);
+        assert!(unmodified_since(
+            &since("Sun, 06 Nov 1994 08:49:37 GMT"),
+            loaded_at
+        ));
+        assert!(unmodified_since(
+            &since("Mon, 07 Nov 1994 08:49:37 GMT"),
+            loaded_at
+        ));
+        assert!(!unmodified_since(
+            &since("Sun, 06 Nov 1994 08:49:36 GMT"),
+            loaded_at
+        ));
+        assert!(!unmodified_since(&since("last week"), loaded_at));
+        assert!(!unmodified_since(&HeaderMap::new(), loaded_at));
+    }
+}
@@ -0,0 +1,1636 @@
+// Startup configuration, gathered from an optional `config.toml`, environment
+// variables and command-line flags. Each source overrides the one before it:
+// built-in defaults < config file < BANDJE_ variables < other environment
+// variables < flags.
+
+use crate::access::{Cidr, Scope, Token};
+use crate::compression::Encoding;
+use crate::data::DataSource;
+use crate::layout;
+use crate::log;
+use crate::ratelimit::Rate;
+use crate::redis;
+use crate::toml;
+use crate::validate;
+use axum::http::HeaderValue;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::RwLock;
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: nog-een-bandje [OPTIONS]
+
+Options:
+  --config <PATH>     Configuration file to read [env: BANDS_CONFIG] [default: config.toml, if present]
+  --host <ADDRESS>    The IP address to listen on [default: 0.0.0.0]
+  --port <PORT>       The port to listen on [default: 3000]
+  --listen <ADDRESS>  Listen on this address and port, or on a Unix socket given as unix:<PATH>,
+                      instead; repeat it to listen on several
+  --data-file <PATH>  The performance data to serve [env: BANDS_FILE] [default: bands.json]
+  --data-dir <PATH>   Serve every data file (*.json, *.csv, *.yaml, *.toml) in this directory
+                      instead [env: BANDS_DIR]
+  --data-url <URL>    Download the data from this http:// or s3:// URL instead [env: BANDS_URL]
+  --import-into <DB>  Copy the data into this SQLite file or postgres:// database, replacing
+                      its contents, and exit
+  --check             Load and check every dataset, print what's in each, and exit; non-zero
+                      if any doesn't load
+  --allow-empty       Start with no data if it can't be loaded, instead of exiting
+  --strict            Refuse data with invalid records instead of leaving them out
+  --log-level <LEVEL> How much to log: error, warn, info or debug [default: info]
+  -h, --help          Print this help and exit";
+
+// Read when present, but not required, so the server still starts with no setup.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const DEFAULT_ALIASES_FILE: &str = "aliases.json";
+const DEFAULT_COUNTRIES_FILE: &str = "countries.json";
+
+// The config file's tables, for naming settings by environment variable, and
+// the settings that are lists there.
+const ENV_PREFIX: &str = "BANDJE_";
+const ENV_TABLES: [&str; 15] = [
+    "server",
+    "data",
+    "log",
+    "snapshots",
+    "audit",
+    "access_log",
+    "admin",
+    "jwt",
+    "cors",
+    "limits",
+    // Before "cache", which would take its variables for its own.
+    "cache_control",
+    "cache",
+    "compression",
+    "session",
+    "features",
+];
+const ENV_LISTS: [&str; 8] = [
+    "server.listen",
+    "admin.keys",
+    "admin.allowed_ips",
+    "admin.trusted_proxies",
+    "cors.allowed_origins",
+    "cors.allowed_methods",
+    "cors.allowed_headers",
+    "compression.algorithms",
+];
+
+// The first path segments of the main dataset's API, which other datasets'
+// names would clash with under /api/.
+const RESERVED_NAMES: [&str; 13] = [
+    "admin",
+    "all-bands",
+    "artists",
+    "autocomplete",
+    "dataset",
+    "datasets",
+    "festivals",
+    "genres",
+    "provenance",
+    "random-bands",
+    "search",
+    "searches",
+    "stats",
+];
+
+#[derive(Debug)]
+pub struct Config {
+    // The IP address and port the HTTP server binds to.
+    pub address: IpAddr,
+    pub port: u16,
+    // Addresses and Unix sockets to listen on instead, all serving the same,
+    // and the permissions to give the sockets.
+    pub listen: Vec<Listen>,
+    pub socket_mode: Option<u32>,
+    // How much the server logs, and at which level it logs each request; `None`
+    // doesn't log requests.
+    pub log_level: log::Level,
+    pub request_log_level: Option<log::Level>,
+    pub log_format: log::Format,
+    // Requests taking this long or longer are logged as slow; `None` logs none.
+    pub slow_request: Option<Duration>,
+    // The config file that was read, if any.
+    pub config_file: Option<PathBuf>,
+    // Where the admin API and pages are served instead, if anywhere; `None`
+    // serves them with the rest.
+    pub admin_address: Option<SocketAddr>,
+    // The path everything is served under, such as "/bandje" behind a proxy
+    // that mounts the app there; "" for the root.
+    pub base_path: String,
+    // Where the performance data is read from.
+    pub data_source: DataSource,
+    // Canonical names for artists spelled in several ways, applied while loading.
+    pub aliases_file: Option<PathBuf>,
+    // Artists' countries, for data that doesn't give them.
+    pub countries_file: Option<PathBuf>,
+    // Whether to reload the data when its files change, and how often to check.
+    pub watch: bool,
+    pub watch_interval: Duration,
+    // How often to download a URL source again; `None` keeps the first download.
+    pub refresh_interval: Option<Duration>,
+    // Whether to start with an empty dataset when the first load fails, and wait
+    // for a reload to fix it, rather than exit.
+    pub allow_empty: bool,
+    // Whether invalid records are left out of the data, or reject all of it.
+    pub validation: validate::Mode,
+    // How performances are kept in memory; see `layout`.
+    pub layout: layout::Kind,
+    // Origins allowed to call the API from a browser; "*" allows any, and
+    // "https://*.example.com" any subdomain.
+    pub cors_origins: Vec<String>,
+    // What those calls may use: methods and request headers, "*" for any, and
+    // how long browsers may remember the answer to a preflight request.
+    pub cors_methods: Vec<String>,
+    pub cors_headers: Vec<String>,
+    pub cors_max_age: Option<Duration>,
+    pub limits: Limits,
+    // The encodings responses can be compressed in, in order of preference, and
+    // the smallest body worth compressing.
+    pub compression: Vec<Encoding>,
+    pub compression_min_bytes: usize,
+    pub cache_control: CacheControl,
+    pub sessions: Sessions,
+    pub features: Features,
+    // A Redis server to share computed responses with other instances through,
+    // and how long entries are kept there.
+    pub shared_cache: Option<redis::Address>,
+    pub shared_cache_ttl: Duration,
+    // Where POST /api/admin/snapshot saves copies of the dataset.
+    pub snapshot_dir: PathBuf,
+    // The append-only log of changes made through the admin API.
+    pub audit_file: PathBuf,
+    pub access_log: AccessLog,
+    // Set by --import-into: copy the data into this database instead of serving it.
+    pub import_into: Option<String>,
+    // Set by --check: load the data and say what's in it instead of serving it.
+    pub check: bool,
+    // Datasets served next to the main one, from `[datasets.<name>]` tables.
+    pub datasets: Vec<Dataset>,
+    // Keys and tokens for the admin endpoints, from `admin.keys` or
+    // BANDS_ADMIN_KEYS and `[tokens.<label>]` tables; with none, they're closed.
+    pub tokens: Vec<Token>,
+    // JWTs from an identity provider accepted in their place, from `[jwt]`.
+    pub jwt: Option<Jwt>,
+    // The addresses the admin API can be used from; empty allows any.
+    pub allowed_ips: Vec<Cidr>,
+    // Proxies whose X-Forwarded-For header is believed.
+    pub trusted_proxies: Vec<Cidr>,
+}
+
+/// The configuration in use, which a reload can replace while the server runs.
+/// Replaced configurations are leaked, as requests being answered may still be
+/// reading them; reloads are rare and a configuration is small.
+pub struct Current(RwLock<&'static Config>);
+
+impl Current {
+    pub fn new(config: Config) -> Current {
+        Current(RwLock::new(Box::leak(Box::new(config))))
+    }
+
+    pub fn replace(&self, config: Config) {
+        *self.0.write().unwrap() = Box::leak(Box::new(config));
+    }
+}
+
+impl Deref for Current {
+    type Target = Config;
+
+    fn deref(&self) -> &Config {
+        *self.0.read().unwrap()
+    }
+}
+
+// Reads one setting, in a form to compare.
+type Setting = fn(&Config) -> String;
+
+/// The settings that differ between `old` and `new` but are only read when the
+/// server starts, so changing them takes a restart.
+pub fn needs_restart(old: &Config, new: &Config) -> Vec<&'static str> {
+    let settings: [(&str, Setting); 26] = [
+        ("server.address and server.port", |c| {
+            format!("{:?}", (c.address, c.port))
+        }),
+        ("server.listen", |c| format!("{:?}", c.listen)),
+        ("server.socket_mode", |c| format!("{:?}", c.socket_mode)),
+        ("server.admin_address", |c| format!("{:?}", c.admin_address)),
+        ("server.base_path", |c| c.base_path.clone()),
+        ("the data source", |c| c.data_source.to_string()),
+        ("data.aliases", |c| format!("{:?}", c.aliases_file)),
+        ("data.countries", |c| format!("{:?}", c.countries_file)),
+        ("the [datasets.<name>] tables", |c| {
+            format!("{:?}", c.datasets)
+        }),
+        ("data.watch", |c| format!("{:?}", c.watch)),
+        ("data.watch_interval", |c| format!("{:?}", c.watch_interval)),
+        ("data.refresh_interval", |c| {
+            format!("{:?}", c.refresh_interval)
+        }),
+        ("audit.file", |c| format!("{:?}", c.audit_file)),
+        ("cache.redis_url and cache.redis_ttl", |c| {
+            format!("{:?}", (&c.shared_cache, c.shared_cache_ttl))
+        }),
+        ("limits.max_body_bytes", |c| {
+            c.limits.max_body_bytes.to_string()
+        }),
+        ("limits.max_saved_searches", |c| {
+            c.limits.max_saved_searches.to_string()
+        }),
+        ("session.max_sessions", |c| {
+            c.sessions.max_sessions.to_string()
+        }),
+        ("limits.max_in_flight", |c| {
+            c.limits.max_in_flight.to_string()
+        }),
+        ("limits.max_exports_in_flight", |c| {
+            c.limits.max_exports_in_flight.to_string()
+        }),
+        ("limits.max_connections", |c| {
+            c.limits.max_connections.to_string()
+        }),
+        ("cors.allowed_methods", |c| format!("{:?}", c.cors_methods)),
+        ("cors.allowed_headers", |c| format!("{:?}", c.cors_headers)),
+        ("cors.max_age", |c| format!("{:?}", c.cors_max_age)),
+        // The search modes are checked per request, so only these two count.
+        ("features.saved_searches", |c| {
+            c.features.saved_searches.to_string()
+        }),
+        ("features.browse", |c| c.features.browse.to_string()),
+        ("--import-into", |c| format!("{:?}", c.import_into)),
+    ];
+    settings
+        .into_iter()
+        .filter(|(_, read)| read(old) != read(new))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// One address the server listens on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Listen {
+    /// Reads `text`: "unix:" and a socket's path, or an IP address and port.
+    pub fn parse(text: &str) -> Result<Listen, String> {
+        if let Some(path) = text.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("needs a socket's path after \"unix:\"".to_string());
+            }
+            return Ok(Listen::Unix(PathBuf::from(path)));
+        }
+        text.parse().map(Listen::Tcp).map_err(|_| {
+            "needs an IP address and port, like \"127.0.0.1:3000\" or \"[::1]:3000\", or unix:<path>"
+                .to_string()
+        })
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Listen::Tcp(addr) => write!(f, "http://{}", addr),
+            Listen::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Where one dataset is read from. The main dataset has no name and is served
+/// under /api/; the others are served under /api/<name>/.
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub name: Option<String>,
+    pub data_source: DataSource,
+    pub aliases_file: Option<PathBuf>,
+    pub countries_file: Option<PathBuf>,
+}
+
+impl Dataset {
+    /// The path this dataset's API is served under, such as "/api/uk-festivals".
+    pub fn api_path(&self) -> String {
+        match &self.name {
+            Some(name) => format!("/api/{}", name),
+            None => "/api".to_string(),
+        }
+    }
+}
+
+/// How JWTs presented to the admin API are checked, and how they map to scopes.
+#[derive(Debug, Clone)]
+pub struct Jwt {
+    // What the `iss` and `aud` claims have to say.
+    pub issuer: String,
+    pub audience: String,
+    // The provider's public keys as a JWK set, for RS256 tokens: a file, or an
+    // http:// URL.
+    pub jwks: Option<String>,
+    // A secret shared with the provider instead, for HS256 tokens.
+    pub secret: Option<String>,
+    // The claim listing the holder's roles.
+    pub roles_claim: String,
+    // How far the provider's clock may be from ours when checking expiry.
+    pub leeway: Duration,
+}
+
+impl Default for Jwt {
+    fn default() -> Jwt {
+        Jwt {
+            issuer: String::new(),
+            audience: String::new(),
+            jwks: None,
+            secret: None,
+            roles_claim: "roles".to_string(),
+            leeway: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Caps on how much a single request, or all of them together, can ask for.
+#[derive(Debug)]
+pub struct Limits {
+    // Upper bound for `count` on /api/random-bands.
+    pub max_random_count: usize,
+    // Upper bound for `limit` on /api/autocomplete.
+    pub max_autocomplete: usize,
+    // How many distinct searches keep their rendered response cached.
+    pub search_cache_size: usize,
+    // How many rendered responses of the derived endpoints (stats, genres and
+    // browsing) are kept, and for how long; `None` keeps them until
+    // the data is reloaded.
+    pub response_cache_size: usize,
+    pub response_cache_ttl: Option<Duration>,
+    // Datasets with more performances than this stream /api/all-bands as it's
+    // sent, rather than keeping it serialized.
+    pub stream_exports_over: usize,
+    // How many searches POST /api/searches will store before refusing more.
+    pub max_saved_searches: usize,
+    // The largest dataset PUT /api/admin/dataset accepts, in bytes.
+    pub max_upload_bytes: usize,
+    // The largest body the other endpoints taking one accept, in bytes.
+    pub max_body_bytes: usize,
+    // How many replaced datasets are kept in memory for POST /api/admin/rollback.
+    pub max_rollback_steps: usize,
+    // How fast each client can call the public API, and export the whole dataset
+    // from /api/all-bands.
+    pub reads: Rate,
+    pub exports: Rate,
+    // How many requests all clients together can have in flight, how many of
+    // those can be exports, and how many connections can be open; 0 is no limit.
+    pub max_in_flight: usize,
+    pub max_exports_in_flight: usize,
+    pub max_connections: usize,
+    // How long a handler may take before the request gets a 503: most of the
+    // public API, exports, and the admin API. `None` is no limit.
+    pub read_timeout: Option<Duration>,
+    pub export_timeout: Option<Duration>,
+    pub admin_timeout: Option<Duration>,
+}
+
+/// The Cache-Control header each group of routes is given; `None` sends none.
+#[derive(Debug)]
+pub struct CacheControl {
+    // The public API apart from the two below, and its pages.
+    pub reads: Option<String>,
+    // /api/all-bands.
+    pub exports: Option<String>,
+    // /api/random-bands, which answers differently every time.
+    pub random: Option<String>,
+    pub admin: Option<String>,
+}
+
+/// Where every request is logged, from `[access_log]`.
+#[derive(Debug)]
+pub struct AccessLog {
+    // `None` logs no requests.
+    pub file: Option<PathBuf>,
+    // How large the file grows before it's started afresh, 0 for no limit, and
+    // whether it's started afresh each day too.
+    pub max_bytes: u64,
+    pub daily: bool,
+    // How many of the files it was started afresh from are kept.
+    pub keep: usize,
+}
+
+/// How visitors' sessions are kept, from `[session]`.
+#[derive(Debug)]
+pub struct Sessions {
+    // What cookies are signed with; `None` makes a random secret at startup.
+    pub secret: Option<String>,
+    // How long a session is kept after it last changed, and how many are kept.
+    pub ttl: Duration,
+    pub max_sessions: usize,
+    // Whether the cookie is only sent over HTTPS, as behind a proxy that
+    // terminates it.
+    pub secure: bool,
+}
+
+/// Optional parts of the API that can be switched off.
+#[derive(Debug)]
+pub struct Features {
+    pub saved_searches: bool,
+    pub browse: bool,
+    pub regex_search: bool,
+    pub phonetic_search: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: IpAddr::from([0, 0, 0, 0]),
+            port: 3000,
+            listen: Vec::new(),
+            socket_mode: None,
+            admin_address: None,
+            base_path: String::new(),
+            log_level: log::Level::Info,
+            request_log_level: Some(log::Level::Info),
+            log_format: log::Format::Text,
+            slow_request: Some(Duration::from_secs(1)),
+            config_file: None,
+            data_source: DataSource::File(PathBuf::from("bands.json")),
+            aliases_file: None,
+            countries_file: None,
+            watch: true,
+            watch_interval: Duration::from_secs(2),
+            refresh_interval: Some(Duration::from_secs(300)),
+            allow_empty: false,
+            validation: validate::Mode::Lenient,
+            layout: layout::Kind::Rows,
+            cors_origins: vec!["*".to_string()],
+            cors_methods: ["GET", "HEAD", "POST"].map(String::from).to_vec(),
+            cors_headers: Vec::new(),
+            cors_max_age: None,
+            limits: Limits {
+                max_random_count: 5,
+                max_autocomplete: 50,
+                search_cache_size: 512,
+                response_cache_size: 128,
+                response_cache_ttl: Some(Duration::from_secs(300)),
+                stream_exports_over: 100_000,
+                max_saved_searches: 10_000,
+                max_upload_bytes: 10 * 1024 * 1024,
+                max_body_bytes: 64 * 1024,
+                max_rollback_steps: 10,
+                reads: Rate {
+                    per_minute: 600,
+                    burst: 100,
+                },
+                exports: Rate {
+                    per_minute: 6,
+                    burst: 3,
+                },
+                max_in_flight: 512,
+                max_exports_in_flight: 16,
+                max_connections: 1024,
+                read_timeout: Some(Duration::from_secs(10)),
+                export_timeout: Some(Duration::from_secs(60)),
+                admin_timeout: Some(Duration::from_secs(120)),
+            },
+            compression: vec![Encoding::Gzip, Encoding::Deflate],
+            compression_min_bytes: 1024,
+            cache_control: CacheControl {
+                reads: None,
+                exports: None,
+                random: Some("no-store".to_string()),
+                admin: Some("no-store".to_string()),
+            },
+            sessions: Sessions {
+                secret: None,
+                ttl: Duration::from_secs(30 * 24 * 3600),
+                max_sessions: 10_000,
+                secure: false,
+            },
+            features: Features {
+                saved_searches: true,
+                browse: true,
+                regex_search: true,
+                phonetic_search: true,
+            },
+            shared_cache: None,
+            shared_cache_ttl: Duration::from_secs(3600),
+            snapshot_dir: PathBuf::from("snapshots"),
+            audit_file: PathBuf::from("audit.log"),
+            access_log: AccessLog {
+                file: None,
+                max_bytes: 100 << 20,
+                daily: false,
+                keep: 7,
+            },
+            import_into: None,
+            check: false,
+            datasets: Vec::new(),
+            tokens: Vec::new(),
+            jwt: None,
+            allowed_ips: Vec::new(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+// What was given on the command line; `None` leaves the setting to other sources.
+#[derive(Debug, Default)]
+struct Flags {
+    config_file: Option<PathBuf>,
+    address: Option<IpAddr>,
+    port: Option<u16>,
+    listen: Vec<String>,
+    log_level: Option<log::Level>,
+    data_source: Option<DataSource>,
+    import_into: Option<String>,
+    check: bool,
+    allow_empty: bool,
+    strict: bool,
+}
+
+impl Config {
+    /// Reads the configuration again, from the same flags, for a reload.
+    pub fn reread() -> Result<Config, String> {
+        Config::from_sources(parse_flags(env::args().skip(1))?)
+    }
+
+    /// Reads the configuration for this process. Invalid flags or settings print
+    /// an error and exit, since there's nothing sensible to start without them.
+    pub fn load() -> Config {
+        let flags = match parse_flags(env::args().skip(1)) {
+            Ok(flags) => flags,
+            Err(message) => {
+                eprintln!("error: {}\n\n{}", message, USAGE);
+                process::exit(2);
+            }
+        };
+
+        match Config::from_sources(flags) {
+            Ok(config) => config,
+            Err(message) => {
+                eprintln!("error: {}", message);
+                process::exit(2);
+            }
+        }
+    }
+
+    /// The dataset configured with the top-level settings, flags and environment.
+    pub fn main_dataset(&self) -> Dataset {
+        Dataset {
+            name: None,
+            data_source: self.data_source.clone(),
+            aliases_file: self.aliases_file.clone(),
+            countries_file: self.countries_file.clone(),
+        }
+    }
+
+    /// The settings in use, by their names in the config file, with secrets left
+    /// out: tokens only show their label and scope, and URLs lose their password.
+    /// Durations are in seconds; `null` is none.
+    pub fn redacted(&self) -> serde_json::Value {
+        let seconds = |duration: Option<Duration>| duration.map(|d| d.as_secs());
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|p| p.display().to_string());
+        let cidrs = |list: &[Cidr]| list.iter().map(Cidr::to_string).collect::<Vec<_>>();
+        let tokens: Vec<serde_json::Value> = self
+            .tokens
+            .iter()
+            .map(|token| {
+                let scope = match &token.scope {
+                    Scope::Read => "read".to_string(),
+                    Scope::Write(None) => "write".to_string(),
+                    Scope::Write(Some(dataset)) => format!("write:{}", dataset),
+                    Scope::Admin => "admin".to_string(),
+                };
+                serde_json::json!({ "label": token.label, "scope": scope })
+            })
+            .collect();
+        let datasets: BTreeMap<&str, serde_json::Value> = self
+            .datasets
+            .iter()
+            .map(|dataset| {
+                let name = dataset.name.as_deref().unwrap_or_default();
+                let settings = serde_json::json!({
+                    "source": dataset.data_source.to_string(),
+                    "aliases": path(&dataset.aliases_file),
+                    "countries": path(&dataset.countries_file),
+                });
+                (name, settings)
+            })
+            .collect();
+        let limits = &self.limits;
+        serde_json::json!({
+            "config_file": path(&self.config_file),
+            "server": {
+                "address": self.address.to_string(),
+                "port": self.port,
+                "listen": self.listen.iter().map(|l| l.to_string()).collect::<Vec<_>>(),
+                "socket_mode": self.socket_mode.map(|mode| format!("{:o}", mode)),
+                "admin_address": self.admin_address.map(|a| a.to_string()),
+                "base_path": self.base_path,
+            },
+            "data": {
+                "source": self.data_source.to_string(),
+                "aliases": path(&self.aliases_file),
+                "countries": path(&self.countries_file),
+                "watch": self.watch,
+                "watch_interval": self.watch_interval.as_secs(),
+                "refresh_interval": seconds(self.refresh_interval),
+                "allow_empty": self.allow_empty,
+                "validation": match self.validation {
+                    validate::Mode::Lenient => "lenient",
+                    validate::Mode::Strict => "strict",
+                },
+                "layout": self.layout.name(),
+            },
+            "datasets": datasets,
+            "log": {
+                "level": self.log_level.name(),
+                "requests": self.request_log_level.map_or("off", log::Level::name),
+                "format": self.log_format.name(),
+                "slow_request_ms": self.slow_request.map_or(0, |d| d.as_millis() as u64),
+            },
+            "snapshots": { "directory": self.snapshot_dir.display().to_string() },
+            "audit": { "file": self.audit_file.display().to_string() },
+            "access_log": {
+                "file": self.access_log.file.as_ref().map(|f| f.display().to_string()),
+                "max_bytes": self.access_log.max_bytes,
+                "daily": self.access_log.daily,
+                "keep": self.access_log.keep,
+            },
+            "admin": {
+                "tokens": tokens,
+                "allowed_ips": cidrs(&self.allowed_ips),
+                "trusted_proxies": cidrs(&self.trusted_proxies),
+            },
+            // The secret is left out; only whether there is one shows.
+            "jwt": self.jwt.as_ref().map(|jwt| serde_json::json!({
+                "issuer": jwt.issuer,
+                "audience": jwt.audience,
+                "jwks": jwt.jwks,
+                "secret": jwt.secret.is_some(),
+                "roles_claim": jwt.roles_claim,
+                "leeway": jwt.leeway.as_secs(),
+            })),
+            "cors": {
+                "allowed_origins": self.cors_origins,
+                "allowed_methods": self.cors_methods,
+                "allowed_headers": self.cors_headers,
+                "max_age": seconds(self.cors_max_age),
+            },
+            "limits": {
+                "max_random_count": limits.max_random_count,
+                "max_autocomplete": limits.max_autocomplete,
+                "search_cache_size": limits.search_cache_size,
+                "response_cache_size": limits.response_cache_size,
+                "response_cache_ttl": seconds(limits.response_cache_ttl),
+                "stream_exports_over": limits.stream_exports_over,
+                "max_saved_searches": limits.max_saved_searches,
+                "max_upload_bytes": limits.max_upload_bytes,
+                "max_body_bytes": limits.max_body_bytes,
+                "max_rollback_steps": limits.max_rollback_steps,
+                "reads_per_minute": limits.reads.per_minute,
+                "reads_burst": limits.reads.burst,
+                "exports_per_minute": limits.exports.per_minute,
+                "exports_burst": limits.exports.burst,
+                "max_in_flight": limits.max_in_flight,
+                "max_exports_in_flight": limits.max_exports_in_flight,
+                "max_connections": limits.max_connections,
+                "read_timeout": seconds(limits.read_timeout),
+                "export_timeout": seconds(limits.export_timeout),
+                "admin_timeout": seconds(limits.admin_timeout),
+            },
+            "compression": {
+                "algorithms": self.compression.iter().map(|e| e.name()).collect::<Vec<_>>(),
+                "min_bytes": self.compression_min_bytes,
+            },
+            "cache_control": {
+                "reads": self.cache_control.reads,
+                "exports": self.cache_control.exports,
+                "random": self.cache_control.random,
+                "admin": self.cache_control.admin,
+            },
+            "cache": {
+                "redis_url": self.shared_cache.as_ref().map(|a| a.to_string()),
+                "redis_ttl": self.shared_cache_ttl.as_secs(),
+            },
+            // The secret is left out; only whether there is one shows.
+            "session": {
+                "secret": self.sessions.secret.is_some(),
+                "ttl": self.sessions.ttl.as_secs(),
+                "max_sessions": self.sessions.max_sessions,
+                "secure": self.sessions.secure,
+            },
+            "features": {
+                "saved_searches": self.features.saved_searches,
+                "browse": self.features.browse,
+                "regex_search": self.features.regex_search,
+                "phonetic_search": self.features.phonetic_search,
+            },
+        })
+    }
+
+    fn from_sources(flags: Flags) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        // An explicitly named config file has to exist; the default one doesn't.
+        let explicit_file = flags
+            .config_file
+            .clone()
+            .or_else(|| env_path("BANDS_CONFIG"));
+        let config_file = explicit_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+        if explicit_file.is_some() || config_file.exists() {
+            config.apply_file(&config_file)?;
+            config.config_file = Some(config_file);
+        }
+
+        config.apply_env()?;
+
+        let from_env: Vec<DataSource> = [
+            env_path("BANDS_FILE").map(DataSource::File),
+            env_path("BANDS_DIR").map(DataSource::Directory),
+            env_text("BANDS_URL").map(DataSource::Url),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        match from_env.len() {
+            0 => {}
+            1 => config.data_source = from_env.into_iter().next().unwrap(),
+            _ => return Err("set only one of BANDS_FILE, BANDS_DIR and BANDS_URL".into()),
+        }
+
+        if let Some(data_source) = flags.data_source {
+            config.data_source = data_source;
+        }
+        if !flags.listen.is_empty() {
+            config.listen = flags
+                .listen
+                .iter()
+                .map(|listen| Listen::parse(listen).map_err(|e| format!("--listen {}", e)))
+                .collect::<Result<_, _>>()?;
+        }
+        config.address = flags.address.unwrap_or(config.address);
+        config.port = flags.port.unwrap_or(config.port);
+        if flags.address.is_some() || flags.port.is_some() {
+            config.listen.clear();
+        }
+        config.log_level = flags.log_level.unwrap_or(config.log_level);
+
+        // Like the config file: a named one has to exist, the default one doesn't.
+        if let Some(path) = env_path("BANDS_ALIASES") {
+            config.aliases_file = Some(path);
+        }
+        if config.aliases_file.is_none() && Path::new(DEFAULT_ALIASES_FILE).exists() {
+            config.aliases_file = Some(PathBuf::from(DEFAULT_ALIASES_FILE));
+        }
+        if let Some(path) = env_path("BANDS_COUNTRIES") {
+            config.countries_file = Some(path);
+        }
+        if config.countries_file.is_none() && Path::new(DEFAULT_COUNTRIES_FILE).exists() {
+            config.countries_file = Some(PathBuf::from(DEFAULT_COUNTRIES_FILE));
+        }
+        if let Some(path) = env_path("BANDS_SNAPSHOTS") {
+            config.snapshot_dir = path;
+        }
+        if let Some(path) = env_path("BANDS_AUDIT_LOG") {
+            config.audit_file = path;
+        }
+        // Replaces the keys in the config file, like the other variables do.
+        if let Some(keys) = env_text("BANDS_ADMIN_KEYS") {
+            let keys = keys.split(',').map(|key| key.trim().to_string()).collect();
+            config
+                .set_admin_keys(keys)
+                .map_err(|message| format!("BANDS_ADMIN_KEYS: {}", message))?;
+        }
+        if let Some(jwt) = &config.jwt {
+            if jwt.issuer.is_empty() || jwt.audience.is_empty() {
+                return Err("[jwt] needs both an issuer and an audience".to_string());
+            }
+            if jwt.jwks.is_some() == jwt.secret.is_some() {
+                return Err("[jwt] needs either jwks or secret, not both".to_string());
+            }
+        }
+        config.import_into = flags.import_into;
+        config.check = flags.check;
+        config.allow_empty |= flags.allow_empty;
+        if flags.strict {
+            config.validation = validate::Mode::Strict;
+        }
+
+        Ok(config)
+    }
+
+    // Every setting in the config file can also be set by a `BANDJE_` variable
+    // naming its table and key, such as BANDJE_LIMITS_MAX_RANDOM_COUNT for
+    // `limits.max_random_count`. Lists are separated by commas.
+    fn apply_env(&mut self) -> Result<(), String> {
+        let vars: BTreeMap<String, String> = env::vars()
+            .filter(|(name, value)| name.starts_with(ENV_PREFIX) && !value.is_empty())
+            .collect();
+        let sources = ["DATA_FILE", "DATA_DIRECTORY", "DATA_URL"]
+            .iter()
+            .filter(|key| vars.contains_key(&format!("{}{}", ENV_PREFIX, key)))
+            .count();
+        if sources > 1 {
+            return Err(format!(
+                "set only one of {0}DATA_FILE, {0}DATA_DIRECTORY and {0}DATA_URL",
+                ENV_PREFIX
+            ));
+        }
+
+        for (var, text) in vars {
+            let rest = var[ENV_PREFIX.len()..].to_ascii_lowercase();
+            let name = ENV_TABLES.iter().find_map(|table| {
+                let key = rest.strip_prefix(table)?.strip_prefix('_')?;
+                Some(format!("{}.{}", table, key))
+            });
+            let Some(name) = name else {
+                return Err(format!("{}: unknown setting", var));
+            };
+            // Relative paths are relative to the working directory.
+            let here = Path::new("env");
+            let result = if ENV_LISTS.contains(&name.as_str()) {
+                let items = text
+                    .split(',')
+                    .map(|item| toml::Value::String(item.trim().into()));
+                self.apply_setting(&name, &toml::Value::Array(items.collect()), here)
+            } else {
+                // Text that reads as a number or boolean is tried as one first,
+                // so the variables need no quotes.
+                let typed = match text.as_str() {
+                    "true" | "false" => Some(toml::Value::Boolean(text == "true")),
+                    _ => text.parse().ok().map(toml::Value::Integer),
+                };
+                let text = toml::Value::String(text.clone());
+                match typed {
+                    Some(typed) => self
+                        .apply_setting(&name, &typed, here)
+                        .or_else(|e| self.apply_setting(&name, &text, here).map_err(|_| e)),
+                    None => self.apply_setting(&name, &text, here),
+                }
+            };
+            result.map_err(|message| format!("{}: {}", var, message))?;
+        }
+        Ok(())
+    }
+
+    fn apply_file(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        let document = toml::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let sources = document.get("data").map_or(0, |data| {
+            ["file", "directory", "url"]
+                .iter()
+                .filter(|key| data.contains_key(**key))
+                .count()
+        });
+        if sources > 1 {
+            return Err(format!(
+                "{}: set only one of data.file, data.directory and data.url",
+                path.display()
+            ));
+        }
+
+        for (table, entries) in document {
+            if let Some(name) = table.strip_prefix("datasets.") {
+                self.datasets.push(dataset(name, &entries, path)?);
+                continue;
+            }
+            if let Some(label) = table.strip_prefix("tokens.") {
+                let token = token(label, &entries, path)?;
+                if let Some(other) = self.tokens.iter().find(|t| t.secret == token.secret) {
+                    return Err(format!(
+                        "{}: tokens {} and {} have the same secret",
+                        path.display(),
+                        other.label,
+                        label
+                    ));
+                }
+                self.tokens.push(token);
+                continue;
+            }
+            for (key, entry) in entries {
+                let name = if table.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", table, key)
+                };
+                self.apply_setting(&name, &entry.value, path)
+                    .map_err(|message| {
+                        format!("{}: line {}: {}", path.display(), entry.line, message)
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    // `config_file` anchors relative paths, so a config works from any directory.
+    fn apply_setting(
+        &mut self,
+        name: &str,
+        value: &toml::Value,
+        config_file: &Path,
+    ) -> Result<(), String> {
+        match name {
+            "server.address" => {
+                self.address = string(name, value)?
+                    .parse()
+                    .map_err(|_| format!("'{}' must be an IP address", name))?;
+            }
+            "server.port" => self.port = integer(name, value, 1, u16::MAX as i64)? as u16,
+            // One address, or a list of them.
+            "server.listen" => {
+                let listen = match value {
+                    toml::Value::String(text) => vec![text.clone()],
+                    _ => strings(name, value)?,
+                };
+                self.listen = listen
+                    .iter()
+                    .map(|listen| Listen::parse(listen).map_err(|e| format!("'{}' {}", name, e)))
+                    .collect::<Result<_, _>>()?;
+            }
+            "server.socket_mode" => {
+                let mode = string(name, value)?;
+                let mode = u32::from_str_radix(&mode, 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o777)
+                    .ok_or(format!(
+                        "'{}' must be octal permissions such as \"660\"",
+                        name
+                    ))?;
+                self.socket_mode = Some(mode);
+            }
+            "server.base_path" => {
+                let path = string(name, value)?;
+                let path = path.trim_end_matches('/');
+                let valid = path.is_empty()
+                    || path.starts_with('/')
+                        && path
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "/-_.~".contains(c));
+                if !valid {
+                    return Err(format!(
+                        "'{}' must be a path such as \"/bandje\", of letters, digits and -_.~",
+                        name
+                    ));
+                }
+                self.base_path = path.to_string();
+            }
+            "server.admin_address" => {
+                self.admin_address = Some(string(name, value)?.parse().map_err(|_| {
+                    format!(
+                        "'{}' must be an IP address and port, like \"127.0.0.1:9090\"",
+                        name
+                    )
+                })?);
+            }
+            "data.file" | "data.directory" => {
+                let base = config_file.parent().unwrap_or(Path::new(""));
+                let path = base.join(string(name, value)?);
+                self.data_source = if name == "data.file" {
+                    DataSource::File(path)
+                } else {
+                    DataSource::Directory(path)
+                };
+            }
+            "data.url" => self.data_source = DataSource::Url(string(name, value)?),
+            "data.aliases" => {
+                let base = config_file.parent().unwrap_or(Path::new(""));
+                self.aliases_file = Some(base.join(string(name, value)?));
+            }
+            "data.countries" => {
+                let base = config_file.parent().unwrap_or(Path::new(""));
+                self.countries_file = Some(base.join(string(name, value)?));
+            }
+            "snapshots.directory" => {
+                let base = config_file.parent().unwrap_or(Path::new(""));
+                self.snapshot_dir = base.join(string(name, value)?);
+            }
+            "audit.file" => {
+                let base = config_file.parent().unwrap_or(Path::new(""));
+                self.audit_file = base.join(string(name, value)?);
+            }
+            "access_log.file" => {
+                let base = config_file.parent().unwrap_or(Path::new(""));
+                let file = string(name, value)?;
+                self.access_log.file = (!file.is_empty()).then(|| base.join(file));
+            }
+            "access_log.max_bytes" => {
+                self.access_log.max_bytes = integer(name, value, 0, 1 << 40)? as u64;
+            }
+            "access_log.daily" => self.access_log.daily = boolean(name, value)?,
+            "access_log.keep" => self.access_log.keep = integer(name, value, 0, 10_000)? as usize,
+            "admin.keys" => self.set_admin_keys(strings(name, value)?)?,
+            "admin.allowed_ips" => self.allowed_ips = cidrs(name, value)?,
+            "admin.trusted_proxies" => self.trusted_proxies = cidrs(name, value)?,
+            "log.level" => {
+                self.log_level = log::Level::parse(&string(name, value)?).ok_or(format!(
+                    "'{}' must be \"error\", \"warn\", \"info\" or \"debug\"",
+                    name
+                ))?;
+            }
+            "log.slow_request_ms" => {
+                let ms = integer(name, value, 0, 3_600_000)? as u64;
+                self.slow_request = (ms > 0).then(|| Duration::from_millis(ms));
+            }
+            "log.format" => {
+                self.log_format = log::Format::parse(&string(name, value)?)
+                    .ok_or(format!("'{}' must be \"text\" or \"json\"", name))?;
+            }
+            "log.requests" => {
+                let level = string(name, value)?;
+                self.request_log_level = match level.as_str() {
+                    "off" => None,
+                    _ => Some(log::Level::parse(&level).ok_or(format!(
+                        "'{}' must be \"off\", \"error\", \"warn\", \"info\" or \"debug\"",
+                        name
+                    ))?),
+                };
+            }
+            "data.watch" => self.watch = boolean(name, value)?,
+            "data.allow_empty" => self.allow_empty = boolean(name, value)?,
+            "data.validation" => {
+                self.validation = match string(name, value)?.as_str() {
+                    "lenient" => validate::Mode::Lenient,
+                    "strict" => validate::Mode::Strict,
+                    _ => return Err(format!("'{}' must be \"lenient\" or \"strict\"", name)),
+                };
+            }
+            "data.layout" => {
+                self.layout = layout::Kind::parse(&string(name, value)?)
+                    .ok_or_else(|| format!("'{}' must be \"rows\" or \"columns\"", name))?;
+            }
+            "data.watch_interval" => {
+                self.watch_interval = Duration::from_secs(integer(name, value, 1, 3600)? as u64);
+            }
+            "data.refresh_interval" => {
+                // 0 turns refreshing off.
+                let seconds = integer(name, value, 0, 7 * 24 * 3600)? as u64;
+                self.refresh_interval = (seconds > 0).then(|| Duration::from_secs(seconds));
+            }
+            "jwt.issuer" => self.jwt_mut().issuer = string(name, value)?,
+            "jwt.audience" => self.jwt_mut().audience = string(name, value)?,
+            "jwt.jwks" => {
+                let source = string(name, value)?;
+                if source.starts_with("https://") {
+                    return Err(format!(
+                        "'{}' can't be an https:// URL, as the server only fetches over http://; save the key set to a file and give its path",
+                        name
+                    ));
+                }
+                let base = config_file.parent().unwrap_or(Path::new(""));
+                self.jwt_mut().jwks = Some(if source.contains("://") {
+                    source
+                } else {
+                    base.join(source).display().to_string()
+                });
+            }
+            "jwt.secret" => {
+                let secret = string(name, value)?;
+                if secret.len() < 32 {
+                    return Err(format!("'{}' must be at least 32 characters", name));
+                }
+                self.jwt_mut().secret = Some(secret);
+            }
+            "jwt.roles_claim" => self.jwt_mut().roles_claim = string(name, value)?,
+            "jwt.leeway" => {
+                self.jwt_mut().leeway = Duration::from_secs(integer(name, value, 0, 3600)? as u64);
+            }
+            "cors.allowed_origins" => {
+                let origins = strings(name, value)?;
+                if let Some(origin) = origins.iter().find(|o| !is_origin(o)) {
+                    return Err(format!(
+                        "'{}' entries must be \"*\" or like \"https://example.com\" or \"https://*.example.com\", not \"{}\"",
+                        name, origin
+                    ));
+                }
+                self.cors_origins = origins;
+            }
+            "cors.allowed_methods" => {
+                let methods = strings(name, value)?;
+                let valid = |method: &String| {
+                    method == "*" || axum::http::Method::from_bytes(method.as_bytes()).is_ok()
+                };
+                if let Some(method) = methods.iter().find(|m| !valid(m)) {
+                    return Err(format!("'{}': \"{}\" isn't an HTTP method", name, method));
+                }
+                self.cors_methods = methods;
+            }
+            "cors.allowed_headers" => {
+                let headers = strings(name, value)?;
+                let valid = |header: &String| {
+                    header == "*" || axum::http::HeaderName::from_bytes(header.as_bytes()).is_ok()
+                };
+                if let Some(header) = headers.iter().find(|h| !valid(h)) {
+                    return Err(format!("'{}': \"{}\" isn't a header name", name, header));
+                }
+                self.cors_headers = headers;
+            }
+            "cors.max_age" => {
+                // 0 leaves it to the browser.
+                let seconds = integer(name, value, 0, 7 * 24 * 3600)? as u64;
+                self.cors_max_age = (seconds > 0).then(|| Duration::from_secs(seconds));
+            }
+            "limits.max_random_count" => {
+                self.limits.max_random_count = integer(name, value, 1, 1000)? as usize;
+            }
+            "limits.max_autocomplete" => {
+                self.limits.max_autocomplete = integer(name, value, 1, 1000)? as usize;
+            }
+            "limits.search_cache_size" => {
+                self.limits.search_cache_size = integer(name, value, 0, 1_000_000)? as usize;
+            }
+            "limits.response_cache_size" => {
+                self.limits.response_cache_size = integer(name, value, 0, 1_000_000)? as usize;
+            }
+            "limits.response_cache_ttl" => self.limits.response_cache_ttl = timeout(name, value)?,
+            "limits.stream_exports_over" => {
+                self.limits.stream_exports_over = integer(name, value, 0, 100_000_000)? as usize;
+            }
+            "limits.max_saved_searches" => {
+                self.limits.max_saved_searches = integer(name, value, 0, 10_000_000)? as usize;
+            }
+            "limits.max_upload_bytes" => {
+                self.limits.max_upload_bytes = integer(name, value, 1, 1 << 30)? as usize;
+            }
+            "limits.max_body_bytes" => {
+                self.limits.max_body_bytes = integer(name, value, 1, 1 << 30)? as usize;
+            }
+            "limits.max_rollback_steps" => {
+                self.limits.max_rollback_steps = integer(name, value, 0, 1000)? as usize;
+            }
+            "limits.reads_per_minute" => {
+                self.limits.reads.per_minute = integer(name, value, 0, 1_000_000)? as u32;
+            }
+            "limits.reads_burst" => {
+                self.limits.reads.burst = integer(name, value, 1, 1_000_000)? as u32;
+            }
+            "limits.exports_per_minute" => {
+                self.limits.exports.per_minute = integer(name, value, 0, 1_000_000)? as u32;
+            }
+            "limits.exports_burst" => {
+                self.limits.exports.burst = integer(name, value, 1, 1_000_000)? as u32;
+            }
+            "limits.max_in_flight" => {
+                self.limits.max_in_flight = integer(name, value, 0, 1_000_000)? as usize;
+            }
+            "limits.max_exports_in_flight" => {
+                self.limits.max_exports_in_flight = integer(name, value, 0, 1_000_000)? as usize;
+            }
+            "limits.max_connections" => {
+                self.limits.max_connections = integer(name, value, 0, 1_000_000)? as usize;
+            }
+            "limits.read_timeout" => self.limits.read_timeout = timeout(name, value)?,
+            "limits.export_timeout" => self.limits.export_timeout = timeout(name, value)?,
+            "limits.admin_timeout" => self.limits.admin_timeout = timeout(name, value)?,
+            "cache.redis_url" => {
+                let url = string(name, value)?;
+                self.shared_cache =
+                    Some(redis::Address::parse(&url).map_err(|e| format!("'{}': {}", name, e))?);
+            }
+            "cache.redis_ttl" => {
+                self.shared_cache_ttl =
+                    Duration::from_secs(integer(name, value, 1, 30 * 24 * 3600)? as u64);
+            }
+            "compression.algorithms" => {
+                self.compression = strings(name, value)?
+                    .iter()
+                    .map(|algorithm| {
+                        Encoding::parse(algorithm).ok_or(format!(
+                            "'{}': \"{}\" isn't available; use \"gzip\" or \"deflate\"",
+                            name, algorithm
+                        ))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "compression.min_bytes" => {
+                self.compression_min_bytes = integer(name, value, 0, 1 << 30)? as usize;
+            }
+            "cache_control.reads" => self.cache_control.reads = cache_control(name, value)?,
+            "cache_control.exports" => self.cache_control.exports = cache_control(name, value)?,
+            "cache_control.random" => self.cache_control.random = cache_control(name, value)?,
+            "cache_control.admin" => self.cache_control.admin = cache_control(name, value)?,
+            "session.secret" => {
+                let secret = string(name, value)?;
+                if secret.len() < 32 {
+                    return Err(format!("'{}' must be at least 32 characters", name));
+                }
+                self.sessions.secret = Some(secret);
+            }
+            "session.ttl" => {
+                self.sessions.ttl =
+                    Duration::from_secs(integer(name, value, 60, 365 * 24 * 3600)? as u64);
+            }
+            "session.max_sessions" => {
+                self.sessions.max_sessions = integer(name, value, 0, 10_000_000)? as usize;
+            }
+            "session.secure" => self.sessions.secure = boolean(name, value)?,
+            "features.saved_searches" => self.features.saved_searches = boolean(name, value)?,
+            "features.browse" => self.features.browse = boolean(name, value)?,
+            "features.regex_search" => self.features.regex_search = boolean(name, value)?,
+            "features.phonetic_search" => self.features.phonetic_search = boolean(name, value)?,
+            _ => return Err(format!("unknown setting '{}'", name)),
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    // Any `jwt.` setting turns JWTs on.
+    fn jwt_mut(&mut self) -> &mut Jwt {
+        self.jwt.get_or_insert_with(Jwt::default)
+    }
+
+    /// Where the public API is served: `server.listen`, or else the address
+    /// and port.
+    pub fn listeners(&self) -> Vec<Listen> {
+        if self.listen.is_empty() {
+            vec![Listen::Tcp(SocketAddr::new(self.address, self.port))]
+        } else {
+            self.listen.clone()
+        }
+    }
+
+    // Admin keys are tokens with the admin scope, labelled by their position:
+    // "admin-key-1" and so on.
+    fn set_admin_keys(&mut self, keys: Vec<String>) -> Result<(), String> {
+        self.tokens
+            .retain(|token| !token.label.starts_with("admin-key-"));
+        for (i, secret) in keys.into_iter().enumerate() {
+            if secret.len() < 16 {
+                return Err(format!(
+                    "admin key {} must be at least 16 characters",
+                    i + 1
+                ));
+            }
+            if let Some(other) = self.tokens.iter().find(|t| t.secret == secret) {
+                return Err(format!(
+                    "admin key {} is the same as {}",
+                    i + 1,
+                    other.label
+                ));
+            }
+            self.tokens.push(Token {
+                label: format!("admin-key-{}", i + 1),
+                secret,
+                scope: Scope::Admin,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Reads a `[datasets.<name>]` table, which names one data source like the
+// `[data]` table does, and optionally the dataset's own aliases and countries.
+fn dataset(
+    name: &str,
+    entries: &BTreeMap<String, toml::Entry>,
+    config_file: &Path,
+) -> Result<Dataset, String> {
+    let first_line = entries.values().map(|entry| entry.line).min().unwrap_or(0);
+    let at = |line: usize| format!("{}: line {}", config_file.display(), line);
+    check_dataset_name(name).map_err(|message| format!("{}: {}", at(first_line), message))?;
+
+    let base = config_file.parent().unwrap_or(Path::new(""));
+    let mut data_source = None;
+    let mut dataset = Dataset {
+        name: Some(name.to_string()),
+        data_source: DataSource::File(PathBuf::new()),
+        aliases_file: None,
+        countries_file: None,
+    };
+    for (key, entry) in entries {
+        let setting = format!("datasets.{}.{}", name, key);
+        let value =
+            string(&setting, &entry.value).map_err(|e| format!("{}: {}", at(entry.line), e))?;
+        let source = match key.as_str() {
+            "file" => DataSource::File(base.join(value)),
+            "directory" => DataSource::Directory(base.join(value)),
+            "url" => DataSource::Url(value),
+            "aliases" => {
+                dataset.aliases_file = Some(base.join(value));
+                continue;
+            }
+            "countries" => {
+                dataset.countries_file = Some(base.join(value));
+                continue;
+            }
+            _ => return Err(format!("{}: unknown setting '{}'", at(entry.line), setting)),
+        };
+        if data_source.replace(source).is_some() {
+            return Err(format!(
+                "{}: set only one of file, directory and url for dataset '{}'",
+                at(entry.line),
+                name
+            ));
+        }
+    }
+    dataset.data_source = data_source.ok_or_else(|| {
+        format!(
+            "{}: dataset '{}' needs a file, directory or url",
+            at(first_line),
+            name
+        )
+    })?;
+    Ok(dataset)
+}
+
+// Reads a `[tokens.<label>]` table: the `token` itself, its `scope` ("read",
+// "write" or "admin"), and for write tokens the `dataset` they can change,
+// the main one when left out.
+fn token(
+    label: &str,
+    entries: &BTreeMap<String, toml::Entry>,
+    config_file: &Path,
+) -> Result<Token, String> {
+    let first_line = entries.values().map(|entry| entry.line).min().unwrap_or(0);
+    let at = |line: usize| format!("{}: line {}", config_file.display(), line);
+    let (mut secret, mut scope, mut dataset) = (None, None, None);
+    for (key, entry) in entries {
+        let setting = format!("tokens.{}.{}", label, key);
+        let value =
+            string(&setting, &entry.value).map_err(|e| format!("{}: {}", at(entry.line), e))?;
+        match key.as_str() {
+            "token" if value.trim().len() < 16 => {
+                return Err(format!(
+                    "{}: '{}' must be at least 16 characters",
+                    at(entry.line),
+                    setting
+                ))
+            }
+            "token" => secret = Some(value.trim().to_string()),
+            "scope" => scope = Some((value, entry.line)),
+            "dataset" => dataset = Some(value),
+            _ => return Err(format!("{}: unknown setting '{}'", at(entry.line), setting)),
+        }
+    }
+    let secret =
+        secret.ok_or_else(|| format!("{}: token '{}' needs a token", at(first_line), label))?;
+    let scope = match scope {
+        Some((scope, _)) if scope == "read" && dataset.is_none() => Scope::Read,
+        Some((scope, _)) if scope == "write" => Scope::Write(dataset),
+        Some((scope, _)) if scope == "admin" && dataset.is_none() => Scope::Admin,
+        Some((scope, line)) if scope == "read" || scope == "admin" => {
+            return Err(format!(
+                "{}: only write tokens are for one dataset, not {} ones",
+                at(line),
+                scope
+            ))
+        }
+        Some((_, line)) => {
+            return Err(format!(
+                "{}: 'tokens.{}.scope' must be \"read\", \"write\" or \"admin\"",
+                at(line),
+                label
+            ))
+        }
+        None => {
+            return Err(format!(
+                "{}: token '{}' needs a scope",
+                at(first_line),
+                label
+            ))
+        }
+    };
+    Ok(Token {
+        label: label.to_string(),
+        secret,
+        scope,
+    })
+}
+
+/// Fails with the reason when `name` can't be a dataset's name.
+pub fn check_dataset_name(name: &str) -> Result<(), String> {
+    let allowed = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !RESERVED_NAMES.contains(&name);
+    if allowed {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{}' can't name a dataset; use lower-case letters, digits and dashes, other than {}",
+            name,
+            RESERVED_NAMES.join(", ")
+        ))
+    }
+}
+
+// --- Setting Values ---
+
+fn wrong_type(name: &str, expected: &str, value: &toml::Value) -> String {
+    format!("'{}' must be {}, not {}", name, expected, value.type_name())
+}
+
+fn string(name: &str, value: &toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(text) => Ok(text.clone()),
+        _ => Err(wrong_type(name, "a string", value)),
+    }
+}
+
+fn strings(name: &str, value: &toml::Value) -> Result<Vec<String>, String> {
+    match value {
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                toml::Value::String(text) => Ok(text.clone()),
+                _ => Err(wrong_type(name, "an array of strings", value)),
+            })
+            .collect(),
+        _ => Err(wrong_type(name, "an array of strings", value)),
+    }
+}
+
+fn cidrs(name: &str, value: &toml::Value) -> Result<Vec<Cidr>, String> {
+    strings(name, value)?
+        .iter()
+        .map(|text| Cidr::parse(text).map_err(|e| format!("'{}': {}", name, e)))
+        .collect()
+}
+
+// A number of seconds, where 0 means no limit.
+fn timeout(name: &str, value: &toml::Value) -> Result<Option<Duration>, String> {
+    let seconds = integer(name, value, 0, 24 * 3600)? as u64;
+    Ok((seconds > 0).then(|| Duration::from_secs(seconds)))
+}
+
+// A header value, where "" means no header.
+fn cache_control(name: &str, value: &toml::Value) -> Result<Option<String>, String> {
+    let policy = string(name, value)?;
+    if policy.is_empty() {
+        return Ok(None);
+    }
+    HeaderValue::from_str(&policy)
+        .map_err(|_| format!("'{}' can't be sent as a header: {:?}", name, policy))?;
+    Ok(Some(policy))
+}
+
+fn integer(name: &str, value: &toml::Value, min: i64, max: i64) -> Result<i64, String> {
+    match value {
+        toml::Value::Integer(n) if (min..=max).contains(n) => Ok(*n),
+        toml::Value::Integer(_) => Err(format!("'{}' must be between {} and {}", name, min, max)),
+        _ => Err(wrong_type(name, "an integer", value)),
+    }
+}
+
+fn boolean(name: &str, value: &toml::Value) -> Result<bool, String> {
+    match value {
+        toml::Value::Boolean(flag) => Ok(*flag),
+        _ => Err(wrong_type(name, "true or false", value)),
+    }
+}
+
+/// Whether `origin` is one of `allowed`, which can be "*" or start a host with
+/// "*." for any subdomain.
+pub fn origin_allowed(allowed: &[String], origin: &str) -> bool {
+    allowed
+        .iter()
+        .any(|pattern| match pattern.split_once("*.") {
+            _ if pattern == "*" => true,
+            Some((scheme, domain)) => origin
+                .strip_prefix(scheme)
+                .and_then(|host| host.strip_suffix(domain)?.strip_suffix('.'))
+                .is_some_and(|subdomain| !subdomain.is_empty() && !subdomain.contains('/')),
+            None => origin == pattern,
+        })
+}
+
+// A browser origin: scheme, host and optional port, with no path. The host can
+// start with "*." for any subdomain.
+fn is_origin(text: &str) -> bool {
+    if text == "*" {
+        return true;
+    }
+    let text = text.replacen("://*.", "://", 1);
+    let rest = text
+        .strip_prefix("https://")
+        .or_else(|| text.strip_prefix("http://"));
+    rest.is_some_and(|host| {
+        !host.is_empty() && !host.contains(['/', '*']) && host.chars().all(|c| c.is_ascii_graphic())
+    })
+}
+
+// --- Command Line and Environment ---
+
+fn env_text(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+fn parse_flags(mut args: impl Iterator<Item = String>) -> Result<Flags, String> {
+    let mut flags = Flags::default();
+
+    while let Some(arg) = args.next() {
+        // Both `--flag value` and `--flag=value` are accepted.
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let mut value = |what: &str| {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(format!("{} needs {}", flag, what))
+        };
+        match flag.as_str() {
+            "--config" => flags.config_file = Some(PathBuf::from(value("a path")?)),
+            "--host" => {
+                let address = value("an IP address")?;
+                flags.address = Some(
+                    address
+                        .parse()
+                        .map_err(|_| format!("--host needs an IP address, not '{}'", address))?,
+                );
+            }
+            "--port" => {
+                let port = value("a port")?;
+                flags.port = Some(port.parse().ok().filter(|port| *port > 0).ok_or(format!(
+                    "--port needs a port from 1 to 65535, not '{}'",
+                    port
+                ))?);
+            }
+            "--listen" => flags.listen.push(value("an address")?),
+            "--log-level" => {
+                let level = value("a level")?;
+                flags.log_level = Some(log::Level::parse(&level).ok_or(format!(
+                    "--log-level needs \"error\", \"warn\", \"info\" or \"debug\", not '{}'",
+                    level
+                ))?);
+            }
+            "--import-into" => flags.import_into = Some(value("a path or URL")?),
+            "--check" if inline_value.is_none() => flags.check = true,
+            "--allow-empty" if inline_value.is_none() => flags.allow_empty = true,
+            "--strict" if inline_value.is_none() => flags.strict = true,
+            "--data-file" | "--data-dir" | "--data-url" => {
+                if flags.data_source.is_some() {
+                    return Err(
+                        "give only one of --data-file, --data-dir and --data-url, once".to_string(),
+                    );
+                }
+                flags.data_source = Some(match flag.as_str() {
+                    "--data-file" => DataSource::File(PathBuf::from(value("a path")?)),
+                    "--data-dir" => DataSource::Directory(PathBuf::from(value("a path")?)),
+                    _ => DataSource::Url(value("a URL")?),
+                });
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unexpected argument '{}'", flag)),
+        }
+    }
+
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_addresses_are_ip_and_port_or_a_socket() {
+        assert_eq!(
+            Listen::parse("[::1]:3000"),
+            Ok(Listen::Tcp("[::1]:3000".parse().unwrap()))
+        );
+        assert_eq!(
+            Listen::parse("unix:/run/bandje.sock"),
+            Ok(Listen::Unix(PathBuf::from("/run/bandje.sock")))
+        );
+        assert!(Listen::parse("unix:").is_err());
+        assert!(Listen::parse("localhost:3000").is_err());
+        assert!(Listen::parse("127.0.0.1").is_err());
+        assert_eq!(
+            Listen::parse("127.0.0.1:8080").unwrap().to_string(),
+            "http://127.0.0.1:8080"
+        );
+    }
+
+    #[test]
+    fn origins_match_exactly_or_by_subdomain() {
+        let allowed = ["https://example.com", "https://*.festivals.nl"].map(String::from);
+        assert!(origin_allowed(&allowed, "https://example.com"));
+        assert!(!origin_allowed(&allowed, "http://example.com"));
+        assert!(!origin_allowed(&allowed, "https://example.com.evil.org"));
+        assert!(origin_allowed(&allowed, "https://pinkpop.festivals.nl"));
+        assert!(origin_allowed(&allowed, "https://a.b.festivals.nl"));
+        assert!(!origin_allowed(&allowed, "https://festivals.nl"));
+        assert!(!origin_allowed(&allowed, "https://evilfestivals.nl"));
+        assert!(origin_allowed(&["*".to_string()], "https://anything.org"));
+        assert!(is_origin("https://*.festivals.nl") && !is_origin("https://*"));
+    }
+}
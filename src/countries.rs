@@ -0,0 +1,143 @@
+// Where artists come from, from an optional countries file giving each artist's
+// two-letter country code, for datasets that don't record it themselves:
+//
+//     { "Muse": "GB", "De Staat": "NL", "Sigur Rós": "IS" }
+//
+// Names are matched by their search key, like aliases, and after aliases are
+// applied, so listing the canonical name covers its variants. A country given in
+// the data itself wins over the file's.
+
+use crate::data::{BandData, RawFile};
+use crate::search;
+use crate::validate;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub struct Countries {
+    // Search key of each artist to their upper-case country code.
+    by_artist: HashMap<String, String>,
+}
+
+/// Reads the countries file at `path`, if one is configured.
+pub fn read(path: Option<&Path>) -> Result<Option<RawFile>, String> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(Some(RawFile {
+        origin: path.display().to_string(),
+        bytes,
+    }))
+}
+
+impl Countries {
+    /// Parses what `read` returned; no file means no countries.
+    pub fn parse(file: Option<&RawFile>) -> Result<Self, String> {
+        let Some(file) = file else {
+            return Ok(Countries::default());
+        };
+        let entries: BTreeMap<String, String> = serde_json::from_slice(&file.bytes)
+            .map_err(|e| format!("Failed to parse {}: {}", file.origin, e))?;
+
+        let mut countries = Countries::default();
+        for (artist, country) in entries {
+            let key = search::normalize(&artist);
+            if key.is_empty() {
+                return Err(format!("{}: an artist name is empty", file.origin));
+            }
+            if !validate::is_country(&country) {
+                return Err(format!(
+                    "{}: \"{}\" isn't a two-letter country code like NL (for \"{}\")",
+                    file.origin, country, artist
+                ));
+            }
+            let country = country.to_ascii_uppercase();
+            match countries.by_artist.get(&key) {
+                Some(other) if *other != country => {
+                    return Err(format!(
+                        "{}: \"{}\" is listed as both {} and {}",
+                        file.origin, artist, other, country
+                    ));
+                }
+                _ => {
+                    countries.by_artist.insert(key, country);
+                }
+            }
+        }
+        Ok(countries)
+    }
+
+    /// Gives every performance in `data` without a country the one listed for
+    /// its artist.
+    pub fn apply(&self, data: &mut BandData) {
+        if self.by_artist.is_empty() {
+            return;
+        }
+        let acts = data
+            .festivals
+            .iter_mut()
+            .flat_map(|festival| &mut festival.years)
+            .flat_map(|edition| &mut edition.artists)
+            .filter(|act| act.country.is_none());
+        for act in acts {
+            act.country = self.by_artist.get(&search::normalize(&act.name)).cloned();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, Festival, FestivalYear};
+    use crate::schema;
+
+    fn countries(json: &str) -> Result<Countries, String> {
+        Countries::parse(Some(&RawFile {
+            origin: "countries.json".to_string(),
+            bytes: json.as_bytes().to_vec(),
+        }))
+    }
+
+    #[test]
+    fn fills_in_countries_the_data_leaves_out() {
+        let countries = countries(r#"{ "sigur ros": "is", "Muse": "GB" }"#).unwrap();
+        let mut muse = Act::named("Muse".to_string());
+        muse.country = Some("US".to_string());
+        let mut data = BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![Festival {
+                name: "Pinkpop".to_string(),
+                years: vec![FestivalYear {
+                    year: 2008,
+                    artists: vec![
+                        Act::named("Sigur Rós".to_string()),
+                        muse,
+                        Act::named("Editors".to_string()),
+                    ],
+                    ..FestivalYear::default()
+                }],
+                ..Festival::default()
+            }],
+        };
+
+        countries.apply(&mut data);
+        let found: Vec<Option<&str>> = data.festivals[0].years[0]
+            .artists
+            .iter()
+            .map(|act| act.country.as_deref())
+            .collect();
+        assert_eq!(found, [Some("IS"), Some("US"), None]);
+    }
+
+    #[test]
+    fn rejects_codes_that_arent_countries() {
+        assert_eq!(
+            countries(r#"{ "Muse": "England" }"#).unwrap_err(),
+            "countries.json: \"England\" isn't a two-letter country code like NL (for \"Muse\")"
+        );
+        assert!(countries(r#"{ "Muse": "GB", "muse": "US" }"#).is_err());
+        assert!(countries(r#"{ "Muse": ["GB"] }"#).is_err());
+    }
+}
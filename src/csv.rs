@@ -0,0 +1,282 @@
+// Lineups kept in a spreadsheet and saved as CSV: a header row naming the
+// columns, then one row per performance.
+//
+//     name,festival,year
+//     Muse,Pinkpop,2008
+//
+// The columns can come in any order and others may be present; `artist` is
+// accepted for `name`. Spreadsheets saved where the comma is the decimal
+// separator use semicolons instead, so the separator (comma, semicolon or tab)
+// is whichever one splits the header into the expected columns. Festivals and
+// editions keep the order they first appear in, and artists their row order.
+//
+// Optional columns fill in the rest of a performance: `genres` (or `genre`,
+// several separated by commas or semicolons within the field), `country`,
+// `stage`, `day`, `start_time` (or `start`) and `headliner` (yes or no). Empty
+// fields leave them out.
+
+use crate::data::{Act, BandData, Festival, FestivalYear};
+
+const SEPARATORS: [char; 3] = [',', ';', '\t'];
+
+/// Reads a CSV file as a dataset.
+pub fn read(bytes: &[u8]) -> Result<BandData, String> {
+    let text = std::str::from_utf8(bytes).map_err(|e| format!("not UTF-8 text: {}", e))?;
+    // Spreadsheet programs like to start the file with a byte order mark.
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let header_line = text.lines().next().unwrap_or("");
+    let (separator, columns) = SEPARATORS
+        .iter()
+        .find_map(|&separator| {
+            let names: Vec<String> = header_line
+                .split(separator)
+                .map(|name| name.trim().trim_matches('"').to_lowercase())
+                .collect();
+            Columns::find(&names).map(|columns| (separator, columns))
+        })
+        .ok_or_else(|| {
+            "line 1: the header needs name (or artist), festival and year columns".to_string()
+        })?;
+
+    let mut data = BandData::default();
+    for (line, row) in records(text, separator)?.into_iter().skip(1) {
+        // Trailing empty rows are common in exported sheets.
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |index: usize, column: &str| {
+            row.get(index)
+                .map(|value| value.trim())
+                .ok_or_else(|| format!("line {}: there is no {} column", line, column))
+        };
+        let name = field(columns.name, "name")?;
+        let festival = field(columns.festival, "festival")?;
+        let year = field(columns.year, "year")?;
+        let year: u16 = year
+            .parse()
+            .map_err(|_| format!("line {}: \"{}\" is not a year", line, year))?;
+
+        let festival = match data
+            .festivals
+            .iter()
+            .position(|f| f.name.eq_ignore_ascii_case(festival))
+        {
+            Some(index) => &mut data.festivals[index],
+            None => {
+                data.festivals.push(Festival {
+                    name: festival.to_string(),
+                    ..Festival::default()
+                });
+                data.festivals.last_mut().unwrap()
+            }
+        };
+        let edition = match festival.years.iter().position(|y| y.year == year) {
+            Some(index) => &mut festival.years[index],
+            None => {
+                festival.years.push(FestivalYear {
+                    year,
+                    ..FestivalYear::default()
+                });
+                festival.years.last_mut().unwrap()
+            }
+        };
+        let optional = |index: Option<usize>| {
+            let value = row.get(index?)?.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        };
+        let genres = optional(columns.genres)
+            .iter()
+            .flat_map(|genres| genres.split([',', ';']))
+            .map(str::trim)
+            .filter(|genre| !genre.is_empty())
+            .map(str::to_string)
+            .collect();
+        let headliner = match optional(columns.headliner).map(|v| v.to_lowercase()) {
+            None => false,
+            Some(value) => match value.as_str() {
+                "yes" | "y" | "true" | "1" | "x" => true,
+                "no" | "n" | "false" | "0" => false,
+                _ => {
+                    return Err(format!(
+                        "line {}: \"{}\" is not yes or no for headliner",
+                        line, value
+                    ))
+                }
+            },
+        };
+        edition.artists.push(Act {
+            name: name.to_string(),
+            genres,
+            country: optional(columns.country),
+            stage: optional(columns.stage),
+            day: optional(columns.day),
+            start_time: optional(columns.start_time),
+            headliner,
+        });
+    }
+    Ok(data)
+}
+
+// Where the needed columns, and any optional ones, are in each row.
+struct Columns {
+    name: usize,
+    festival: usize,
+    year: usize,
+    genres: Option<usize>,
+    country: Option<usize>,
+    stage: Option<usize>,
+    day: Option<usize>,
+    start_time: Option<usize>,
+    headliner: Option<usize>,
+}
+
+impl Columns {
+    fn find(names: &[String]) -> Option<Self> {
+        let position = |wanted: &[&str]| names.iter().position(|n| wanted.contains(&n.as_str()));
+        Some(Columns {
+            name: position(&["name", "artist"])?,
+            festival: position(&["festival"])?,
+            year: position(&["year"])?,
+            genres: position(&["genres", "genre"]),
+            country: position(&["country"]),
+            stage: position(&["stage"]),
+            day: position(&["day"]),
+            start_time: position(&["start_time", "start"]),
+            headliner: position(&["headliner"]),
+        })
+    }
+}
+
+// Splits `text` into rows of fields, each with the line it starts on. Fields may
+// be quoted, with `""` for a quote inside; quoted fields can span lines.
+fn records(text: &str, separator: char) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            '\n' if quoted => {
+                line += 1;
+                field.push(c);
+            }
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push((row_line, std::mem::take(&mut row)));
+                line += 1;
+                row_line = line;
+            }
+            c if c == separator && !quoted => row.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("line {}: a quoted field is never closed", row_line));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lineups(data: &BandData) -> Vec<(&str, u16, Vec<&str>)> {
+        data.festivals
+            .iter()
+            .flat_map(|f| {
+                f.years.iter().map(|y| {
+                    let names = y.artists.iter().map(|a| a.name.as_str()).collect();
+                    (f.name.as_str(), y.year, names)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_rows_into_festivals_and_editions() {
+        let csv = "festival,year,name,stage\r\n\
+                   Pinkpop,2008,Muse,Main\r\n\
+                   Lowlands,2008,\"Crosby, Stills & Nash\",Alpha\r\n\
+                   Pinkpop,2008,\"The \"\"Bird\"\" and the Bee\",\r\n\
+                   Pinkpop,2009,Editors,\r\n\
+                   ,,,\r\n";
+        let data = read(csv.as_bytes()).unwrap();
+        assert_eq!(
+            lineups(&data),
+            [
+                ("Pinkpop", 2008, vec!["Muse", "The \"Bird\" and the Bee"]),
+                ("Pinkpop", 2009, vec!["Editors"]),
+                ("Lowlands", 2008, vec!["Crosby, Stills & Nash"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_semicolons_and_reports_lines() {
+        let csv = "\u{feff}Artist;Festival;Year\nMuse;Pinkpop;2008\n";
+        assert_eq!(
+            lineups(&read(csv.as_bytes()).unwrap()),
+            [("Pinkpop", 2008, vec!["Muse"])]
+        );
+
+        let csv = "name,festival,year\nMuse,Pinkpop,2008\n\"Editors\nLive\",Pinkpop,next\n";
+        assert_eq!(
+            read(csv.as_bytes()).unwrap_err(),
+            "line 3: \"next\" is not a year"
+        );
+        assert!(read(b"name,festival\nMuse,Pinkpop\n").is_err());
+        assert_eq!(
+            read(b"name,festival,year\nMuse,Pinkpop\n").unwrap_err(),
+            "line 2: there is no year column"
+        );
+    }
+
+    #[test]
+    fn reads_optional_columns() {
+        let csv = "name;festival;year;genre;stage;day;start;headliner;country\n\
+                   Slayer;Graspop;2019;\"metal, thrash\";Main;Saturday;21:30;Yes;US\n\
+                   Muse;Graspop;2019\n";
+        let data = read(csv.as_bytes()).unwrap();
+        let [slayer, muse] = &data.festivals[0].years[0].artists[..] else {
+            panic!("expected two artists");
+        };
+        assert_eq!(slayer.genres, ["metal", "thrash"]);
+        assert_eq!(
+            (
+                slayer.stage.as_deref(),
+                slayer.day.as_deref(),
+                slayer.start_time.as_deref()
+            ),
+            (Some("Main"), Some("Saturday"), Some("21:30"))
+        );
+        assert!(slayer.headliner && !muse.headliner);
+        assert_eq!(slayer.country.as_deref(), Some("US"));
+        assert!(muse.genres.is_empty() && muse.stage.is_none() && muse.start_time.is_none());
+        assert_eq!(
+            read(b"name,festival,year,headliner\nMuse,Pinkpop,2008,maybe\n").unwrap_err(),
+            "line 2: \"maybe\" is not yes or no for headliner"
+        );
+    }
+}
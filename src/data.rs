@@ -0,0 +1,436 @@
+// The dataset as it is stored on disk, and reading it from the configured source:
+// a single file, a directory of files that are merged together, or a URL. Files
+// are JSON, or CSV when their name ends in `.csv`.
+
+use crate::aliases::Aliases;
+use crate::countries::Countries;
+use crate::validate::{self, Problem, Report};
+use crate::{csv, fetch, parallel, schema, store, toml, yaml};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// --- Data Structures ---
+
+// Structs to parse the initial JSON data from the file, in the current format;
+// `schema` reads older ones.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BandData {
+    // Always `schema::CURRENT` once read, so saved data says what it follows.
+    pub schema_version: u64,
+    pub festivals: Vec<Festival>,
+}
+
+impl Default for BandData {
+    fn default() -> Self {
+        BandData {
+            schema_version: schema::CURRENT,
+            festivals: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Festival {
+    pub name: String,
+    // Where it's held, when known: a place such as "Landgraaf" and the country's
+    // two-letter code, such as "NL".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    // Where the lineups were taken from, unless an edition says otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    pub years: Vec<FestivalYear>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FestivalYear {
+    pub year: u16,
+    // The first and last day of the edition as "2008-05-31", when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    // An edition that was announced but didn't take place (2020, mostly), with
+    // its lineup as announced.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+    // Free text about the edition, such as why it was cancelled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    pub artists: Vec<Act>,
+}
+
+/// Where a lineup was taken from.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Source {
+    // An http:// or https:// address, such as the festival's own lineup page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    // The day it was looked at, as "2024-03-01".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retrieved: Option<String>,
+}
+
+/// One artist on an edition's lineup.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Act {
+    pub name: String,
+    // E.g. ["metal"]. Performances that list none get the genres the same artist
+    // has elsewhere in the data.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    // Where the artist comes from, as a two-letter code such as "NL". Like genres,
+    // it only needs to be given once per artist.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    // Where and when the set was, when known: a stage such as "Main", a day of
+    // the week such as "saturday" (in any case), and a start time such as "21:30".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+    // Whether the artist topped the bill that edition.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub headliner: bool,
+}
+
+impl Act {
+    pub fn named(name: String) -> Self {
+        Act {
+            name,
+            ..Act::default()
+        }
+    }
+}
+
+// --- Data Sources ---
+
+/// Where the dataset is read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataSource {
+    // A JSON, CSV, YAML or TOML file, or a SQLite database (`.db`, `.sqlite`) filled with --import-into.
+    File(PathBuf),
+    // Every data file (`*.json`, `*.csv`, `*.yaml`, `*.yml`, `*.toml`) directly
+    // inside, e.g. one per festival.
+    Directory(PathBuf),
+    // An http:// URL serving the same format as a single file, an s3:// object,
+    // or a postgres:// database filled with --import-into.
+    Url(String),
+}
+
+impl fmt::Display for DataSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataSource::File(path) => write!(f, "{}", path.display()),
+            DataSource::Directory(path) => {
+                write!(f, "{}/*.{{json,csv,yaml,yml,toml}}", path.display())
+            }
+            DataSource::Url(url) => f.write_str(&redact_password(url)),
+        }
+    }
+}
+
+/// One undecoded piece of the dataset and where it came from.
+pub struct RawFile {
+    // A path or URL, for error messages.
+    pub origin: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Fetches the raw content of every file making up the dataset.
+pub fn read(source: &DataSource) -> Result<Vec<RawFile>, String> {
+    if let Some(location) = store::Location::of(source) {
+        return Ok(vec![read_store(source, location)?]);
+    }
+    match source {
+        DataSource::File(path) => Ok(vec![read_file(path)?]),
+        DataSource::Directory(dir) => {
+            let files = data_files(dir)?;
+            if files.is_empty() {
+                return Err(format!(
+                    "{} contains no data files (.json, .csv, .yaml, .yml or .toml)",
+                    dir.display()
+                ));
+            }
+            files.iter().map(|path| read_file(path)).collect()
+        }
+        DataSource::Url(url) => {
+            let bytes = fetch_url(url).map_err(|e| format!("Failed to fetch {}: {}", source, e))?;
+            Ok(vec![RawFile {
+                origin: source.to_string(),
+                bytes,
+            }])
+        }
+    }
+}
+
+/// Parses, tidies, renames aliases in, fills in countries for, checks and merges
+/// what `read` returned, along with what the checks found. Even a single file goes through the merge,
+/// so a file repeating an edition is caught too. In strict mode, any invalid
+/// record fails the whole parse.
+pub fn parse(
+    files: &[RawFile],
+    aliases: &Aliases,
+    countries: &Countries,
+    mode: validate::Mode,
+) -> Result<(BandData, Report), String> {
+    // Until the merge, each file is read and checked on its own, so they're
+    // done in parallel.
+    let parsed = parallel::map(files, |file| {
+        let started = Instant::now();
+        let mut data = Format::of(&file.origin)
+            .read(&file.bytes)
+            .map_err(|e| format!("Failed to parse {}: {}", file.origin, e))?;
+        let tidied = validate::tidy(&file.origin, &mut data);
+        aliases.apply(&mut data);
+        countries.apply(&mut data);
+        let skipped = validate::check(&file.origin, &mut data);
+        tracing::info!(
+            "->> Parsed {} in {} ms.",
+            file.origin,
+            started.elapsed().as_millis()
+        );
+        Ok::<_, String>((data, tidied, skipped))
+    });
+    let mut report = Report::default();
+    let mut parts = Vec::new();
+    for (file, parsed) in files.iter().zip(parsed) {
+        let (data, tidied, skipped) = parsed?;
+        report.tidied.extend(tidied);
+        report.skipped.extend(skipped);
+        parts.push((file.origin.as_str(), data));
+    }
+    let data = merge(parts, &mut report.skipped);
+
+    if mode == validate::Mode::Strict && !report.skipped.is_empty() {
+        return Err(validate::rejection(&report.skipped));
+    }
+    Ok((data, report))
+}
+
+/// A file name for data sent with the media type `content_type`, whose
+/// extension picks the format it's read in; `None` for a type none of them has.
+/// Data sent without a type is taken to be JSON.
+pub fn upload_name(content_type: Option<&str>) -> Option<&'static str> {
+    let media_type = content_type.map_or("application/json", |value| {
+        value.split(';').next().unwrap_or("").trim()
+    });
+    match media_type.to_ascii_lowercase().as_str() {
+        "application/json" => Some("upload.json"),
+        "text/csv" => Some("upload.csv"),
+        "application/yaml" | "application/x-yaml" | "text/yaml" => Some("upload.yaml"),
+        "application/toml" => Some("upload.toml"),
+        _ => None,
+    }
+}
+
+/// A hash of the raw dataset, to cheaply tell whether a new read changed anything.
+pub fn content_hash<'a>(files: impl IntoIterator<Item = &'a RawFile>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        file.origin.hash(&mut hasher);
+        file.bytes.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn fetch_url(url: &str) -> Result<Vec<u8>, fetch::FetchError> {
+    if url.starts_with("s3://") {
+        #[cfg(feature = "s3")]
+        return crate::s3::get(url);
+        #[cfg(not(feature = "s3"))]
+        return Err(fetch::FetchError {
+            message: "this build has no S3 support; rebuild with `--features s3`".to_string(),
+            retryable: false,
+        });
+    }
+    fetch::get_with_retries(url)
+}
+
+// A store is handed on as JSON, so it's parsed, checked and compared with the
+// data being served exactly like a file.
+fn read_store(source: &DataSource, location: store::Location) -> Result<RawFile, String> {
+    let data = store::open(location)?.load()?;
+    Ok(RawFile {
+        origin: source.to_string(),
+        bytes: serde_json::to_vec(&data).map_err(|e| e.to_string())?,
+    })
+}
+
+/// Copies the dataset from `source` into the database `target` (a SQLite file or
+/// a postgres:// URL), replacing whatever it held, and returns how many
+/// performances were copied.
+pub fn import_into(
+    source: &DataSource,
+    target: &str,
+    aliases: &Aliases,
+    countries: &Countries,
+    mode: validate::Mode,
+) -> Result<usize, String> {
+    let location = store::Location::parse(target).ok_or_else(|| {
+        format!(
+            "{} should be a SQLite database ending in .db, .sqlite or .sqlite3, or a postgres:// URL",
+            target
+        )
+    })?;
+    let store = store::open(location)?;
+    let (data, report) = parse(&read(source)?, aliases, countries, mode)?;
+    validate::log(&report);
+    store::import(&data, store.as_ref())
+}
+
+/// `url` with any password replaced, so it can be logged.
+pub fn redact_password(url: &str) -> String {
+    let Some(scheme_end) = url.find("://").map(|i| i + 3) else {
+        return url.to_string();
+    };
+    let authority_end = url[scheme_end..]
+        .find('/')
+        .map_or(url.len(), |i| scheme_end + i);
+    let authority = &url[scheme_end..authority_end];
+    match authority.rsplit_once('@') {
+        Some((user_info, host)) if user_info.contains(':') => {
+            let user = user_info.split(':').next().unwrap_or("");
+            format!(
+                "{}{}:***@{}{}",
+                &url[..scheme_end],
+                user,
+                host,
+                &url[authority_end..]
+            )
+        }
+        _ => url.to_string(),
+    }
+}
+
+fn read_file(path: &Path) -> Result<RawFile, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(RawFile {
+        origin: path.display().to_string(),
+        bytes,
+    })
+}
+
+// The formats a data file can be in, told apart by extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn from_extension(ext: &std::ffi::OsStr) -> Option<Self> {
+        match ext.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    // The format of a file or URL, going by its extension; JSON when it has
+    // none or an unknown one, as URLs often do.
+    fn of(origin: &str) -> Self {
+        let path = origin.split(['?', '#']).next().unwrap_or(origin);
+        Path::new(path)
+            .extension()
+            .and_then(Format::from_extension)
+            .unwrap_or(Format::Json)
+    }
+
+    fn read(self, bytes: &[u8]) -> Result<BandData, String> {
+        let text = || std::str::from_utf8(bytes).map_err(|e| format!("not UTF-8 text: {}", e));
+        match self {
+            Format::Json => schema::read(bytes),
+            Format::Csv => csv::read(bytes),
+            Format::Yaml => schema::from_value(yaml::parse(text()?)?),
+            Format::Toml => schema::from_value(toml::parse_tree(text()?)?.into_json()),
+        }
+    }
+}
+
+// The data files directly inside `dir`, sorted by name so the
+// merged order doesn't depend on the file system. Hidden files (editor backups)
+// are skipped.
+pub fn data_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+            .path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let is_data = path.extension().and_then(Format::from_extension).is_some();
+        if is_data && !name.starts_with('.') && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Combines datasets into one. A festival that appears in several of them (e.g. a
+// file per decade) gets all their years, and its location, country and source
+// from the first that gives them. When the same edition, festival and
+// year, appears twice, only the first is kept and the other is reported, since
+// there's no way to tell which one is right.
+fn merge(parts: Vec<(&str, BandData)>, problems: &mut Vec<Problem>) -> BandData {
+    let mut merged = BandData::default();
+    // Where each edition came from, to name both places in the report.
+    let mut seen: Vec<(String, u16, &str)> = Vec::new();
+
+    for (path, data) in parts {
+        for mut festival in data.festivals {
+            festival.years.retain(|year| {
+                let previous = seen.iter().find(|(name, y, _)| {
+                    *y == year.year && name.eq_ignore_ascii_case(&festival.name)
+                });
+                if let Some((_, _, first)) = previous {
+                    problems.push(Problem {
+                        origin: path.to_string(),
+                        context: format!("{} {}", festival.name, year.year),
+                        message: if *first == path {
+                            "the edition is listed twice".to_string()
+                        } else {
+                            format!("the edition is already listed in {}", first)
+                        },
+                    });
+                    return false;
+                }
+                seen.push((festival.name.clone(), year.year, path));
+                true
+            });
+
+            let existing = merged
+                .festivals
+                .iter_mut()
+                .find(|f| f.name.eq_ignore_ascii_case(&festival.name));
+            match existing {
+                Some(existing) => {
+                    existing.location = existing.location.take().or(festival.location);
+                    existing.country = existing.country.take().or(festival.country);
+                    existing.source = existing.source.take().or(festival.source);
+                    existing.years.extend(festival.years);
+                }
+                None => merged.festivals.push(festival),
+            }
+        }
+    }
+
+    merged
+}
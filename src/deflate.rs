@@ -0,0 +1,269 @@
+// DEFLATE compression (RFC 1951) in the gzip (RFC 1952) and zlib (RFC 1950)
+// wrappers, for compressing responses. Repeats are found with a hash chain over
+// the last 32 KiB and written with the fixed Huffman codes, which is simple and
+// quick, and still shrinks JSON, with its repeated keys, to a fraction of its
+// size; dynamic codes would save little more on top.
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+// How many earlier positions with the same hash to try; more finds longer
+// repeats, slowly.
+const MAX_CHAIN: usize = 48;
+// No position yet.
+const NONE: u32 = u32::MAX;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// `data` compressed in the gzip format, as sent with `Content-Encoding: gzip`.
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // No name, no modification time, and an unknown operating system.
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+    out.extend(deflate(data));
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+/// `data` compressed in the zlib format, as sent with `Content-Encoding: deflate`.
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    // A 32 KiB window and no dictionary.
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+// The raw DEFLATE stream: one final block with the fixed codes.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut out = Bits::default();
+    out.write(1, 1);
+    out.write(1, 2);
+    let mut chains = Chains::new();
+    let mut at = 0;
+    while at < data.len() {
+        match chains.longest_match(data, at) {
+            Some((length, distance)) => {
+                out.length(length);
+                out.distance(distance);
+                for i in at..at + length {
+                    chains.insert(data, i);
+                }
+                at += length;
+            }
+            None => {
+                out.literal(data[at]);
+                chains.insert(data, at);
+                at += 1;
+            }
+        }
+    }
+    out.symbol(256);
+    out.finish()
+}
+
+// The positions already seen, by the hash of the three bytes there: the latest
+// for each hash, and for each position the one before it with the same hash.
+struct Chains {
+    head: Vec<u32>,
+    prev: Vec<u32>,
+}
+
+impl Chains {
+    fn new() -> Chains {
+        Chains {
+            head: vec![NONE; 1 << HASH_BITS],
+            prev: vec![NONE; WINDOW],
+        }
+    }
+
+    fn insert(&mut self, data: &[u8], at: usize) {
+        if at + MIN_MATCH <= data.len() {
+            let hash = hash(&data[at..]);
+            self.prev[at % WINDOW] = self.head[hash];
+            self.head[hash] = at as u32;
+        }
+    }
+
+    // The longest earlier repeat of the bytes at `at`, as its length and how far
+    // back it is, if there's one of at least `MIN_MATCH` bytes.
+    fn longest_match(&self, data: &[u8], at: usize) -> Option<(usize, usize)> {
+        if at + MIN_MATCH > data.len() {
+            return None;
+        }
+        let limit = MAX_MATCH.min(data.len() - at);
+        let mut best = (0, 0);
+        let mut candidate = self.head[hash(&data[at..])];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NONE || at - candidate as usize > WINDOW {
+                break;
+            }
+            let from = candidate as usize;
+            let length = data[from..]
+                .iter()
+                .zip(&data[at..at + limit])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, at - from);
+                if length == limit {
+                    break;
+                }
+            }
+            let next = self.prev[from % WINDOW];
+            // Older positions only; anything else is a slot reused since.
+            if next == NONE || next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        (best.0 >= MIN_MATCH).then_some(best)
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+// Bits written least significant first, as DEFLATE packs them.
+#[derive(Default)]
+struct Bits {
+    out: Vec<u8>,
+    pending: u64,
+    count: u32,
+}
+
+impl Bits {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.pending |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.pending as u8);
+            self.pending >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // A Huffman code, which goes most significant bit first.
+    fn code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    // A literal/length symbol in the fixed code.
+    fn symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0b0011_0000 + symbol, 8),
+            144..=255 => self.code(0b1_1001_0000 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0b1100_0000 + symbol - 280, 8),
+        }
+    }
+
+    fn literal(&mut self, byte: u8) {
+        self.symbol(byte as u32);
+    }
+
+    fn length(&mut self, length: usize) {
+        let index = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+        self.symbol(257 + index as u32);
+        let extra = (length - LENGTH_BASE[index] as usize) as u32;
+        self.write(extra, LENGTH_EXTRA[index] as u32);
+    }
+
+    fn distance(&mut self, distance: usize) {
+        let index = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+        self.code(index as u32, 5);
+        let extra = (distance - DISTANCE_BASE[index] as usize) as u32;
+        self.write(extra, DISTANCE_EXTRA[index] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.pending as u8);
+        }
+        self.out
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums over this many bytes can't overflow before they're reduced.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn repeats_become_back_references() {
+        // "abc" as literals, then one match of 9 bytes, 3 back.
+        assert_eq!(
+            deflate(b"abcabcabcabc"),
+            [0x4b, 0x4c, 0x4a, 0x86, 0x23, 0x00]
+        );
+        assert_eq!(deflate(b""), [0x03, 0x00]);
+        let json = br#"[{"name":"Editors","festival":"Pinkpop"},{"name":"Kensington","festival":"Pinkpop"}]"#;
+        assert!(gzip(json).len() < json.len());
+        assert_eq!(&gzip(json)[..2], [0x1f, 0x8b]);
+        assert_eq!(zlib(b"")[..2], [0x78, 0x01]);
+    }
+}
@@ -0,0 +1,255 @@
+// How one dataset differs from another, edition by edition and artist by
+// artist, for reviewing a replacement before it's uploaded. Festivals and
+// artists are matched by name ignoring case, editions by year, as the checks
+// that keep them from being listed twice do; a change in case alone shows as a
+// changed `name`. Every other field is compared as JSON, so new fields in the
+// format are picked up without changes here.
+
+use crate::data::{BandData, Festival};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Default, Serialize)]
+pub struct DatasetDiff<'a> {
+    // Festival-level details such as the location, for festivals in both.
+    pub changed_festivals: Vec<FestivalChange<'a>>,
+    pub added_editions: Vec<EditionSummary<'a>>,
+    pub removed_editions: Vec<EditionSummary<'a>>,
+    pub changed_editions: Vec<EditionChange<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FestivalChange<'a> {
+    pub festival: &'a str,
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditionSummary<'a> {
+    pub festival: &'a str,
+    pub year: u16,
+    // Performances on the lineup.
+    pub artists: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditionChange<'a> {
+    pub festival: &'a str,
+    pub year: u16,
+    // The edition's own details, such as its dates.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added_artists: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_artists: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed_artists: Vec<ArtistChange<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtistChange<'a> {
+    // As the new data spells it.
+    pub name: &'a str,
+    pub fields: Vec<FieldChange>,
+}
+
+/// One field that differs; `null` where a side doesn't have it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// What changes going from `before` to `after`, listed in `after`'s order, with
+/// what `after` no longer has in `before`'s.
+pub fn between<'a>(before: &'a BandData, after: &'a BandData) -> DatasetDiff<'a> {
+    let mut diff = DatasetDiff::default();
+    for new in &after.festivals {
+        let Some(old) = find_festival(before, &new.name) else {
+            diff.added_editions
+                .extend(new.years.iter().map(|edition| EditionSummary {
+                    festival: &new.name,
+                    year: edition.year,
+                    artists: edition.artists.len(),
+                }));
+            continue;
+        };
+        let fields = changed_fields(old, new, &["name", "years"]);
+        if !fields.is_empty() {
+            diff.changed_festivals.push(FestivalChange {
+                festival: &new.name,
+                fields,
+            });
+        }
+
+        for edition in &new.years {
+            let Some(old_edition) = old.years.iter().find(|e| e.year == edition.year) else {
+                diff.added_editions.push(EditionSummary {
+                    festival: &new.name,
+                    year: edition.year,
+                    artists: edition.artists.len(),
+                });
+                continue;
+            };
+            let mut change = EditionChange {
+                festival: &new.name,
+                year: edition.year,
+                fields: changed_fields(old_edition, edition, &["year", "artists"]),
+                added_artists: Vec::new(),
+                removed_artists: Vec::new(),
+                changed_artists: Vec::new(),
+            };
+            for act in &edition.artists {
+                match old_edition
+                    .artists
+                    .iter()
+                    .find(|a| same_name(&a.name, &act.name))
+                {
+                    Some(old_act) => {
+                        let fields = changed_fields(old_act, act, &[]);
+                        if !fields.is_empty() {
+                            change.changed_artists.push(ArtistChange {
+                                name: &act.name,
+                                fields,
+                            });
+                        }
+                    }
+                    None => change.added_artists.push(&act.name),
+                }
+            }
+            change.removed_artists = old_edition
+                .artists
+                .iter()
+                .filter(|a| !edition.artists.iter().any(|b| same_name(&a.name, &b.name)))
+                .map(|a| a.name.as_str())
+                .collect();
+            if !(change.fields.is_empty()
+                && change.added_artists.is_empty()
+                && change.removed_artists.is_empty()
+                && change.changed_artists.is_empty())
+            {
+                diff.changed_editions.push(change);
+            }
+        }
+    }
+
+    for old in &before.festivals {
+        let new = find_festival(after, &old.name);
+        diff.removed_editions.extend(
+            old.years
+                .iter()
+                .filter(|e| !new.is_some_and(|new| new.years.iter().any(|n| n.year == e.year)))
+                .map(|edition| EditionSummary {
+                    festival: &old.name,
+                    year: edition.year,
+                    artists: edition.artists.len(),
+                }),
+        );
+    }
+    diff
+}
+
+fn find_festival<'a>(data: &'a BandData, name: &str) -> Option<&'a Festival> {
+    data.festivals.iter().find(|f| same_name(&f.name, name))
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+// The fields of `before` and `after` that differ, by name, apart from `skip`.
+fn changed_fields(
+    before: &impl Serialize,
+    after: &impl Serialize,
+    skip: &[&str],
+) -> Vec<FieldChange> {
+    let (before, after) = (fields(before), fields(after));
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter(|name| !skip.contains(&name.as_str()))
+        .filter_map(|name| {
+            let old = before.get(name).cloned().unwrap_or(Value::Null);
+            let new = after.get(name).cloned().unwrap_or(Value::Null);
+            (old != new).then(|| FieldChange {
+                field: name.clone(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+fn fields(record: &impl Serialize) -> Map<String, Value> {
+    match serde_json::to_value(record) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, FestivalYear};
+    use serde_json::json;
+
+    fn lineup(editions: &[(u16, &[&str])]) -> BandData {
+        BandData {
+            festivals: vec![Festival {
+                name: "Pinkpop".to_string(),
+                years: editions
+                    .iter()
+                    .map(|(year, names)| FestivalYear {
+                        year: *year,
+                        artists: names.iter().map(|n| Act::named(n.to_string())).collect(),
+                        ..FestivalYear::default()
+                    })
+                    .collect(),
+                ..Festival::default()
+            }],
+            ..BandData::default()
+        }
+    }
+
+    #[test]
+    fn lists_changed_editions_and_artists() {
+        let before = lineup(&[(2008, &["Muse", "dEUS", "Editors"]), (2009, &["Moby"])]);
+        let mut after = lineup(&[
+            (2008, &["Muse", "Deus", "Kaiser Chiefs"]),
+            (2010, &["Moby"]),
+        ]);
+        after.festivals[0].location = Some("Landgraaf".to_string());
+        after.festivals[0].years[0].artists[0].headliner = true;
+
+        let diff = between(&before, &after);
+        assert_eq!(diff.changed_festivals[0].fields[0].field, "location");
+        assert_eq!(
+            (diff.added_editions[0].year, diff.removed_editions[0].year),
+            (2010, 2009)
+        );
+        let [change] = &diff.changed_editions[..] else {
+            panic!("expected one changed edition");
+        };
+        assert_eq!(change.added_artists, ["Kaiser Chiefs"]);
+        assert_eq!(change.removed_artists, ["Editors"]);
+        let changed: Vec<(&str, &str, &Value)> = change
+            .changed_artists
+            .iter()
+            .map(|a| (a.name, a.fields[0].field.as_str(), &a.fields[0].after))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                ("Muse", "headliner", &json!(true)),
+                ("Deus", "name", &json!("Deus"))
+            ]
+        );
+
+        let unchanged = between(&before, &before);
+        assert!(unchanged.changed_editions.is_empty() && unchanged.added_editions.is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+// Corrections to the data being served, made through the admin API: adding a
+// performance, taking one out, or fixing its artist's name or the year it's
+// listed under.
+// Performances are found by ID in the dataset itself, and `SharedState::edit`
+// checks the result like loaded data before anything is served from it.
+
+use crate::data::{Act, BandData};
+use crate::state::{self, EditError};
+use serde::Deserialize;
+
+/// Where an act is in a dataset: the indexes of its festival, edition and act.
+pub type Location = (usize, usize, usize);
+
+/// What a PATCH changes about a performance; fields left out stay as they are.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Correction {
+    pub name: Option<String>,
+    // Moves the performance to this year's edition of the same festival.
+    pub year: Option<u16>,
+}
+
+/// A performance to add: the artist as a data file lists them, and the edition.
+#[derive(Debug, Deserialize)]
+pub struct Addition {
+    pub festival: String,
+    pub year: u16,
+    #[serde(flatten)]
+    pub act: Act,
+}
+
+/// Adds `addition` to the end of its edition's lineup, returning where it is.
+/// The edition has to be in `data` already.
+pub fn add(data: &mut BandData, addition: Addition) -> Result<Location, EditError> {
+    let missing = || {
+        EditError::Invalid(format!(
+            "{} has no {} edition to add the performance to",
+            addition.festival, addition.year
+        ))
+    };
+    let festival = data
+        .festivals
+        .iter()
+        .position(|f| f.name.to_lowercase() == addition.festival.to_lowercase())
+        .ok_or_else(missing)?;
+    let years = &mut data.festivals[festival].years;
+    let edition = years
+        .iter()
+        .position(|y| y.year == addition.year)
+        .ok_or_else(missing)?;
+    years[edition].artists.push(addition.act);
+    Ok((festival, edition, years[edition].artists.len() - 1))
+}
+
+/// Takes the performance `id` out of `data`.
+pub fn delete(data: &mut BandData, id: &str) -> Result<(), EditError> {
+    let (festival, edition, act) = locate(data, id).ok_or(EditError::NotFound)?;
+    data.festivals[festival].years[edition].artists.remove(act);
+    Ok(())
+}
+
+/// Applies `correction` to the performance `id` in `data`, returning where the
+/// performance is afterwards.
+pub fn correct(
+    data: &mut BandData,
+    id: &str,
+    correction: &Correction,
+) -> Result<Location, EditError> {
+    let (festival_index, edition, act) = locate(data, id).ok_or(EditError::NotFound)?;
+    let festival = &mut data.festivals[festival_index];
+    if let Some(name) = &correction.name {
+        festival.years[edition].artists[act].name = name.clone();
+    }
+
+    let Some(year) = correction
+        .year
+        .filter(|year| *year != festival.years[edition].year)
+    else {
+        return Ok((festival_index, edition, act));
+    };
+    let target = festival
+        .years
+        .iter()
+        .position(|y| y.year == year)
+        .ok_or_else(|| {
+            EditError::Invalid(format!(
+                "{} has no {} edition to move the performance to",
+                festival.name, year
+            ))
+        })?;
+    let moved = festival.years[edition].artists.remove(act);
+    festival.years[target].artists.push(moved);
+    Ok((
+        festival_index,
+        target,
+        festival.years[target].artists.len() - 1,
+    ))
+}
+
+/// The ID of the performance at `location` in `data`. A name or year change
+/// gives a performance a new one.
+pub fn id_at(data: &BandData, (festival, edition, act): Location) -> String {
+    let festival = &data.festivals[festival];
+    let edition = &festival.years[edition];
+    state::performance_id(&festival.name, edition.year, &edition.artists[act].name)
+}
+
+fn locate(data: &BandData, id: &str) -> Option<Location> {
+    data.festivals.iter().enumerate().find_map(|(f, festival)| {
+        festival.years.iter().enumerate().find_map(|(e, edition)| {
+            let a = edition.artists.iter().position(|act| {
+                state::performance_id(&festival.name, edition.year, &act.name) == id
+            })?;
+            Some((f, e, a))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, Festival, FestivalYear};
+
+    fn pinkpop() -> BandData {
+        let edition = |year, names: &[&str]| FestivalYear {
+            year,
+            artists: names.iter().map(|n| Act::named(n.to_string())).collect(),
+            ..FestivalYear::default()
+        };
+        BandData {
+            festivals: vec![Festival {
+                name: "Pinkpop".to_string(),
+                years: vec![
+                    edition(2008, &["Muse", "Editors"]),
+                    edition(2009, &["Moby"]),
+                ],
+                ..Festival::default()
+            }],
+            ..BandData::default()
+        }
+    }
+
+    #[test]
+    fn adds_corrects_and_deletes_performances() {
+        let mut data = pinkpop();
+        let editors = state::performance_id("Pinkpop", 2008, "Editors");
+        let correction = Correction {
+            name: Some("The Editors".to_string()),
+            year: Some(2009),
+        };
+        let location = correct(&mut data, &editors, &correction).unwrap();
+        assert_eq!(location, (0, 1, 1));
+        assert_eq!(
+            id_at(&data, location),
+            state::performance_id("pinkpop", 2009, "the editors")
+        );
+        assert_eq!(data.festivals[0].years[0].artists.len(), 1);
+
+        let to_2010 = Correction {
+            name: None,
+            year: Some(2010),
+        };
+        let moby = state::performance_id("Pinkpop", 2009, "Moby");
+        assert!(matches!(
+            correct(&mut data, &moby, &to_2010),
+            Err(EditError::Invalid(_))
+        ));
+        let addition: Addition = serde_json::from_str(
+            r#"{ "festival": "pinkpop", "year": 2008, "name": "Editors", "stage": "Main" }"#,
+        )
+        .unwrap();
+        assert_eq!(add(&mut data, addition).unwrap(), (0, 0, 1));
+        assert_eq!(
+            data.festivals[0].years[0].artists[1].stage.as_deref(),
+            Some("Main")
+        );
+        delete(&mut data, &moby).unwrap();
+        assert!(matches!(delete(&mut data, &moby), Err(EditError::NotFound)));
+        assert_eq!(data.festivals[0].years[1].artists[0].name, "The Editors");
+    }
+}
@@ -0,0 +1,118 @@
+// Streamed exports, for datasets too large to keep a serialized copy of: over
+// `limits.stream_exports_over` performances, /api/all-bands is serialized a
+// batch of performances at a time while it's sent, so the whole JSON is never
+// in memory at once. The next batch is only serialized once the connection has
+// taken the last one, so a slow client holds up its own download rather than
+// piling its body up in the server's memory.
+//
+// Streamed bodies go uncompressed: compressing them would mean gathering them
+// first.
+
+use crate::state::AppState;
+use axum::body::Bytes;
+use http_body::{Body, Frame};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+// How many performances go in each chunk of the body, about 100 KB of JSON.
+const BATCH: usize = 1000;
+
+/// The body of an export of a snapshot's performances, as one JSON array.
+pub struct Stream {
+    state: Arc<AppState>,
+    // The next performance to send.
+    next: usize,
+    done: bool,
+}
+
+impl Stream {
+    pub fn new(state: Arc<AppState>) -> Stream {
+        Stream {
+            state,
+            next: 0,
+            done: false,
+        }
+    }
+}
+
+impl Body for Stream {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let performances = &this.state.all_performances;
+        let end = (this.next + BATCH).min(performances.len());
+        let mut chunk = Vec::new();
+        if this.next == 0 {
+            chunk.push(b'[');
+        }
+        for i in this.next..end {
+            if i > 0 {
+                chunk.push(b',');
+            }
+            let performance = performances.get(i as u32);
+            serde_json::to_writer(&mut chunk, &performance).expect("performances serialize");
+        }
+        if end == performances.len() {
+            chunk.push(b']');
+            this.done = true;
+        }
+        this.next = end;
+        Poll::Ready(Some(Ok(Frame::data(Bytes::from(chunk)))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, BandData, Festival, FestivalYear};
+    use std::task::Waker;
+
+    fn export(acts: usize) -> (Vec<u8>, usize) {
+        let data = BandData {
+            schema_version: crate::schema::CURRENT,
+            festivals: vec![Festival {
+                name: "Pinkpop".to_string(),
+                years: vec![FestivalYear {
+                    year: 2008,
+                    artists: (0..acts)
+                        .map(|i| Act::named(format!("Band {}", i)))
+                        .collect(),
+                    ..FestivalYear::default()
+                }],
+                ..Festival::default()
+            }],
+        };
+        let state = Arc::new(AppState::new(data, 0));
+        let mut stream = Stream::new(state.clone());
+        let mut context = Context::from_waker(Waker::noop());
+        let (mut body, mut chunks) = (Vec::new(), 0);
+        while let Poll::Ready(Some(frame)) = Pin::new(&mut stream).poll_frame(&mut context) {
+            body.extend_from_slice(&frame.unwrap().into_data().unwrap());
+            chunks += 1;
+        }
+        assert!(stream.is_end_stream());
+        assert_eq!(body, serde_json::to_vec(&state.all_performances).unwrap());
+        (body, chunks)
+    }
+
+    #[test]
+    fn batches_join_into_the_whole_array() {
+        assert_eq!(export(BATCH * 2 + 1).1, 3);
+        assert_eq!(export(BATCH).1, 1);
+        assert_eq!(export(0), (b"[]".to_vec(), 1));
+    }
+}
@@ -0,0 +1,49 @@
+// JSON errors for requests no route answers: a path under /api/ that isn't an
+// endpoint gets a 404 listing the ones there are, and a method an endpoint
+// doesn't take gets a 405 pointing to the `Allow` header, which names those it
+// does. Other paths keep the router's empty 404, and 404s and 405s from
+// handlers, which explain themselves already, are left alone.
+
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+
+/// Middleware giving the router's own 404s under /api/ and 405s JSON errors.
+pub async fn explain_missing(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let method = request.method().clone();
+    let response = next.run(request).await;
+    if is_json(response.headers()) {
+        return response;
+    }
+    match response.status() {
+        StatusCode::NOT_FOUND if path.starts_with("/api/") => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": format!("There's no API endpoint at {}.", path),
+                "endpoints": crate::public_endpoints(),
+            })),
+        )
+            .into_response(),
+        // The router adds the `Allow` header on the way out.
+        StatusCode::METHOD_NOT_ALLOWED => {
+            let (mut parts, _) = response.into_parts();
+            parts.headers.remove(header::CONTENT_LENGTH);
+            let error = Json(serde_json::json!({
+                "error": format!(
+                    "{} isn't allowed on {}; the Allow header lists the methods that are.",
+                    method, path
+                ),
+            }));
+            (parts, error).into_response()
+        }
+        _ => response,
+    }
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"))
+}
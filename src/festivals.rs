@@ -0,0 +1,95 @@
+// The festivals in the data with what's known about them: where each is held,
+// when its editions were, how big their lineups are and where they were taken
+// from. Performances only carry
+// a festival's name, so this is taken from the dataset before it's flattened.
+
+use crate::data::{BandData, Source};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct FestivalInfo {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    // A two-letter code such as "NL".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    // Oldest first.
+    pub editions: Vec<EditionInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditionInfo {
+    pub year: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    // Only when it differs from the festival's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    // Performances on the lineup.
+    pub artists: usize,
+}
+
+/// Every festival in `data`, in the order they appear in it.
+pub fn summarize(data: &BandData) -> Vec<FestivalInfo> {
+    data.festivals
+        .iter()
+        .map(|festival| {
+            let mut editions: Vec<EditionInfo> = festival
+                .years
+                .iter()
+                .map(|edition| EditionInfo {
+                    year: edition.year,
+                    start_date: edition.start_date.clone(),
+                    end_date: edition.end_date.clone(),
+                    cancelled: edition.cancelled,
+                    note: edition.note.clone(),
+                    source: edition
+                        .source
+                        .clone()
+                        .filter(|source| festival.source.as_ref() != Some(source)),
+                    artists: edition.artists.len(),
+                })
+                .collect();
+            editions.sort_by_key(|edition| edition.year);
+            FestivalInfo {
+                name: festival.name.clone(),
+                location: festival.location.clone(),
+                country: festival.country.clone(),
+                source: festival.source.clone(),
+                editions,
+            }
+        })
+        .collect()
+}
+
+/// Where the lineup of one edition came from.
+#[derive(Debug, Serialize)]
+pub struct Provenance<'a> {
+    pub festival: &'a str,
+    pub year: u16,
+    // The edition's own source, else the festival's; `None` when neither is known.
+    pub source: Option<&'a Source>,
+}
+
+/// The source of every edition in `festivals`.
+pub fn provenance(festivals: &[FestivalInfo]) -> Vec<Provenance<'_>> {
+    festivals
+        .iter()
+        .flat_map(|festival| {
+            festival.editions.iter().map(move |edition| Provenance {
+                festival: &festival.name,
+                year: edition.year,
+                source: edition.source.as_ref().or(festival.source.as_ref()),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,265 @@
+// A minimal blocking HTTP client for downloading the dataset from a URL.
+//
+// It speaks plain HTTP/1.0 (so responses are never chunked), follows a few
+// redirects and caps the body size. There is no TLS support in this build, so
+// `https://` URLs are refused with an explanation rather than attempted.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+// Applies to connecting and to every read and write after that.
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+// Far beyond any festival dataset, but stops a misconfigured URL filling memory.
+const MAX_BODY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Attempts made by `get_with_retries`, and the delay before the first retry,
+/// which doubles after each further failure.
+const ATTEMPTS: usize = 3;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct FetchError {
+    pub message: String,
+    // Network trouble and server errors may pass; a 404 or a bad URL won't.
+    pub retryable: bool,
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+fn permanent(message: String) -> FetchError {
+    FetchError {
+        message,
+        retryable: false,
+    }
+}
+
+fn transient(message: String) -> FetchError {
+    FetchError {
+        message,
+        retryable: true,
+    }
+}
+
+// The parts of an `http://host[:port]/path` URL needed to make a request.
+struct Url {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+fn parse_url(url: &str) -> Result<Url, FetchError> {
+    if url.starts_with("https://") {
+        return Err(permanent(format!(
+            "{} uses https, but this build has no TLS support; serve the data over http:// or use a local file",
+            url
+        )));
+    }
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| permanent(format!("{} is not an http:// URL", url)))?;
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    // Fragments are never sent to the server.
+    let path = path.split('#').next().unwrap_or("/").to_string();
+
+    let (host, port) = match authority.rsplit_once(':') {
+        // A colon inside brackets is part of an IPv6 address, not a port.
+        Some((host, port)) if !port.contains(']') => {
+            let port = port
+                .parse()
+                .map_err(|_| permanent(format!("{} has an invalid port", url)))?;
+            (host, port)
+        }
+        _ => (authority, 80),
+    };
+    if host.is_empty() || host.contains('@') {
+        return Err(permanent(format!("{} has no usable host", url)));
+    }
+
+    Ok(Url {
+        host: host.to_string(),
+        port,
+        path,
+    })
+}
+
+impl Url {
+    // The `Host` header, which leaves out the default port.
+    fn host_header(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// The `Host` header a request to `url` is sent with, for signing it.
+#[cfg(feature = "s3")]
+pub fn host_header(url: &str) -> Result<String, FetchError> {
+    parse_url(url).map(|url| url.host_header())
+}
+
+/// Downloads `url`, following redirects.
+pub fn get(url: &str) -> Result<Vec<u8>, FetchError> {
+    get_with_headers(url, &[])
+}
+
+/// Like `get`, sending `headers` along with each request.
+pub fn get_with_headers(url: &str, headers: &[(String, String)]) -> Result<Vec<u8>, FetchError> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        match request(&parse_url(&url)?, headers)? {
+            Response::Body(body) => return Ok(body),
+            Response::Redirect(location) => {
+                url = resolve(&url, &location);
+            }
+        }
+    }
+    Err(permanent(format!("{} redirects too many times", url)))
+}
+
+/// Like `get`, but retries transient failures up to `ATTEMPTS` times in total,
+/// waiting a little longer before each retry.
+pub fn get_with_retries(url: &str) -> Result<Vec<u8>, FetchError> {
+    retrying(url, || get(url))
+}
+
+/// Runs `fetch` until it succeeds, fails permanently or has been tried
+/// `ATTEMPTS` times. `url` is only used in the log messages.
+pub fn retrying(
+    url: &str,
+    mut fetch: impl FnMut() -> Result<Vec<u8>, FetchError>,
+) -> Result<Vec<u8>, FetchError> {
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match fetch() {
+            Ok(body) => return Ok(body),
+            Err(e) if e.retryable && attempt < ATTEMPTS => {
+                tracing::warn!(
+                    "->> Fetching {} failed ({}); retrying in {}s...",
+                    url,
+                    e,
+                    delay.as_secs()
+                );
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) if e.retryable => {
+                return Err(transient(format!(
+                    "{} (gave up after {} attempts)",
+                    e, ATTEMPTS
+                )));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// A `Location` header may be absolute or relative to the current host.
+fn resolve(current: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let scheme_end = current.find("://").map_or(0, |i| i + 3);
+    let origin_end = current[scheme_end..]
+        .find('/')
+        .map_or(current.len(), |i| scheme_end + i);
+    let origin = &current[..origin_end];
+    if location.starts_with('/') {
+        format!("{}{}", origin, location)
+    } else {
+        format!("{}/{}", origin, location)
+    }
+}
+
+enum Response {
+    Body(Vec<u8>),
+    Redirect(String),
+}
+
+fn request(url: &Url, headers: &[(String, String)]) -> Result<Response, FetchError> {
+    let target = format!("{}:{}", url.host.trim_matches(['[', ']']), url.port);
+    let address = target
+        .to_socket_addrs()
+        .map_err(|e| transient(format!("could not resolve {}: {}", url.host, e)))?
+        .next()
+        .ok_or_else(|| transient(format!("could not resolve {}", url.host)))?;
+
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)
+        .map_err(|e| transient(format!("could not connect to {}: {}", target, e)))?;
+    let io_error = |e: std::io::Error| transient(format!("connection to {} failed: {}", target, e));
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(io_error)?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(io_error)?;
+
+    let mut request_head = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\nUser-Agent: nog-een-bandje\r\n",
+        url.path,
+        url.host_header()
+    );
+    for (name, value) in headers {
+        request_head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request_head.push_str("\r\n");
+    stream
+        .write_all(request_head.as_bytes())
+        .map_err(io_error)?;
+
+    // HTTP/1.0: the server closes the connection after the body.
+    let mut raw = Vec::new();
+    stream
+        .take(MAX_BODY_BYTES + 64 * 1024)
+        .read_to_end(&mut raw)
+        .map_err(io_error)?;
+
+    let header_end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| transient(format!("{} sent an incomplete response", target)))?;
+    let head = String::from_utf8_lossy(&raw[..header_end]).into_owned();
+    let body = raw.split_off(header_end + 4);
+    if body.len() as u64 > MAX_BODY_BYTES {
+        return Err(permanent(format!(
+            "the response from {} is larger than {} MiB",
+            target,
+            MAX_BODY_BYTES / (1024 * 1024)
+        )));
+    }
+
+    let status: u16 = head
+        .split("\r\n")
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| transient(format!("{} sent a malformed status line", target)))?;
+    let header = |name: &str| {
+        head.split("\r\n").skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+
+    match status {
+        200..=299 => Ok(Response::Body(body)),
+        301 | 302 | 303 | 307 | 308 => header("location")
+            .map(Response::Redirect)
+            .ok_or_else(|| permanent(format!("{} redirected without a Location", target))),
+        429 | 500..=599 => Err(transient(format!("{} answered HTTP {}", target, status))),
+        _ => Err(permanent(format!("{} answered HTTP {}", target, status))),
+    }
+}
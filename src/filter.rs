@@ -0,0 +1,273 @@
+// The filter model: narrowing criteria on festival, year, genre, country, stage,
+// day, billing and cancelled editions that endpoints apply on top of (or instead
+// of) artist-name matching.
+
+use crate::layout::{Performance, Performances};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// --- Filters ---
+
+/// Restricts a selection of performances. Empty lists and missing bounds don't
+/// restrict anything, so `Filters::default()` lets every performance through.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Filters {
+    // A performance passes if it was at any of these festivals (case-insensitive).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub festivals: Vec<String>,
+    // ...and in any of these years.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub years: Vec<u16>,
+    // ...and within this inclusive year range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year_from: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub year_to: Option<u16>,
+    // ...and by an artist with any of these genres (case-insensitive).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    // ...and by an artist from any of these countries (case-insensitive).
+    // Performances by artists whose country isn't known don't pass this.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub countries: Vec<String>,
+    // ...and on any of these stages and days (case-insensitive). Performances
+    // without a stage or day don't pass these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<String>,
+    // ...and, when set, by a headliner.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub headliners_only: bool,
+    // ...and, when set, at an edition that wasn't cancelled.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude_cancelled: bool,
+}
+
+impl Filters {
+    pub fn is_empty(&self) -> bool {
+        *self == Filters::default()
+    }
+
+    pub fn matches(&self, performance: Performance<'_>) -> bool {
+        let year = performance.year();
+        let festival_ok = self.festivals.is_empty()
+            || self
+                .festivals
+                .iter()
+                .any(|f| f.eq_ignore_ascii_case(performance.festival()));
+        let year_ok = self.years.is_empty() || self.years.contains(&year);
+        let from_ok = self.year_from.is_none_or(|from| year >= from);
+        let to_ok = self.year_to.is_none_or(|to| year <= to);
+        let genre_ok = self.genres.is_empty()
+            || self.genres.iter().any(|wanted| {
+                performance
+                    .genres()
+                    .iter()
+                    .any(|g| g.eq_ignore_ascii_case(wanted))
+            });
+        let listed = |wanted: &[String], value: Option<&str>| {
+            wanted.is_empty()
+                || value.is_some_and(|value| wanted.iter().any(|w| w.eq_ignore_ascii_case(value)))
+        };
+
+        festival_ok
+            && year_ok
+            && from_ok
+            && to_ok
+            && genre_ok
+            && listed(&self.countries, performance.country())
+            && listed(&self.stages, performance.stage())
+            && listed(&self.days, performance.day())
+            && (!self.headliners_only || performance.headliner())
+            && (!self.exclude_cancelled || !performance.cancelled())
+    }
+
+    /// An equivalent set of filters in a fixed form: names lowercased, and every
+    /// list sorted and deduplicated. Useful as (part of) a
+    /// cache key.
+    pub fn canonical(&self) -> Filters {
+        let lowercase = |names: &[String]| {
+            let mut names: Vec<String> = names.iter().map(|f| f.to_ascii_lowercase()).collect();
+            names.sort_unstable();
+            names.dedup();
+            names
+        };
+
+        let mut years = self.years.clone();
+        years.sort_unstable();
+        years.dedup();
+
+        Filters {
+            festivals: lowercase(&self.festivals),
+            years,
+            year_from: self.year_from,
+            year_to: self.year_to,
+            genres: lowercase(&self.genres),
+            countries: lowercase(&self.countries),
+            stages: lowercase(&self.stages),
+            days: lowercase(&self.days),
+            headliners_only: self.headliners_only,
+            exclude_cancelled: self.exclude_cancelled,
+        }
+    }
+}
+
+/// The filter query parameters accepted by the selection endpoints.
+#[derive(Debug, Deserialize)]
+pub struct FilterParams {
+    // One festival name, or several separated by commas.
+    festival: Option<String>,
+    year: Option<u16>,
+    from: Option<u16>,
+    to: Option<u16>,
+    // One genre, or several separated by commas; the same for countries, stages
+    // and days.
+    genre: Option<String>,
+    country: Option<String>,
+    stage: Option<String>,
+    day: Option<String>,
+    headliners_only: Option<bool>,
+    // Whether performances at cancelled editions count; endpoints choose the default.
+    include_cancelled: Option<bool>,
+}
+
+impl FilterParams {
+    /// Like `Filters::from`, but leaving out cancelled editions unless
+    /// `include_cancelled=true` is given, for picks that should have happened.
+    pub fn without_cancelled(self) -> Filters {
+        let include = self.include_cancelled.unwrap_or(false);
+        Filters {
+            exclude_cancelled: !include,
+            ..Filters::from(self)
+        }
+    }
+}
+
+impl From<FilterParams> for Filters {
+    fn from(params: FilterParams) -> Self {
+        Filters {
+            festivals: list(params.festival.as_deref()),
+            years: params.year.into_iter().collect(),
+            year_from: params.from,
+            year_to: params.to,
+            genres: list(params.genre.as_deref()),
+            countries: list(params.country.as_deref()),
+            stages: list(params.stage.as_deref()),
+            days: list(params.day.as_deref()),
+            headliners_only: params.headliners_only.unwrap_or(false),
+            exclude_cancelled: params.include_cancelled == Some(false),
+        }
+    }
+}
+
+// The names in a comma-separated parameter.
+fn list(param: Option<&str>) -> Vec<String> {
+    param
+        .iter()
+        .flat_map(|names| names.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// --- Catalog ---
+
+/// The festivals and years present in the dataset, so free-text queries can
+/// recognise "lowlands" or "2011" as filters rather than artist names, and
+/// where each one's performances are, so filtering on them doesn't have to go
+/// through every performance.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    // Display names in the order they first appear in the data.
+    pub festivals: Vec<String>,
+    // Sorted and deduplicated.
+    pub years: Vec<u16>,
+    // Positions in the list of performances, in order, by lowercased festival
+    // name and by year.
+    by_festival: HashMap<String, Vec<u32>>,
+    by_year: BTreeMap<u16, Vec<u32>>,
+}
+
+impl Catalog {
+    pub fn new(performances: &Performances) -> Self {
+        let mut catalog = Catalog::default();
+        for performance in performances.iter() {
+            let (i, festival) = (performance.position(), performance.festival());
+            if !catalog.festivals.iter().any(|f| f == festival) {
+                catalog.festivals.push(festival.to_string());
+            }
+            catalog.years.push(performance.year());
+            catalog
+                .by_festival
+                .entry(festival.to_ascii_lowercase())
+                .or_default()
+                .push(i);
+            catalog
+                .by_year
+                .entry(performance.year())
+                .or_default()
+                .push(i);
+        }
+        catalog.years.sort_unstable();
+        catalog.years.dedup();
+        catalog
+    }
+
+    /// Where the performances `filters` could let through are in the list, in
+    /// order, going by the festivals or years it names, whichever leaves fewer;
+    /// `None` when it names neither. The other filters still need checking.
+    pub fn positions(&self, filters: &Filters) -> Option<Vec<u32>> {
+        let by_festival = (!filters.festivals.is_empty()).then(|| {
+            union(
+                filters
+                    .festivals
+                    .iter()
+                    .filter_map(|f| self.by_festival.get(&f.to_ascii_lowercase())),
+            )
+        });
+        let from = filters.year_from.unwrap_or(u16::MIN);
+        let to = filters.year_to.unwrap_or(u16::MAX);
+        let by_year = if !filters.years.is_empty() {
+            Some(union(
+                filters.years.iter().filter_map(|y| self.by_year.get(y)),
+            ))
+        } else if from > to {
+            Some(Vec::new())
+        } else if filters.year_from.is_some() || filters.year_to.is_some() {
+            Some(union(self.by_year.range(from..=to).map(|(_, p)| p)))
+        } else {
+            None
+        };
+        match (by_festival, by_year) {
+            (Some(a), Some(b)) => Some(if a.len() <= b.len() { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Parses a year ("2011") or year range ("2010-2014", "2010..2014") if every
+    /// year mentioned is one the dataset covers.
+    pub fn parse_years(&self, token: &str) -> Option<(u16, u16)> {
+        let known = |text: &str| {
+            text.parse::<u16>()
+                .ok()
+                .filter(|year| self.years.binary_search(year).is_ok())
+        };
+        match token.split_once("..").or_else(|| token.split_once('-')) {
+            Some((from, to)) => {
+                let (from, to) = (known(from)?, known(to)?);
+                Some((from.min(to), from.max(to)))
+            }
+            None => known(token).map(|year| (year, year)),
+        }
+    }
+}
+
+// The positions in any of `lists`, in order and each once.
+fn union<'a>(lists: impl Iterator<Item = &'a Vec<u32>>) -> Vec<u32> {
+    let mut positions: Vec<u32> = lists.flatten().copied().collect();
+    positions.sort_unstable();
+    positions.dedup();
+    positions
+}
@@ -0,0 +1,100 @@
+// Probes for load balancers and orchestrators. `/healthz` answers whenever the
+// process can answer at all; `/readyz` only when it has data to serve and the
+// shared cache, if one is configured, can be reached. Both are cheap, and are
+// left out of the rate and in-flight limits, so a busy server isn't taken for a
+// dead one.
+//
+// While a dataset has no performances, because it hasn't loaded yet or what
+// loaded was empty, its data endpoints answer 503 saying so, rather than 404s
+// and empty lists that look like a bug. `/api/dataset` and the admin API keep
+// working, so it can be looked into and fixed with a reload or an upload.
+
+use crate::{state, SHARED_CACHE};
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use std::collections::BTreeMap;
+
+/// Handler for `/healthz`: the process is up.
+pub async fn live() -> Response {
+    Json(serde_json::json!({ "status": "ok" })).into_response()
+}
+
+/// Handler for `/readyz`: each check with "ok" or what's wrong, and a 503
+/// unless they all pass.
+pub async fn ready(State(datasets): State<state::Datasets>) -> Response {
+    let mut checks = BTreeMap::new();
+    let served = std::iter::once(datasets.main.clone()).chain(datasets.list());
+    let empty: Vec<String> = served
+        .filter(|shared| shared.current().all_performances.is_empty())
+        .map(|shared| match &shared.dataset.name {
+            Some(name) => name.clone(),
+            None => "the main dataset".to_string(),
+        })
+        .collect();
+    checks.insert("data", problem(&empty, "no performances loaded"));
+    if let Some(cache) = SHARED_CACHE.as_ref() {
+        let reachable = cache.ping().await;
+        checks.insert(
+            "shared_cache",
+            if reachable { "ok" } else { "unreachable" }.into(),
+        );
+    }
+
+    let ready = checks.values().all(|check| check == "ok");
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = serde_json::json!({
+        "status": if ready { "ok" } else { "unavailable" },
+        "checks": checks,
+    });
+    (status, Json(body)).into_response()
+}
+
+// "ok", or which datasets have `what` wrong with them.
+fn problem(datasets: &[String], what: &str) -> String {
+    if datasets.is_empty() {
+        "ok".to_string()
+    } else {
+        format!("{}: {}", datasets.join(", "), what)
+    }
+}
+
+/// Middleware answering requests with a 503 while the dataset has no
+/// performances, with a `reason` for clients: "not_loaded" before anything has
+/// loaded, and "empty" when what loaded had none.
+pub async fn require_data(
+    State(shared): State<state::SharedState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let current = shared.current();
+    if !current.all_performances.is_empty() {
+        drop(current);
+        return next.run(request).await;
+    }
+    let (reason, error) = match &current.source {
+        None => (
+            "not_loaded",
+            "No data has loaded yet; it's served as soon as the source can be read.".to_string(),
+        ),
+        Some(source) => (
+            "empty",
+            format!(
+                "{} has no performances, so there's nothing to serve.",
+                source
+            ),
+        ),
+    };
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        // Watching and refreshing pick up a fixed source within about this long.
+        [(header::RETRY_AFTER, "30")],
+        Json(serde_json::json!({ "error": error, "reason": reason })),
+    )
+        .into_response()
+}
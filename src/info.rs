@@ -0,0 +1,72 @@
+// What a running server is: its build, how long it's been up, how much memory
+// it and each dataset take, which requests were slow lately, and the settings
+// it's using, for GET /api/admin/info. Meant for working out what a deployment
+// is actually doing, so secrets are left out of the settings.
+
+use crate::search::IndexSizes;
+use crate::{clock, slow, state, CONFIG, STARTED};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::response::{IntoResponse, Json, Response};
+use serde::Serialize;
+use std::fs;
+
+#[derive(Debug, Serialize)]
+struct Dataset {
+    name: Option<String>,
+    performances: usize,
+    // About how many bytes the performances and the index take; see
+    // `AppState::performance_bytes` and `SearchIndex::sizes`.
+    performance_bytes: usize,
+    // The size of the /api/all-bands body, which is kept serialized; `None`
+    // when it's streamed instead.
+    export_bytes: Option<usize>,
+    index: IndexSizes,
+}
+
+/// Admin handler describing the server.
+pub async fn handler(State(datasets): State<state::Datasets>) -> Response {
+    let (started_at, started) = *STARTED;
+    let served = std::iter::once(datasets.main.clone()).chain(datasets.list());
+    let datasets: Vec<Dataset> = served
+        .map(|shared| {
+            let state = shared.current();
+            Dataset {
+                name: shared.dataset.name.clone(),
+                performances: state.all_performances.len(),
+                performance_bytes: state.performance_bytes(),
+                export_bytes: state.all_bands_json.as_ref().map(Bytes::len),
+                index: state.search_index.sizes(),
+            }
+        })
+        .collect();
+    let (slow_routes, slow_recent) = slow::report();
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_hash": option_env!("GIT_HASH"),
+        "started_at": clock::rfc3339(started_at),
+        "uptime_seconds": started.elapsed().as_secs(),
+        "resident_bytes": resident_bytes(),
+        "datasets": datasets,
+        "slow_requests": {
+            "routes": slow_routes,
+            "recent": slow_recent,
+        },
+        "config": CONFIG.redacted(),
+    }))
+    .into_response()
+}
+
+// The memory the process has in RAM, where /proc says; `None` elsewhere.
+fn resident_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
@@ -0,0 +1,613 @@
+// How a snapshot keeps its performances in memory, picked by `data.layout`:
+//
+// - "rows", the default: one `ArtistPerformance` per performance, each with its
+//   own strings and lists.
+// - "columns": a column per field, where each distinct string, artist and list
+//   of genres is kept once and performances refer to them by number: 31 bytes
+//   each, plus the artists and other values they share, rather than several
+//   hundred, which is what a dataset of millions of performances needs. IDs are
+//   written out again whenever one is sent, which is a little slower.
+//
+// Everything that reads performances (searching, filtering, browsing, the
+// statistics and exports) does so through `Performance`, so it works the same
+// over either layout.
+
+use crate::state;
+use crate::ArtistPerformance;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// The layouts `data.layout` can pick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Rows,
+    Columns,
+}
+
+impl Kind {
+    pub fn parse(text: &str) -> Option<Kind> {
+        match text {
+            "rows" => Some(Kind::Rows),
+            "columns" => Some(Kind::Columns),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Rows => "rows",
+            Kind::Columns => "columns",
+        }
+    }
+}
+
+// What a layout answers about the performance at each position.
+trait Layout: fmt::Debug + Send + Sync {
+    fn len(&self) -> usize;
+    fn id(&self, i: usize) -> Cow<'_, str>;
+    fn id_hash(&self, i: usize) -> u64;
+    fn name(&self, i: usize) -> &Arc<str>;
+    fn festival(&self, i: usize) -> &str;
+    fn year(&self, i: usize) -> u16;
+    fn genres(&self, i: usize) -> &[String];
+    fn country(&self, i: usize) -> Option<&str>;
+    fn stage(&self, i: usize) -> Option<&str>;
+    fn day(&self, i: usize) -> Option<&str>;
+    fn start_time(&self, i: usize) -> Option<&str>;
+    fn headliner(&self, i: usize) -> bool;
+    fn cancelled(&self, i: usize) -> bool;
+    fn search_key(&self, i: usize) -> &str;
+    fn phonetic_codes(&self, i: usize) -> &[String];
+    // About how many bytes the performances take, counting shared values once.
+    fn bytes(&self) -> usize;
+}
+
+/// A snapshot's performances, in the order they were flattened, in whichever
+/// layout the config picked.
+#[derive(Debug)]
+pub struct Performances(Box<dyn Layout>);
+
+impl Performances {
+    pub fn new(rows: Vec<ArtistPerformance>, kind: Kind) -> Self {
+        match kind {
+            Kind::Rows => Performances(Box::new(Rows(rows))),
+            Kind::Columns => Performances(Box::new(Columns::new(rows))),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The performance at `position`, which has to be below `len`.
+    pub fn get(&self, position: u32) -> Performance<'_> {
+        Performance {
+            layout: &*self.0,
+            position,
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Performance<'_>> + '_ {
+        (0..self.len() as u32).map(|position| self.get(position))
+    }
+
+    /// About how many bytes the performances take, counting names and other
+    /// values shared between them once; the index comes on top.
+    pub fn bytes(&self) -> usize {
+        self.0.bytes()
+    }
+}
+
+impl Serialize for Performances {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// One performance, borrowed from a snapshot's `Performances`.
+#[derive(Clone, Copy)]
+pub struct Performance<'a> {
+    layout: &'a dyn Layout,
+    position: u32,
+}
+
+impl<'a> Performance<'a> {
+    /// Where it is among the snapshot's performances.
+    pub fn position(self) -> u32 {
+        self.position
+    }
+
+    // Stays the same across reloads of the same data; see `state::performance_id`.
+    pub fn id(self) -> Cow<'a, str> {
+        self.layout.id(self.position as usize)
+    }
+
+    /// The hash the ID is written from, to compare IDs without writing them out.
+    pub fn id_hash(self) -> u64 {
+        self.layout.id_hash(self.position as usize)
+    }
+
+    pub fn name(self) -> &'a str {
+        self.layout.name(self.position as usize)
+    }
+
+    /// The name, as the copy every performance by the artist shares.
+    pub fn shared_name(self) -> Arc<str> {
+        Arc::clone(self.layout.name(self.position as usize))
+    }
+
+    pub fn festival(self) -> &'a str {
+        self.layout.festival(self.position as usize)
+    }
+
+    pub fn year(self) -> u16 {
+        self.layout.year(self.position as usize)
+    }
+
+    pub fn genres(self) -> &'a [String] {
+        self.layout.genres(self.position as usize)
+    }
+
+    pub fn country(self) -> Option<&'a str> {
+        self.layout.country(self.position as usize)
+    }
+
+    pub fn stage(self) -> Option<&'a str> {
+        self.layout.stage(self.position as usize)
+    }
+
+    pub fn day(self) -> Option<&'a str> {
+        self.layout.day(self.position as usize)
+    }
+
+    pub fn start_time(self) -> Option<&'a str> {
+        self.layout.start_time(self.position as usize)
+    }
+
+    pub fn headliner(self) -> bool {
+        self.layout.headliner(self.position as usize)
+    }
+
+    // Whether the edition was cancelled, so the performance never happened.
+    pub fn cancelled(self) -> bool {
+        self.layout.cancelled(self.position as usize)
+    }
+
+    // Normalized form of `name` that all matching happens against.
+    pub fn search_key(self) -> &'a str {
+        self.layout.search_key(self.position as usize)
+    }
+
+    // Sound-alike keys for each word of the name, used by phonetic search.
+    pub fn phonetic_codes(self) -> &'a [String] {
+        self.layout.phonetic_codes(self.position as usize)
+    }
+
+    /// A copy of its own, to keep once the snapshot is gone.
+    pub fn to_owned(self) -> ArtistPerformance {
+        ArtistPerformance {
+            id: self.id().into_owned(),
+            name: self.shared_name(),
+            festival: self.festival().into(),
+            year: self.year(),
+            genres: self.genres().to_vec(),
+            country: self.country().map(str::to_string),
+            stage: self.stage().map(str::to_string),
+            day: self.day().map(str::to_string),
+            start_time: self.start_time().map(str::to_string),
+            headliner: self.headliner(),
+            cancelled: self.cancelled(),
+            search_key: self.search_key().to_string(),
+            phonetic_codes: self.phonetic_codes().to_vec(),
+        }
+    }
+}
+
+impl fmt::Debug for Performance<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Performance")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .field("festival", &self.festival())
+            .field("year", &self.year())
+            .finish_non_exhaustive()
+    }
+}
+
+// The same fields, with the same ones left out, as `ArtistPerformance`.
+impl Serialize for Performance<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut fields = serializer.serialize_struct("ArtistPerformance", 11)?;
+        fields.serialize_field("id", &self.id())?;
+        fields.serialize_field("name", self.name())?;
+        fields.serialize_field("festival", self.festival())?;
+        fields.serialize_field("year", &self.year())?;
+        let genres = self.genres();
+        if genres.is_empty() {
+            fields.skip_field("genres")?;
+        } else {
+            fields.serialize_field("genres", genres)?;
+        }
+        for (name, value) in [
+            ("country", self.country()),
+            ("stage", self.stage()),
+            ("day", self.day()),
+            ("start_time", self.start_time()),
+        ] {
+            match value {
+                Some(value) => fields.serialize_field(name, value)?,
+                None => fields.skip_field(name)?,
+            }
+        }
+        for (name, value) in [
+            ("headliner", self.headliner()),
+            ("cancelled", self.cancelled()),
+        ] {
+            if value {
+                fields.serialize_field(name, &true)?;
+            } else {
+                fields.skip_field(name)?;
+            }
+        }
+        fields.end()
+    }
+}
+
+// --- Rows ---
+
+#[derive(Debug)]
+struct Rows(Vec<ArtistPerformance>);
+
+impl Layout for Rows {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn id(&self, i: usize) -> Cow<'_, str> {
+        Cow::Borrowed(&self.0[i].id)
+    }
+
+    fn id_hash(&self, i: usize) -> u64 {
+        state::parse_id(&self.0[i].id).expect("IDs are 16 hexadecimal digits")
+    }
+
+    fn name(&self, i: usize) -> &Arc<str> {
+        &self.0[i].name
+    }
+
+    fn festival(&self, i: usize) -> &str {
+        &self.0[i].festival
+    }
+
+    fn year(&self, i: usize) -> u16 {
+        self.0[i].year
+    }
+
+    fn genres(&self, i: usize) -> &[String] {
+        &self.0[i].genres
+    }
+
+    fn country(&self, i: usize) -> Option<&str> {
+        self.0[i].country.as_deref()
+    }
+
+    fn stage(&self, i: usize) -> Option<&str> {
+        self.0[i].stage.as_deref()
+    }
+
+    fn day(&self, i: usize) -> Option<&str> {
+        self.0[i].day.as_deref()
+    }
+
+    fn start_time(&self, i: usize) -> Option<&str> {
+        self.0[i].start_time.as_deref()
+    }
+
+    fn headliner(&self, i: usize) -> bool {
+        self.0[i].headliner
+    }
+
+    fn cancelled(&self, i: usize) -> bool {
+        self.0[i].cancelled
+    }
+
+    fn search_key(&self, i: usize) -> &str {
+        &self.0[i].search_key
+    }
+
+    fn phonetic_codes(&self, i: usize) -> &[String] {
+        &self.0[i].phonetic_codes
+    }
+
+    fn bytes(&self) -> usize {
+        let strings = |list: &[String]| list.iter().map(String::capacity).sum::<usize>();
+        let optional = |text: &Option<String>| text.as_ref().map_or(0, String::capacity);
+        let mut counted = HashSet::new();
+        let mut shared = |text: &Arc<str>| {
+            if counted.insert(text.as_ptr()) {
+                text.len()
+            } else {
+                0
+            }
+        };
+        self.0
+            .iter()
+            .map(|p| {
+                size_of::<ArtistPerformance>()
+                    + p.id.capacity()
+                    + shared(&p.name)
+                    + shared(&p.festival)
+                    + p.genres.capacity() * size_of::<String>()
+                    + strings(&p.genres)
+                    + optional(&p.country)
+                    + optional(&p.stage)
+                    + optional(&p.day)
+                    + optional(&p.start_time)
+                    + p.search_key.capacity()
+                    + p.phonetic_codes.capacity() * size_of::<String>()
+                    + strings(&p.phonetic_codes)
+            })
+            .sum()
+    }
+}
+
+// --- Columns ---
+
+// Marks a text column's missing values.
+const NONE: u32 = u32::MAX;
+
+const HEADLINER: u8 = 1;
+const CANCELLED: u8 = 2;
+
+#[derive(Debug, Default)]
+struct Columns {
+    // A value per performance, or a number into the tables below.
+    ids: Vec<u64>,
+    artists: Vec<u32>,
+    festivals: Vec<u32>,
+    years: Vec<u16>,
+    genres: Vec<u32>,
+    countries: Vec<u32>,
+    stages: Vec<u32>,
+    days: Vec<u32>,
+    start_times: Vec<u32>,
+    flags: Vec<u8>,
+
+    // Each distinct spelling of an artist's name, with what it's matched by.
+    names: Vec<Artist>,
+    // Each distinct list of genres.
+    genre_lists: Vec<Box<[String]>>,
+    // Each distinct festival name, country, stage, day and start time.
+    texts: Vec<Arc<str>>,
+}
+
+#[derive(Debug)]
+struct Artist {
+    name: Arc<str>,
+    search_key: Box<str>,
+    phonetic_codes: Box<[String]>,
+}
+
+impl Columns {
+    fn new(rows: Vec<ArtistPerformance>) -> Self {
+        let mut columns = Columns::default();
+        let mut artists: HashMap<Arc<str>, u32> = HashMap::new();
+        let mut genre_lists: HashMap<Vec<String>, u32> = HashMap::new();
+        let mut texts: HashMap<Arc<str>, u32> = HashMap::new();
+        let mut text = |columns: &mut Columns, value: &str| -> u32 {
+            if let Some(&number) = texts.get(value) {
+                return number;
+            }
+            let number = columns.texts.len() as u32;
+            let value: Arc<str> = value.into();
+            columns.texts.push(value.clone());
+            texts.insert(value, number);
+            number
+        };
+
+        for row in rows {
+            columns
+                .ids
+                .push(state::parse_id(&row.id).expect("IDs are 16 hexadecimal digits"));
+            let artist = *artists.entry(row.name.clone()).or_insert_with(|| {
+                columns.names.push(Artist {
+                    name: row.name.clone(),
+                    search_key: row.search_key.into(),
+                    phonetic_codes: row.phonetic_codes.into(),
+                });
+                columns.names.len() as u32 - 1
+            });
+            columns.artists.push(artist);
+            let festival = text(&mut columns, &row.festival);
+            columns.festivals.push(festival);
+            columns.years.push(row.year);
+            let genres = *genre_lists.entry(row.genres).or_insert_with_key(|genres| {
+                columns.genre_lists.push(genres.clone().into());
+                columns.genre_lists.len() as u32 - 1
+            });
+            columns.genres.push(genres);
+            let [country, stage, day, start_time] =
+                [row.country, row.stage, row.day, row.start_time]
+                    .map(|value| value.map_or(NONE, |value| text(&mut columns, &value)));
+            columns.countries.push(country);
+            columns.stages.push(stage);
+            columns.days.push(day);
+            columns.start_times.push(start_time);
+            let mut flags = 0;
+            if row.headliner {
+                flags |= HEADLINER;
+            }
+            if row.cancelled {
+                flags |= CANCELLED;
+            }
+            columns.flags.push(flags);
+        }
+        columns
+    }
+
+    fn artist(&self, i: usize) -> &Artist {
+        &self.names[self.artists[i] as usize]
+    }
+
+    fn text(&self, number: u32) -> Option<&str> {
+        (number != NONE).then(|| &*self.texts[number as usize])
+    }
+}
+
+impl Layout for Columns {
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn id(&self, i: usize) -> Cow<'_, str> {
+        Cow::Owned(state::format_id(self.ids[i]))
+    }
+
+    fn id_hash(&self, i: usize) -> u64 {
+        self.ids[i]
+    }
+
+    fn name(&self, i: usize) -> &Arc<str> {
+        &self.artist(i).name
+    }
+
+    fn festival(&self, i: usize) -> &str {
+        &self.texts[self.festivals[i] as usize]
+    }
+
+    fn year(&self, i: usize) -> u16 {
+        self.years[i]
+    }
+
+    fn genres(&self, i: usize) -> &[String] {
+        &self.genre_lists[self.genres[i] as usize]
+    }
+
+    fn country(&self, i: usize) -> Option<&str> {
+        self.text(self.countries[i])
+    }
+
+    fn stage(&self, i: usize) -> Option<&str> {
+        self.text(self.stages[i])
+    }
+
+    fn day(&self, i: usize) -> Option<&str> {
+        self.text(self.days[i])
+    }
+
+    fn start_time(&self, i: usize) -> Option<&str> {
+        self.text(self.start_times[i])
+    }
+
+    fn headliner(&self, i: usize) -> bool {
+        self.flags[i] & HEADLINER != 0
+    }
+
+    fn cancelled(&self, i: usize) -> bool {
+        self.flags[i] & CANCELLED != 0
+    }
+
+    fn search_key(&self, i: usize) -> &str {
+        &self.artist(i).search_key
+    }
+
+    fn phonetic_codes(&self, i: usize) -> &[String] {
+        &self.artist(i).phonetic_codes
+    }
+
+    fn bytes(&self) -> usize {
+        // The ID, seven numbers into the tables, the year and the flags.
+        let per_performance =
+            size_of::<u64>() + size_of::<[u32; 7]>() + size_of::<u16>() + size_of::<u8>();
+        let names: usize = self
+            .names
+            .iter()
+            .map(|artist| {
+                size_of::<Artist>()
+                    + artist.name.len()
+                    + artist.search_key.len()
+                    + artist.phonetic_codes.len() * size_of::<String>()
+                    + artist.phonetic_codes.iter().map(String::len).sum::<usize>()
+            })
+            .sum();
+        let lists: usize = self
+            .genre_lists
+            .iter()
+            .map(|list| {
+                list.len() * size_of::<String>() + list.iter().map(String::len).sum::<usize>()
+            })
+            .sum();
+        let texts: usize = self
+            .texts
+            .iter()
+            .map(|text| size_of::<Arc<str>>() + text.len())
+            .sum();
+        self.len() * per_performance + names + lists + texts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<ArtistPerformance> {
+        let row = |name: &str, festival: &str, year: u16| {
+            let search_key = crate::search::normalize(name);
+            ArtistPerformance {
+                id: state::performance_id(festival, year, name),
+                name: name.into(),
+                festival: festival.into(),
+                year,
+                genres: Vec::new(),
+                country: None,
+                stage: None,
+                day: None,
+                start_time: None,
+                headliner: false,
+                cancelled: false,
+                phonetic_codes: crate::phonetic::codes(&search_key),
+                search_key,
+            }
+        };
+        let mut rows = vec![
+            row("Muse", "Pinkpop", 2008),
+            row("Editors", "Pinkpop", 2008),
+            row("Muse", "Lowlands", 2011),
+        ];
+        rows[0].genres = vec!["rock".to_string(), "alternative".to_string()];
+        rows[0].country = Some("GB".to_string());
+        rows[0].headliner = true;
+        rows[2].genres = rows[0].genres.clone();
+        rows[2].stage = Some("Alpha".to_string());
+        rows[2].cancelled = true;
+        rows
+    }
+
+    #[test]
+    fn columns_read_the_same_as_rows() {
+        let by_rows = Performances::new(rows(), Kind::Rows);
+        let by_columns = Performances::new(rows(), Kind::Columns);
+        assert_eq!(
+            serde_json::to_value(&by_columns).unwrap(),
+            serde_json::to_value(&by_rows).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&by_rows).unwrap(),
+            serde_json::to_value(rows()).unwrap()
+        );
+        for (a, b) in by_rows.iter().zip(by_columns.iter()) {
+            assert_eq!(a.id(), b.id());
+            assert_eq!(a.search_key(), b.search_key());
+            assert_eq!(a.phonetic_codes(), b.phonetic_codes());
+        }
+        // Muse, its genres and Pinkpop are each kept once.
+        assert!(by_columns.bytes() < by_rows.bytes());
+    }
+}
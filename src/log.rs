@@ -0,0 +1,392 @@
+// The server's log: `tracing` events about what it's doing, printed to stdout
+// when the configured level (--log-level, or `log.level` in the config file)
+// lets them through, and to stderr for errors. Lines are plain text for people
+// to read, or JSON objects for log collectors (`log.format`). Only problems
+// with the config or flags, found before there's a log to write to, are
+// printed to stderr directly.
+//
+// Each request runs in a span naming its method, path and ID, and every event
+// logged while it's handled ends with those, so they can be told apart. Spans
+// are always kept, whatever the level, as they're only printed as part of
+// events.
+
+use crate::requestid::RequestId;
+use crate::{clock, CONFIG};
+use axum::http::{Request, Response};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Span, Subscriber};
+use tracing_core::span::Current;
+
+/// How much to log, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    // Only errors.
+    Error,
+    // Also problems the server works around, such as a source that didn't load.
+    Warn,
+    // Also what it does: loading data, reloads, changes made through the admin API.
+    Info,
+    // Also the details, from the libraries too, such as why a request was rejected.
+    Debug,
+}
+
+impl Level {
+    pub fn parse(text: &str) -> Option<Level> {
+        match text {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    fn of(level: &tracing::Level) -> Level {
+        match *level {
+            tracing::Level::ERROR => Level::Error,
+            tracing::Level::WARN => Level::Warn,
+            tracing::Level::INFO => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+}
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    // The message, then " name=value" for each field.
+    Text,
+    // One JSON object per line, with the time, level, message and fields.
+    Json,
+}
+
+impl Format {
+    pub fn parse(text: &str) -> Option<Format> {
+        match text {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::Json => "json",
+        }
+    }
+}
+
+/// Sends the server's `tracing` events to the log.
+pub fn init() {
+    let printer = Printer::new(|| (CONFIG.log_level, CONFIG.log_format), print);
+    tracing::subscriber::set_global_default(printer).expect("the log is only set up once");
+}
+
+fn print(level: &tracing::Level, line: &str) {
+    if *level == tracing::Level::ERROR {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// The span each request is handled in.
+pub fn request_span<B>(request: &Request<B>) -> Span {
+    let id = request.extensions().get::<RequestId>().map(|id| &id.0);
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri(),
+        request_id = id.map(tracing::field::display),
+    )
+}
+
+/// Logs a response at the level `log.requests` sets, with its status and how
+/// long it took.
+pub fn response<B>(response: &Response<B>, latency: Duration, _: &Span) {
+    let status = response.status().as_u16();
+    let ms = latency.as_millis() as u64;
+    match CONFIG.request_log_level {
+        Some(Level::Error) => tracing::error!(status, ms, "->> Answered"),
+        Some(Level::Warn) => tracing::warn!(status, ms, "->> Answered"),
+        Some(Level::Info) => tracing::info!(status, ms, "->> Answered"),
+        Some(Level::Debug) => tracing::debug!(status, ms, "->> Answered"),
+        None => {}
+    }
+}
+
+struct Printer {
+    spans: Mutex<HashMap<u64, Open>>,
+    next_id: AtomicU64,
+    // The most to log and how, and where log lines go.
+    settings: fn() -> (Level, Format),
+    write: fn(&tracing::Level, &str),
+}
+
+impl Printer {
+    fn new(settings: fn() -> (Level, Format), write: fn(&tracing::Level, &str)) -> Printer {
+        Printer {
+            spans: Mutex::default(),
+            next_id: AtomicU64::new(1),
+            settings,
+            write,
+        }
+    }
+}
+
+// A span that's still open.
+struct Open {
+    metadata: &'static Metadata<'static>,
+    fields: Vec<(&'static str, Value)>,
+    // How many handles it has.
+    handles: usize,
+}
+
+thread_local! {
+    // The spans entered on this thread, innermost last.
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Subscriber for Printer {
+    // The level can change on a reload, so every event is checked as it comes.
+    fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span() || Level::of(metadata.level()) <= (self.settings)().0
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let span = Open {
+            metadata: attributes.metadata(),
+            fields: fields.rest,
+            handles: 1,
+        };
+        self.spans.lock().unwrap().insert(id, span);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            span.fields.extend(fields.rest);
+        }
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        {
+            let spans = self.spans.lock().unwrap();
+            ENTERED.with_borrow(|entered| {
+                for id in entered {
+                    if let Some(span) = spans.get(id) {
+                        fields.rest.extend(span.fields.iter().cloned());
+                    }
+                }
+            });
+        }
+        let level = event.metadata().level();
+        let line = match (self.settings)().1 {
+            Format::Text => text(level, &fields),
+            Format::Json => json(SystemTime::now(), level, &fields),
+        };
+        (self.write)(level, &line);
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with_borrow_mut(|entered| entered.push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with_borrow_mut(|entered| {
+            if let Some(at) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(at);
+            }
+        });
+    }
+
+    // So that `Span::current()` finds it, as when handlers are spawned.
+    fn current_span(&self) -> Current {
+        let Some(id) = ENTERED.with_borrow(|entered| entered.last().copied()) else {
+            return Current::none();
+        };
+        match self.spans.lock().unwrap().get(&id) {
+            Some(span) => Current::new(Id::from_u64(id), span.metadata),
+            None => Current::none(),
+        }
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            span.handles += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let Some(open) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+        open.handles -= 1;
+        let closed = open.handles == 0;
+        if closed {
+            spans.remove(&span.into_u64());
+        }
+        closed
+    }
+}
+
+// An event's message, and its other fields, followed by those of the spans
+// it's in, outermost first.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: Vec<(&'static str, Value)>,
+}
+
+impl Visit for Fields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.rest.push((field.name(), value.into()));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.rest.push((field.name(), value.into()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.rest.push((field.name(), value.into()));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{}", value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let value = format!("{:?}", value);
+        if field.name() == "message" {
+            self.message = value;
+        } else {
+            self.rest.push((field.name(), value.into()));
+        }
+    }
+}
+
+// The message, then " name=value" for each field. Errors say so first.
+fn text(level: &tracing::Level, fields: &Fields) -> String {
+    let mut line = match *level {
+        tracing::Level::ERROR => format!("error: {}", fields.message),
+        _ => fields.message.clone(),
+    };
+    for (name, value) in &fields.rest {
+        let value = match value {
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        };
+        line.push_str(&format!(" {}={}", name, value));
+    }
+    line
+}
+
+// A JSON object with the time, level and message first. The message loses the
+// marker and line breaks that set it apart in a terminal.
+fn json(time: SystemTime, level: &tracing::Level, fields: &Fields) -> String {
+    let message = fields.message.trim();
+    let message = message.strip_prefix("->>").unwrap_or(message).trim_start();
+    let mut line = format!(
+        "{{\"time\":\"{}\",\"level\":\"{}\",\"message\":{}",
+        clock::rfc3339(time),
+        Level::of(level).name(),
+        Value::from(message),
+    );
+    for (name, value) in &fields.rest {
+        line.push_str(&format!(",{}:{}", Value::from(*name), value));
+    }
+    line.push('}');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    thread_local! {
+        static LINES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn keep(_: &tracing::Level, line: &str) {
+        LINES.with_borrow_mut(|lines| lines.push(line.to_string()));
+    }
+
+    #[test]
+    fn events_end_with_the_fields_of_their_spans() {
+        tracing::subscriber::with_default(
+            Printer::new(|| (Level::Info, Format::Text), keep),
+            || {
+                tracing::info!("->> Loaded {} performances", 3);
+                let span = tracing::info_span!("request", method = "GET", path = %"/api/search");
+                span.in_scope(|| tracing::info!(status = 200, ms = 4, "->> Answered"));
+                let current = span.in_scope(Span::current);
+                current.in_scope(|| tracing::info!("->> Still"));
+                drop(current);
+                drop(span);
+                tracing::debug!("->> Not logged");
+                tracing::warn!("->> Outside");
+            },
+        );
+        let lines = LINES.take();
+        assert_eq!(
+            lines,
+            [
+                "->> Loaded 3 performances",
+                "->> Answered status=200 ms=4 method=GET path=/api/search",
+                "->> Still method=GET path=/api/search",
+                "->> Outside",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_have_the_time_level_and_message_first() {
+        let fields = Fields {
+            message: "->> Loaded \"uk\"\n".to_string(),
+            rest: vec![("status", 200.into()), ("path", "/api".into())],
+        };
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(86_400);
+        assert_eq!(
+            json(time, &tracing::Level::WARN, &fields),
+            r#"{"time":"1970-01-02T00:00:00Z","level":"warn","message":"Loaded \"uk\"","status":200,"path":"/api"}"#
+        );
+        assert_eq!(
+            text(&tracing::Level::INFO, &fields),
+            "->> Loaded \"uk\"\n status=200 path=/api"
+        );
+    }
+}
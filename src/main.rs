@@ -1,157 +1,945 @@
 use axum::{
-    extract::{Query, State},
-    http::{header, StatusCode},
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    handler::Handler,
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    middleware,
     response::{Html, IntoResponse, Json},
-    routing::get,
+    routing::{any, any_service, delete, get, post, put},
     Router,
 };
 use once_cell::sync::Lazy;
 use rand::seq::IndexedRandom;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
+use std::time::{Instant, SystemTime};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+use tower_http::trace::TraceLayer;
 
-// --- Data Structures ---
-
-// Structs to parse the initial JSON data from the file.
-#[derive(Debug, Deserialize)]
-struct BandData {
-    festivals: Vec<Festival>,
-}
+mod access;
+mod accesslog;
+mod admin;
+mod aliases;
+mod artists;
+mod audit;
+mod body;
+mod browse;
+mod cache;
+mod cachecontrol;
+mod clock;
+mod compression;
+mod concurrency;
+mod conditional;
+mod config;
+mod countries;
+mod csv;
+mod data;
+mod deflate;
+mod diff;
+mod digest;
+mod edit;
+mod export;
+mod fallback;
+mod festivals;
+mod fetch;
+mod filter;
+mod health;
+mod info;
+mod jwt;
+mod layout;
+mod log;
+mod nfc;
+mod panics;
+mod parallel;
+mod pattern;
+mod phonetic;
+mod quality;
+mod ratelimit;
+mod redis;
+mod requestid;
+mod rsa;
+#[cfg(feature = "s3")]
+mod s3;
+mod saved;
+mod schema;
+mod search;
+mod session;
+mod slow;
+mod snapshot;
+mod state;
+mod stats;
+mod store;
+mod timeout;
+mod toml;
+mod validate;
+mod yaml;
 
-#[derive(Debug, Deserialize)]
-struct Festival {
-    name: String,
-    years: Vec<FestivalYear>,
-}
+use state::{AppState, SharedState};
 
-#[derive(Debug, Deserialize)]
-struct FestivalYear {
-    year: u16,
-    artists: Vec<String>,
-}
+// --- Data Structures ---
 
 // A new, flattened struct to hold performance details.
 // This is easier to work with and will be used for all API responses.
 #[derive(Debug, Clone, Serialize)]
 struct ArtistPerformance {
-    name: String,
-    festival: String,
+    // Stays the same across reloads of the same data; see `state::performance_id`.
+    id: String,
+    // Shared with every other performance by the same artist, or at the same
+    // festival; see `state::Interner`.
+    name: Arc<str>,
+    festival: Arc<str>,
     year: u16,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    genres: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    day: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_time: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    headliner: bool,
+    // Whether the edition was cancelled, so the performance never happened.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cancelled: bool,
+    // Normalized form of `name` that all matching happens against.
+    #[serde(skip)]
+    search_key: String,
+    // Sound-alike keys for each word of the name, used by phonetic search.
+    #[serde(skip)]
+    phonetic_codes: Vec<String>,
 }
 
-// --- Application State ---
-// This struct holds the flattened list of all performances, ready for any operation.
-#[derive(Debug)]
-struct AppState {
-    all_performances: Vec<ArtistPerformance>,
+// Flags, environment variables and the config file, read before anything else
+// and again on SIGHUP.
+static CONFIG: Lazy<config::Current> = Lazy::new(|| config::Current::new(config::Config::load()));
+
+// Responses shared with other instances, when the config names a Redis server.
+static SHARED_CACHE: Lazy<Option<redis::SharedCache>> = Lazy::new(|| {
+    CONFIG
+        .shared_cache
+        .clone()
+        .map(|address| redis::SharedCache::new(address, CONFIG.shared_cache_ttl))
+});
+
+// When the server started, for GET /api/admin/info.
+static STARTED: Lazy<(SystemTime, Instant)> = Lazy::new(|| (SystemTime::now(), Instant::now()));
+
+// --- Query Parameters for the API Request ---
+
+#[derive(Debug, Deserialize)]
+struct RandomBandParams {
+    count: Option<usize>,
+    // Whether to leave out performances this visitor's session was given before.
+    #[serde(default)]
+    unseen: bool,
+    // Whether to give at most one performance of each artist.
+    #[serde(default)]
+    distinct: bool,
 }
 
-// Use Lazy to read and process the file only once at application startup.
-static APP_STATE: Lazy<Arc<AppState>> = Lazy::new(|| {
-    println!("Loading bands.json into memory...");
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SearchMode {
+    // Normalized substring matching with typo tolerance.
+    #[default]
+    Fuzzy,
+    // `q` is a regular expression over the displayed artist names.
+    Regex,
+    // Sound-alike matching on precomputed Metaphone keys.
+    Phonetic,
+}
 
-    // Read the JSON file from the project root.
-    let file_content = fs::read_to_string("bands.json")
-        .expect("Failed to read bands.json. Make sure the file is in the project root.");
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    q: String,
+    #[serde(default)]
+    mode: SearchMode,
+    // Maximum edit distance per term; picked from each term's length when omitted.
+    fuzziness: Option<usize>,
+}
 
-    // Parse the JSON into our Rust structs.
-    let band_data: BandData =
-        serde_json::from_str(&file_content).expect("Failed to parse bands.json.");
+// A complete search request: what `/api/search` takes as query parameters, and
+// the JSON body that `POST /api/searches` stores.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+struct SearchDefinition {
+    q: String,
+    #[serde(default)]
+    mode: SearchMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fuzziness: Option<usize>,
+    #[serde(flatten)]
+    filters: filter::Filters,
+}
 
-    // Flatten the nested structure into a single list of `ArtistPerformance` objects.
-    let mut all_performances = Vec::new();
-    for festival in band_data.festivals {
-        for year in festival.years {
-            for artist in year.artists {
-                all_performances.push(ArtistPerformance {
-                    name: artist,
-                    festival: festival.name.clone(),
-                    year: year.year,
-                });
-            }
+impl SearchDefinition {
+    // The same search with incidental differences removed, so equivalent requests
+    // share a cache entry: surrounding spaces (kept for regexes, where they
+    // matter), fuzziness outside fuzzy mode or above the maximum, and filter order.
+    fn canonical(&self) -> SearchDefinition {
+        let (q, fuzziness) = match self.mode {
+            SearchMode::Fuzzy => (
+                self.q.trim().to_string(),
+                self.fuzziness.map(|f| f.min(search::MAX_FUZZINESS)),
+            ),
+            SearchMode::Phonetic => (self.q.trim().to_string(), None),
+            SearchMode::Regex => (self.q.clone(), None),
+        };
+        SearchDefinition {
+            q,
+            mode: self.mode,
+            fuzziness,
+            filters: self.filters.canonical(),
         }
     }
+}
 
-    let performance_count = all_performances.len();
-    println!(
-        "Successfully loaded {} total artist performances.",
-        performance_count
-    );
+#[derive(Debug, Deserialize)]
+struct ArtistsParams {
+    // A letter, a longer name prefix, or "#" for names not starting with A-Z.
+    starts_with: Option<String>,
+}
 
-    // Store the final list in our shared state, wrapped in an Arc for thread-safety.
-    Arc::new(AppState { all_performances })
-});
+#[derive(Debug, Deserialize)]
+struct AutocompleteParams {
+    q: String,
+    limit: Option<usize>,
+}
 
-// --- Query Parameters for the API Request ---
+// --- Response Bodies ---
 
-#[derive(Debug, Deserialize)]
-struct RandomBandParams {
-    count: Option<usize>,
+#[derive(Debug, Serialize)]
+struct SearchHit<'a> {
+    #[serde(flatten)]
+    performance: layout::Performance<'a>,
+    distance: usize,
+    // Char ranges of `name` that matched, for highlighting in the UI.
+    highlights: Vec<search::Span>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchResponse<'a> {
+    query: &'a str,
+    mode: SearchMode,
+    // Festival and year filters recognised in the query text itself.
+    #[serde(skip_serializing_if = "filter::Filters::is_empty")]
+    filters: filter::Filters,
+    // Only present when the caller fixed it; otherwise each term picks its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fuzziness: Option<usize>,
+    results: Vec<SearchHit<'a>>,
+    // Closest artist names, filled in only when a fuzzy search finds nothing.
+    suggestions: Vec<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReloadResponse {
+    source: String,
+    // Performances in the data now being served.
+    performances: usize,
+    added_count: usize,
+    removed_count: usize,
+    added: Vec<ArtistPerformance>,
+    removed: Vec<ArtistPerformance>,
 }
 
 // --- Main Application Entry Point ---
 
 #[tokio::main]
 async fn main() {
-    // Set up a permissive CORS layer, allowing requests from any origin.
-    let cors = CorsLayer::new().allow_origin(Any).allow_methods(Any);
+    // Parse the flags first, so `--help` or a typo doesn't wait on loading the data.
+    Lazy::force(&STARTED);
+    Lazy::force(&CONFIG);
+    log::init();
+    panics::log_panics();
+    if let Some(file) = &CONFIG.config_file {
+        tracing::info!("->> Using configuration from {}", file.display());
+    }
+
+    // A one-off copy into a database, e.g. `--data-file bands.json --import-into bands.db`.
+    if let Some(target) = &CONFIG.import_into {
+        let imported = aliases::read(CONFIG.aliases_file.as_deref())
+            .and_then(|file| aliases::Aliases::parse(file.as_ref()))
+            .and_then(|aliases| {
+                let file = countries::read(CONFIG.countries_file.as_deref())?;
+                let countries = countries::Countries::parse(file.as_ref())?;
+                data::import_into(
+                    &CONFIG.data_source,
+                    target,
+                    &aliases,
+                    &countries,
+                    CONFIG.validation,
+                )
+            });
+        match imported {
+            Ok(count) => {
+                tracing::info!(
+                    "->> Imported {} artist performances from {} into {}.",
+                    count,
+                    CONFIG.data_source,
+                    data::redact_password(target)
+                );
+                return;
+            }
+            Err(message) => {
+                tracing::error!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // A dry run for CI and deploys, e.g. `--check --strict`: every dataset is
+    // loaded, checked and indexed as for serving, but nothing listens.
+    if CONFIG.check {
+        let datasets = std::iter::once(CONFIG.main_dataset()).chain(CONFIG.datasets.clone());
+        let mut failed = 0;
+        for dataset in datasets {
+            let name = dataset.name.as_deref().unwrap_or("main");
+            match AppState::load(&dataset) {
+                Ok(state) => {
+                    let info = state.dataset_info();
+                    tracing::info!(
+                        "->> {} ({}): {} festivals, {} editions, {} performances by {} artists; {} invalid records left out, {} names tidied.",
+                        name,
+                        dataset.data_source,
+                        info.festivals,
+                        info.editions,
+                        info.performances,
+                        info.artists,
+                        state.checks.skipped.len(),
+                        state.checks.tidied.len()
+                    );
+                    if info.performances == 0 {
+                        tracing::warn!("->> {} has no performances.", name);
+                    }
+                }
+                Err(message) => {
+                    tracing::error!(
+                        "{} ({}) doesn't load: {}",
+                        name,
+                        dataset.data_source,
+                        message
+                    );
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Set up the CORS layer from the config. The origins are looked up on every
+    // request, so a reload can change them.
+    let origins = AllowOrigin::predicate(|origin, _| {
+        let origin = origin.to_str().unwrap_or_default();
+        config::origin_allowed(&CONFIG.cors_origins, origin)
+    });
+    let methods = if CONFIG.cors_methods.iter().any(|m| m == "*") {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(CONFIG.cors_methods.iter().map(|m| {
+            Method::from_bytes(m.as_bytes()).expect("methods are validated when loading the config")
+        }))
+    };
+    let headers = if CONFIG.cors_headers.iter().any(|h| h == "*") {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(CONFIG.cors_headers.iter().map(|h| {
+            header::HeaderName::from_bytes(h.as_bytes())
+                .expect("headers are validated when loading the config")
+        }))
+    };
+    let mut cors = CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers);
+    if let Some(max_age) = CONFIG.cors_max_age {
+        cors = cors.max_age(max_age);
+    }
+
+    let trace = TraceLayer::new_for_http()
+        .make_span_with(log::request_span)
+        .on_request(())
+        .on_response(log::response)
+        .on_failure(());
+
+    // Read the data once at startup, every dataset at the same time; after that,
+    // reloads swap in new snapshots.
+    let configured: Vec<config::Dataset> = std::iter::once(CONFIG.main_dataset())
+        .chain(CONFIG.datasets.clone())
+        .collect();
+    let loaded = parallel::map(&configured, load_dataset);
+    let mut served = configured
+        .into_iter()
+        .zip(loaded)
+        .map(|(dataset, initial)| serve_dataset(dataset, initial));
+    let shared = served.next().expect("the main dataset comes first");
+    let datasets = state::Datasets::new(shared.clone());
+    for shared in served {
+        datasets.insert(shared.clone(), dataset_routes(shared));
+    }
 
-    // Build our application router.
-    let app = Router::new()
+    // Build our application routers: the public one, and the admin one, which
+    // gets a listener of its own when the config gives it an address.
+    let public = Router::new()
         // Serves the main HTML interface.
         .route("/", get(root_handler))
+        .merge(public_routes(shared.clone()))
+        // API endpoint listing the datasets.
+        .route(
+            "/api/datasets",
+            get(datasets_handler).with_state(datasets.clone()),
+        )
+        // The other datasets' APIs, under their names.
+        .route(
+            "/api/{dataset}/{*rest}",
+            any(dataset_api_handler).with_state(datasets.clone()),
+        )
+        // Times the handlers, for the slow request log.
+        .layer(middleware::from_fn(slow::time_handler))
+        // Holds JSON bodies to their limit, with a JSON error beyond it.
+        .layer(DefaultBodyLimit::max(CONFIG.limits.max_body_bytes))
+        .layer(middleware::from_fn(body::explain_too_large))
+        // Explains unknown API paths and methods in JSON too.
+        .layer(middleware::from_fn(fallback::explain_missing))
+        // Compresses responses for clients that take it, inside the time limit.
+        .layer(middleware::from_fn(compression::compress))
+        // Tells browsers and CDNs how long they may keep each kind of response.
+        .layer(middleware::from_fn(cachecontrol::public))
+        // Gives up on handlers that take too long, then holds each client to
+        // the configured request rates.
+        .layer(middleware::from_fn(timeout::public))
+        .layer(middleware::from_fn(ratelimit::limit))
+        // Sheds requests beyond the in-flight limits, shared with the admin API.
+        .layer(middleware::from_fn(concurrency::shed_load))
+        // Probes for load balancers, outside the limits above.
+        .route("/healthz", get(health::live))
+        .route("/readyz", get(health::ready).with_state(datasets.clone()))
+        // Answers requests whose handler panicked with a 500.
+        .layer(middleware::from_fn(panics::catch))
+        // Logs requests that took too long, with where the time went.
+        .layer(middleware::from_fn(slow::watch))
+        .layer(cors.clone())
+        // Logs every request, with its status and how long it took, under the
+        // ID it's given first.
+        .layer(trace.clone())
+        // Writes each request to the access log, with the ID it's given first.
+        .layer(middleware::from_fn(accesslog::record))
+        .layer(middleware::from_fn(requestid::assign));
+    let admin = Router::new()
+        // Admin page for curators, with forms for the admin endpoints.
+        .route("/admin", get(admin_page_handler))
+        .merge(admin_routes())
+        .with_state(shared)
+        // Admin endpoints for adding, renaming and removing datasets.
+        .route(
+            "/api/admin/datasets/{name}",
+            post(admin_create_dataset_handler)
+                .patch(admin_rename_dataset_handler)
+                .delete(admin_delete_dataset_handler)
+                .with_state(datasets.clone()),
+        )
+        // Admin endpoint describing the server: its build, memory and settings.
+        .route(
+            "/api/admin/info",
+            get(info::handler).with_state(datasets.clone()),
+        )
+        // The other datasets' admin APIs and pages, under their names.
+        .route(
+            "/api/{dataset}/admin/{*rest}",
+            any(dataset_admin_api_handler).with_state(datasets.clone()),
+        )
+        .route(
+            "/admin/{dataset}",
+            get(dataset_admin_page_handler).with_state(datasets.clone()),
+        )
+        .layer(middleware::from_fn(slow::time_handler))
+        // Uploads have a limit of their own; other bodies are held to this one.
+        .layer(DefaultBodyLimit::max(CONFIG.limits.max_body_bytes))
+        .layer(middleware::from_fn(body::explain_too_large))
+        .layer(middleware::from_fn(fallback::explain_missing))
+        .layer(middleware::from_fn(compression::compress))
+        .layer(middleware::from_fn(cachecontrol::admin))
+        // Checks API keys and tokens on every admin route, after the address.
+        .layer(middleware::from_fn(access::authorize))
+        .layer(middleware::from_fn(access::allow_ips))
+        // Outside `authorize`, as the handler's task doesn't see who's asking.
+        .layer(middleware::from_fn(timeout::admin))
+        .layer(middleware::from_fn(concurrency::shed_load))
+        .layer(middleware::from_fn(panics::catch))
+        .layer(middleware::from_fn(slow::watch))
+        .layer(cors)
+        .layer(trace)
+        .layer(middleware::from_fn(accesslog::record))
+        .layer(middleware::from_fn(requestid::assign));
+
+    // The addresses and sockets to run the server on, all serving the same.
+    let listeners = CONFIG.listeners();
+    if CONFIG.tokens.is_empty() && CONFIG.jwt.is_none() {
+        tracing::warn!(
+            "->> No admin keys, API tokens or JWT issuer configured: the admin API is closed"
+        );
+    }
+    for listen in &listeners {
+        tracing::info!("->> LISTENING on {}", listen);
+    }
+    let first_tcp = listeners.iter().find_map(|listen| match listen {
+        config::Listen::Tcp(addr) => Some(addr),
+        config::Listen::Unix(_) => None,
+    });
+    if let Some(addr) = first_tcp {
+        tracing::info!("->> UI available at:           http://{}", addr);
+        tracing::info!(
+            "->> Download API available at: http://{}/api/all-bands",
+            addr
+        );
+        tracing::info!(
+            "->> Randomizer API available at: http://{}/api/random-bands?count=3",
+            addr
+        );
+        tracing::info!(
+            "->> Search API available at:   http://{}/api/search?q=kaiser\n",
+            addr
+        );
+    }
+
+    // Run the server until it's told to stop.
+    let (stop, stopping) = tokio::sync::watch::channel(false);
+    tokio::spawn(shut_down_on_signal(stop));
+    tokio::spawn(reload_on_sighup(datasets.clone()));
+    let main_app = match CONFIG.admin_address {
+        Some(_) => public,
+        None => public.merge(admin.clone()),
+    };
+    // Behind a proxy that mounts the app under a path, everything is served there.
+    let (main_app, admin) = match CONFIG.base_path.as_str() {
+        "" => (main_app, admin),
+        base => (
+            Router::new().nest_service(base, main_app),
+            Router::new().nest_service(base, admin),
+        ),
+    };
+    let mut servers = tokio::task::JoinSet::new();
+    for listen in listeners.clone() {
+        let (app, stopping) = (main_app.clone(), stopping.clone());
+        match listen {
+            config::Listen::Tcp(addr) => servers.spawn(serve(addr, app, stopping)),
+            config::Listen::Unix(path) => {
+                servers.spawn(async move { serve_unix(&path, app, stopping).await })
+            }
+        };
+    }
+    if let Some(admin_addr) = CONFIG.admin_address {
+        tracing::info!(
+            "->> Admin API available at:   http://{}/admin\n",
+            admin_addr
+        );
+        servers.spawn(serve(admin_addr, admin, stopping.clone()));
+    }
+    while servers.join_next().await.is_some() {}
+
+    // Every request has been answered; stop watching the data sources too.
+    datasets.main.retire();
+    for shared in datasets.list() {
+        shared.retire();
+    }
+    for listen in &listeners {
+        if let config::Listen::Unix(path) = listen {
+            let _ = fs::remove_file(path);
+        }
+    }
+    tracing::info!("->> Stopped.");
+}
+
+// --- Shutdown ---
+
+/// Tells the listeners to stop on SIGINT or SIGTERM, after answering the
+/// requests they're working on. A second signal exits straight away.
+async fn shut_down_on_signal(stop: tokio::sync::watch::Sender<bool>) {
+    let signal = next_signal().await;
+    tracing::info!(
+        "->> Received {}, finishing the requests in progress...",
+        signal
+    );
+    let _ = stop.send(true);
+    let signal = next_signal().await;
+    tracing::warn!("->> Received {} again, exiting now.", signal);
+    std::process::exit(1);
+}
+
+async fn next_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM can be handled");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = terminate.recv() => "SIGTERM",
+    }
+}
+
+/// Reads the configuration and every dataset's source again on each SIGHUP.
+/// Settings only read at startup keep their old values until a restart, and
+/// the log says which of those changed.
+async fn reload_on_sighup(datasets: state::Datasets) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).expect("SIGHUP can be handled");
+    while hangup.recv().await.is_some() {
+        tracing::info!("->> Received SIGHUP, reloading the configuration and data...");
+        match config::Config::reread() {
+            Ok(config) => {
+                let changed = config::needs_restart(&CONFIG, &config);
+                CONFIG.replace(config);
+                for setting in changed {
+                    tracing::warn!("->> Changing {} takes a restart.", setting);
+                }
+            }
+            Err(message) => {
+                tracing::warn!("->> Keeping the configuration as it was: {}", message)
+            }
+        }
+        let all = std::iter::once(datasets.main.clone()).chain(datasets.list());
+        for shared in all {
+            state::log_refresh(&shared.dataset, shared.refresh().await);
+        }
+    }
+}
+
+// Resolves once the server has been told to stop.
+async fn stopped(mut stopping: tokio::sync::watch::Receiver<bool>) {
+    let _ = stopping.wait_for(|stop| *stop).await;
+}
+
+/// Serves `app` on `addr` until it's told to stop, exiting if it stops because
+/// of an error.
+async fn serve(addr: SocketAddr, app: Router, stopping: tokio::sync::watch::Receiver<bool>) {
+    let listener = match bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("could not listen on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    // Who each connection is from is kept for the admin API's IP allowlist.
+    let app = app.into_make_service_with_connect_info::<access::Peer>();
+    let listener = concurrency::Limited(listener);
+    let server = axum::serve(listener, app).with_graceful_shutdown(stopped(stopping));
+    if let Err(e) = server.await {
+        tracing::error!("the server stopped: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// Binds `addr` as std does, except that an IPv6 address takes only IPv6
+// connections, whatever the system's default, so "[::]:3000" and
+// "0.0.0.0:3000" can be listened on side by side.
+fn bind(addr: SocketAddr) -> std::io::Result<tokio::net::TcpListener> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+/// Serves `app` on the Unix socket at `path` like `serve` does.
+async fn serve_unix(
+    path: &std::path::Path,
+    app: Router,
+    stopping: tokio::sync::watch::Receiver<bool>,
+) {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    // A socket left behind by an earlier run would make binding fail.
+    if fs::metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        let _ = fs::remove_file(path);
+    }
+    let bound = tokio::net::UnixListener::bind(path).and_then(|listener| {
+        if let Some(mode) = CONFIG.socket_mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(listener)
+    });
+    let listener = match bound {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("could not listen on unix:{}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let app = app.into_make_service_with_connect_info::<access::Peer>();
+    let listener = concurrency::Limited(listener);
+    let server = axum::serve(listener, app).with_graceful_shutdown(stopped(stopping));
+    if let Err(e) = server.await {
+        tracing::error!("the server stopped: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Both halves of one named dataset's API.
+fn dataset_routes(shared: SharedState) -> state::Routes {
+    state::Routes {
+        public: public_routes(shared.clone()),
+        admin: admin_routes().with_state(shared),
+    }
+}
+
+/// Loads `dataset` for serving. Exits when it can't be loaded, unless the config
+/// allows starting empty.
+fn load_dataset(dataset: &config::Dataset) -> AppState {
+    let source = &dataset.data_source;
+    tracing::info!("Loading {} into memory...", source);
+    match AppState::load(dataset) {
+        Ok(initial) => {
+            tracing::info!(
+                "Successfully loaded {} total artist performances.",
+                initial.all_performances.len()
+            );
+            initial
+        }
+        Err(message) if CONFIG.allow_empty => {
+            tracing::warn!(
+                "->> Could not load {}: {}\n->> Starting with no data; it's loaded once the source is fixed or reloaded.",
+                source, message
+            );
+            AppState::empty()
+        }
+        Err(message) => {
+            tracing::error!(
+                "could not load the data: {}\n(start with --allow-empty to serve no data until it can be loaded)",
+                message
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Serves `dataset`, starting with `initial`, and keeps it up to date as its
+/// source changes.
+fn serve_dataset(dataset: config::Dataset, initial: AppState) -> SharedState {
+    let source = dataset.data_source.clone();
+    let shared = SharedState::new(initial, dataset);
+
+    // Local files are watched for changes; URLs are re-fetched on a timer.
+    match source {
+        data::DataSource::Url(_) => {
+            if let Some(interval) = CONFIG.refresh_interval {
+                state::refresh_periodically(shared.clone(), interval);
+            }
+        }
+        _ => {
+            if CONFIG.watch {
+                state::watch(shared.clone(), CONFIG.watch_interval);
+            }
+        }
+    }
+    shared
+}
+
+/// The public API of the dataset in `shared`, at the paths the main dataset
+/// serves it on.
+fn public_routes(shared: SharedState) -> Router {
+    let mut app = Router::new()
         // API endpoint for getting random bands.
         .route("/api/random-bands", get(random_bands_api_handler))
+        // API endpoint for searching artists by name, tolerating typos.
+        .route("/api/search", get(search_handler))
+        // API endpoint for as-you-type artist name suggestions.
+        .route("/api/autocomplete", get(autocomplete_handler))
+        // API endpoint for listing the genres in the data.
+        .route("/api/genres", get(genres_handler))
+        // API endpoint for the festivals and their editions.
+        .route("/api/festivals", get(festivals_handler))
+        // API endpoint for where each lineup was taken from.
+        .route("/api/provenance", get(provenance_handler))
+        // API endpoint for where the artists come from.
+        .route("/api/stats/countries", get(country_stats_handler))
         // New API endpoint for downloading all band data.
         .route("/api/all-bands", get(all_bands_handler))
-        .layer(cors)
-        .with_state(Arc::clone(&APP_STATE));
-
-    // Define the address and port to run the server on.
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    println!("->> LISTENING on http://{}\n", addr);
-    println!("->> UI available at:           http://{}", addr);
-    println!(
-        "->> Download API available at: http://{}/api/all-bands",
-        addr
+        // Gives the handlers above the visitor's session, from their cookie.
+        .layer(middleware::from_fn(session::attach));
+
+    // Optional endpoints, each switched on unless the config turns it off.
+    if CONFIG.features.saved_searches {
+        // API endpoints for storing a search and re-running it by ID.
+        app = app
+            .route("/api/searches", post(create_saved_search_handler))
+            .route("/api/searches/{id}", get(saved_search_handler));
+    }
+    if CONFIG.features.browse {
+        // API endpoints for browsing distinct artists alphabetically.
+        app = app
+            .route("/api/artists", get(artists_handler))
+            .route("/api/artists/letters", get(artist_letters_handler));
+    }
+
+    // While the dataset is empty, the endpoints above say so with a 503.
+    app.route_layer(middleware::from_fn_with_state(
+        shared.clone(),
+        health::require_data,
+    ))
+    // API endpoint for which dataset is loaded, which still answers then.
+    .route("/api/dataset", get(dataset_handler))
+    // Times the handlers, for the slow request log.
+    .route_layer(middleware::from_fn(slow::time_handler))
+    .with_state(shared)
+}
+
+/// The endpoints `public_routes` serves, and /api/datasets, for the hint in
+/// 404s under /api/; keep them in step.
+fn public_endpoints() -> Vec<&'static str> {
+    let mut endpoints = vec![
+        "GET /api/datasets",
+        "GET /api/random-bands",
+        "GET /api/search",
+        "GET /api/autocomplete",
+        "GET /api/genres",
+        "GET /api/festivals",
+        "GET /api/provenance",
+        "GET /api/dataset",
+        "GET /api/stats/countries",
+        "GET /api/all-bands",
+    ];
+    if CONFIG.features.saved_searches {
+        endpoints.extend(["POST /api/searches", "GET /api/searches/{id}"]);
+    }
+    if CONFIG.features.browse {
+        endpoints.extend(["GET /api/artists", "GET /api/artists/letters"]);
+    }
+    endpoints
+}
+
+/// The admin API of one dataset, at the paths the main dataset serves it on.
+fn admin_routes() -> Router<SharedState> {
+    // Admin endpoint for re-reading the data source on demand.
+    let app = Router::new().route("/api/admin/reload", post(admin_reload_handler));
+    // Admin endpoint listing probable duplicate artists and fixed or skipped records.
+    let app = app.route("/api/admin/quality-report", get(quality_report_handler));
+    // Admin endpoints for replacing the whole dataset, or checking a replacement
+    // and comparing it with the current data first, up to the upload limit.
+    let app = app
+        .route("/api/admin/dataset", put(admin_upload_dataset_handler))
+        .route(
+            "/api/admin/dataset/validate",
+            post(admin_validate_dataset_handler),
+        )
+        .route("/api/admin/dataset/diff", post(admin_diff_dataset_handler));
+    // Admin endpoint for adding a performance to an edition.
+    let app = app.route(
+        "/api/admin/performances",
+        post(admin_add_performance_handler),
     );
-    println!(
-        "->> Randomizer API available at: http://{}/api/random-bands?count=3\n",
-        addr
+    // Admin endpoints for taking out or correcting one performance.
+    let app = app.route(
+        "/api/admin/performances/{id}",
+        delete(admin_delete_performance_handler).patch(admin_correct_performance_handler),
     );
-
-    // Run the server.
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Admin endpoint for dropping cached responses and searches.
+    let app = app.route("/api/admin/cache/purge", post(admin_purge_cache_handler));
+    // Admin endpoint for undoing recent changes.
+    let app = app.route("/api/admin/rollback", post(admin_rollback_handler));
+    // Admin endpoint listing the changes made through the admin API.
+    let app = app.route("/api/admin/audit", get(admin_audit_handler));
+    // Admin endpoints for saving the data being served, and putting a saved copy back.
+    app.route("/api/admin/snapshot", post(admin_snapshot_handler))
+        .route("/api/admin/snapshots", get(admin_snapshots_handler))
+        .route(
+            "/api/admin/snapshots/{name}/restore",
+            post(admin_restore_snapshot_handler),
+        )
+        // Times the handlers, for the slow request log.
+        .route_layer(middleware::from_fn(slow::time_handler))
 }
 
 // --- HTML Page Handler ---
 
-async fn root_handler() -> Html<&'static str> {
-    // Serve the static HTML content.
-    Html(HTML_PAGE)
+async fn root_handler() -> Html<String> {
+    // Serve the static HTML content, with its links behind the base path.
+    Html(HTML_PAGE.replace("{{base}}", &CONFIG.base_path))
 }
 
 // --- API Handlers ---
 
+// Where random-bands keeps the performances a session was given, and how many
+// of the latest it remembers.
+const SEEN_KEY: &str = "random_seen";
+const MAX_SEEN: usize = 1000;
+
 /// API handler for providing a random selection of artist performances.
 async fn random_bands_api_handler(
     State(state): State<Arc<AppState>>,
+    session: session::Session,
     Query(params): Query<RandomBandParams>,
+    Query(filter_params): Query<filter::FilterParams>,
 ) -> impl IntoResponse {
-    // Clamp the requested count between 1 and 5. Default to 1 if not provided.
-    let count = params.count.unwrap_or(1).clamp(1, 5);
+    let filters = filter_params.without_cancelled();
+    // Clamp the requested count between 1 and the configured maximum (5 by default).
+    // Default to 1 if not provided.
+    let count = params
+        .count
+        .unwrap_or(1)
+        .clamp(1, CONFIG.limits.max_random_count);
     let mut rng = rand::rng();
 
-    // Choose multiple random performances from the shared state.
-    let random_selection: Vec<ArtistPerformance> = state
-        .all_performances
-        .choose_multiple(&mut rng, count)
-        .cloned()
-        .collect();
+    // Choose multiple random performances among those passing the filters.
+    let mut candidates: Vec<layout::Performance> = state.passing(&filters).collect();
+    let mut seen: Vec<String> = Vec::new();
+    if params.unseen {
+        seen = session.get(SEEN_KEY).unwrap_or_default();
+        let seen_ids: HashSet<u64> = seen.iter().filter_map(|id| state::parse_id(id)).collect();
+        let unseen: Vec<layout::Performance> = candidates
+            .iter()
+            .filter(|p| !seen_ids.contains(&p.id_hash()))
+            .copied()
+            .collect();
+        // Once they've all been seen, start over with them.
+        if unseen.is_empty() {
+            let matching: HashSet<u64> = candidates.iter().map(|p| p.id_hash()).collect();
+            seen.retain(|id| state::parse_id(id).is_none_or(|id| !matching.contains(&id)));
+        } else {
+            candidates = unseen;
+        }
+    }
+    // The picks are serialized straight from the snapshot, without copies.
+    let random_selection: Vec<layout::Performance> = if params.distinct {
+        // Artists are picked first, so one with many performances is no likelier
+        // to come up than one with a single performance.
+        let mut by_artist: HashMap<u32, Vec<layout::Performance>> = HashMap::new();
+        for performance in candidates {
+            by_artist
+                .entry(state.artists.of(performance.position()))
+                .or_default()
+                .push(performance);
+        }
+        let artists: Vec<&Vec<layout::Performance>> = by_artist.values().collect();
+        artists
+            .choose_multiple(&mut rng, count)
+            .filter_map(|performances| performances.choose(&mut rng).copied())
+            .collect()
+    } else {
+        candidates
+            .choose_multiple(&mut rng, count)
+            .copied()
+            .collect()
+    };
+    if params.unseen {
+        seen.extend(random_selection.iter().map(|p| p.id().into_owned()));
+        let excess = seen.len().saturating_sub(MAX_SEEN);
+        seen.drain(..excess);
+        session.insert(SEEN_KEY, seen);
+    }
 
     if !random_selection.is_empty() {
         (StatusCode::OK, Json(random_selection)).into_response()
@@ -164,18 +952,1059 @@ async fn random_bands_api_handler(
     }
 }
 
+/// API handler for searching performances by artist name, ordered by match quality.
+async fn search_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+    Query(filter_params): Query<filter::FilterParams>,
+) -> impl IntoResponse {
+    let definition = SearchDefinition {
+        q: params.q,
+        mode: params.mode,
+        fuzziness: params.fuzziness,
+        filters: filter::Filters::from(filter_params),
+    };
+    run_search(&state, &definition).await
+}
+
+/// API handler for saving a search definition under a short, shareable ID.
+async fn create_saved_search_handler(
+    State(state): State<Arc<AppState>>,
+    State(saved_searches): State<Arc<saved::SavedSearches>>,
+    State(dataset): State<Arc<config::Dataset>>,
+    Json(definition): Json<SearchDefinition>,
+) -> impl IntoResponse {
+    // Run it once so that only definitions that actually work get stored.
+    let response = run_search(&state, &definition).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    match saved_searches.insert(definition.clone()) {
+        Ok(id) => {
+            let url = format!("{}/searches/{}", api_url(&dataset), id);
+            (
+                StatusCode::CREATED,
+                [(header::LOCATION, url.clone())],
+                Json(serde_json::json!({
+                    "id": id,
+                    "url": url,
+                    "search": definition,
+                })),
+            )
+                .into_response()
+        }
+        Err(saved::StoreFull) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"error": "Too many saved searches. Try again later."})),
+        )
+            .into_response(),
+    }
+}
+
+/// API handler for re-running a saved search against the current data.
+async fn saved_search_handler(
+    State(state): State<Arc<AppState>>,
+    State(saved_searches): State<Arc<saved::SavedSearches>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match saved_searches.get(&id) {
+        Some(definition) => run_search(&state, &definition).await,
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "No saved search with that ID."})),
+        )
+            .into_response(),
+    }
+}
+
+// Runs a search definition in any mode and renders the response, shared by the
+// search endpoint and saved searches. Successful responses are cached.
+async fn run_search(
+    state: &Arc<AppState>,
+    definition: &SearchDefinition,
+) -> axum::response::Response {
+    let definition = definition.canonical();
+    if let Some(body) = state.search_cache.get(&definition) {
+        return json_body(body);
+    }
+
+    // Another instance serving the same data may have run this search already.
+    let shared_key = SHARED_CACHE.as_ref().map(|_| {
+        let canonical = serde_json::to_string(&definition).expect("definitions serialize");
+        redis::SharedCache::key(state.content_hash, "search", &canonical)
+    });
+    if let (Some(cache), Some(key)) = (SHARED_CACHE.as_ref(), &shared_key) {
+        if let Some(body) = cache.get(key).await {
+            let body = Bytes::from(body);
+            state.search_cache.insert(definition, body.clone());
+            return json_body(body);
+        }
+    }
+
+    // A regex search can take up to `REGEX_TIME_LIMIT`, which would hold up
+    // every other request waiting on this worker, so it runs on a blocking
+    // thread.
+    let rendered = if definition.mode == SearchMode::Regex {
+        let (state, definition) = (Arc::clone(state), definition.clone());
+        tokio::task::spawn_blocking(move || render_search(&state, &definition))
+            .await
+            .unwrap_or_else(|e| Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())))
+    } else {
+        render_search(state, &definition)
+    };
+    let body = match rendered {
+        Ok(body) => body,
+        Err((status, message)) => {
+            return (status, Json(serde_json::json!({ "error": message }))).into_response()
+        }
+    };
+    state.search_cache.insert(definition, body.clone());
+    if let (Some(cache), Some(key)) = (SHARED_CACHE.as_ref(), shared_key) {
+        // Storing it shouldn't hold up this response.
+        let shared = body.clone();
+        tokio::spawn(async move { cache.set(&key, &shared).await });
+    }
+    json_body(body)
+}
+
+// Runs a canonical search and serializes its response. Invalid searches give
+// the status and error message to send instead; they aren't cached.
+fn render_search(
+    state: &AppState,
+    definition: &SearchDefinition,
+) -> Result<Bytes, (StatusCode, String)> {
+    let enabled = match definition.mode {
+        SearchMode::Fuzzy => true,
+        SearchMode::Regex => CONFIG.features.regex_search,
+        SearchMode::Phonetic => CONFIG.features.phonetic_search,
+    };
+    if !enabled {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "This search mode is disabled on this server.".to_string(),
+        ));
+    }
+
+    let filters = &definition.filters;
+    let mut understood = filter::Filters::default();
+
+    let (query, fuzziness, matches) = match definition.mode {
+        SearchMode::Fuzzy => {
+            let query = definition.q.as_str();
+            let fuzziness = definition.fuzziness;
+
+            // Punctuation-only or operator-only queries leave nothing to match on.
+            let parsed = search::ParsedQuery::parse(query, fuzziness, &state.catalog);
+            if parsed.is_empty() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Query parameter 'q' must contain letters or digits.".to_string(),
+                ));
+            }
+
+            let matches = search::search(
+                &state.all_performances,
+                &state.search_index,
+                &parsed,
+                filters,
+            );
+            understood = parsed.filters;
+            (query, fuzziness, matches)
+        }
+        SearchMode::Phonetic => {
+            let query = definition.q.as_str();
+            if search::normalize(query).is_empty() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Query parameter 'q' must contain letters or digits.".to_string(),
+                ));
+            }
+            let matches = search::phonetic_search(state.passing(filters), query);
+            (query, None, matches)
+        }
+        SearchMode::Regex => {
+            // Patterns are used verbatim; leading or trailing spaces may be intentional.
+            let query = definition.q.as_str();
+            if query.is_empty() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Query parameter 'q' must not be empty.".to_string(),
+                ));
+            }
+
+            let regex = match pattern::Regex::new(query) {
+                Ok(regex) => regex,
+                Err(message) => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid regex: {}", message),
+                    ))
+                }
+            };
+
+            match search::regex_search(state.passing(filters), &regex) {
+                Ok(matches) => (query, None, matches),
+                Err(search::TimedOut) => {
+                    return Err((
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "Regex search took too long. Try a more specific pattern.".to_string(),
+                    ));
+                }
+            }
+        }
+    };
+
+    let suggestions = match definition.mode {
+        SearchMode::Fuzzy if matches.is_empty() => state.search_index.closest(query, 3),
+        _ => Vec::new(),
+    };
+
+    let results = matches
+        .into_iter()
+        .map(|m| SearchHit {
+            performance: m.performance,
+            distance: m.distance,
+            highlights: m.highlights,
+        })
+        .collect();
+
+    let response = SearchResponse {
+        query,
+        mode: definition.mode,
+        filters: understood,
+        fuzziness,
+        results,
+        suggestions,
+    };
+    Ok(Bytes::from(
+        serde_json::to_vec(&response).expect("search responses serialize"),
+    ))
+}
+
+// Wraps an already serialized JSON body in a response.
+fn json_body(body: Bytes) -> axum::response::Response {
+    ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+/// API handler for suggesting unique artist names while the user types.
+async fn autocomplete_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<AutocompleteParams>,
+) -> impl IntoResponse {
+    // Clamp the limit between 1 and the configured maximum (50 by default).
+    // Default to 10 if not provided.
+    let limit = params
+        .limit
+        .unwrap_or(10)
+        .clamp(1, CONFIG.limits.max_autocomplete);
+
+    let suggestions = state.search_index.complete(&params.q, limit);
+
+    Json(suggestions).into_response()
+}
+
+/// API handler for listing distinct artists alphabetically, optionally by first letter.
+async fn artists_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ArtistsParams>,
+    Query(filter_params): Query<filter::FilterParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let filters = filter::Filters::from(filter_params);
+
+    let selection = match params.starts_with.as_deref().map(str::trim) {
+        None => browse::Selection::All,
+        Some("#") => browse::Selection::Section(browse::OTHER_SECTION),
+        Some(prefix) => match search::normalize(prefix) {
+            key if key.is_empty() => {
+                return bad_request(
+                    "Query parameter 'starts_with' must be a letter, a name prefix or '#'.",
+                );
+            }
+            key => browse::Selection::Prefix(key),
+        },
+    };
+
+    conditional::respond(&state, &uri, &headers, || {
+        json_body(state.cached_json(&uri, || {
+            browse::artists(
+                &state.artists,
+                &state.all_performances,
+                &selection,
+                &filters,
+            )
+        }))
+    })
+}
+
+/// API handler for counting how many artists start with each letter.
+async fn artist_letters_handler(
+    State(state): State<Arc<AppState>>,
+    Query(filter_params): Query<filter::FilterParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let filters = filter::Filters::from(filter_params);
+    conditional::respond(&state, &uri, &headers, || {
+        json_body(
+            state.statistic(&state.statistics.letters, &filters, &uri, || {
+                browse::letters(&state.artists, &state.all_performances, &filters)
+            }),
+        )
+    })
+}
+
+/// API handler for listing every genre with how many artists have it.
+async fn genres_handler(
+    State(state): State<Arc<AppState>>,
+    Query(filter_params): Query<filter::FilterParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let filters = filter::Filters::from(filter_params);
+    conditional::respond(&state, &uri, &headers, || {
+        json_body(
+            state.statistic(&state.statistics.genres, &filters, &uri, || {
+                browse::genres(&state.artists, state.passing(&filters))
+            }),
+        )
+    })
+}
+
+/// API handler for listing the festivals with their locations and edition dates.
+async fn festivals_handler(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    conditional::respond(&state, &uri, &headers, || {
+        Json(&state.festivals).into_response()
+    })
+}
+
+/// API handler for the source of every edition's lineup.
+async fn provenance_handler(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    conditional::respond(&state, &uri, &headers, || {
+        Json(festivals::provenance(&state.festivals)).into_response()
+    })
+}
+
+/// API handler for the loaded dataset's hash, load time, size and source.
+async fn dataset_handler(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    conditional::respond(&state, &uri, &headers, || {
+        Json(state.dataset_info()).into_response()
+    })
+}
+
+/// API handler for how many artists and performances come from each country.
+async fn country_stats_handler(
+    State(state): State<Arc<AppState>>,
+    Query(filter_params): Query<filter::FilterParams>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let filters = filter::Filters::from(filter_params);
+    conditional::respond(&state, &uri, &headers, || {
+        json_body(
+            state.statistic(&state.statistics.countries, &filters, &uri, || {
+                stats::countries(&state.artists, state.passing(&filters))
+            }),
+        )
+    })
+}
+
 /// API handler for downloading the complete list of performances.
-async fn all_bands_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let headers = [
-        (header::CONTENT_TYPE, "application/json".to_string()),
-        // This header suggests that the browser should download the file.
-        (
-            header::CONTENT_DISPOSITION,
-            "attachment; filename=\"all_bands.json\"".to_string(),
-        ),
-    ];
+async fn all_bands_handler(
+    State(state): State<Arc<AppState>>,
+    uri: Uri,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    conditional::respond(&state, &uri, &headers, || {
+        let download = [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            // This header suggests that the browser should download the file.
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"all_bands.json\"".to_string(),
+            ),
+        ];
+        match &state.all_bands_json {
+            Some(json) => (download, json.clone()).into_response(),
+            None => (download, Body::new(export::Stream::new(state.clone()))).into_response(),
+        }
+    })
+}
+
+/// API handler for the data-quality report of the data being served.
+async fn quality_report_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(quality::report(&state)).into_response()
+}
+
+/// API handler for re-reading the data source and swapping in the result.
+async fn admin_reload_handler(State(shared): State<SharedState>) -> impl IntoResponse {
+    let source = shared.dataset.data_source.clone();
+    reloaded(&shared.audit, "reload", &source, shared.reload().await)
+}
+
+// Which cached responses to drop: those whose key the pattern, a regular
+// expression as in `mode=regex` searches, is found in; all of them without one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PurgeRequest {
+    pattern: Option<String>,
+}
+
+/// Admin handler for emptying the response and search caches, or the entries
+/// matching a pattern, so corrections show straight away.
+async fn admin_purge_cache_handler(
+    State(shared): State<SharedState>,
+    request: Option<Json<PurgeRequest>>,
+) -> impl IntoResponse {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let pattern = match request.pattern.as_deref().map(pattern::Regex::new) {
+        Some(Ok(regex)) => Some(regex),
+        Some(Err(e)) => return bad_request(&format!("Invalid pattern: {}", e)),
+        None => None,
+    };
+    let matches = |key: &str| pattern.as_ref().is_none_or(|p| p.find(key).is_some());
+    let state = shared.current();
+    let responses = state.response_cache.retain(|key| !matches(key));
+    let searches = state
+        .search_cache
+        .retain(|definition| !matches(&search_cache_key(definition)));
+    tracing::info!(
+        "->> Purged {} cached responses and {} cached searches",
+        responses,
+        searches
+    );
+    Json(serde_json::json!({
+        "purged": { "responses": responses, "searches": searches },
+    }))
+    .into_response()
+}
+
+// What a cached search is matched against when purging: the request for it as
+// the search page sends it, with `q` percent-encoded like `encodeURIComponent`
+// does and the mode unless it's fuzzy, but without the filters.
+fn search_cache_key(definition: &SearchDefinition) -> String {
+    let mut key = String::from("/api/search?q=");
+    for byte in definition.q.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => key.push(byte as char),
+            b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => key.push(byte as char),
+            _ => key.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    match definition.mode {
+        SearchMode::Fuzzy => {}
+        SearchMode::Regex => key.push_str("&mode=regex"),
+        SearchMode::Phonetic => key.push_str("&mode=phonetic"),
+    }
+    key
+}
+
+/// Admin handler for saving the dataset being served as a new snapshot.
+async fn admin_snapshot_handler(
+    State(shared): State<SharedState>,
+    State(state): State<Arc<AppState>>,
+    State(audit): State<Arc<audit::AuditLog>>,
+) -> impl IntoResponse {
+    let content_hash = state.content_hash;
+    let dir = shared.snapshot_dir();
+    // Writing out a large dataset takes a while; keep it off the async workers.
+    let saved =
+        tokio::task::spawn_blocking(move || snapshot::save(&dir, &state.data, SystemTime::now()))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|saved| saved);
+    match saved {
+        Ok(info) => {
+            tracing::info!("->> Saved snapshot {}.", info.name);
+            audit.record(audit::Entry::new(
+                "snapshot",
+                (content_hash, content_hash),
+                serde_json::json!({ "name": info.name }),
+            ));
+            (StatusCode::CREATED, Json(info)).into_response()
+        }
+        Err(message) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("The snapshot was not saved: {}", message),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditParams {
+    limit: Option<usize>,
+}
+
+/// Admin handler listing the most recent changes made through the admin API.
+async fn admin_audit_handler(
+    State(audit): State<Arc<audit::AuditLog>>,
+    Query(params): Query<AuditParams>,
+) -> impl IntoResponse {
+    match audit.recent(params.limit.unwrap_or(100)) {
+        Ok(entries) => Json(entries).into_response(),
+        Err(message) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response(),
+    }
+}
+
+/// Admin handler listing the saved snapshots, newest first.
+async fn admin_snapshots_handler(State(shared): State<SharedState>) -> impl IntoResponse {
+    match snapshot::list(&shared.snapshot_dir()) {
+        Ok(snapshots) => Json(snapshots).into_response(),
+        Err(message) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": message })),
+        )
+            .into_response(),
+    }
+}
+
+/// Admin handler for serving a saved snapshot in place of the current data.
+async fn admin_restore_snapshot_handler(
+    State(shared): State<SharedState>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let path = match snapshot::find(&shared.snapshot_dir(), &name) {
+        Ok(path) => path,
+        Err(message) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "error": message })),
+            )
+                .into_response()
+        }
+    };
+    let source = path.display().to_string();
+    reloaded(
+        &shared.audit,
+        "restore",
+        &source,
+        shared.restore(path).await,
+    )
+}
+
+// What to roll back to: a number of changes, or a saved snapshot.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RollbackRequest {
+    steps: Option<usize>,
+    snapshot: Option<String>,
+}
+
+/// Admin handler for undoing the most recent changes to the data, or going
+/// back to a saved snapshot.
+async fn admin_rollback_handler(
+    State(shared): State<SharedState>,
+    Json(request): Json<RollbackRequest>,
+) -> impl IntoResponse {
+    match request {
+        RollbackRequest {
+            steps: Some(steps),
+            snapshot: None,
+        } => {
+            let result = shared.roll_back(steps).await;
+            let source = shared
+                .current()
+                .source
+                .clone()
+                .unwrap_or_else(|| "no data".to_string());
+            reloaded(&shared.audit, "rollback", &source, result)
+        }
+        RollbackRequest {
+            steps: None,
+            snapshot: Some(name),
+        } => admin_restore_snapshot_handler(State(shared), Path(name))
+            .await
+            .into_response(),
+        _ => bad_request("Give either steps or snapshot."),
+    }
+}
+
+/// Admin handler serving the main dataset's admin page.
+async fn admin_page_handler(State(shared): State<SharedState>) -> Html<String> {
+    admin_page(&shared)
+}
+
+/// Admin handler serving another dataset's admin page.
+async fn dataset_admin_page_handler(
+    State(datasets): State<state::Datasets>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match datasets.get(&name) {
+        Some((shared, _)) => admin_page(&shared).into_response(),
+        None => unknown_dataset(&name),
+    }
+}
+
+fn admin_page(shared: &SharedState) -> Html<String> {
+    // A missing or unreadable snapshot directory just means there's nothing to restore.
+    let snapshots = snapshot::list(&shared.snapshot_dir()).unwrap_or_default();
+    let api = api_url(&shared.dataset);
+    Html(admin::page(&shared.current(), &snapshots, &api))
+}
+
+#[derive(Debug, Serialize)]
+struct DatasetSummary<'a> {
+    // `None` for the main dataset.
+    name: Option<&'a str>,
+    // Where its API is served, such as "/api/uk-festivals".
+    path: String,
+    #[serde(flatten)]
+    info: state::DatasetInfo<'a>,
+}
+
+/// API handler listing the datasets being served, the main one first.
+async fn datasets_handler(State(datasets): State<state::Datasets>) -> impl IntoResponse {
+    let served: Vec<SharedState> = std::iter::once(datasets.main.clone())
+        .chain(datasets.list())
+        .collect();
+    let current: Vec<Arc<AppState>> = served.iter().map(SharedState::current).collect();
+    let summaries: Vec<DatasetSummary> = served
+        .iter()
+        .zip(&current)
+        .map(|(shared, state)| DatasetSummary {
+            name: shared.dataset.name.as_deref(),
+            path: api_url(&shared.dataset),
+            info: state.dataset_info(),
+        })
+        .collect();
+    Json(summaries).into_response()
+}
+
+/// Admin handler for serving a new dataset, read from the body.
+async fn admin_create_dataset_handler(
+    State(datasets): State<state::Datasets>,
+    Path(name): Path<String>,
+    body::Upload(file): body::Upload,
+) -> impl IntoResponse {
+    if let Err(message) = config::check_dataset_name(&name) {
+        return bad_request(&message);
+    }
+    if datasets.get(&name).is_some() {
+        return dataset_exists(&name);
+    }
+    let source = format!("POST /api/admin/datasets/{}", name);
+    let shared = match SharedState::create(name.clone(), source, file).await {
+        Ok(shared) => shared,
+        Err(message) => return unusable_upload(&message),
+    };
+    // Another request may have created it in the meantime.
+    if !datasets.insert(shared.clone(), dataset_routes(shared.clone())) {
+        return dataset_exists(&name);
+    }
+    let state = shared.current();
+    tracing::info!("->> Created dataset {} from an upload.", name);
+    shared.audit.record(audit::Entry::new(
+        "create_dataset",
+        (0, state.content_hash),
+        serde_json::json!({
+            "source": shared.dataset.data_source.to_string(),
+            "performances": state.all_performances.len(),
+        }),
+    ));
+    let summary = DatasetSummary {
+        name: Some(&name),
+        path: api_url(&shared.dataset),
+        info: state.dataset_info(),
+    };
+    (StatusCode::CREATED, Json(summary)).into_response()
+}
+
+// The new name for a dataset.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RenameRequest {
+    name: String,
+}
+
+/// Admin handler for serving a dataset under another name.
+async fn admin_rename_dataset_handler(
+    State(datasets): State<state::Datasets>,
+    Path(name): Path<String>,
+    Json(request): Json<RenameRequest>,
+) -> impl IntoResponse {
+    let to = request.name;
+    if let Err(message) = config::check_dataset_name(&to) {
+        return bad_request(&message);
+    }
+    let renamed = datasets.rename(&name, &to, |shared| {
+        let renamed = shared.renamed(to.clone())?;
+        Ok((renamed.clone(), dataset_routes(renamed)))
+    });
+    match renamed {
+        Ok(shared) => {
+            tracing::info!("->> Renamed dataset {} to {}.", name, to);
+            let content_hash = shared.current().content_hash;
+            shared.audit.record(audit::Entry::new(
+                "rename_dataset",
+                (content_hash, content_hash),
+                serde_json::json!({ "from": name, "to": to }),
+            ));
+            let state = shared.current();
+            Json(DatasetSummary {
+                name: Some(&to),
+                path: api_url(&shared.dataset),
+                info: state.dataset_info(),
+            })
+            .into_response()
+        }
+        Err(state::RenameError::NotFound) => unknown_dataset(&name),
+        Err(state::RenameError::Taken) => dataset_exists(&to),
+        Err(state::RenameError::Failed(message)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("The dataset was not renamed: {}", message),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Admin handler for no longer serving a dataset.
+async fn admin_delete_dataset_handler(
+    State(datasets): State<state::Datasets>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let Some(shared) = datasets.remove(&name) else {
+        return unknown_dataset(&name);
+    };
+    tracing::info!("->> Removed dataset {}.", name);
+    let content_hash = shared.current().content_hash;
+    shared.audit.record(audit::Entry::new(
+        "delete_dataset",
+        (content_hash, 0),
+        serde_json::json!({ "source": shared.dataset.data_source.to_string() }),
+    ));
+    StatusCode::NO_CONTENT.into_response()
+}
+
+fn dataset_exists(name: &str) -> axum::response::Response {
+    (
+        StatusCode::CONFLICT,
+        Json(serde_json::json!({ "error": format!("There is a dataset named {} already.", name) })),
+    )
+        .into_response()
+}
+
+/// Hands a request for another dataset's public API to that dataset's routes,
+/// as if it were for the main dataset's.
+async fn dataset_api_handler(
+    State(datasets): State<state::Datasets>,
+    Path((name, _)): Path<(String, String)>,
+    request: axum::extract::Request,
+) -> axum::response::Response {
+    match datasets.get(&name) {
+        Some((_, routes)) => call_dataset(&name, routes.public, request).await,
+        None => unknown_dataset(&name),
+    }
+}
+
+/// The same for another dataset's admin API.
+async fn dataset_admin_api_handler(
+    State(datasets): State<state::Datasets>,
+    Path((name, _)): Path<(String, String)>,
+    request: axum::extract::Request,
+) -> axum::response::Response {
+    match datasets.get(&name) {
+        Some((_, routes)) => call_dataset(&name, routes.admin, request).await,
+        None => unknown_dataset(&name),
+    }
+}
+
+/// Where `dataset`'s API is reached from outside: its path behind
+/// `server.base_path`.
+fn api_url(dataset: &config::Dataset) -> String {
+    format!("{}{}", CONFIG.base_path, dataset.api_path())
+}
+
+// Hands `request` to `routes`, one of the dataset `name`'s halves, with the
+// path the main dataset would serve it on.
+async fn call_dataset(
+    name: &str,
+    routes: Router,
+    mut request: axum::extract::Request,
+) -> axum::response::Response {
+    // Dataset names need no escaping, so the raw path starts with exactly this.
+    let rest = &request.uri().path()[format!("/api/{}", name).len()..];
+    let path = match request.uri().query() {
+        Some(query) => format!("/api{}?{}", rest, query),
+        None => format!("/api{}", rest),
+    };
+    *request.uri_mut() = path
+        .parse()
+        .expect("a path that parsed already parses again");
+    // The path parameters and route matched here would otherwise show up
+    // alongside those the dataset's routes match. axum doesn't let its
+    // parameters be removed on their own, so the request starts over with only
+    // what the middleware outside put there for it.
+    let mut outside = std::mem::take(request.extensions_mut());
+    let extensions = request.extensions_mut();
+    if let Some(peer) = outside.remove::<axum::extract::ConnectInfo<access::Peer>>() {
+        extensions.insert(peer);
+    }
+    if let Some(id) = outside.remove::<requestid::RequestId>() {
+        extensions.insert(id);
+    }
+    if let Some(uri) = outside.remove::<axum::extract::OriginalUri>() {
+        extensions.insert(uri);
+    }
+    Handler::call(any_service(routes), request, ()).await
+}
+
+fn unknown_dataset(name: &str) -> axum::response::Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": format!("There is no dataset named {}.", name) })),
+    )
+        .into_response()
+}
+
+/// Admin handler for adding a performance to one of the editions in the data.
+async fn admin_add_performance_handler(
+    State(shared): State<SharedState>,
+    Json(addition): Json<edit::Addition>,
+) -> impl IntoResponse {
+    match shared.edit(move |data| edit::add(data, addition)).await {
+        Ok(edited) => {
+            let after = &edited.after;
+            let id = edit::id_at(&after.data, edited.outcome);
+            tracing::info!("->> Added performance {} on request.", id);
+            let performance = after.performance(&id);
+            shared.audit.record(audit::Entry::new(
+                "add",
+                (edited.before.content_hash, after.content_hash),
+                serde_json::json!({ "performance": performance }),
+            ));
+            match performance {
+                Some(performance) => (StatusCode::CREATED, Json(performance)).into_response(),
+                None => StatusCode::CREATED.into_response(),
+            }
+        }
+        Err(error) => edit_failed(error),
+    }
+}
+
+/// Admin handler for taking a performance out of the data being served.
+async fn admin_delete_performance_handler(
+    State(shared): State<SharedState>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let target = id.clone();
+    match shared.edit(move |data| edit::delete(data, &target)).await {
+        Ok(edited) => {
+            tracing::info!("->> Deleted performance {} on request.", id);
+            shared.audit.record(audit::Entry::new(
+                "delete",
+                (edited.before.content_hash, edited.after.content_hash),
+                serde_json::json!({ "performance": edited.before.performance(&id) }),
+            ));
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(error) => edit_failed(error),
+    }
+}
+
+/// Admin handler for correcting a performance's artist name or year.
+async fn admin_correct_performance_handler(
+    State(shared): State<SharedState>,
+    Path(id): Path<String>,
+    Json(correction): Json<edit::Correction>,
+) -> impl IntoResponse {
+    let target = id.clone();
+    let edited = shared
+        .edit(move |data| edit::correct(data, &target, &correction))
+        .await;
+    match edited {
+        Ok(edited) => {
+            let (before, after) = (&edited.before, &edited.after);
+            let new_id = edit::id_at(&after.data, edited.outcome);
+            tracing::info!(
+                "->> Corrected performance {} (now {}) on request.",
+                id,
+                new_id
+            );
+            let performance = after.performance(&new_id);
+            shared.audit.record(audit::Entry::new(
+                "correct",
+                (before.content_hash, after.content_hash),
+                serde_json::json!({
+                    "before": before.performance(&id),
+                    "after": performance,
+                }),
+            ));
+            match performance {
+                Some(performance) => Json(performance).into_response(),
+                None => StatusCode::NO_CONTENT.into_response(),
+            }
+        }
+        Err(error) => edit_failed(error),
+    }
+}
+
+fn edit_failed(error: state::EditError) -> axum::response::Response {
+    match error {
+        state::EditError::NotFound => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "No performance with that ID."})),
+        )
+            .into_response(),
+        state::EditError::Invalid(message) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": format!("The data was not changed: {}", message),
+            })),
+        )
+            .into_response(),
+        state::EditError::Unsaved(message) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "error": format!("The data was not changed: {}", message),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Admin handler for replacing the whole dataset with the one in the body.
+async fn admin_upload_dataset_handler(
+    State(shared): State<SharedState>,
+    body::Upload(file): body::Upload,
+) -> impl IntoResponse {
+    let source = format!("PUT {}/admin/dataset", shared.dataset.api_path());
+    let result = shared.upload(source.clone(), file).await;
+    reloaded(&shared.audit, "upload", &source, result)
+}
+
+#[derive(Debug, Serialize)]
+struct ValidationResponse<'a> {
+    // Whether PUT /api/admin/dataset would take the same body: always in lenient
+    // mode, and only without invalid records in strict mode.
+    accepted: bool,
+    #[serde(flatten)]
+    report: quality::QualityReport<'a>,
+}
+
+/// Admin handler for checking a dataset the way an upload would, without
+/// serving it.
+async fn admin_validate_dataset_handler(
+    State(dataset): State<Arc<config::Dataset>>,
+    body::Upload(file): body::Upload,
+) -> impl IntoResponse {
+    match preview(dataset, file).await {
+        Ok(preview) => {
+            let report = quality::report(&preview);
+            Json(ValidationResponse {
+                accepted: CONFIG.validation == validate::Mode::Lenient || report.skipped.is_empty(),
+                report,
+            })
+            .into_response()
+        }
+        Err(message) => unusable_upload(&message),
+    }
+}
+
+/// Admin handler for how a dataset differs from the one being served.
+async fn admin_diff_dataset_handler(
+    State(state): State<Arc<AppState>>,
+    State(dataset): State<Arc<config::Dataset>>,
+    body::Upload(file): body::Upload,
+) -> impl IntoResponse {
+    match preview(dataset, file).await {
+        Ok(preview) => Json(diff::between(&state.data, &preview.data)).into_response(),
+        Err(message) => unusable_upload(&message),
+    }
+}
 
-    (headers, Json(state.all_performances.clone()))
+// Reading and indexing an upload takes a while; keep it off the async workers.
+async fn preview(dataset: Arc<config::Dataset>, file: data::RawFile) -> Result<AppState, String> {
+    tokio::task::spawn_blocking(move || AppState::preview(&dataset, file))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn unusable_upload(message: &str) -> axum::response::Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(serde_json::json!({
+            "error": format!("The data can't be used: {}", message),
+        })),
+    )
+        .into_response()
+}
+
+// The response to swapping in the data read from `source`, which is recorded in
+// `audit` as `action` when it succeeds.
+fn reloaded(
+    audit: &audit::AuditLog,
+    action: &str,
+    source: &impl std::fmt::Display,
+    result: Result<state::ReloadSummary, String>,
+) -> axum::response::Response {
+    match result {
+        Ok(summary) => {
+            tracing::info!(
+                "->> Reloaded {} on request: {} total artist performances.",
+                source,
+                summary.performances
+            );
+            audit.record(audit::Entry::new(
+                action,
+                summary.content_hashes,
+                serde_json::json!({
+                    "source": source.to_string(),
+                    "performances": summary.performances,
+                    "added": summary.added.len(),
+                    "removed": summary.removed.len(),
+                }),
+            ));
+            Json(ReloadResponse {
+                source: source.to_string(),
+                performances: summary.performances,
+                added_count: summary.added.len(),
+                removed_count: summary.removed.len(),
+                added: summary.added,
+                removed: summary.removed,
+            })
+            .into_response()
+        }
+        Err(message) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": format!("The data was not reloaded: {}", message),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+// --- Error Responses ---
+
+fn bad_request(message: &str) -> axum::response::Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({ "error": message })),
+    )
+        .into_response()
 }
 
 // --- Static HTML Content ---
@@ -223,14 +2052,22 @@ const HTML_PAGE: &str = r#"
             <section id="search">
                  <h2 class="text-2xl font-semibold mb-4 text-teal-400 border-b-2 border-gray-700 pb-2">Search for an Artist</h2>
                  <div class="bg-gray-800 rounded-xl shadow-lg p-6 md:p-8">
-                    <input type="text" id="search-input" placeholder="Type an artist name (e.g., 'kaiser')..." class="w-full bg-gray-700 border border-gray-600 text-white rounded-md p-3 text-lg focus:ring-2 focus:ring-teal-500 focus:outline-none">
+                    <input type="text" id="search-input" list="search-suggestions" autocomplete="off" placeholder="Type an artist name (e.g., 'kaiser')..." class="w-full bg-gray-700 border border-gray-600 text-white rounded-md p-3 text-lg focus:ring-2 focus:ring-teal-500 focus:outline-none">
+                    <datalist id="search-suggestions"></datalist>
                  </div>
                  <div id="search-results-container" class="mt-6 space-y-4"></div>
             </section>
 
+            <!-- Browse Section -->
+            <section id="browse">
+                 <h2 class="text-2xl font-semibold mb-4 text-amber-400 border-b-2 border-gray-700 pb-2">Browse A-Z</h2>
+                 <div id="letter-index" class="bg-gray-800 rounded-xl shadow-lg p-4 flex flex-wrap justify-center gap-2"></div>
+                 <div id="browse-results-container" class="mt-6 space-y-4"></div>
+            </section>
+
              <!-- Download Section -->
             <section id="download" class="text-center mt-16">
-                 <a href="/api/all-bands" class="bg-gray-700 hover:bg-gray-600 text-gray-300 font-bold py-3 px-6 rounded-md transition duration-300 ease-in-out">
+                 <a href="{{base}}/api/all-bands" class="bg-gray-700 hover:bg-gray-600 text-gray-300 font-bold py-3 px-6 rounded-md transition duration-300 ease-in-out">
                     Download Full List (.json)
                 </a>
             </section>
@@ -247,10 +2084,13 @@ const HTML_PAGE: &str = r#"
 
         const searchInput = document.getElementById('search-input');
         const searchResultsContainer = document.getElementById('search-results-container');
+        const searchSuggestions = document.getElementById('search-suggestions');
+
+        const letterIndex = document.getElementById('letter-index');
+        const browseResultsContainer = document.getElementById('browse-results-container');
 
-        // This will hold all the performance data once fetched
-        let allPerformances = [];
-        let isDataFetched = false;
+        // Debounce timer so we don't hit the search API on every keystroke
+        let searchTimer = null;
 
         // --- Event Listeners ---
 
@@ -264,7 +2104,7 @@ const HTML_PAGE: &str = r#"
 
             try {
                 const count = countSelect.value;
-                const response = await fetch(`/api/random-bands?count=${count}`);
+                const response = await fetch(`{{base}}/api/random-bands?count=${count}`);
                 if (!response.ok) throw new Error(`HTTP error! status: ${response.status}`);
                 const performances = await response.json();
 
@@ -281,53 +2121,119 @@ const HTML_PAGE: &str = r#"
 
         // Listener for the search input field
         searchInput.addEventListener('input', () => {
-            handleSearch();
+            clearTimeout(searchTimer);
+            searchTimer = setTimeout(() => {
+                updateSuggestions();
+                handleSearch();
+            }, 150);
         });
 
-        // Fetch all data when the search box is first focused to improve perceived performance
-        searchInput.addEventListener('focus', () => {
-            if (!isDataFetched) {
-                fetchAllBandData();
-            }
-        }, { once: true });
 
+        // Build the A-Z index as soon as the page loads
+        loadLetterIndex();
 
         // --- Core Functions ---
 
-        async function fetchAllBandData() {
+        async function handleSearch() {
+            const query = searchInput.value.trim();
+
+            if (query.length < 2) {
+                searchResultsContainer.innerHTML = ''; // Clear results if query is too short
+                return;
+            }
+
             try {
-                const response = await fetch('/api/all-bands');
-                if(!response.ok) throw new Error('Network response was not ok');
-                allPerformances = await response.json();
-                isDataFetched = true;
-                console.log(`Fetched ${allPerformances.length} total performances.`);
+                const response = await fetch(`{{base}}/api/search?q=${encodeURIComponent(query)}`);
+                if (!response.ok) throw new Error(`HTTP error! status: ${response.status}`);
+                const data = await response.json();
+
+                // Ignore responses for queries the user has already typed past
+                if (searchInput.value.trim() !== query) return;
+
+                displayPerformances(data.results, searchResultsContainer, 'teal');
+
+                if (data.results.length === 0) {
+                     searchResultsContainer.innerHTML = `<p class="text-center text-gray-400">No matches found for "${searchInput.value}".</p>`;
+                     if (data.suggestions.length > 0) {
+                         searchResultsContainer.appendChild(createSuggestionLine(data.suggestions));
+                     }
+                }
             } catch (error) {
-                console.error("Could not fetch all band data:", error);
-                searchResultsContainer.innerHTML = `<p class="text-center text-red-500">Could not load search data. Please refresh.</p>`;
+                console.error("Search failed:", error);
+                searchResultsContainer.innerHTML = `<p class="text-center text-red-500">Search is unavailable right now. Please try again.</p>`;
             }
         }
 
-        function handleSearch() {
-            if (!isDataFetched) {
-                searchResultsContainer.innerHTML = `<p class="text-center text-gray-500">Start typing to search...</p>`;
+        async function updateSuggestions() {
+            const query = searchInput.value.trim();
+
+            if (query.length < 2) {
+                searchSuggestions.innerHTML = '';
                 return;
             }
 
-            const query = searchInput.value.trim().toLowerCase();
+            try {
+                const response = await fetch(`{{base}}/api/autocomplete?q=${encodeURIComponent(query)}&limit=10`);
+                if (!response.ok) throw new Error(`HTTP error! status: ${response.status}`);
+                const names = await response.json();
 
-            if (query.length < 2) {
-                searchResultsContainer.innerHTML = ''; // Clear results if query is too short
-                return;
+                searchSuggestions.innerHTML = '';
+                names.forEach(name => {
+                    const option = document.createElement('option');
+                    option.value = name;
+                    searchSuggestions.appendChild(option);
+                });
+            } catch (error) {
+                // Suggestions are a nicety; the search results still work without them.
+                console.error("Failed to fetch suggestions:", error);
             }
+        }
 
-            const filtered = allPerformances.filter(perf =>
-                perf.name.toLowerCase().includes(query)
-            );
+        async function loadLetterIndex() {
+            try {
+                const response = await fetch('{{base}}/api/artists/letters');
+                if (!response.ok) throw new Error(`HTTP error! status: ${response.status}`);
+                const letters = await response.json();
+
+                letters.forEach(({ letter, count }) => {
+                    const button = document.createElement('button');
+                    button.type = 'button';
+                    button.textContent = letter;
+                    button.title = `${count} artists`;
+                    button.disabled = count === 0;
+                    button.className = 'w-9 h-9 rounded-md font-bold bg-gray-700 text-amber-400 hover:bg-gray-600 disabled:opacity-30 disabled:cursor-default';
+                    button.addEventListener('click', () => browseLetter(letter));
+                    letterIndex.appendChild(button);
+                });
+            } catch (error) {
+                console.error("Failed to load the letter index:", error);
+                letterIndex.innerHTML = '<p class="text-gray-400">Browsing is unavailable right now.</p>';
+            }
+        }
 
-            displayPerformances(filtered, searchResultsContainer, 'teal');
+        async function browseLetter(letter) {
+            browseResultsContainer.innerHTML = '<p class="text-center text-gray-400">Loading artists...</p>';
+            try {
+                const response = await fetch(`{{base}}/api/artists?starts_with=${encodeURIComponent(letter)}`);
+                if (!response.ok) throw new Error(`HTTP error! status: ${response.status}`);
+                const artists = await response.json();
 
-            if(filtered.length === 0) {
-                 searchResultsContainer.innerHTML = `<p class="text-center text-gray-400">No matches found for "${searchInput.value}".</p>`;
+                browseResultsContainer.innerHTML = '';
+                artists.forEach(artist => {
+                    const card = document.createElement('div');
+                    card.className = 'bg-gray-800 p-5 rounded-lg shadow-md border-l-4 border-amber-500';
+                    const editions = artist.performances
+                        .map(p => `${escapeHtml(p.festival)} ${p.year}`)
+                        .join(', ');
+                    card.innerHTML = `
+                        <h3 class="text-2xl font-bold text-amber-400">${escapeHtml(artist.name)}</h3>
+                        <p class="text-gray-400 mt-1">${editions}</p>
+                    `;
+                    browseResultsContainer.appendChild(card);
+                });
+            } catch (error) {
+                console.error("Failed to browse artists:", error);
+                browseResultsContainer.innerHTML = '<p class="text-center text-red-400">Failed to load artists. Please try again.</p>';
             }
         }
 
@@ -341,13 +2247,54 @@ const HTML_PAGE: &str = r#"
             });
         }
 
+        function createSuggestionLine(names) {
+            const line = document.createElement('p');
+            line.className = 'text-center text-gray-400 mt-2';
+            line.append('Did you mean ');
+            names.forEach((name, index) => {
+                if (index > 0) line.append(index === names.length - 1 ? ' or ' : ', ');
+                const link = document.createElement('button');
+                link.type = 'button';
+                link.className = 'font-semibold text-teal-400 hover:underline';
+                link.textContent = name;
+                link.addEventListener('click', () => {
+                    searchInput.value = name;
+                    handleSearch();
+                });
+                line.appendChild(link);
+            });
+            line.append('?');
+            return line;
+        }
+
+        function escapeHtml(text) {
+            const div = document.createElement('div');
+            div.textContent = text;
+            return div.innerHTML;
+        }
+
+        // Wraps the matched ranges reported by the search API in <mark> tags.
+        // Offsets count characters (code points), hence Array.from.
+        function highlightName(name, highlights) {
+            if (!highlights || highlights.length === 0) return escapeHtml(name);
+            const chars = Array.from(name);
+            let html = '';
+            let last = 0;
+            highlights.forEach(({ start, end }) => {
+                html += escapeHtml(chars.slice(last, start).join(''));
+                html += `<mark class="bg-transparent text-white underline decoration-2">${escapeHtml(chars.slice(start, end).join(''))}</mark>`;
+                last = end;
+            });
+            return html + escapeHtml(chars.slice(last).join(''));
+        }
+
         function createPerformanceCard(perf, color) {
             const card = document.createElement('div');
             card.className = `bg-gray-800 p-5 rounded-lg shadow-md transition transform hover:scale-[1.02] duration-300 border-l-4 border-${color}-500`;
             card.innerHTML = `
-                <h3 class="text-2xl font-bold text-${color}-400">${perf.name}</h3>
+                <h3 class="text-2xl font-bold text-${color}-400">${highlightName(perf.name, perf.highlights)}</h3>
                 <p class="text-gray-400 mt-1">
-                    Played at <span class="font-semibold text-gray-300">${perf.festival}</span> in <span class="font-semibold text-gray-300">${perf.year}</span>
+                    Played at <span class="font-semibold text-gray-300">${escapeHtml(perf.festival)}</span> in <span class="font-semibold text-gray-300">${perf.year}</span>
                 </p>
             `;
             return card;
@@ -0,0 +1,194 @@
+// Unicode Normalization Form C for names in the data. The same name can be
+// typed as "Sigur Rós" with a single "ó" or as "Sigur Ro" plus a combining
+// accent (macOS and some editors write that); NFC turns both into the single
+// character, so names compare, deduplicate and sort alike however they were
+// authored.
+//
+// This is the standard algorithm: decompose everything, put combining marks in
+// canonical order, then compose again. The data it needs is in `tables`.
+
+mod tables;
+
+use std::borrow::Cow;
+use tables::{COMBINING_CLASSES, COMPOSITIONS, DECOMPOSITIONS};
+
+// Nothing below the combining diacritics decomposes or combines, so text made
+// only of such characters is already normalized.
+const FIRST_AFFECTED: char = '\u{0300}';
+
+// Hangul syllables are composed from their letters arithmetically.
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// `text` in NFC, borrowed when it already was.
+pub fn nfc(text: &str) -> Cow<'_, str> {
+    if text.chars().all(|c| c < FIRST_AFFECTED) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut chars = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        decompose(c, &mut chars);
+    }
+    reorder(&mut chars);
+    compose(&mut chars);
+
+    let normalized: String = chars.into_iter().collect();
+    if normalized == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+fn from_code(code: u32) -> char {
+    char::from_u32(code).expect("Hangul arithmetic stays within valid characters")
+}
+
+// Appends the full canonical decomposition of `c`.
+fn decompose(c: char, out: &mut Vec<char>) {
+    let code = c as u32;
+    if (S_BASE..S_BASE + S_COUNT).contains(&code) {
+        let index = code - S_BASE;
+        out.push(from_code(L_BASE + index / N_COUNT));
+        out.push(from_code(V_BASE + (index % N_COUNT) / T_COUNT));
+        if !index.is_multiple_of(T_COUNT) {
+            out.push(from_code(T_BASE + index % T_COUNT));
+        }
+        return;
+    }
+
+    match DECOMPOSITIONS.binary_search_by_key(&c, |&(composite, _, _)| composite) {
+        Ok(i) => {
+            let (_, first, second) = DECOMPOSITIONS[i];
+            decompose(first, out);
+            if second != '\0' {
+                decompose(second, out);
+            }
+        }
+        Err(_) => out.push(c),
+    }
+}
+
+fn combining_class(c: char) -> u8 {
+    if c < FIRST_AFFECTED {
+        return 0;
+    }
+    match COMBINING_CLASSES.binary_search_by(|&(first, last, _)| {
+        if last < c {
+            std::cmp::Ordering::Less
+        } else if first > c {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }) {
+        Ok(i) => COMBINING_CLASSES[i].2,
+        Err(_) => 0,
+    }
+}
+
+// Sorts every run of combining marks by class, keeping the order of marks of
+// the same class (a stable sort).
+fn reorder(chars: &mut [char]) {
+    let mut start = 0;
+    while start < chars.len() {
+        let run = chars[start..]
+            .iter()
+            .take_while(|&&c| combining_class(c) != 0)
+            .count();
+        if run > 1 {
+            chars[start..start + run].sort_by_key(|&c| combining_class(c));
+        }
+        start += run.max(1);
+    }
+}
+
+// Combines each mark with the last starter (a class 0 character) before it,
+// unless a mark of the same or a higher class comes in between.
+fn compose(chars: &mut Vec<char>) {
+    let mut starter: Option<usize> = None;
+    // Class of the last character kept.
+    let mut last_class = 0;
+    let mut kept = 0;
+
+    for i in 0..chars.len() {
+        let c = chars[i];
+        let class = combining_class(c);
+        if let Some(at) = starter {
+            let blocked = kept != at + 1 && last_class >= class;
+            if let Some(composite) = (!blocked).then(|| compose_pair(chars[at], c)).flatten() {
+                chars[at] = composite;
+                continue;
+            }
+        }
+        if class == 0 {
+            starter = Some(kept);
+        }
+        last_class = class;
+        chars[kept] = c;
+        kept += 1;
+    }
+    chars.truncate(kept);
+}
+
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (first as u32, second as u32);
+    if (L_BASE..L_BASE + L_COUNT).contains(&a) && (V_BASE..V_BASE + V_COUNT).contains(&b) {
+        return Some(from_code(
+            S_BASE + ((a - L_BASE) * V_COUNT + (b - V_BASE)) * T_COUNT,
+        ));
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&a)
+        && (a - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&b)
+    {
+        return Some(from_code(a + b - T_BASE));
+    }
+
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(a, b, _)| (a, b))
+        .ok()
+        .map(|i| COMPOSITIONS[i].2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_accents_onto_their_letters() {
+        assert_eq!(nfc("Sigur Ro\u{301}s"), "Sigur R\u{f3}s");
+        assert_eq!(nfc("Mo\u{308}tley Cru\u{308}e"), "M\u{f6}tley Cr\u{fc}e");
+        // Marks in either order, and a letter that only composes in steps.
+        assert_eq!(nfc("a\u{302}\u{323}"), "\u{1ead}");
+        assert_eq!(nfc("a\u{323}\u{302}"), "\u{1ead}");
+        // Two marks of the same class: only the first can combine.
+        assert_eq!(nfc("e\u{301}\u{301}"), "\u{e9}\u{301}");
+    }
+
+    #[test]
+    fn normalizes_singletons_and_hangul() {
+        // The Ångström sign is a different character from Å, but the same letter.
+        assert_eq!(nfc("\u{212b}"), "\u{c5}");
+        assert_eq!(nfc("\u{1100}\u{1161}\u{11a8}"), "\u{ac01}");
+        assert_eq!(nfc("\u{ac01}"), "\u{ac01}");
+    }
+
+    #[test]
+    fn leaves_normalized_text_borrowed() {
+        assert!(matches!(nfc("Kaiser Chiefs"), Cow::Borrowed(_)));
+        assert!(matches!(nfc("Sigur R\u{f3}s"), Cow::Borrowed(_)));
+        assert!(matches!(
+            nfc("\u{30b7}\u{30ac}\u{30ec}\u{30c3}\u{30c8}"),
+            Cow::Borrowed(_)
+        ));
+    }
+}
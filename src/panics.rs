@@ -0,0 +1,99 @@
+// Panics: a bug, not something a client did, so they're logged as errors with
+// a backtrace, which names the request they happened in like any other event.
+// One in a handler only costs that request a 500 with a JSON error and its ID,
+// rather than the connection, which would otherwise be dropped without a reply.
+
+use axum::extract::Request;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Logs every panic, in place of printing it to stderr.
+pub fn log_panics() {
+    panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map_or_else(|| "an unknown place".to_string(), |l| l.to_string());
+        let backtrace = Backtrace::force_capture();
+        tracing::error!(
+            %backtrace,
+            "->> Panicked at {}: {}",
+            location,
+            message(info.payload())
+        );
+    }));
+}
+
+/// Middleware answering a request whose handler panicked with a 500.
+pub async fn catch(request: Request, next: Next) -> Response {
+    match CatchUnwind(Box::pin(next.run(request))).await {
+        Ok(response) => response,
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": "Something went wrong on the server while answering this request.",
+            })),
+        )
+            .into_response(),
+    }
+}
+
+fn message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "(no message)"
+    }
+}
+
+// A future that finishes with `Err` and the panic's payload if polling it
+// panics.
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The future isn't polled again after a panic, so whatever state it was
+        // left in isn't seen.
+        let inner = &mut self.0;
+        match panic::catch_unwind(AssertUnwindSafe(|| inner.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+
+    #[test]
+    fn a_panic_ends_the_future_with_its_message() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut fine = CatchUnwind(Box::pin(async { 7 }));
+        assert!(matches!(
+            Pin::new(&mut fine).poll(&mut cx),
+            Poll::Ready(Ok(7))
+        ));
+
+        let mut broken = CatchUnwind(Box::pin(async {
+            let performances: Vec<u32> = Vec::new();
+            performances[3]
+        }));
+        let Poll::Ready(Err(payload)) = Pin::new(&mut broken).poll(&mut cx) else {
+            panic!("expected the panic to be caught");
+        };
+        assert!(message(payload.as_ref()).contains("index out of bounds"));
+    }
+}
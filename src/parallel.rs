@@ -0,0 +1,45 @@
+// Work split across threads while loading, so startup keeps up as datasets
+// grow: a dataset's files are parsed on as many threads as there are cores, and
+// the parts of its index are built at the same time. Scoped threads are enough
+// for that; each job is over once the load is.
+
+use std::panic;
+use std::thread;
+
+/// `f` of each of `items`, in order, worked out on up to one thread per core.
+pub fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(items.len().div_ceil(threads))
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        handles.into_iter().flat_map(join).collect()
+    })
+}
+
+/// What a scoped thread returned, passing on its panic if it had one.
+pub fn join<R>(handle: thread::ScopedJoinHandle<'_, R>) -> R {
+    handle
+        .join()
+        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_keep_the_order_of_the_items() {
+        let items: Vec<u32> = (0..1000).collect();
+        let doubled = map(&items, |n| n * 2);
+        assert_eq!(doubled, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+        assert!(map(&[] as &[u32], |n| *n).is_empty());
+    }
+}
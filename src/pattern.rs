@@ -0,0 +1,563 @@
+// A small regular expression engine for the search endpoint's `mode=regex`.
+//
+// Patterns compile to a Thompson NFA that is simulated as a Pike VM, so matching
+// never backtracks and runs in O(pattern × text) time no matter what users send.
+// Supported syntax: literals, `.`, `^`, `$`, `[...]` classes (ranges, negation),
+// `\d \w \s` (and their negations), groups `(...)` / `(?:...)`, alternation `|`,
+// and the quantifiers `* + ? {m} {m,} {m,n}`. A leading `(?i)` makes the whole
+// pattern case-insensitive.
+
+/// Longest pattern we accept, in characters.
+const MAX_PATTERN_LEN: usize = 256;
+
+/// Upper bound on compiled program size; counted repetitions can blow up quickly.
+const MAX_PROGRAM_LEN: usize = 1_000;
+
+// --- Syntax Tree ---
+
+#[derive(Debug, Clone)]
+struct ClassSet {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl ClassSet {
+    fn contains(&self, c: char) -> bool {
+        let found = self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        found != self.negated
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(ClassSet),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+// --- Parser ---
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("Unexpected end of pattern.")?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            items.push(self.parse_repeat()?);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items),
+        })
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_atom()?;
+        loop {
+            let (min, max) = if self.eat('*') {
+                (0, None)
+            } else if self.eat('+') {
+                (1, None)
+            } else if self.eat('?') {
+                (0, Some(1))
+            } else if self.eat('{') {
+                self.parse_counted()?
+            } else {
+                break;
+            };
+            if matches!(node, Node::Start | Node::End | Node::Empty) {
+                return Err("Quantifier has nothing to repeat.".to_string());
+            }
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            };
+        }
+        Ok(node)
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn parse_counted(&mut self) -> Result<(u32, Option<u32>), String> {
+        let min = self.parse_number().ok_or("Expected a number after '{'.")?;
+        let max = if self.eat(',') {
+            self.parse_number()
+        } else {
+            Some(min)
+        };
+        if !self.eat('}') {
+            return Err("Unclosed counted repetition '{'.".to_string());
+        }
+        if let Some(max) = max.filter(|&max| max < min) {
+            return Err(format!("Invalid repetition range {{{},{}}}.", min, max));
+        }
+        Ok((min, max))
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            '(' => {
+                // Non-capturing groups behave exactly like plain groups here.
+                if self.eat('?') && !self.eat(':') {
+                    return Err("Only '(?:' groups and a leading '(?i)' are supported.".to_string());
+                }
+                let node = self.parse_alternation()?;
+                if !self.eat(')') {
+                    return Err("Unclosed group '('.".to_string());
+                }
+                Ok(node)
+            }
+            ')' => Err("Unmatched ')'.".to_string()),
+            '[' => self.parse_class(),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '*' | '+' | '?' | '{' => Err("Quantifier has nothing to repeat.".to_string()),
+            '\\' => self.parse_escape(),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, String> {
+        let c = self.next().map_err(|_| "Pattern ends with a lone '\\'.")?;
+        Ok(match perl_class(c) {
+            Some(class) => Node::Class(class),
+            None if c.is_alphanumeric() => {
+                return Err(format!("Unknown escape sequence '\\{}'.", c));
+            }
+            None => Node::Char(c),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = self.next().map_err(|_| "Unclosed character class '['.")?;
+            match c {
+                // A ']' right after the opening bracket is a literal.
+                ']' if !first => break,
+                '\\' => {
+                    let escaped = self.next().map_err(|_| "Unclosed character class '['.")?;
+                    match perl_class(escaped) {
+                        Some(class) if !class.negated => ranges.extend(class.ranges),
+                        Some(_) => {
+                            return Err("Negated classes like \\D are not allowed inside [...]."
+                                .to_string());
+                        }
+                        None => ranges.push((escaped, escaped)),
+                    }
+                }
+                lo => {
+                    if self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']') {
+                        self.pos += 1;
+                        let hi = self.next().map_err(|_| "Unclosed character class '['.")?;
+                        if hi < lo {
+                            return Err(format!("Invalid class range '{}-{}'.", lo, hi));
+                        }
+                        ranges.push((lo, hi));
+                    } else {
+                        ranges.push((lo, lo));
+                    }
+                }
+            }
+            first = false;
+        }
+
+        Ok(Node::Class(ClassSet { negated, ranges }))
+    }
+}
+
+/// The `\d`, `\w` and `\s` shorthands (and their upper-case negations).
+fn perl_class(c: char) -> Option<ClassSet> {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+        's' => vec![(' ', ' '), ('\t', '\r')],
+        _ => return None,
+    };
+    Some(ClassSet {
+        negated: c.is_ascii_uppercase(),
+        ranges,
+    })
+}
+
+// --- Compiler ---
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(ClassSet),
+    Start,
+    End,
+    // Try the first branch before the second (greedy unless swapped).
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM_LEN {
+            return Err("Pattern is too complex.".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Empty => {}
+            Node::Char(c) => {
+                self.push(Inst::Char(*c))?;
+            }
+            Node::Any => {
+                self.push(Inst::Any)?;
+            }
+            Node::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Node::Start => {
+                self.push(Inst::Start)?;
+            }
+            Node::End => {
+                self.push(Inst::End)?;
+            }
+            Node::Concat(items) => {
+                for item in items {
+                    self.compile(item)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                let mut exits = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 < branches.len() {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.compile(branch)?;
+                        exits.push(self.push(Inst::Jump(0))?);
+                        let next = self.program.len();
+                        self.program[split] = Inst::Split(split + 1, next);
+                    } else {
+                        self.compile(branch)?;
+                    }
+                }
+                let end = self.program.len();
+                for exit in exits {
+                    self.program[exit] = Inst::Jump(end);
+                }
+            }
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    // x* : L1: split L2, L3; L2: x; jump L1; L3:
+                    None => {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.compile(node)?;
+                        self.push(Inst::Jump(split))?;
+                        let end = self.program.len();
+                        self.program[split] = Inst::Split(split + 1, end);
+                    }
+                    // x{0,n} : n nested optional copies.
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.push(Inst::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.program[split] = Inst::Split(split + 1, end);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// --- Matcher ---
+
+/// A compiled pattern.
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Inst>,
+    case_insensitive: bool,
+}
+
+impl Regex {
+    /// Parses and compiles `pattern`, returning a readable message on syntax errors.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        if pattern.chars().count() > MAX_PATTERN_LEN {
+            return Err(format!(
+                "Pattern is longer than {} characters.",
+                MAX_PATTERN_LEN
+            ));
+        }
+
+        let (case_insensitive, pattern) = match pattern.strip_prefix("(?i)") {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let ast = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err("Unmatched ')'.".to_string());
+        }
+
+        let mut compiler = Compiler {
+            program: Vec::new(),
+        };
+        compiler.compile(&ast)?;
+        compiler.push(Inst::Match)?;
+
+        Ok(Regex {
+            program: compiler.program,
+            case_insensitive,
+        })
+    }
+
+    /// Returns the byte range of the leftmost match in `text`, preferring the
+    /// longest match a greedy backtracking engine would report.
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let mut current: Vec<(usize, usize)> = Vec::new();
+        let mut next: Vec<(usize, usize)> = Vec::new();
+        // The step at which each pc was last queued, so it's added at most once per step.
+        let mut seen = vec![usize::MAX; self.program.len()];
+        let mut found = None;
+
+        for step in 0..=chars.len() {
+            let at = chars.get(step).map_or(text.len(), |&(i, _)| i);
+            let c = chars.get(step).map(|&(_, c)| c);
+
+            // Keep starting new attempts until the leftmost match has been found.
+            if found.is_none() {
+                self.add_thread(&mut current, &mut seen, 0, at, step, chars.len());
+            }
+
+            for &(pc, start) in current.iter() {
+                let consumes = match &self.program[pc] {
+                    Inst::Match => {
+                        found = Some((start, at));
+                        // Lower-priority threads can't produce a preferred match.
+                        break;
+                    }
+                    Inst::Char(expected) => c.is_some_and(|c| self.char_eq(*expected, c)),
+                    Inst::Any => c.is_some(),
+                    Inst::Class(class) => c.is_some_and(|c| self.class_contains(class, c)),
+                    _ => false,
+                };
+                if consumes {
+                    self.add_thread(&mut next, &mut seen, pc + 1, start, step + 1, chars.len());
+                }
+            }
+
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+            if current.is_empty() && found.is_some() {
+                break;
+            }
+        }
+        found
+    }
+
+    /// Follows jumps, splits and assertions from `pc`, queueing the threads that sit
+    /// on a consuming instruction (or `Match`) for the given input position.
+    fn add_thread(
+        &self,
+        list: &mut Vec<(usize, usize)>,
+        seen: &mut [usize],
+        pc: usize,
+        start: usize,
+        position: usize,
+        len: usize,
+    ) {
+        if seen[pc] == position {
+            return;
+        }
+        seen[pc] = position;
+        match self.program[pc] {
+            Inst::Jump(target) => self.add_thread(list, seen, target, start, position, len),
+            Inst::Split(first, second) => {
+                self.add_thread(list, seen, first, start, position, len);
+                self.add_thread(list, seen, second, start, position, len);
+            }
+            Inst::Start => {
+                if position == 0 {
+                    self.add_thread(list, seen, pc + 1, start, position, len);
+                }
+            }
+            Inst::End => {
+                if position == len {
+                    self.add_thread(list, seen, pc + 1, start, position, len);
+                }
+            }
+            _ => list.push((pc, start)),
+        }
+    }
+
+    fn char_eq(&self, expected: char, c: char) -> bool {
+        expected == c || (self.case_insensitive && fold(expected) == fold(c))
+    }
+
+    fn class_contains(&self, class: &ClassSet, c: char) -> bool {
+        if !self.case_insensitive {
+            return class.contains(c);
+        }
+        let lower = fold(c);
+        let upper = c.to_uppercase().next().unwrap_or(c);
+        // A negated class must reject every case variant, a plain one accept any.
+        if class.negated {
+            class.contains(c) && class.contains(lower) && class.contains(upper)
+        } else {
+            class.contains(c) || class.contains(lower) || class.contains(upper)
+        }
+    }
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern).unwrap().find(text)
+    }
+
+    #[test]
+    fn anchors_and_classes() {
+        assert_eq!(find("^Kai", "Kaiser Chiefs"), Some((0, 3)));
+        assert_eq!(find("^Kai", "The Kaiser Chiefs"), None);
+        assert_eq!(find("fs$", "Kaiser Chiefs"), Some((11, 13)));
+        assert_eq!(find("^$", ""), Some((0, 0)));
+        assert_eq!(find("[A-C]at", "a Bat"), Some((2, 5)));
+        assert_eq!(find("[^aeiou ]+", "aai bcd"), Some((4, 7)));
+        assert_eq!(find(r"\d+", "Blink-182"), Some((6, 9)));
+        assert_eq!(find(r"\W", "AC/DC"), Some((2, 3)));
+        // A ']' first in a class is a literal.
+        assert_eq!(find("[]a]+", "x]a"), Some((1, 3)));
+        assert!(Regex::new(r"[\D]").is_err());
+        assert!(Regex::new("[z-a]").is_err());
+        assert!(Regex::new(r"\q").is_err());
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("a)").is_err());
+    }
+
+    #[test]
+    fn counted_repeats_and_their_limit() {
+        assert_eq!(find("a{2}", "aaa"), Some((0, 2)));
+        assert_eq!(find("a{2,}", "baaaa"), Some((1, 5)));
+        assert_eq!(find("a{1,2}", "aaa"), Some((0, 2)));
+        assert_eq!(find("ba{0,2}c", "bc"), Some((0, 2)));
+        assert!(Regex::new("a{3,1}").is_err());
+        assert!(Regex::new("a{,2}").is_err());
+        assert!(Regex::new("(a){999}").is_ok());
+        assert_eq!(
+            Regex::new("(a){1000}").unwrap_err(),
+            "Pattern is too complex."
+        );
+        assert!(Regex::new(&"a".repeat(MAX_PATTERN_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn case_insensitive_patterns() {
+        assert_eq!(find("kai", "Kaiser"), None);
+        assert_eq!(find("(?i)kai", "Kaiser"), Some((0, 3)));
+        assert_eq!(find("(?i)^motör", "MOTÖRHEAD"), Some((0, 6)));
+        assert_eq!(find("(?i)[a-c]x", "BX"), Some((0, 2)));
+        // A negated class rejects every case of the letters it lists.
+        assert_eq!(find("(?i)[^a-z]", "Ab1"), Some((2, 3)));
+    }
+
+    #[test]
+    fn spans_are_leftmost_and_greedy_in_bytes() {
+        assert_eq!(find("R.s", "Sigur Rós"), Some((6, 10)));
+        assert_eq!(find("ó+", "Bjóóórk"), Some((2, 8)));
+        assert_eq!(find("o", "Motörhead"), Some((1, 2)));
+        // Alternatives are tried in order, as a backtracking engine would.
+        assert_eq!(find("a|ab", "ab"), Some((0, 1)));
+        assert_eq!(find("ab|a", "ab"), Some((0, 2)));
+    }
+
+    #[test]
+    fn loops_that_can_match_nothing_end() {
+        assert_eq!(find("(a|)*b", "aab"), Some((0, 3)));
+        assert_eq!(find("(a|)*b", "b"), Some((0, 1)));
+        assert_eq!(find("(a|)*b", "xyz"), None);
+        assert_eq!(find("(a*)*", "aaa"), Some((0, 3)));
+        assert_eq!(find("(a?)+$", "aa"), Some((0, 2)));
+    }
+}
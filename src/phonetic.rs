@@ -0,0 +1,141 @@
+// Phonetic keys for the search endpoint's `mode=phonetic`.
+//
+// This is a compact take on Lawrence Philips' original Metaphone: each word is
+// reduced to consonant sounds, so "Fleet" and "Fleigh" both start with "FL" and
+// "Foxes" and "Focks" share "FKS". Input is expected to be a normalized search
+// key (lowercase ASCII letters, digits and spaces).
+
+/// Encodes every word of a normalized name. Words without a sound (rare) are dropped.
+pub fn codes(search_key: &str) -> Vec<String> {
+    search_key
+        .split(' ')
+        .map(metaphone)
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
+fn is_vowel(c: u8) -> bool {
+    matches!(c, b'A' | b'E' | b'I' | b'O' | b'U')
+}
+
+/// Returns the Metaphone key of a single word.
+pub fn metaphone(word: &str) -> String {
+    let mut chars: Vec<u8> = word
+        .bytes()
+        .filter(u8::is_ascii_alphanumeric)
+        .map(|b| b.to_ascii_uppercase())
+        .collect();
+
+    // Initial letter combinations that are pronounced differently.
+    match chars.as_slice() {
+        [b'A', b'E', ..] | [b'G' | b'K' | b'P', b'N', ..] | [b'W', b'R', ..] => {
+            chars.remove(0);
+        }
+        [b'X', ..] => chars[0] = b'S',
+        [b'W', b'H', ..] => {
+            chars.remove(1);
+        }
+        _ => {}
+    }
+
+    let at = |i: usize| chars.get(i).copied().unwrap_or(0);
+    let mut code = String::new();
+
+    for i in 0..chars.len() {
+        let c = chars[i];
+        let prev = if i > 0 { chars[i - 1] } else { 0 };
+        let next = at(i + 1);
+
+        // Doubled letters sound like one, except "CC" as in "accent".
+        if c == prev && c != b'C' {
+            continue;
+        }
+
+        match c {
+            b'0'..=b'9' => code.push(c as char),
+            b'A' | b'E' | b'I' | b'O' | b'U' => {
+                if i == 0 {
+                    code.push(c as char);
+                }
+            }
+            b'B' => {
+                // Silent in a trailing "MB" ("bomb").
+                if !(prev == b'M' && i + 1 == chars.len()) {
+                    code.push('B');
+                }
+            }
+            b'C' => {
+                if next == b'I' && at(i + 2) == b'A' || next == b'H' && prev != b'S' {
+                    code.push('X');
+                } else if matches!(next, b'I' | b'E' | b'Y') {
+                    if prev != b'S' {
+                        code.push('S');
+                    }
+                } else {
+                    code.push('K');
+                }
+            }
+            b'D' => {
+                if next == b'G' && matches!(at(i + 2), b'E' | b'I' | b'Y') {
+                    code.push('J');
+                } else {
+                    code.push('T');
+                }
+            }
+            b'G' => {
+                let silent = (next == b'H' && !is_vowel(at(i + 2)))
+                    || (next == b'N' && (i + 2 == chars.len() || chars[i + 2..] == *b"ED"));
+                if silent {
+                    continue;
+                }
+                if matches!(next, b'I' | b'E' | b'Y') && prev != b'G' {
+                    code.push('J');
+                } else {
+                    code.push('K');
+                }
+            }
+            b'H' => {
+                let after_modifier = matches!(prev, b'C' | b'S' | b'P' | b'T' | b'G');
+                let between_vowel_and_consonant = is_vowel(prev) && !is_vowel(next);
+                if !after_modifier && !between_vowel_and_consonant {
+                    code.push('H');
+                }
+            }
+            b'K' => {
+                if prev != b'C' {
+                    code.push('K');
+                }
+            }
+            b'P' => code.push(if next == b'H' { 'F' } else { 'P' }),
+            b'Q' => code.push('K'),
+            b'S' => {
+                if next == b'H' || (next == b'I' && matches!(at(i + 2), b'O' | b'A')) {
+                    code.push('X');
+                } else {
+                    code.push('S');
+                }
+            }
+            b'T' => {
+                if next == b'I' && matches!(at(i + 2), b'O' | b'A') {
+                    code.push('X');
+                } else if next == b'H' {
+                    code.push('0');
+                } else if !(next == b'C' && at(i + 2) == b'H') {
+                    code.push('T');
+                }
+            }
+            b'V' => code.push('F'),
+            b'W' | b'Y' => {
+                if is_vowel(next) {
+                    code.push(c as char);
+                }
+            }
+            b'X' => code.push_str("KS"),
+            b'Z' => code.push('S'),
+            // F, J, L, M, N and R sound like themselves.
+            _ => code.push(c as char),
+        }
+    }
+
+    code
+}
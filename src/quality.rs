@@ -0,0 +1,85 @@
+// The data-quality report behind GET /api/admin/quality-report: names that are
+// probably one artist written in different ways, plus what loading the data
+// tidied or left out, so the source data can be fixed.
+//
+// Two spellings count as probable duplicates when they fold to the same search
+// key, ignoring a leading "The": "dEUS" and "Deus", "Sigur Rós" and "Sigur Ros",
+// "The Kooks" and "Kooks". Search already treats them as one artist; the report
+// only points out that the source disagrees with itself.
+
+use crate::browse::{self, Appearance};
+use crate::state::AppState;
+use crate::validate::Problem;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+pub struct QualityReport<'a> {
+    pub performances: usize,
+    pub distinct_artists: usize,
+    pub probable_duplicates: Vec<DuplicateGroup<'a>>,
+    // Names whose whitespace was fixed while loading.
+    pub tidied: &'a [Problem],
+    // Invalid records that were left out while loading.
+    pub skipped: &'a [Problem],
+}
+
+/// Spellings of what is probably one artist, most used first.
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup<'a> {
+    pub key: &'a str,
+    pub spellings: Vec<Spelling<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Spelling<'a> {
+    pub name: &'a str,
+    // Oldest first.
+    pub performances: Vec<Appearance<'a>>,
+}
+
+pub fn report(state: &AppState) -> QualityReport<'_> {
+    let performances = &state.all_performances;
+
+    // Spellings under each key, in the order the data has them.
+    let mut groups: BTreeMap<&str, Vec<Spelling>> = BTreeMap::new();
+    let mut distinct_artists = 0;
+    for artist in state.artists.iter() {
+        distinct_artists += 1;
+        let spellings = groups.entry(browse::sort_key(&artist.key)).or_default();
+        for &id in &artist.performances {
+            let performance = performances.get(id);
+            let appearance = Appearance::of(performance);
+            match spellings.iter_mut().find(|s| s.name == performance.name()) {
+                Some(spelling) => spelling.performances.push(appearance),
+                None => spellings.push(Spelling {
+                    name: performance.name(),
+                    performances: vec![appearance],
+                }),
+            }
+        }
+    }
+
+    let probable_duplicates = groups
+        .into_iter()
+        .filter(|(_, spellings)| spellings.len() > 1)
+        .map(|(key, mut spellings)| {
+            for spelling in &mut spellings {
+                spelling
+                    .performances
+                    .sort_by(|a, b| (a.year, a.festival).cmp(&(b.year, b.festival)));
+            }
+            spellings.sort_by_key(|s| Reverse(s.performances.len()));
+            DuplicateGroup { key, spellings }
+        })
+        .collect();
+
+    QualityReport {
+        performances: performances.len(),
+        distinct_artists,
+        probable_duplicates,
+        tidied: &state.checks.tidied,
+        skipped: &state.checks.skipped,
+    }
+}
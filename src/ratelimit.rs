@@ -0,0 +1,227 @@
+// Rate limits on the public API, per client: each client IP gets a token bucket
+// for each group of routes, which refills at the configured rate up to its
+// burst size and pays for one request per token. Exports of the whole dataset
+// are costly to send, so they have a group of their own with a lower rate.
+//
+// Clients are told where they stand with X-RateLimit-* headers on every
+// response, and an empty bucket makes a 429 with Retry-After.
+
+use crate::{access, CONFIG};
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How fast one group of routes can be called; a `per_minute` of 0 means no limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    pub per_minute: u32,
+    // How many requests can be made at once after a quiet spell.
+    pub burst: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Group {
+    Reads,
+    Exports,
+}
+
+impl Group {
+    // The group a public request for `path` is in.
+    fn of(path: &str) -> Group {
+        if is_export(path) {
+            Group::Exports
+        } else {
+            Group::Reads
+        }
+    }
+
+    fn rate(self) -> Rate {
+        match self {
+            Group::Reads => CONFIG.limits.reads,
+            Group::Exports => CONFIG.limits.exports,
+        }
+    }
+}
+
+/// Whether a public request for `path` exports a whole dataset.
+pub fn is_export(path: &str) -> bool {
+    path.ends_with("/all-bands")
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    // Takes a token if there is one, returning how many are left, or else how
+    // long until there is one.
+    fn take(&mut self, rate: Rate, now: Instant) -> Result<u32, Duration> {
+        let per_second = rate.per_minute as f64 / 60.0;
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(rate.burst as f64);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(self.tokens as u32)
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+
+    // How long until the bucket is full again.
+    fn until_full(&self, rate: Rate) -> Duration {
+        let per_second = rate.per_minute as f64 / 60.0;
+        Duration::from_secs_f64((rate.burst as f64 - self.tokens).max(0.0) / per_second)
+    }
+}
+
+// Clients over a Unix socket with no X-Forwarded-For share one bucket.
+type Key = (Group, Option<IpAddr>);
+
+static BUCKETS: Lazy<Mutex<HashMap<Key, Bucket>>> = Lazy::new(Mutex::default);
+
+// Buckets that have filled up again are no different from new ones, so once
+// there are this many, those are dropped.
+const PRUNE_AT: usize = 10_000;
+
+// The address a client is counted under. An IPv6 client usually has a whole /64
+// to pick addresses from, so that is what's limited.
+fn client_key(address: IpAddr) -> IpAddr {
+    match address.to_canonical() {
+        IpAddr::V6(address) => IpAddr::V6((u128::from(address) & !(u64::MAX as u128)).into()),
+        address => address,
+    }
+}
+
+// Drops the buckets that are full again, and those of groups that are no longer
+// limited since a reload. If that still leaves more than half of `PRUNE_AT`, as
+// when a client keeps switching addresses, the ones used longest ago go too, so
+// the map stays bounded and pruning runs at most once per `PRUNE_AT / 2` new
+// clients.
+fn prune(buckets: &mut HashMap<Key, Bucket>, now: Instant) {
+    buckets.retain(|(group, _), bucket| {
+        let rate = group.rate();
+        rate.per_minute > 0 && now.duration_since(bucket.updated) < bucket.until_full(rate)
+    });
+    let keep = PRUNE_AT / 2;
+    if buckets.len() > keep {
+        let mut updated: Vec<Instant> = buckets.values().map(|b| b.updated).collect();
+        let (_, &mut oldest_kept, _) = updated.select_nth_unstable_by(keep - 1, |a, b| b.cmp(a));
+        buckets.retain(|_, bucket| bucket.updated >= oldest_kept);
+    }
+}
+
+/// Middleware holding each client to the rate of the group of routes it calls.
+pub async fn limit(request: Request, next: Next) -> Response {
+    let group = Group::of(request.uri().path());
+    let rate = group.rate();
+    if rate.per_minute == 0 {
+        return next.run(request).await;
+    }
+    let key = (group, access::client_address(&request).map(client_key));
+    let now = Instant::now();
+
+    let (taken, until_full) = {
+        let mut buckets = BUCKETS.lock().unwrap();
+        if buckets.len() >= PRUNE_AT {
+            prune(&mut buckets, now);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: rate.burst as f64,
+            updated: now,
+        });
+        let taken = bucket.take(rate, now);
+        (taken, bucket.until_full(rate))
+    };
+
+    let mut response = match taken {
+        Ok(_) => next.run(request).await,
+        Err(wait) => {
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({
+                    "error": format!("Too many requests; try again in {} seconds.", seconds(wait)),
+                })),
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert("retry-after", HeaderValue::from(seconds(wait)));
+            response
+        }
+    };
+    let remaining = taken.unwrap_or(0);
+    set_headers(response.headers_mut(), rate, remaining, until_full);
+    response
+}
+
+fn set_headers(headers: &mut HeaderMap, rate: Rate, remaining: u32, until_full: Duration) {
+    headers.insert("x-ratelimit-limit", HeaderValue::from(rate.burst));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(seconds(until_full)));
+}
+
+// Whole seconds, rounded up so that waiting that long is always enough.
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_refill_at_the_rate_up_to_the_burst() {
+        let rate = Rate {
+            per_minute: 60,
+            burst: 2,
+        };
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 2.0,
+            updated: start,
+        };
+        assert_eq!(bucket.take(rate, start), Ok(1));
+        assert_eq!(bucket.take(rate, start), Ok(0));
+        let wait = bucket.take(rate, start).unwrap_err();
+        assert_eq!(seconds(wait), 1);
+        assert_eq!(bucket.take(rate, start + Duration::from_secs(1)), Ok(0));
+        // A long pause fills it no further than the burst.
+        assert_eq!(bucket.take(rate, start + Duration::from_secs(60)), Ok(1));
+        assert_eq!(Group::of("/api/uk-festivals/all-bands"), Group::Exports);
+        assert_eq!(Group::of("/api/search"), Group::Reads);
+    }
+
+    #[test]
+    fn pruning_keeps_the_map_bounded() {
+        let start = Instant::now();
+        let mut buckets: HashMap<Key, Bucket> = (0..PRUNE_AT as u32)
+            .map(|n| {
+                let bucket = Bucket {
+                    tokens: 0.0,
+                    updated: start + Duration::from_millis(n as u64),
+                };
+                ((Group::Reads, Some(IpAddr::from(n.to_be_bytes()))), bucket)
+            })
+            .collect();
+        prune(&mut buckets, start + Duration::from_secs(1));
+        assert!(buckets.len() <= PRUNE_AT / 2);
+        let newest = Some(IpAddr::from((PRUNE_AT as u32 - 1).to_be_bytes()));
+        assert!(buckets.contains_key(&(Group::Reads, newest)));
+
+        let a: IpAddr = "2001:db8:1:2:aaaa::1".parse().unwrap();
+        let b: IpAddr = "2001:db8:1:2:bbbb::2".parse().unwrap();
+        assert_eq!(client_key(a), client_key(b));
+        assert_eq!(client_key(a), "2001:db8:1:2::".parse::<IpAddr>().unwrap());
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(client_key("::ffff:192.0.2.1".parse().unwrap()), v4);
+    }
+}
@@ -0,0 +1,105 @@
+// Request IDs: every request gets one, the X-Request-Id it came with if that's
+// usable, or else a new random one. It's a field of the request's span, so the
+// log lines about the request name it, and it's sent back in X-Request-Id and
+// in the body of every JSON error, so a client's report can be matched to the
+// server's log.
+
+use axum::body::{self, Body, HttpBody};
+use axum::extract::Request;
+use axum::http::{header, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use rand::Rng;
+
+const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+// Error bodies larger than this are left as they are.
+const MAX_ERROR_BYTES: u64 = 64 * 1024;
+
+/// The ID of the request being handled, as a request extension.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Middleware giving each request its ID. It goes outside the trace layer, so
+/// the request's span can include it.
+pub async fn assign(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| usable(id))
+        .map_or_else(random_id, String::from);
+    request.extensions_mut().insert(RequestId(id.clone()));
+
+    let response = next.run(request).await;
+    let mut response = if is_json_error(&response) {
+        with_id(response, &id).await
+    } else {
+        response
+    };
+    let value = HeaderValue::from_str(&id).expect("IDs are visible ASCII");
+    response.headers_mut().insert(X_REQUEST_ID, value);
+    response
+}
+
+// Whether an incoming ID can be used as it is: short, and only characters that
+// are safe in headers and log lines.
+fn usable(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+fn random_id() -> String {
+    format!("{:016x}", rand::rng().random::<u64>())
+}
+
+fn is_json_error(response: &Response) -> bool {
+    let json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    let small = response
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|size| size <= MAX_ERROR_BYTES);
+    (response.status().is_client_error() || response.status().is_server_error()) && json && small
+}
+
+// Adds `request_id` to an `{"error": ...}` body.
+async fn with_id(response: Response, id: &str) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, MAX_ERROR_BYTES as usize).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let bytes = match serde_json::from_slice(&bytes) {
+        Ok(serde_json::Value::Object(mut error)) if error.contains_key("error") => {
+            error.insert("request_id".to_string(), id.into());
+            parts.headers.remove(header::CONTENT_LENGTH);
+            serde_json::to_vec(&error)
+                .expect("JSON values serialize")
+                .into()
+        }
+        _ => bytes,
+    };
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_short_plain_ids_are_kept() {
+        assert!(usable("3f2a9c1e-client.42"));
+        assert!(!usable(""));
+        assert!(!usable(&"a".repeat(65)));
+        assert!(!usable("two words"));
+        assert!(!usable("line\nbreak"));
+        let id = random_id();
+        assert_eq!(id.len(), 16);
+        assert!(usable(&id));
+    }
+}
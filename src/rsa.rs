@@ -0,0 +1,170 @@
+// RSA signature checks, PKCS #1 v1.5 with SHA-256 (RFC 8017), as JWTs signed
+// with RS256 need. Checking only takes the public key, whose exponent is small,
+// so plain schoolbook arithmetic on 32-bit limbs is quick enough: a check is a
+// few dozen multiplications modulo a 2048- or 4096-bit number.
+
+use crate::digest;
+use std::cmp::Ordering;
+
+// The DER encoding of SHA-256's DigestInfo, which comes before the hash.
+const SHA256_PREFIX: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+/// Whether `signature` is `message` signed with SHA-256 by the key whose
+/// modulus and exponent are `modulus` and `exponent`, as big-endian bytes.
+pub fn verify_sha256(modulus: &[u8], exponent: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let n = Uint::from_be_bytes(modulus);
+    let length = n.bits().div_ceil(8);
+    // Keys under 2048 bits are too weak to trust.
+    if length < 256 || signature.len() != length {
+        return false;
+    }
+    let s = Uint::from_be_bytes(signature);
+    if s.cmp(&n) != Ordering::Less {
+        return false;
+    }
+    let encoded = s
+        .pow_mod(&Uint::from_be_bytes(exponent), &n)
+        .to_be_bytes(length);
+
+    let mut expected = vec![0x00, 0x01];
+    expected.resize(length - SHA256_PREFIX.len() - 32 - 1, 0xff);
+    expected.push(0x00);
+    expected.extend(SHA256_PREFIX);
+    expected.extend(digest::sha256(message));
+    encoded == expected
+}
+
+// An unsigned number as 32-bit limbs, least significant first, with no zero
+// limbs on top.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Uint(Vec<u32>);
+
+impl Uint {
+    fn from_be_bytes(bytes: &[u8]) -> Uint {
+        let limbs = bytes
+            .rchunks(4)
+            .map(|chunk| chunk.iter().fold(0, |limb, &byte| limb << 8 | byte as u32))
+            .collect();
+        Uint(limbs).trimmed()
+    }
+
+    // `length` bytes, big-endian, with leading zeros; the number has to fit.
+    fn to_be_bytes(&self, length: usize) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.0.iter().flat_map(|limb| limb.to_le_bytes()).collect();
+        bytes.resize(length.max(bytes.len()), 0);
+        bytes.truncate(length);
+        bytes.reverse();
+        bytes
+    }
+
+    fn trimmed(mut self) -> Uint {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        self
+    }
+
+    fn bits(&self) -> usize {
+        match self.0.last() {
+            Some(top) => self.0.len() * 32 - top.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        self.0
+            .get(i / 32)
+            .is_some_and(|limb| limb >> (i % 32) & 1 == 1)
+    }
+
+    fn cmp(&self, other: &Uint) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+
+    fn mul(&self, other: &Uint) -> Uint {
+        let mut product = vec![0u32; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.0.iter().enumerate() {
+                let sum = product[i + j] as u64 + a as u64 * b as u64 + carry;
+                product[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            product[i + other.0.len()] = carry as u32;
+        }
+        Uint(product).trimmed()
+    }
+
+    // The remainder by `modulus`, a bit at a time.
+    fn rem(&self, modulus: &Uint) -> Uint {
+        let mut remainder = Uint(Vec::with_capacity(modulus.0.len() + 1));
+        for i in (0..self.bits()).rev() {
+            remainder.shift_in(self.bit(i));
+            if remainder.cmp(modulus) != Ordering::Less {
+                remainder.sub(modulus);
+            }
+        }
+        remainder
+    }
+
+    // Doubles the number and adds `bit`.
+    fn shift_in(&mut self, bit: bool) {
+        let mut carry = bit as u32;
+        for limb in &mut self.0 {
+            let top = *limb >> 31;
+            *limb = *limb << 1 | carry;
+            carry = top;
+        }
+        if carry > 0 {
+            self.0.push(carry);
+        }
+    }
+
+    // Takes `other` away, which is no larger.
+    fn sub(&mut self, other: &Uint) {
+        let mut borrow = 0i64;
+        for (i, limb) in self.0.iter_mut().enumerate() {
+            let difference = *limb as i64 - *other.0.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = (difference < 0) as i64;
+            *limb = difference.rem_euclid(1 << 32) as u32;
+        }
+        let trimmed = std::mem::take(self).trimmed();
+        *self = trimmed;
+    }
+
+    fn pow_mod(&self, exponent: &Uint, modulus: &Uint) -> Uint {
+        let base = self.rem(modulus);
+        let mut result = Uint(vec![1]).rem(modulus);
+        for i in (0..exponent.bits()).rev() {
+            result = result.mul(&result).rem(modulus);
+            if exponent.bit(i) {
+                result = result.mul(&base).rem(modulus);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_matches_small_numbers() {
+        let n = |value: u64| Uint::from_be_bytes(&value.to_be_bytes());
+        assert_eq!(
+            n(0x1_0000_0001).mul(&n(0xffff_ffff)),
+            n(0xffff_ffff_ffff_ffff)
+        );
+        assert_eq!(n(1_000_000_007).rem(&n(97)), n(1_000_000_007 % 97));
+        // 4^13 mod 497, the textbook example.
+        assert_eq!(n(4).pow_mod(&n(13), &n(497)), n(445));
+        assert_eq!(n(0x0102).to_be_bytes(4), [0, 0, 1, 2]);
+    }
+}
@@ -0,0 +1,62 @@
+// Saved searches: search definitions stored under short random IDs, so a query
+// like "Pinkpop 2010-2014 artists containing 'kings'" can be bookmarked and shared.
+//
+// Definitions are kept in memory only and are lost when the server restarts.
+
+use crate::SearchDefinition;
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+// Eight base-62 characters leave room for far more IDs than we ever store.
+const ID_LENGTH: usize = 8;
+const ID_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Returned by `SavedSearches::insert` once the store holds its maximum.
+#[derive(Debug)]
+pub struct StoreFull;
+
+#[derive(Debug)]
+pub(crate) struct SavedSearches {
+    // Keeps an anonymous endpoint from growing memory without bound.
+    capacity: usize,
+    entries: RwLock<HashMap<String, SearchDefinition>>,
+}
+
+impl SavedSearches {
+    pub fn new(capacity: usize) -> Self {
+        SavedSearches {
+            capacity,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Stores `definition` and returns its new ID.
+    pub fn insert(&self, definition: SearchDefinition) -> Result<String, StoreFull> {
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() >= self.capacity {
+            return Err(StoreFull);
+        }
+
+        // Collisions are vanishingly rare, but never overwrite someone's bookmark.
+        let id = loop {
+            let id = random_id();
+            if !entries.contains_key(&id) {
+                break id;
+            }
+        };
+        entries.insert(id.clone(), definition);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<SearchDefinition> {
+        self.entries.read().unwrap().get(id).cloned()
+    }
+}
+
+fn random_id() -> String {
+    let mut rng = rand::rng();
+    (0..ID_LENGTH)
+        .map(|_| ID_ALPHABET[rng.random_range(0..ID_ALPHABET.len())] as char)
+        .collect()
+}
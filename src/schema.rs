@@ -0,0 +1,186 @@
+// Versions of the on-disk data format, for files written before the current one.
+// A file says which version it follows in `schema_version`; files from before
+// there was such a field are version 1.
+//
+//   1. Each edition lists its artists by name: `"artists": ["Muse", "Editors"]`.
+//   2. Each artist is an object, so a performance can carry more than a name:
+//      `"artists": [{ "name": "Muse" }, { "name": "Editors" }]`.
+//
+// Every older version has its own structs here and converts into the next one,
+// so a version 1 file still reports mistakes with their line and column.
+
+use crate::data::{Act, BandData, Festival, FestivalYear};
+use serde::Deserialize;
+
+/// The version written, and the newest one read.
+pub const CURRENT: u64 = 2;
+
+#[derive(Deserialize)]
+struct Header {
+    schema_version: Option<u64>,
+}
+
+/// Reads a file of any supported version as the current one.
+pub fn read(bytes: &[u8]) -> Result<BandData, String> {
+    let header = match leading_version(bytes) {
+        Some(version) => Header {
+            schema_version: Some(version),
+        },
+        None => serde_json::from_slice(bytes).map_err(|e| e.to_string())?,
+    };
+    match version(header)? {
+        1 => Ok(serde_json::from_slice::<v1::BandData>(bytes)
+            .map_err(|e| e.to_string())?
+            .into()),
+        _ => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+    }
+}
+
+/// Like `read`, for a file in another format (YAML, TOML) parsed into the same
+/// structure.
+pub fn from_value(value: serde_json::Value) -> Result<BandData, String> {
+    let header = Header::deserialize(&value).map_err(|e| e.to_string())?;
+    match version(header)? {
+        1 => Ok(v1::BandData::deserialize(value)
+            .map_err(|e| e.to_string())?
+            .into()),
+        _ => BandData::deserialize(value).map_err(|e| e.to_string()),
+    }
+}
+
+// The version of a file that starts with it, as `{"schema_version": 2, ...`, the
+// way this server writes them, so a large file is only gone through once;
+// `None` when it's further in or missing, and the file has to be looked
+// through for it first.
+fn leading_version(bytes: &[u8]) -> Option<u64> {
+    let rest = bytes
+        .trim_ascii_start()
+        .strip_prefix(b"{")?
+        .trim_ascii_start();
+    let rest = rest.strip_prefix(b"\"schema_version\"")?.trim_ascii_start();
+    let rest = rest.strip_prefix(b":")?.trim_ascii_start();
+    let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+    let next = rest[digits..].trim_ascii_start().first()?;
+    if digits == 0 || !matches!(next, b',' | b'}') {
+        return None;
+    }
+    std::str::from_utf8(&rest[..digits]).ok()?.parse().ok()
+}
+
+fn version(header: Header) -> Result<u64, String> {
+    match header.schema_version.unwrap_or(1) {
+        version @ 1..=CURRENT => Ok(version),
+        version => Err(format!(
+            "schema_version {} isn't one this server understands (1 to {}){}",
+            version,
+            CURRENT,
+            if version > CURRENT {
+                "; the server may need upgrading"
+            } else {
+                ""
+            }
+        )),
+    }
+}
+
+mod v1 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct BandData {
+        pub festivals: Vec<Festival>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Festival {
+        pub name: String,
+        pub years: Vec<FestivalYear>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct FestivalYear {
+        pub year: u16,
+        pub artists: Vec<String>,
+    }
+}
+
+impl From<v1::BandData> for BandData {
+    fn from(old: v1::BandData) -> Self {
+        BandData {
+            festivals: old
+                .festivals
+                .into_iter()
+                .map(|festival| Festival {
+                    name: festival.name,
+                    years: festival
+                        .years
+                        .into_iter()
+                        .map(|year| FestivalYear {
+                            year: year.year,
+                            artists: year.artists.into_iter().map(Act::named).collect(),
+                            ..FestivalYear::default()
+                        })
+                        .collect(),
+                    ..Festival::default()
+                })
+                .collect(),
+            ..BandData::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_version_the_same() {
+        let v1 = r#"{ "festivals": [{ "name": "Pinkpop", "years": [
+            { "year": 2008, "artists": ["Muse", "Editors"] }
+        ] }] }"#;
+        let v2 = r#"{ "schema_version": 2, "festivals": [{ "name": "Pinkpop", "years": [
+            { "year": 2008, "artists": [{ "name": "Muse" }, { "name": "Editors" }] }
+        ] }] }"#;
+
+        for text in [v1, v2] {
+            let data = read(text.as_bytes()).unwrap();
+            let value: serde_json::Value = serde_json::from_str(text).unwrap();
+            assert_eq!(
+                serde_json::to_value(from_value(value).unwrap()).unwrap(),
+                serde_json::to_value(&data).unwrap()
+            );
+            assert_eq!(data.schema_version, CURRENT);
+            let names: Vec<&str> = data.festivals[0].years[0]
+                .artists
+                .iter()
+                .map(|act| act.name.as_str())
+                .collect();
+            assert_eq!(names, ["Muse", "Editors"]);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let error = read(br#"{ "schema_version": 9, "festivals": [] }"#).unwrap_err();
+        assert_eq!(
+            error,
+            "schema_version 9 isn't one this server understands (1 to 2); the server may need upgrading"
+        );
+        assert!(read(br#"{ "schema_version": 0, "festivals": [] }"#).is_err());
+        assert!(from_value(serde_json::json!({ "schema_version": 3, "festivals": [] })).is_err());
+        // Only a plain number straight after the opening brace is taken as it is.
+        assert_eq!(leading_version(b" {\n  \"schema_version\" : 2,"), Some(2));
+        assert_eq!(leading_version(br#"{"schema_version":2.5}"#), None);
+        assert_eq!(
+            leading_version(br#"{"festivals":[],"schema_version":2}"#),
+            None
+        );
+        // A version 2 artist in a file that doesn't say it's version 2.
+        assert!(read(
+            br#"{ "festivals": [{ "name": "Pinkpop", "years": [
+            { "year": 2008, "artists": [{ "name": "Muse" }] }
+        ] }] }"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,1064 @@
+// Matching logic behind the `/api/search` endpoint.
+
+use crate::artists::{Artist, Artists};
+use crate::filter::{Catalog, Filters};
+use crate::layout::{Performance, Performances};
+use crate::pattern::Regex;
+use crate::phonetic;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use trigram::TrigramIndex;
+
+mod trigram;
+
+// --- Normalization ---
+
+/// Folds a name (or query) into the key used for all matching: lowercase, accents
+/// stripped, punctuation removed or turned into spaces, and whitespace collapsed.
+/// "Sigur Rós" and "sigur ros" both become "sigur ros"; "R.E.M." becomes "rem".
+pub fn normalize(text: &str) -> String {
+    normalize_mapped(text).0
+}
+
+/// Like `normalize`, but also returns, for every char of the key, the index of the
+/// char in `text` it came from, so matches can be mapped back onto the display name.
+fn normalize_mapped(text: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut sources = Vec::with_capacity(text.len());
+    let mut pending_space = false;
+
+    for (index, original) in text.chars().enumerate() {
+        for c in original.to_lowercase() {
+            match c {
+                // Combining marks left over from decomposed input (e.g. "o" + U+0301).
+                '\u{0300}'..='\u{036f}' => {}
+                // Joining punctuation disappears so "Guns N' Roses" matches "guns n roses".
+                '\'' | '’' | '`' | '´' | '.' => {}
+                c if c.is_alphanumeric() => {
+                    if pending_space && !folded.is_empty() {
+                        folded.push(' ');
+                        sources.push(index);
+                    }
+                    pending_space = false;
+                    match fold_char(c) {
+                        Some(replacement) => {
+                            folded.push_str(replacement);
+                            sources.extend(replacement.chars().map(|_| index));
+                        }
+                        None => {
+                            folded.push(c);
+                            sources.push(index);
+                        }
+                    }
+                }
+                // Everything else separates words, collapsing into a single space.
+                _ => pending_space = true,
+            }
+        }
+    }
+
+    (folded, sources)
+}
+
+/// ASCII replacements for the accented Latin letters that show up in lineups.
+fn fold_char(c: char) -> Option<&'static str> {
+    let replacement = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' | 'ª' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' | 'º' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(replacement)
+}
+
+// --- Fuzzy Matching ---
+
+/// Upper bound for the `fuzziness` parameter; beyond this nearly every name matches.
+pub const MAX_FUZZINESS: usize = 3;
+
+/// Picks an edit-distance budget from the query length when the caller doesn't pass one.
+/// Short queries get no tolerance, otherwise "ka" would match half the lineup.
+fn auto_fuzziness(query: &str) -> usize {
+    match query.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// Plain Levenshtein distance between two whole strings.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, x) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(x != y);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+/// Returns the smallest edit distance between `query` and any substring of `text`.
+fn substring_distance(query: &[char], text: &[char]) -> usize {
+    // Sellers' variant of Levenshtein: a match may start anywhere in `text`, so the
+    // first row is all zeros and the answer is the minimum over the last row.
+    let mut prev = vec![0; text.len() + 1];
+    let mut curr = vec![0; text.len() + 1];
+
+    for (i, q) in query.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, t) in text.iter().enumerate() {
+            let substitution = prev[j] + usize::from(q != t);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev.into_iter().min().unwrap_or(0)
+}
+
+// --- Query Parsing ---
+
+/// One normalized phrase from a query, with the edit budget it is matched under.
+#[derive(Debug)]
+struct Term {
+    key: Vec<char>,
+    fuzziness: usize,
+}
+
+/// A fuzzy-mode query broken into its boolean structure: every group in `required`
+/// must have at least one matching term, no `excluded` term may match, and the
+/// performance has to pass the `filters` recognised in the query text.
+#[derive(Debug, Default)]
+pub struct ParsedQuery {
+    required: Vec<Vec<Term>>,
+    excluded: Vec<Vec<char>>,
+    pub filters: Filters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Occur {
+    Must,
+    MustNot,
+}
+
+impl ParsedQuery {
+    /// Parses `AND`, `OR` and `NOT` (upper-case only, so "Florence and the Machine"
+    /// stays literal) plus `+term` / `-term` prefixes. Operators split the query into
+    /// phrases; words between them form one phrase, so `kaiser chiefs OR editors`
+    /// means "kaiser chiefs" or "editors", and `king -kings` means "king" but not
+    /// "kings". Double quotes keep operator words literal. `OR` binds tighter than
+    /// the implicit `AND`. Every term uses `fuzziness`, or a budget based on its own
+    /// length when that's `None`. Exclusions always match exactly.
+    ///
+    /// Bare words naming a festival in `catalog` ("lowlands") or one of its years
+    /// ("2011", "2010-2014") become filters instead of name terms, so
+    /// `lowlands 2011` narrows to that edition.
+    pub fn parse(query: &str, fuzziness: Option<usize>, catalog: &Catalog) -> Self {
+        let mut parsed = ParsedQuery::default();
+        let mut filters = Filters::default();
+        let mut phrase: Vec<&str> = Vec::new();
+        let mut occur = Occur::Must;
+        let mut join_previous = false;
+
+        let mut flush = |phrase: &mut Vec<&str>, occur: &mut Occur, join: &mut bool| {
+            let key = normalize(&phrase.join(" "));
+            phrase.clear();
+            if !key.is_empty() {
+                match *occur {
+                    Occur::MustNot => parsed.excluded.push(key.chars().collect()),
+                    Occur::Must => {
+                        let term = Term {
+                            fuzziness: fuzziness
+                                .unwrap_or_else(|| auto_fuzziness(&key))
+                                .min(MAX_FUZZINESS),
+                            key: key.chars().collect(),
+                        };
+                        match parsed.required.last_mut() {
+                            Some(group) if *join => group.push(term),
+                            _ => parsed.required.push(vec![term]),
+                        }
+                    }
+                }
+            }
+            *occur = Occur::Must;
+            *join = false;
+        };
+
+        let tokens = tokenize(query);
+        let mut i = 0;
+        while i < tokens.len() {
+            let (token, quoted) = tokens[i];
+            i += 1;
+            if quoted {
+                phrase.push(token);
+                continue;
+            }
+            if occur == Occur::Must {
+                if let Some((festival, words)) = match_festival(&tokens[i - 1..], catalog) {
+                    flush(&mut phrase, &mut occur, &mut join_previous);
+                    filters.festivals.push(festival.to_string());
+                    i += words - 1;
+                    continue;
+                }
+                if let Some((from, to)) = catalog.parse_years(token) {
+                    flush(&mut phrase, &mut occur, &mut join_previous);
+                    if from == to {
+                        filters.years.push(from);
+                    } else {
+                        filters.year_from = Some(from);
+                        filters.year_to = Some(to);
+                    }
+                    continue;
+                }
+            }
+            match token {
+                "AND" => flush(&mut phrase, &mut occur, &mut join_previous),
+                "OR" => {
+                    flush(&mut phrase, &mut occur, &mut join_previous);
+                    join_previous = true;
+                }
+                "NOT" => {
+                    flush(&mut phrase, &mut occur, &mut join_previous);
+                    occur = Occur::MustNot;
+                }
+                _ if token.len() > 1 && (token.starts_with('-') || token.starts_with('+')) => {
+                    flush(&mut phrase, &mut occur, &mut join_previous);
+                    if token.starts_with('-') {
+                        occur = Occur::MustNot;
+                    }
+                    phrase.push(&token[1..]);
+                }
+                _ => phrase.push(token),
+            }
+        }
+        flush(&mut phrase, &mut occur, &mut join_previous);
+
+        parsed.filters = filters;
+        parsed
+    }
+
+    /// True when the query has no usable terms or filters at all.
+    pub fn is_empty(&self) -> bool {
+        self.required.is_empty() && self.excluded.is_empty() && self.filters.is_empty()
+    }
+
+    /// Finds where the best term of each required group matched inside `name`.
+    fn highlights(&self, name: &str) -> Vec<Span> {
+        let (key, sources) = normalize_mapped(name);
+        let key: Vec<char> = key.chars().collect();
+
+        let spans = self
+            .required
+            .iter()
+            .filter_map(|group| {
+                group
+                    .iter()
+                    .map(|term| (substring_span(&term.key, &key), term.fuzziness))
+                    .filter(|((distance, _, _), fuzziness)| distance <= fuzziness)
+                    .map(|(span, _)| span)
+                    .min()
+            })
+            .filter_map(|(_, start, end)| source_span(&sources, start, end))
+            .collect();
+        merge_spans(spans)
+    }
+
+    /// Returns the total edit distance if `search_key` satisfies the query.
+    fn distance(&self, search_key: &str) -> Option<usize> {
+        let name: Vec<char> = search_key.chars().collect();
+
+        if self
+            .excluded
+            .iter()
+            .any(|term| substring_distance(term, &name) == 0)
+        {
+            return None;
+        }
+
+        self.required.iter().try_fold(0, |total, group| {
+            group
+                .iter()
+                .filter_map(|term| {
+                    let distance = substring_distance(&term.key, &name);
+                    (distance <= term.fuzziness).then_some(distance)
+                })
+                .min()
+                .map(|best| total + best)
+        })
+    }
+}
+
+/// Checks whether the unquoted tokens at the start of `tokens` spell out one of the
+/// catalog's festival names, returning it and how many tokens it spans.
+fn match_festival<'c>(tokens: &[(&str, bool)], catalog: &'c Catalog) -> Option<(&'c str, usize)> {
+    catalog.festivals.iter().find_map(|festival| {
+        let key = normalize(festival);
+        let words: Vec<&str> = key.split(' ').collect();
+        let matched = words.len() <= tokens.len()
+            && words
+                .iter()
+                .zip(tokens)
+                .all(|(word, (token, quoted))| !quoted && normalize(token) == *word);
+        matched.then_some((festival.as_str(), words.len()))
+    })
+}
+
+/// Splits on whitespace, keeping `"double quoted"` runs together. Yields each piece
+/// and whether it was quoted.
+fn tokenize(query: &str) -> Vec<(&str, bool)> {
+    let mut tokens = Vec::new();
+    let mut rest = query.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            tokens.push((&quoted[..end], true));
+            rest = quoted.get(end + 1..).unwrap_or("");
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push((&rest[..end], false));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    tokens
+}
+
+// --- Searching ---
+
+/// A single search hit, borrowed from the shared state.
+pub struct Match<'a> {
+    pub performance: Performance<'a>,
+    pub distance: usize,
+    pub highlights: Vec<Span>,
+}
+
+// --- Highlighting ---
+
+/// A matched stretch of an artist name, as `[start, end)` char offsets into the
+/// displayed `name` (not the normalized key).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Like `substring_distance`, but also reports which `[start, end)` stretch of
+/// `text` the best match covers. Only run for hits, so the scan stays cheap.
+fn substring_span(query: &[char], text: &[char]) -> (usize, usize, usize) {
+    // Each cell carries (cost, start of the alignment that reached it).
+    let mut prev: Vec<(usize, usize)> = (0..=text.len()).map(|j| (0, j)).collect();
+    let mut curr = vec![(0, 0); text.len() + 1];
+
+    for (i, q) in query.iter().enumerate() {
+        curr[0] = (i + 1, 0);
+        for (j, t) in text.iter().enumerate() {
+            let diagonal = (prev[j].0 + usize::from(q != t), prev[j].1);
+            let up = (prev[j + 1].0 + 1, prev[j + 1].1);
+            let left = (curr[j].0 + 1, curr[j].1);
+            // Prefer the diagonal on ties, then the later start (shorter span).
+            curr[j + 1] = [up, left].into_iter().fold(diagonal, |best, cell| {
+                if cell.0 < best.0 || (cell.0 == best.0 && cell.1 > best.1) {
+                    cell
+                } else {
+                    best
+                }
+            });
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    let (end, &(distance, start)) = prev
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, (cost, _))| *cost)
+        .unwrap_or((0, &(0, 0)));
+    (distance, start, end)
+}
+
+/// Maps `[start, end)` in a normalized key back onto the display name.
+fn source_span(sources: &[usize], start: usize, end: usize) -> Option<Span> {
+    (start < end).then(|| Span {
+        start: sources[start],
+        end: sources[end - 1] + 1,
+    })
+}
+
+/// Sorts spans and merges the ones that overlap or touch.
+fn merge_spans(mut spans: Vec<Span>) -> Vec<Span> {
+    spans.sort();
+    let mut merged: Vec<Span> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// Converts a byte range from the regex engine into char offsets.
+fn char_span(text: &str, start: usize, end: usize) -> Span {
+    let start_chars = text[..start].chars().count();
+    Span {
+        start: start_chars,
+        end: start_chars + text[start..end].chars().count(),
+    }
+}
+
+/// Yields each word of a normalized key with its `[start, end)` char offsets.
+fn key_words(key: &[char]) -> Vec<(String, usize, usize)> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    for (i, c) in key.iter().chain(std::iter::once(&' ')).enumerate() {
+        if *c == ' ' {
+            if i > start {
+                words.push((key[start..i].iter().collect(), start, i));
+            }
+            start = i + 1;
+        }
+    }
+    words
+}
+
+/// Finds all performances passing `filters` whose search key satisfies `query`,
+/// ordered by match quality (fewest total edits first), then by name and year.
+/// Only the names `index` reports as candidates are checked.
+pub fn search<'a>(
+    performances: &'a Performances,
+    index: &SearchIndex,
+    query: &ParsedQuery,
+    filters: &Filters,
+) -> Vec<Match<'a>> {
+    let names: Vec<&Artist> = match index.candidates(query) {
+        Some(ids) => ids.iter().map(|&id| index.names.get(id)).collect(),
+        None => index.names.iter().collect(),
+    };
+
+    let mut matches = Vec::new();
+    for name in names {
+        let Some(distance) = query.distance(&name.key) else {
+            continue;
+        };
+        for &i in &name.performances {
+            let performance = performances.get(i);
+            if filters.matches(performance) && query.filters.matches(performance) {
+                matches.push(Match {
+                    performance,
+                    distance,
+                    highlights: query.highlights(performance.name()),
+                });
+            }
+        }
+    }
+
+    sort_matches(&mut matches);
+    matches
+}
+
+fn sort_matches(matches: &mut [Match]) {
+    matches.sort_by(|a, b| {
+        a.distance
+            .cmp(&b.distance)
+            .then_with(|| a.performance.name().cmp(b.performance.name()))
+            .then_with(|| a.performance.year().cmp(&b.performance.year()))
+    });
+}
+
+// --- Phonetic Matching ---
+
+/// How far apart two phonetic codes sound: equal codes are a perfect match, and a
+/// code that extends the other ("FL" vs "FLT") costs the number of extra sounds.
+fn phonetic_distance(query: &str, name: &str) -> Option<usize> {
+    let (shorter, longer) = if query.len() <= name.len() {
+        (query, name)
+    } else {
+        (name, query)
+    };
+    if shorter == longer {
+        Some(0)
+    } else if shorter.len() >= 2 && longer.starts_with(shorter) {
+        Some(longer.len() - shorter.len())
+    } else {
+        None
+    }
+}
+
+/// Highlights every word of `name` that sounds like one of the query words.
+fn phonetic_highlights(query_codes: &[String], name: &str) -> Vec<Span> {
+    let (key, sources) = normalize_mapped(name);
+    let key: Vec<char> = key.chars().collect();
+
+    let spans = key_words(&key)
+        .into_iter()
+        .filter(|(word, _, _)| {
+            let code = phonetic::metaphone(word);
+            query_codes
+                .iter()
+                .any(|query_code| phonetic_distance(query_code, &code).is_some())
+        })
+        .filter_map(|(_, start, end)| source_span(&sources, start, end))
+        .collect();
+    merge_spans(spans)
+}
+
+/// Finds all of `performances` where every word of `query` sounds like some
+/// word of the artist name, using the phonetic codes precomputed at load.
+pub fn phonetic_search<'a>(
+    performances: impl Iterator<Item = Performance<'a>>,
+    query: &str,
+) -> Vec<Match<'a>> {
+    let query_codes = phonetic::codes(&normalize(query));
+    if query_codes.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches: Vec<Match> = performances
+        .filter_map(|performance| {
+            let distance = query_codes.iter().try_fold(0, |total, query_code| {
+                performance
+                    .phonetic_codes()
+                    .iter()
+                    .filter_map(|name_code| phonetic_distance(query_code, name_code))
+                    .min()
+                    .map(|best| total + best)
+            })?;
+            Some(Match {
+                performance,
+                distance,
+                highlights: phonetic_highlights(&query_codes, performance.name()),
+            })
+        })
+        .collect();
+
+    sort_matches(&mut matches);
+    matches
+}
+
+// --- Regex Matching ---
+
+/// Wall-clock budget for one regex search across the whole dataset.
+pub const REGEX_TIME_LIMIT: Duration = Duration::from_millis(250);
+
+/// Returned when a regex search runs past `REGEX_TIME_LIMIT`.
+#[derive(Debug)]
+pub struct TimedOut;
+
+/// Finds all of `performances` whose artist name (as displayed, not normalized)
+/// matches `regex`, giving up once `REGEX_TIME_LIMIT` has passed.
+pub fn regex_search<'a>(
+    performances: impl Iterator<Item = Performance<'a>>,
+    regex: &Regex,
+) -> Result<Vec<Match<'a>>, TimedOut> {
+    let deadline = Instant::now() + REGEX_TIME_LIMIT;
+    let mut matches = Vec::new();
+
+    for performance in performances {
+        // Checked between names, as matching one can't be interrupted. Its cost
+        // grows with the program's length times the name's, so a very long name
+        // can overrun the limit by that much.
+        if Instant::now() > deadline {
+            return Err(TimedOut);
+        }
+        if let Some((start, end)) = regex.find(performance.name()) {
+            // Empty matches (e.g. `^`) have nothing to highlight.
+            let span = char_span(performance.name(), start, end);
+            matches.push(Match {
+                performance,
+                distance: 0,
+                highlights: (span.start < span.end)
+                    .then_some(span)
+                    .into_iter()
+                    .collect(),
+            });
+        }
+    }
+
+    sort_matches(&mut matches);
+    Ok(matches)
+}
+
+// --- Search Index ---
+
+/// A vocabulary entry: a normalized word (or joined word pair) and the names using it.
+#[derive(Debug)]
+struct Token {
+    text: String,
+    chars: Vec<char>,
+    names: Vec<u32>,
+}
+
+/// The number of entries in each part of a `SearchIndex`.
+#[derive(Debug, Serialize)]
+pub struct IndexSizes {
+    pub names: usize,
+    pub words: usize,
+    // Adjacent word pairs written together.
+    pub joined_words: usize,
+    pub trigrams: usize,
+    // An estimate, from the lengths of the index's strings and lists.
+    pub bytes: usize,
+}
+
+/// Built once when the data loads, so searches verify a handful of candidate names
+/// instead of scanning every performance, and autocomplete can binary search.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    // The snapshot's artists; the ids in `Token::names` index into this.
+    names: Arc<Artists>,
+    // Every word of every name, sorted by text.
+    tokens: Vec<Token>,
+    // Adjacent word pairs written together ("arcticmonkeys"), so a query that
+    // drops a space between words still finds its candidates.
+    joined: Vec<Token>,
+    // Trigrams of each name key, for substring queries of any shape.
+    trigrams: TrigramIndex,
+}
+
+impl SearchIndex {
+    pub fn new(names: Arc<Artists>) -> Self {
+        let mut tokens: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        let mut joined: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        for (id, name) in names.iter().enumerate() {
+            let id = id as u32;
+            let words: Vec<&str> = name.key.split(' ').filter(|w| !w.is_empty()).collect();
+            for word in &words {
+                tokens.entry(word.to_string()).or_default().push(id);
+            }
+            for pair in words.windows(2) {
+                joined.entry(pair.concat()).or_default().push(id);
+            }
+        }
+
+        let into_tokens = |map: BTreeMap<String, Vec<u32>>| -> Vec<Token> {
+            map.into_iter()
+                .map(|(text, mut names)| {
+                    // Ids arrive in order, so a word repeated within one name is adjacent.
+                    names.dedup();
+                    Token {
+                        chars: text.chars().collect(),
+                        text,
+                        names,
+                    }
+                })
+                .collect()
+        };
+
+        SearchIndex {
+            trigrams: TrigramIndex::new(names.iter().map(|n| n.key.as_str())),
+            names,
+            tokens: into_tokens(tokens),
+            joined: into_tokens(joined),
+        }
+    }
+
+    /// Ids of the names that could satisfy `query`, or `None` when the query has no
+    /// required terms to narrow by (only exclusions or filters).
+    fn candidates(&self, query: &ParsedQuery) -> Option<Vec<u32>> {
+        query.required.iter().fold(None, |result, group| {
+            let mut ids: Vec<u32> = group
+                .iter()
+                .flat_map(|term| self.term_candidates(term))
+                .collect();
+            ids.sort_unstable();
+            ids.dedup();
+            Some(match result {
+                Some(previous) => intersect(&previous, &ids),
+                None => ids,
+            })
+        })
+    }
+
+    /// Names that could contain `term` within its edit budget. The full phrase is
+    /// verified against each candidate afterwards.
+    fn term_candidates(&self, term: &Term) -> Vec<u32> {
+        // Trigrams give an exact candidate set whenever the phrase is long enough
+        // for its budget; otherwise fall back to matching word by word against the
+        // vocabulary, which requires every word to sit within some token.
+        if let Some(ids) = self.trigrams.candidates(&term.key, term.fuzziness) {
+            return ids;
+        }
+
+        let words = term.key.split(|c| *c == ' ').filter(|w| !w.is_empty());
+        let per_word = words.map(|word| {
+            let mut ids: Vec<u32> = self
+                .tokens
+                .iter()
+                .chain(&self.joined)
+                .filter(|token| substring_distance(word, &token.chars) <= term.fuzziness)
+                .flat_map(|token| token.names.iter().copied())
+                .collect();
+            ids.sort_unstable();
+            ids.dedup();
+            ids
+        });
+        per_word
+            .reduce(|previous, ids| intersect(&previous, &ids))
+            .unwrap_or_default()
+    }
+
+    /// How many entries each part of the index has, and about how many bytes
+    /// it takes on the heap altogether.
+    pub fn sizes(&self) -> IndexSizes {
+        let names = self.names.bytes();
+        let words: usize = self
+            .tokens
+            .iter()
+            .chain(&self.joined)
+            .map(|t| {
+                size_of::<Token>()
+                    + t.text.capacity()
+                    + t.chars.capacity() * 4
+                    + t.names.capacity() * 4
+            })
+            .sum();
+        let (trigrams, trigram_bytes) = self.trigrams.size();
+        IndexSizes {
+            names: self.names.len(),
+            words: self.tokens.len(),
+            joined_words: self.joined.len(),
+            trigrams,
+            bytes: names + words + trigram_bytes,
+        }
+    }
+
+    /// Returns up to `limit` names starting with `query`, topped up with names that
+    /// have a later word starting with it ("chiefs" suggests "Kaiser Chiefs") and
+    /// then with names that merely contain it.
+    pub fn complete(&self, query: &str, limit: usize) -> Vec<&str> {
+        let query = normalize(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        // Prefix matches form one contiguous run in the sorted name list...
+        let sorted = self.names.as_slice();
+        let start = sorted.partition_point(|n| n.key.as_str() < query.as_str());
+        let mut names: Vec<&str> = sorted[start..]
+            .iter()
+            .take_while(|n| n.key.starts_with(&query))
+            .take(limit)
+            .map(|n| &*n.name)
+            .collect();
+
+        // ...and so do the words starting with it in the sorted vocabulary.
+        if names.len() < limit {
+            let start = self
+                .tokens
+                .partition_point(|t| t.text.as_str() < query.as_str());
+            let mut ids: Vec<u32> = self.tokens[start..]
+                .iter()
+                .take_while(|t| t.text.starts_with(&query))
+                .flat_map(|t| t.names.iter().copied())
+                .filter(|&id| !self.names.get(id).key.starts_with(&query))
+                .collect();
+            ids.sort_unstable();
+            ids.dedup();
+            names.extend(
+                ids.into_iter()
+                    .take(limit - names.len())
+                    .map(|id| &*self.names.get(id).name),
+            );
+        }
+
+        // Anything else merely containing the query comes last.
+        if names.len() < limit {
+            let query_chars: Vec<char> = query.chars().collect();
+            let candidates: Vec<&Artist> = match self.trigrams.candidates(&query_chars, 0) {
+                Some(ids) => ids.iter().map(|&id| self.names.get(id)).collect(),
+                None => self.names.iter().collect(),
+            };
+            let remaining = limit - names.len();
+            let contained: Vec<&str> = candidates
+                .into_iter()
+                .filter(|n| n.key.contains(&query) && !names.contains(&&*n.name))
+                .take(remaining)
+                .map(|n| &*n.name)
+                .collect();
+            names.extend(contained);
+        }
+        names
+    }
+
+    /// Returns up to `limit` names closest to `query` as a whole, for "did you mean"
+    /// hints. Names more than half the query length away aren't worth suggesting.
+    pub fn closest(&self, query: &str, limit: usize) -> Vec<&str> {
+        let query: Vec<char> = normalize(query).chars().collect();
+        let max_distance = query.len() / 2;
+        if max_distance == 0 {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<(usize, &str)> = self
+            .names
+            .iter()
+            .filter_map(|name| {
+                let key: Vec<char> = name.key.chars().collect();
+                let distance = edit_distance(&query, &key);
+                (distance <= max_distance).then_some((distance, &*name.name))
+            })
+            .collect();
+
+        candidates.sort();
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(_, name)| name)
+            .collect()
+    }
+}
+
+/// Intersects two sorted, deduplicated id lists.
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::new();
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Kind;
+    use crate::ArtistPerformance;
+
+    fn performance(name: &str, festival: &str, year: u16) -> ArtistPerformance {
+        let search_key = normalize(name);
+        ArtistPerformance {
+            id: crate::state::performance_id(festival, year, name),
+            name: name.into(),
+            festival: festival.into(),
+            year,
+            genres: Vec::new(),
+            country: None,
+            stage: None,
+            day: None,
+            start_time: None,
+            headliner: false,
+            cancelled: false,
+            phonetic_codes: phonetic::codes(&search_key),
+            search_key,
+        }
+    }
+
+    fn rows(lineup: &[ArtistPerformance]) -> Performances {
+        Performances::new(lineup.to_vec(), Kind::Rows)
+    }
+
+    fn index(performances: &Performances) -> SearchIndex {
+        SearchIndex::new(Arc::new(Artists::new(performances)))
+    }
+
+    fn lineup() -> Vec<ArtistPerformance> {
+        vec![
+            performance("Kaiser Chiefs", "Pinkpop", 2008),
+            performance("Sigur Rós", "Lowlands", 2008),
+            performance("Kings of Leon", "Pinkpop", 2011),
+            performance("King Gizzard & the Lizard Wizard", "Lowlands", 2017),
+            performance("Arctic Monkeys", "Lowlands", 2011),
+        ]
+    }
+
+    fn names<'a>(matches: &[Match<'a>]) -> Vec<&'a str> {
+        matches.iter().map(|m| m.performance.name()).collect()
+    }
+
+    fn run(
+        performances: &[ArtistPerformance],
+        query: &str,
+        fuzziness: Option<usize>,
+    ) -> Vec<String> {
+        let performances = rows(performances);
+        let index = index(&performances);
+        let catalog = Catalog::new(&performances);
+        let parsed = ParsedQuery::parse(query, fuzziness, &catalog);
+        let matches = search(&performances, &index, &parsed, &Filters::default());
+        names(&matches).into_iter().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn normalize_folds_accents_case_and_punctuation() {
+        assert_eq!(normalize("Sigur Rós"), "sigur ros");
+        assert_eq!(normalize("R.E.M."), "rem");
+        assert_eq!(
+            normalize("  King Gizzard & the Lizard Wizard "),
+            "king gizzard the lizard wizard"
+        );
+    }
+
+    #[test]
+    fn substring_matches_span_word_boundaries() {
+        let lineup = lineup();
+        assert_eq!(run(&lineup, "r chie", Some(0)), ["Kaiser Chiefs"]);
+        assert_eq!(run(&lineup, "c monk", Some(0)), ["Arctic Monkeys"]);
+    }
+
+    #[test]
+    fn fuzzy_matches_survive_the_index() {
+        let lineup = lineup();
+        assert_eq!(run(&lineup, "arcti monkeys", None), ["Arctic Monkeys"]);
+        assert_eq!(run(&lineup, "arcticmonkeys", Some(1)), ["Arctic Monkeys"]);
+        assert_eq!(run(&lineup, "sigur ros", Some(0)), ["Sigur Rós"]);
+    }
+
+    #[test]
+    fn boolean_operators_and_understood_filters() {
+        let lineup = lineup();
+        assert_eq!(
+            run(&lineup, "king -kings", Some(0)),
+            ["King Gizzard & the Lizard Wizard"]
+        );
+        assert_eq!(
+            run(&lineup, "kaiser OR sigur", Some(0)),
+            ["Kaiser Chiefs", "Sigur Rós"]
+        );
+        assert_eq!(run(&lineup, "lowlands 2011", None), ["Arctic Monkeys"]);
+    }
+
+    #[test]
+    fn artist_and_performance_detail_filters() {
+        let mut lineup = lineup();
+        lineup[0].genres = vec!["Indie".to_string()];
+        lineup[2].genres = vec!["rock".to_string(), "indie".to_string()];
+        let index = index(&rows(&lineup));
+        let parsed = ParsedQuery::parse("k", Some(0), &Catalog::new(&rows(&lineup)));
+        let filters = Filters {
+            genres: vec!["INDIE".to_string()],
+            ..Filters::default()
+        };
+        assert_eq!(
+            names(&search(&rows(&lineup), &index, &parsed, &filters)),
+            ["Kaiser Chiefs", "Kings of Leon"]
+        );
+        assert_eq!(
+            filters.canonical(),
+            Filters {
+                genres: vec!["indie".to_string()],
+                ..Filters::default()
+            }
+        );
+
+        lineup[0].stage = Some("Main".to_string());
+        lineup[0].day = Some("Saturday".to_string());
+        lineup[2].stage = Some("main".to_string());
+        lineup[2].country = Some("US".to_string());
+        let filters = Filters {
+            countries: vec!["us".to_string()],
+            ..Filters::default()
+        };
+        assert_eq!(
+            names(&search(&rows(&lineup), &index, &parsed, &filters)),
+            ["Kings of Leon"]
+        );
+        let filters = Filters {
+            stages: vec!["MAIN".to_string()],
+            days: vec!["saturday".to_string(), "sunday".to_string()],
+            ..Filters::default()
+        };
+        assert_eq!(
+            names(&search(&rows(&lineup), &index, &parsed, &filters)),
+            ["Kaiser Chiefs"]
+        );
+
+        lineup[2].headliner = true;
+        let filters = Filters {
+            headliners_only: true,
+            ..Filters::default()
+        };
+        assert_eq!(
+            names(&search(&rows(&lineup), &index, &parsed, &filters)),
+            ["Kings of Leon"]
+        );
+
+        lineup[0].cancelled = true;
+        let filters = Filters {
+            exclude_cancelled: true,
+            ..Filters::default()
+        };
+        assert!(
+            !names(&search(&rows(&lineup), &index, &parsed, &filters)).contains(&"Kaiser Chiefs")
+        );
+    }
+
+    #[test]
+    fn autocomplete_prefers_prefixes_then_words_then_substrings() {
+        let index = index(&rows(&lineup()));
+        assert_eq!(
+            index.complete("ki", 10),
+            ["King Gizzard & the Lizard Wizard", "Kings of Leon"]
+        );
+        assert_eq!(index.complete("monk", 10), ["Arctic Monkeys"]);
+        assert_eq!(
+            index.complete("izzard", 10),
+            ["King Gizzard & the Lizard Wizard"]
+        );
+    }
+
+    #[test]
+    fn catalog_narrows_filters_to_festival_and_year_positions() {
+        let catalog = Catalog::new(&rows(&lineup()));
+        let positions = |filters: Filters| catalog.positions(&filters);
+        assert_eq!(positions(Filters::default()), None);
+        let pinkpop = Filters {
+            festivals: vec!["PINKPOP".to_string(), "Pinkpop".to_string()],
+            ..Filters::default()
+        };
+        assert_eq!(positions(pinkpop), Some(vec![0, 2]));
+        let later = Filters {
+            year_from: Some(2011),
+            ..Filters::default()
+        };
+        assert_eq!(positions(later), Some(vec![2, 3, 4]));
+        let lowlands_2008 = Filters {
+            festivals: vec!["lowlands".to_string()],
+            years: vec![2008],
+            ..Filters::default()
+        };
+        assert_eq!(positions(lowlands_2008), Some(vec![0, 1]));
+        let backwards = Filters {
+            year_from: Some(2017),
+            year_to: Some(2008),
+            ..Filters::default()
+        };
+        assert_eq!(positions(backwards), Some(vec![]));
+    }
+}
@@ -0,0 +1,123 @@
+// Trigram index over normalized names for substring ("contains") queries.
+//
+// Every name key is broken into its overlapping three-character windows, and each
+// window maps to the ids of the keys containing it. A query that occurs in a key
+// exactly has all of its trigrams there too, and one that matches within `k` edits
+// still has at least `(m - 2) - 3k` of its `m - 2` trigrams there, because a single
+// edit can only disturb the three windows around it. Counting shared trigrams thus
+// yields a candidate set that never misses a real match.
+
+use std::collections::HashMap;
+
+type Trigram = [char; 3];
+
+#[derive(Debug, Default)]
+pub struct TrigramIndex {
+    // Sorted, deduplicated key ids per trigram.
+    postings: HashMap<Trigram, Vec<u32>>,
+}
+
+fn trigrams(text: &[char]) -> impl Iterator<Item = Trigram> + '_ {
+    text.windows(3).map(|w| [w[0], w[1], w[2]])
+}
+
+impl TrigramIndex {
+    /// Indexes `keys`; ids are their positions in iteration order.
+    pub fn new<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut postings: HashMap<Trigram, Vec<u32>> = HashMap::new();
+        for (id, key) in keys.into_iter().enumerate() {
+            let chars: Vec<char> = key.chars().collect();
+            for gram in trigrams(&chars) {
+                let ids = postings.entry(gram).or_default();
+                // Ids arrive in order, so a repeated trigram within one key is the last entry.
+                if ids.last() != Some(&(id as u32)) {
+                    ids.push(id as u32);
+                }
+            }
+        }
+        TrigramIndex { postings }
+    }
+
+    /// Ids of keys that could contain `query` within `edits` edits, in ascending
+    /// order. Returns `None` when the query is too short for trigrams to rule
+    /// anything out, in which case the caller has to scan.
+    pub fn candidates(&self, query: &[char], edits: usize) -> Option<Vec<u32>> {
+        let total = query.len().saturating_sub(2);
+        let required = total.checked_sub(3 * edits).filter(|&n| n > 0)?;
+
+        // Count, with multiplicity, how many of the query's trigrams each key has.
+        let mut counts: HashMap<u32, usize> = HashMap::new();
+        for gram in trigrams(query) {
+            for &id in self.postings.get(&gram).into_iter().flatten() {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+
+        let mut ids: Vec<u32> = counts
+            .into_iter()
+            .filter(|&(_, count)| count >= required)
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort_unstable();
+        Some(ids)
+    }
+
+    /// How many distinct trigrams there are, and about how many bytes the index
+    /// takes on the heap.
+    pub fn size(&self) -> (usize, usize) {
+        let table = self.postings.capacity() * (size_of::<(Trigram, Vec<u32>)>() + 1);
+        let ids: usize = self.postings.values().map(|ids| ids.capacity() * 4).sum();
+        (self.postings.len(), table + ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn index() -> TrigramIndex {
+        TrigramIndex::new(["kaiser chiefs", "editors", "arctic monkeys", "aaaa"])
+    }
+
+    #[test]
+    fn finds_exact_substrings_across_words() {
+        let index = index();
+        assert_eq!(index.candidates(&chars("r chie"), 0), Some(vec![0]));
+        assert_eq!(index.candidates(&chars("itor"), 0), Some(vec![1]));
+    }
+
+    #[test]
+    fn exact_lookup_excludes_keys_missing_a_trigram() {
+        let index = index();
+        assert_eq!(index.candidates(&chars("kaiser monkeys"), 0), Some(vec![]));
+        assert_eq!(index.candidates(&chars("zzz"), 0), Some(vec![]));
+    }
+
+    #[test]
+    fn tolerates_edits_within_budget() {
+        let index = index();
+        // One substitution ("c" -> "k") still leaves enough shared trigrams.
+        assert_eq!(index.candidates(&chars("arktic monkeys"), 1), Some(vec![2]));
+        // A missing letter counts as one edit too.
+        assert_eq!(index.candidates(&chars("arcti monkeys"), 1), Some(vec![2]));
+    }
+
+    #[test]
+    fn short_or_heavily_fuzzed_queries_need_a_scan() {
+        let index = index();
+        assert_eq!(index.candidates(&chars("ka"), 0), None);
+        // "editor" has four trigrams; two edits could disturb all of them.
+        assert_eq!(index.candidates(&chars("editor"), 2), None);
+    }
+
+    #[test]
+    fn repeated_trigrams_count_with_multiplicity() {
+        let index = index();
+        assert_eq!(index.candidates(&chars("aaaa"), 0), Some(vec![3]));
+        assert_eq!(index.candidates(&chars("aaa"), 0), Some(vec![3]));
+    }
+}
@@ -0,0 +1,223 @@
+// Cookie sessions, for features that remember something about each visitor
+// between requests, such as random picks that don't repeat. The cookie holds
+// only a random session ID and its signature, an HMAC-SHA256 with
+// `session.secret`, so it can't be guessed or forged; what the session holds
+// stays on the server, in the session store. Without a secret in the config, a
+// random one is made at startup, which ends every session on a restart; with
+// the memory store they'd end then anyway.
+//
+// A cookie is only sent once a handler puts something in the session, so
+// visitors who never use such a feature never get one. Each change keeps the
+// session for another `session.ttl`.
+
+use crate::cache::LruCache;
+use crate::{access, digest, CONFIG};
+use axum::extract::{FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+const COOKIE: &str = "bandje_session";
+
+// Used when the config doesn't give a secret.
+static RANDOM_SECRET: Lazy<String> = Lazy::new(|| digest::hex(&rand::rng().random::<[u8; 32]>()));
+
+static STORE: Lazy<Store> =
+    Lazy::new(|| Store::Memory(LruCache::new(CONFIG.sessions.max_sessions)));
+
+/// What one visitor's session holds, for handlers to read and change. Values
+/// are kept as JSON under keys the features choose.
+#[derive(Clone, Default)]
+pub struct Session {
+    inner: Arc<Mutex<Inner>>,
+}
+
+// The data is shared with the store until a handler changes it, so reading a
+// session doesn't copy what it holds.
+#[derive(Default)]
+struct Inner {
+    data: Arc<Map<String, Value>>,
+    changed: bool,
+}
+
+impl Session {
+    /// The value under `key`, if there's one of type `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let inner = self.inner.lock().unwrap();
+        T::deserialize(inner.data.get(key)?).ok()
+    }
+
+    /// Puts `value` under `key`, keeping the session.
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) {
+        let value = serde_json::to_value(value).expect("session values are plain data");
+        let mut inner = self.inner.lock().unwrap();
+        Arc::make_mut(&mut inner.data).insert(key.to_string(), value);
+        inner.changed = true;
+    }
+
+    // What to store, if a handler changed anything.
+    fn changes(&self) -> Option<Arc<Map<String, Value>>> {
+        let inner = self.inner.lock().unwrap();
+        inner.changed.then(|| Arc::clone(&inner.data))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Session {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Response> {
+        parts.extensions.get::<Session>().cloned().ok_or_else(|| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": "Sessions aren't set up for this route." })),
+            )
+                .into_response()
+        })
+    }
+}
+
+/// Middleware giving handlers the session the request's cookie names, or a new
+/// one, and storing it afterwards if it changed.
+pub async fn attach(mut request: Request, next: Next) -> Response {
+    let found = match cookie(request.headers()).and_then(verified) {
+        Some(id) => STORE.load(&id).await.map(|data| (id, data)),
+        None => None,
+    };
+    let (id, data) = found.unwrap_or_else(|| (new_id(), Arc::default()));
+    let session = Session {
+        inner: Arc::new(Mutex::new(Inner {
+            data,
+            changed: false,
+        })),
+    };
+    request.extensions_mut().insert(session.clone());
+    let mut response = next.run(request).await;
+
+    if let Some(data) = session.changes() {
+        STORE.save(&id, data).await;
+        let cookie = set_cookie(&format!("{}.{}", id, signature(&id)));
+        response.headers_mut().append(
+            header::SET_COOKIE,
+            HeaderValue::from_str(&cookie).expect("session cookies are visible ASCII"),
+        );
+    }
+    response
+}
+
+// Where sessions are kept between requests. Only in memory for now: a store
+// shared between instances, such as Redis, would be another variant.
+enum Store {
+    // Sessions by ID, with when they expire; the least recently used go first
+    // once `session.max_sessions` are kept.
+    Memory(LruCache<String, (Instant, Arc<Map<String, Value>>)>),
+}
+
+impl Store {
+    async fn load(&self, id: &str) -> Option<Arc<Map<String, Value>>> {
+        match self {
+            Store::Memory(sessions) => {
+                let (expires, data) = sessions.get(&id.to_string())?;
+                (Instant::now() < expires).then_some(data)
+            }
+        }
+    }
+
+    async fn save(&self, id: &str, data: Arc<Map<String, Value>>) {
+        match self {
+            Store::Memory(sessions) => {
+                let expires = Instant::now() + CONFIG.sessions.ttl;
+                sessions.insert(id.to_string(), (expires, data));
+            }
+        }
+    }
+}
+
+fn new_id() -> String {
+    digest::hex(&rand::rng().random::<[u8; 16]>())
+}
+
+fn signature(id: &str) -> String {
+    let secret = CONFIG.sessions.secret.as_deref().unwrap_or(&RANDOM_SECRET);
+    digest::hex(&digest::hmac_sha256(secret.as_bytes(), id.as_bytes()))
+}
+
+// The session ID in a cookie value, if it's signed with the secret.
+fn verified(value: &str) -> Option<String> {
+    let (id, signature) = value.split_once('.')?;
+    access::same(signature.as_bytes(), self::signature(id).as_bytes()).then(|| id.to_string())
+}
+
+// The value of the session cookie among those the request sends.
+fn cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(COOKIE)?.strip_prefix('='))
+}
+
+// Scripts can't read the cookie, and other sites' pages don't send it along
+// with requests they make, other than links to follow.
+fn set_cookie(value: &str) -> String {
+    let path = if CONFIG.base_path.is_empty() {
+        "/"
+    } else {
+        &CONFIG.base_path
+    };
+    let mut cookie = format!(
+        "{}={}; Path={}; Max-Age={}; HttpOnly; SameSite=Lax",
+        COOKIE,
+        value,
+        path,
+        CONFIG.sessions.ttl.as_secs()
+    );
+    if CONFIG.sessions.secure {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookies_name_signed_sessions() {
+        let id = new_id();
+        assert_eq!(id.len(), 32);
+        let value = format!("{}.{}", id, signature(&id));
+        assert_eq!(verified(&value), Some(id.clone()));
+        assert_eq!(verified(&format!("{}0.{}", id, signature(&id))), None);
+        assert_eq!(verified(&id), None);
+
+        let mut headers = HeaderMap::new();
+        let sent = format!("theme=dark; {}={}; other=1", COOKIE, value);
+        headers.insert(header::COOKIE, sent.parse().unwrap());
+        assert_eq!(cookie(&headers), Some(value.as_str()));
+        headers.insert(header::COOKIE, "bandje_session_old=1".parse().unwrap());
+        assert_eq!(cookie(&headers), None);
+    }
+
+    #[test]
+    fn only_changed_sessions_are_stored() {
+        let session = Session::default();
+        assert_eq!(session.get::<Vec<String>>("seen"), None);
+        assert!(session.changes().is_none());
+        session.insert("seen", ["a", "b"]);
+        assert_eq!(
+            session.get::<Vec<String>>("seen"),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(session.get::<u32>("seen"), None);
+        assert!(session.changes().is_some());
+    }
+}
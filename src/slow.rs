@@ -0,0 +1,193 @@
+// Slow requests: any that takes `log.slow_request_ms` or longer is logged as a
+// warning, with its route, its query and where the time went, and the latest
+// are kept for GET /api/admin/info, grouped by route, so the endpoints that are
+// slow in practice stand out without digging through the log.
+//
+// The time is split between the handler, measured around the routes, and
+// everything around it: waiting on the limits, compressing and the rest.
+
+use crate::{clock, CONFIG};
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+// How many of the latest slow requests are kept.
+const KEPT: usize = 100;
+// Queries are cut short in the log after this many characters.
+const MAX_QUERY: usize = 200;
+
+static RECENT: Mutex<VecDeque<Slow>> = Mutex::new(VecDeque::new());
+
+// What the handler layer found, left on the response for `watch`.
+#[derive(Debug, Clone)]
+struct Handled {
+    route: String,
+    took: Duration,
+}
+
+/// One slow request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Slow {
+    at: String,
+    method: String,
+    // The route's pattern, such as "/api/searches/{id}", or the path for
+    // requests that didn't match one.
+    route: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query: Option<String>,
+    status: u16,
+    total_ms: u64,
+    // `None` when it was answered before reaching a handler, as when shed.
+    handler_ms: Option<u64>,
+}
+
+/// Middleware timing the handler of each route, innermost first: a dataset's
+/// own routes count rather than the route that hands requests over to them.
+pub async fn time_handler(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let started = Instant::now();
+    let mut response = next.run(request).await;
+    if let Some(route) = route.filter(|_| response.extensions().get::<Handled>().is_none()) {
+        let took = started.elapsed();
+        response.extensions_mut().insert(Handled { route, took });
+    }
+    response
+}
+
+/// Middleware logging and keeping requests that take `log.slow_request_ms` or
+/// longer.
+pub async fn watch(request: Request, next: Next) -> Response {
+    let Some(threshold) = CONFIG.slow_request else {
+        return next.run(request).await;
+    };
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let query = request.uri().query().map(|query| {
+        let mut query = query.to_string();
+        if query.len() > MAX_QUERY {
+            let end = (0..=MAX_QUERY).rev().find(|&i| query.is_char_boundary(i));
+            query.truncate(end.unwrap_or(0));
+            query.push('…');
+        }
+        query
+    });
+    let response = next.run(request).await;
+    let total = started.elapsed();
+    if total < threshold {
+        return response;
+    }
+
+    let handled = response.extensions().get::<Handled>();
+    let handler_ms = handled.map(|handled| handled.took.as_millis() as u64);
+    let total_ms = total.as_millis() as u64;
+    let slow = Slow {
+        at: clock::rfc3339(SystemTime::now() - total),
+        method,
+        route: handled.map_or(path, |handled| handled.route.clone()),
+        query,
+        status: response.status().as_u16(),
+        total_ms,
+        handler_ms,
+    };
+    tracing::warn!(
+        route = %slow.route,
+        query = slow.query.as_deref().unwrap_or(""),
+        status = slow.status,
+        total_ms,
+        handler_ms,
+        other_ms = handler_ms.map(|handler| total_ms.saturating_sub(handler)),
+        "->> Slow request"
+    );
+    let mut recent = RECENT.lock().unwrap();
+    if recent.len() == KEPT {
+        recent.pop_front();
+    }
+    recent.push_back(slow);
+    response
+}
+
+/// How slow requests have been on one route, among the latest kept.
+#[derive(Debug, Serialize)]
+pub struct Route {
+    method: String,
+    route: String,
+    count: usize,
+    max_ms: u64,
+    average_ms: u64,
+    last_at: String,
+}
+
+/// The routes of the latest slow requests, slowest first, and those requests,
+/// newest first, for GET /api/admin/info.
+pub fn report() -> (Vec<Route>, Vec<Slow>) {
+    let recent = RECENT.lock().unwrap();
+    (
+        summarize(recent.iter()),
+        recent.iter().rev().cloned().collect(),
+    )
+}
+
+fn summarize<'a>(requests: impl Iterator<Item = &'a Slow>) -> Vec<Route> {
+    let mut routes: HashMap<(&str, &str), Route> = HashMap::new();
+    let mut totals: HashMap<(&str, &str), u64> = HashMap::new();
+    for slow in requests {
+        let key = (slow.method.as_str(), slow.route.as_str());
+        let route = routes.entry(key).or_insert_with(|| Route {
+            method: slow.method.clone(),
+            route: slow.route.clone(),
+            count: 0,
+            max_ms: 0,
+            average_ms: 0,
+            last_at: String::new(),
+        });
+        route.count += 1;
+        route.max_ms = route.max_ms.max(slow.total_ms);
+        route.last_at.clone_from(&slow.at);
+        let total = totals.entry(key).or_default();
+        *total += slow.total_ms;
+        route.average_ms = *total / route.count as u64;
+    }
+    let mut routes: Vec<Route> = routes.into_values().collect();
+    routes.sort_by(|a, b| b.max_ms.cmp(&a.max_ms).then_with(|| a.route.cmp(&b.route)));
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_are_summarized_slowest_first() {
+        let slow = |route: &str, total_ms: u64, at: &str| Slow {
+            at: at.to_string(),
+            method: "GET".to_string(),
+            route: route.to_string(),
+            query: None,
+            status: 200,
+            total_ms,
+            handler_ms: Some(total_ms),
+        };
+        let requests = [
+            slow("/api/search", 1200, "1"),
+            slow("/api/all-bands", 3000, "2"),
+            slow("/api/search", 1800, "3"),
+        ];
+        let routes = summarize(requests.iter());
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].route, "/api/all-bands");
+        let search = &routes[1];
+        assert_eq!(
+            (search.count, search.max_ms, search.average_ms),
+            (2, 1800, 1500)
+        );
+        assert_eq!(search.last_at, "3");
+    }
+}
@@ -0,0 +1,158 @@
+// Copies of the dataset being served, saved to a directory so it can be backed
+// up before it's changed and put back afterwards. Each snapshot is a data file
+// in the current format named after when it was taken, such as
+// `bands-20240301T120000Z.json`, so it can also be served with --data-file.
+
+use crate::clock;
+use crate::data::BandData;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const PREFIX: &str = "bands-";
+const SUFFIX: &str = ".json";
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotInfo {
+    // The file name, which identifies the snapshot.
+    pub name: String,
+    // RFC 3339, in UTC.
+    pub taken_at: String,
+    pub bytes: u64,
+}
+
+/// Writes `data` to a new snapshot in `dir`, creating the directory if needed.
+pub fn save(dir: &Path, data: &BandData, now: SystemTime) -> Result<SnapshotInfo, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let json = serde_json::to_vec_pretty(data).map_err(|e| e.to_string())?;
+
+    // Snapshots taken within the same second are numbered.
+    let stamp = clock::basic(now);
+    let name = (1..)
+        .map(|n| match n {
+            1 => format!("{}{}{}", PREFIX, stamp, SUFFIX),
+            n => format!("{}{}-{}{}", PREFIX, stamp, n, SUFFIX),
+        })
+        .find(|name| !dir.join(name).exists())
+        .expect("some number is free");
+
+    // Written under another name first, so a half-written file is never listed.
+    let path = dir.join(&name);
+    let partial = dir.join(format!(".{}.partial", name));
+    fs::write(&partial, &json)
+        .and_then(|()| fs::rename(&partial, &path))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(SnapshotInfo {
+        taken_at: clock::rfc3339(now),
+        name,
+        bytes: json.len() as u64,
+    })
+}
+
+/// The snapshots in `dir`, newest first. A directory that doesn't exist yet has
+/// none.
+pub fn list(dir: &Path) -> Result<Vec<SnapshotInfo>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let mut snapshots: Vec<(String, u32, SnapshotInfo)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let (stamp, number) = parse_name(&name)?;
+            let taken_at = taken_at(&stamp)?;
+            let bytes = entry.metadata().ok()?.len();
+            Some((
+                stamp,
+                number,
+                SnapshotInfo {
+                    name,
+                    taken_at,
+                    bytes,
+                },
+            ))
+        })
+        .collect();
+    snapshots.sort_by(|a, b| (&b.0, b.1).cmp(&(&a.0, a.1)));
+    Ok(snapshots.into_iter().map(|(_, _, info)| info).collect())
+}
+
+/// The path of the snapshot called `name` in `dir`. Only names `list` gives are
+/// accepted, so a request can't reach files outside the directory.
+pub fn find(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = dir.join(name);
+    if parse_name(name).is_none() || !path.is_file() {
+        return Err(format!("there is no snapshot named \"{}\"", name));
+    }
+    Ok(path)
+}
+
+// The time stamp and number in a snapshot's file name; the first of a second is 1.
+fn parse_name(name: &str) -> Option<(String, u32)> {
+    let rest = name.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+    let (stamp, number) = match rest.split_once('-') {
+        Some((stamp, number)) if !number.starts_with('0') => (stamp, number.parse().ok()?),
+        Some(_) => return None,
+        None => (rest, 1),
+    };
+    let valid = stamp.len() == 16
+        && stamp.char_indices().all(|(i, c)| match i {
+            8 => c == 'T',
+            15 => c == 'Z',
+            _ => c.is_ascii_digit(),
+        });
+    valid.then(|| (stamp.to_string(), number))
+}
+
+// `20240301T120000Z` as `2024-03-01T12:00:00Z`.
+fn taken_at(stamp: &str) -> Option<String> {
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        stamp.get(0..4)?,
+        stamp.get(4..6)?,
+        stamp.get(6..8)?,
+        stamp.get(9..11)?,
+        stamp.get(11..13)?,
+        stamp.get(13..15)?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn saves_and_lists_snapshots_newest_first() {
+        let dir = std::env::temp_dir().join(format!("bands-snapshots-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let noon = SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_294_400);
+        let data = BandData::default();
+
+        assert!(list(&dir).unwrap().is_empty());
+        save(&dir, &data, noon).unwrap();
+        save(&dir, &data, noon).unwrap();
+        save(&dir, &data, noon - Duration::from_secs(60)).unwrap();
+        fs::write(dir.join("notes.json"), "{}").unwrap();
+
+        let snapshots = list(&dir).unwrap();
+        let names: Vec<&str> = snapshots.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "bands-20240301T120000Z-2.json",
+                "bands-20240301T120000Z.json",
+                "bands-20240301T115900Z.json"
+            ]
+        );
+        assert_eq!(snapshots[0].taken_at, "2024-03-01T12:00:00Z");
+
+        assert!(find(&dir, "bands-20240301T120000Z.json").is_ok());
+        assert!(find(&dir, "notes.json").is_err());
+        assert!(find(&dir, "../bands-20240301T120000Z.json").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1104 @@
+// Application state: an immutable snapshot of the loaded dataset, and the
+// container that lets a reload swap in a fresh snapshot while the server runs.
+//
+// Handlers extract `State<Arc<AppState>>` and keep that snapshot for the whole
+// request, so a reload never changes the data under a request in flight; the old
+// snapshot is freed once the last request using it finishes.
+
+use crate::aliases::{self, Aliases};
+use crate::artists::Artists;
+use crate::config::Dataset;
+use crate::countries::{self, Countries};
+use crate::data::{self, DataSource};
+use crate::filter::Filters;
+use crate::layout::{Performance, Performances};
+use crate::{
+    audit, cache, clock, festivals, filter, parallel, phonetic, saved, search, snapshot, store,
+    validate, ArtistPerformance, SearchDefinition, CONFIG,
+};
+use axum::body::Bytes;
+use axum::extract::FromRef;
+use axum::http::Uri;
+use axum::Router;
+use serde::Serialize;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// --- Application State ---
+// This struct holds the flattened list of all performances, ready for any operation.
+#[derive(Debug)]
+pub(crate) struct AppState {
+    // The dataset as loaded and checked, for saving it again.
+    pub data: data::BandData,
+    // Kept in the layout `data.layout` picks.
+    pub all_performances: Performances,
+    // Every distinct artist once, with where its performances are; the search
+    // index, browsing and the statistics all go by it.
+    pub artists: Arc<Artists>,
+    // `all_performances` as JSON, serialized once here for /api/all-bands,
+    // which every download is sent straight from; `None` for datasets over
+    // `limits.stream_exports_over` performances, whose downloads are streamed.
+    pub all_bands_json: Option<Bytes>,
+    // The words of the distinct names, for fast search and autocomplete.
+    pub search_index: search::SearchIndex,
+    // Festivals and years in the data, for recognising them in search queries.
+    pub catalog: filter::Catalog,
+    // Every festival with its location and editions, for listing them.
+    pub festivals: Vec<festivals::FestivalInfo>,
+    // Rendered bodies of recent successful searches. It belongs to this snapshot,
+    // so newly loaded data always starts with an empty cache.
+    pub search_cache: cache::LruCache<SearchDefinition, Bytes>,
+    // Rendered bodies of the derived endpoints by path and query, with when they
+    // were rendered, for `limits.response_cache_ttl`. Like the search cache, it
+    // goes with the snapshot.
+    pub response_cache: cache::LruCache<String, (Instant, Bytes)>,
+    // The same endpoints' answers for the whole dataset, kept for as long as the
+    // snapshot is.
+    pub statistics: Statistics,
+    // Hash of the raw data this was built from, to skip reloads that change nothing.
+    pub content_hash: u64,
+    // Where the data was read from, with any password left out; `None` before
+    // anything has loaded.
+    pub source: Option<String>,
+    // When this snapshot was built.
+    pub loaded_at: SystemTime,
+    // What checking the data found: records left out and names tidied.
+    pub checks: validate::Report,
+}
+
+impl AppState {
+    /// Flattens parsed data and builds everything searching needs.
+    pub fn new(band_data: data::BandData, content_hash: u64) -> Self {
+        let started = Instant::now();
+        // Flatten the nested structure into a single list of `ArtistPerformance` objects.
+        let mut all_performances = Vec::new();
+        let mut names = Interner::default();
+        for festival in &band_data.festivals {
+            let festival_name = names.get(&festival.name);
+            for year in &festival.years {
+                for act in &year.artists {
+                    let search_key = search::normalize(&act.name);
+                    all_performances.push(ArtistPerformance {
+                        id: performance_id(&festival.name, year.year, &act.name),
+                        phonetic_codes: phonetic::codes(&search_key),
+                        search_key,
+                        name: names.get(&act.name),
+                        festival: Arc::clone(&festival_name),
+                        year: year.year,
+                        genres: act.genres.clone(),
+                        country: act.country.clone(),
+                        stage: act.stage.clone(),
+                        day: act.day.clone(),
+                        start_time: act.start_time.clone(),
+                        headliner: act.headliner,
+                        cancelled: year.cancelled,
+                    });
+                }
+            }
+        }
+        inherit_genres(&mut all_performances);
+        inherit_countries(&mut all_performances);
+        let all_performances = Performances::new(all_performances, CONFIG.layout);
+        let flattened = started.elapsed();
+        let (artists, listing) = timed(|| Arc::new(Artists::new(&all_performances)));
+
+        // These only read what's been flattened, so they're built side by side.
+        let (
+            festivals,
+            (search_index, indexing),
+            (catalog, cataloguing),
+            (all_bands_json, serializing),
+        ) = thread::scope(|scope| {
+            let performances = &all_performances;
+            let search_index =
+                scope.spawn(|| timed(|| search::SearchIndex::new(Arc::clone(&artists))));
+            let catalog = scope.spawn(|| timed(|| filter::Catalog::new(performances)));
+            let all_bands_json = scope.spawn(|| {
+                timed(|| {
+                    (performances.len() <= CONFIG.limits.stream_exports_over)
+                        .then(|| serde_json::to_vec(performances).expect("performances serialize"))
+                        .map(Bytes::from)
+                })
+            });
+            (
+                festivals::summarize(&band_data),
+                parallel::join(search_index),
+                parallel::join(catalog),
+                parallel::join(all_bands_json),
+            )
+        });
+        if !all_performances.is_empty() {
+            tracing::info!(
+                "->> Indexed {} performances in {} ms: flattening {} ms, artists {} ms, search index {} ms, catalog {} ms, export {} ms.",
+                all_performances.len(),
+                started.elapsed().as_millis(),
+                flattened.as_millis(),
+                listing.as_millis(),
+                indexing.as_millis(),
+                cataloguing.as_millis(),
+                serializing.as_millis()
+            );
+        }
+
+        AppState {
+            data: band_data,
+            all_performances,
+            artists,
+            all_bands_json,
+            search_index,
+            catalog,
+            festivals,
+            search_cache: cache::LruCache::new(CONFIG.limits.search_cache_size),
+            response_cache: cache::LruCache::new(CONFIG.limits.response_cache_size),
+            statistics: Statistics::default(),
+            content_hash,
+            source: None,
+            loaded_at: SystemTime::now(),
+            checks: validate::Report::default(),
+        }
+    }
+
+    /// The performances passing `filters`, in order. Only those at the festivals
+    /// or in the years it names are looked at, if it names any.
+    pub fn passing<'a>(
+        &'a self,
+        filters: &'a Filters,
+    ) -> Box<dyn Iterator<Item = Performance<'a>> + 'a> {
+        let performances = &self.all_performances;
+        match self.catalog.positions(filters) {
+            Some(positions) => Box::new(
+                positions
+                    .into_iter()
+                    .map(|i| performances.get(i))
+                    .filter(move |p| filters.matches(*p)),
+            ),
+            None => Box::new(performances.iter().filter(move |p| filters.matches(*p))),
+        }
+    }
+
+    /// The performance with the ID `id`.
+    pub fn performance(&self, id: &str) -> Option<Performance<'_>> {
+        self.all_performances.iter().find(|p| p.id() == id)
+    }
+
+    /// The JSON `render` gives for `uri`, rendered again only once the cached
+    /// copy is older than `limits.response_cache_ttl`.
+    pub fn cached_json<T: Serialize>(&self, uri: &Uri, render: impl FnOnce() -> T) -> Bytes {
+        let key = uri
+            .path_and_query()
+            .map_or_else(|| uri.path().to_string(), |p| p.to_string());
+        let ttl = CONFIG.limits.response_cache_ttl;
+        if let Some((rendered, body)) = self.response_cache.get(&key) {
+            if ttl.is_none_or(|ttl| rendered.elapsed() < ttl) {
+                return body;
+            }
+        }
+        let body = Bytes::from(serde_json::to_vec(&render()).expect("responses serialize"));
+        self.response_cache
+            .insert(key, (Instant::now(), body.clone()));
+        body
+    }
+
+    /// The JSON `render` gives for `uri`: without `filters`, kept in `whole` from
+    /// its first request on, and otherwise cached like `cached_json` does.
+    pub fn statistic<T: Serialize>(
+        &self,
+        whole: &OnceLock<Bytes>,
+        filters: &Filters,
+        uri: &Uri,
+        render: impl FnOnce() -> T,
+    ) -> Bytes {
+        if !filters.is_empty() {
+            return self.cached_json(uri, render);
+        }
+        let body = whole.get_or_init(|| {
+            Bytes::from(serde_json::to_vec(&render()).expect("responses serialize"))
+        });
+        body.clone()
+    }
+
+    /// About how many bytes the performances take, from the lengths of their
+    /// strings and lists, counting shared names once; the parsed data and the
+    /// index come on top.
+    pub fn performance_bytes(&self) -> usize {
+        self.all_performances.bytes()
+    }
+
+    /// A snapshot with no performances, for starting before any data has loaded.
+    pub fn empty() -> Self {
+        // No real content hashes to 0, so the first successful refresh swaps.
+        AppState::new(data::BandData::default(), 0)
+    }
+
+    /// Reads `dataset` and builds a snapshot from it.
+    pub fn load(dataset: &Dataset) -> Result<Self, String> {
+        AppState::from_raw(&RawInput::read(dataset, &dataset.data_source)?)
+    }
+
+    /// Builds a snapshot from `file` the way an upload to `dataset` would, with
+    /// its aliases and countries, except that invalid records are left out
+    /// whatever the validation mode, so all of them show in `checks`.
+    pub fn preview(dataset: &Dataset, file: data::RawFile) -> Result<Self, String> {
+        let input = RawInput::of(dataset, file.origin.clone(), vec![file])?;
+        let aliases = Aliases::parse(input.aliases.as_ref())?;
+        let countries = Countries::parse(input.countries.as_ref())?;
+        let (band_data, checks) =
+            data::parse(&input.files, &aliases, &countries, validate::Mode::Lenient)?;
+        Ok(AppState {
+            checks,
+            source: Some(input.source.clone()),
+            ..AppState::new(band_data, input.content_hash())
+        })
+    }
+
+    fn from_raw(input: &RawInput) -> Result<Self, String> {
+        let aliases = Aliases::parse(input.aliases.as_ref())?;
+        let countries = Countries::parse(input.countries.as_ref())?;
+        let (band_data, checks) =
+            data::parse(&input.files, &aliases, &countries, CONFIG.validation)?;
+        validate::log(&checks);
+        Ok(AppState {
+            checks,
+            source: Some(input.source.clone()),
+            ..AppState::new(band_data, input.content_hash())
+        })
+    }
+}
+
+/// What the derived endpoints give for the whole dataset, without filters, each
+/// rendered on its first request and then kept with the snapshot. Reloads and
+/// edits build a new snapshot, so they always describe the data being served,
+/// and unlike the response cache they're never evicted or rendered again.
+#[derive(Debug, Default)]
+pub struct Statistics {
+    pub countries: OnceLock<Bytes>,
+    pub genres: OnceLock<Bytes>,
+    pub letters: OnceLock<Bytes>,
+}
+
+/// Which dataset a snapshot holds, for clients and caches to tell when it changes.
+#[derive(Debug, Serialize)]
+pub struct DatasetInfo<'a> {
+    // 16 hex digits; changes whenever the data, aliases or countries files do.
+    pub content_hash: String,
+    // RFC 3339, in UTC.
+    pub loaded_at: String,
+    pub source: Option<&'a str>,
+    pub festivals: usize,
+    pub editions: usize,
+    pub performances: usize,
+    // Distinct artists, by search key.
+    pub artists: usize,
+}
+
+impl AppState {
+    /// Describes this snapshot's dataset.
+    pub fn dataset_info(&self) -> DatasetInfo<'_> {
+        DatasetInfo {
+            content_hash: format!("{:016x}", self.content_hash),
+            loaded_at: clock::rfc3339(self.loaded_at),
+            source: self.source.as_deref(),
+            festivals: self.festivals.len(),
+            editions: self.festivals.iter().map(|f| f.editions.len()).sum(),
+            performances: self.all_performances.len(),
+            artists: self.artists.len(),
+        }
+    }
+}
+
+// One shared copy of each distinct name, so an artist or festival listed
+// thousands of times is kept in memory once.
+#[derive(Default)]
+struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    fn get(&mut self, text: &str) -> Arc<str> {
+        if let Some(shared) = self.0.get(text) {
+            return Arc::clone(shared);
+        }
+        let shared: Arc<str> = Arc::from(text);
+        self.0.insert(Arc::clone(&shared));
+        shared
+    }
+}
+
+// What `build` returns, with how long it took.
+fn timed<T>(build: impl FnOnce() -> T) -> (T, Duration) {
+    let started = Instant::now();
+    (build(), started.elapsed())
+}
+
+/// The ID of an artist's performance at one edition of a festival. It's worked
+/// out from the festival, year and name, ignoring case like the checks that keep
+/// an artist from being listed twice, so the same data always gets the same IDs.
+pub fn performance_id(festival: &str, year: u16, name: &str) -> String {
+    // FNV-1a, which unlike the standard library's hasher is fixed, so IDs don't
+    // change with the compiler either.
+    let text = format!(
+        "{}\0{}\0{}",
+        festival.to_lowercase(),
+        year,
+        name.to_lowercase()
+    );
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format_id(hash)
+}
+
+/// How a performance ID is written, from its hash.
+pub fn format_id(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+/// The hash a performance ID was written from; `None` for anything that isn't
+/// one.
+pub fn parse_id(id: &str) -> Option<u64> {
+    u64::from_str_radix(id, 16).ok()
+}
+
+// Genres describe the artist, so they only need listing on one performance:
+// every performance without genres of its own gets all those given to the same
+// artist (by search key) anywhere else, in the order they first appear.
+fn inherit_genres(performances: &mut [ArtistPerformance]) {
+    let mut by_artist: HashMap<&str, Vec<String>> = HashMap::new();
+    for performance in performances.iter() {
+        let genres = by_artist.entry(&performance.search_key).or_default();
+        for genre in &performance.genres {
+            if !genres.iter().any(|g| g.eq_ignore_ascii_case(genre)) {
+                genres.push(genre.clone());
+            }
+        }
+    }
+    let by_artist: HashMap<String, Vec<String>> = by_artist
+        .into_iter()
+        .filter(|(_, genres)| !genres.is_empty())
+        .map(|(key, genres)| (key.to_string(), genres))
+        .collect();
+
+    for performance in performances {
+        if performance.genres.is_empty() {
+            if let Some(genres) = by_artist.get(&performance.search_key) {
+                performance.genres.clone_from(genres);
+            }
+        }
+    }
+}
+
+// The same for where the artist comes from: performances without a country get
+// the first one given for the artist.
+fn inherit_countries(performances: &mut [ArtistPerformance]) {
+    let mut by_artist: HashMap<String, String> = HashMap::new();
+    for performance in performances.iter() {
+        if let Some(country) = &performance.country {
+            by_artist
+                .entry(performance.search_key.clone())
+                .or_insert_with(|| country.clone());
+        }
+    }
+    for performance in performances {
+        if performance.country.is_none() {
+            performance.country = by_artist.get(&performance.search_key).cloned();
+        }
+    }
+}
+
+// What a snapshot is built from, as read: where from, the data's files, the aliases file and
+// the countries file.
+struct RawInput {
+    source: String,
+    files: Vec<data::RawFile>,
+    aliases: Option<data::RawFile>,
+    countries: Option<data::RawFile>,
+}
+
+impl RawInput {
+    // `source`'s files, with the aliases and countries of `dataset`.
+    // The aliases and countries are read while the data is, which for a URL
+    // or a store can take a while.
+    fn read(dataset: &Dataset, source: &DataSource) -> Result<Self, String> {
+        thread::scope(|scope| {
+            let aliases = scope.spawn(|| aliases::read(dataset.aliases_file.as_deref()));
+            let countries = scope.spawn(|| countries::read(dataset.countries_file.as_deref()));
+            let (files, took) = timed(|| data::read(source));
+            tracing::info!("->> Read {} in {} ms.", source, took.as_millis());
+            Ok(RawInput {
+                source: source.to_string(),
+                files: files?,
+                aliases: parallel::join(aliases)?,
+                countries: parallel::join(countries)?,
+            })
+        })
+    }
+
+    // `files` from `source`, with the aliases and countries of `dataset`.
+    fn of(dataset: &Dataset, source: String, files: Vec<data::RawFile>) -> Result<Self, String> {
+        Ok(RawInput {
+            source,
+            files,
+            aliases: aliases::read(dataset.aliases_file.as_deref())?,
+            countries: countries::read(dataset.countries_file.as_deref())?,
+        })
+    }
+
+    // Editing only the aliases or countries changes the snapshot too.
+    fn content_hash(&self) -> u64 {
+        data::content_hash(
+            self.files
+                .iter()
+                .chain(&self.aliases)
+                .chain(&self.countries),
+        )
+    }
+}
+
+// --- Shared State ---
+
+/// What the router hands to every handler: the current snapshot, plus state that
+/// outlives any one dataset.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedState {
+    // Where the data is read from, what reloads read again.
+    pub dataset: Arc<Dataset>,
+    current: Arc<Serving>,
+    // Held for a whole reload or edit, so two can't interleave and each summary
+    // describes exactly the swap it made.
+    reloading: Arc<tokio::sync::Mutex<()>>,
+    // The content hash of the configured source as last read. Edits and restored
+    // snapshots leave it alone, so a refresh only replaces them once the source
+    // itself changes.
+    source_hash: Arc<AtomicU64>,
+    // Saved searches are re-run against whatever data is current, so they're
+    // kept across reloads.
+    pub saved_searches: Arc<saved::SavedSearches>,
+    // Where admin changes are recorded.
+    pub audit: Arc<audit::AuditLog>,
+    // The snapshots most recently replaced, oldest first, for rolling back to.
+    history: Arc<Mutex<VecDeque<Arc<AppState>>>>,
+    // Set once the dataset is no longer served, so its watcher stops.
+    retired: Arc<AtomicBool>,
+}
+
+// The snapshot being served, which swaps replace whole. Readers only hold the
+// lock for as long as it takes to clone the `Arc`, so a request never waits on a
+// reload, only on the moment of the swap itself.
+#[derive(Debug)]
+struct Serving(RwLock<Arc<AppState>>);
+
+impl Serving {
+    fn load(&self) -> Arc<AppState> {
+        Arc::clone(&self.0.read().unwrap())
+    }
+
+    // Serves `next` from now on, handing back the snapshot it replaces.
+    fn swap(&self, next: Arc<AppState>) -> Arc<AppState> {
+        std::mem::replace(&mut *self.0.write().unwrap(), next)
+    }
+}
+
+fn snapshot_dir(name: Option<&str>) -> PathBuf {
+    match name {
+        Some(name) => CONFIG.snapshot_dir.join(name),
+        None => CONFIG.snapshot_dir.clone(),
+    }
+}
+
+// What a swap reads, and what it does when that's what's being served already.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Swap {
+    // The configured source, swapped in regardless.
+    Reload,
+    // The configured source, kept out when its content hasn't changed.
+    Refresh,
+    // Data from elsewhere, such as a snapshot or an upload. When the source is a
+    // database, it's written there too.
+    Replace,
+}
+
+// Writes `data` to `dataset`'s source when that's a database, so what the admin
+// API changes outlives a restart and reaches every instance reading the same
+// database. Returns the content hash reading it back gives, which is what
+// `refresh` compares against; `None` for sources that aren't written.
+fn persist(dataset: &Dataset, data: &data::BandData) -> Result<Option<u64>, String> {
+    let Some(location) = store::Location::of(&dataset.data_source) else {
+        return Ok(None);
+    };
+    store::open(location)?
+        .save(data)
+        .map_err(|e| format!("it couldn't be saved to {}: {}", location, e))?;
+    Ok(Some(
+        RawInput::read(dataset, &dataset.data_source)?.content_hash(),
+    ))
+}
+
+impl SharedState {
+    pub fn new(initial: AppState, dataset: Dataset) -> Self {
+        SharedState {
+            audit: Arc::new(audit::AuditLog::new(
+                CONFIG.audit_file.clone(),
+                dataset.name.clone(),
+            )),
+            dataset: Arc::new(dataset),
+            source_hash: Arc::new(AtomicU64::new(initial.content_hash)),
+            current: Arc::new(Serving(RwLock::new(Arc::new(initial)))),
+            reloading: Arc::new(tokio::sync::Mutex::new(())),
+            saved_searches: Arc::new(saved::SavedSearches::new(CONFIG.limits.max_saved_searches)),
+            history: Arc::new(Mutex::new(VecDeque::new())),
+            retired: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Starts serving a new dataset called `name` from an uploaded `file`, with
+    /// no aliases or countries of its own. The data is saved as the dataset's
+    /// first snapshot, which is then its source for reloads.
+    pub async fn create(name: String, source: String, file: data::RawFile) -> Result<Self, String> {
+        tokio::task::spawn_blocking(move || {
+            let input = RawInput {
+                source,
+                files: vec![file],
+                aliases: None,
+                countries: None,
+            };
+            let initial = AppState::from_raw(&input)?;
+            let dir = snapshot_dir(Some(&name));
+            let saved = snapshot::save(&dir, &initial.data, SystemTime::now())?;
+            let dataset = Dataset {
+                name: Some(name),
+                data_source: DataSource::File(dir.join(saved.name)),
+                aliases_file: None,
+                countries_file: None,
+            };
+            Ok(SharedState::new(initial, dataset))
+        })
+        .await
+        .map_err(|e| format!("Creating the dataset stopped unexpectedly: {}", e))?
+    }
+
+    /// The same dataset, called `name` from now on. Its snapshots move to the
+    /// directory for the new name, and a source among them moves with them.
+    pub fn renamed(&self, name: String) -> Result<Self, String> {
+        let (from, to) = (self.snapshot_dir(), snapshot_dir(Some(&name)));
+        if to.exists() {
+            return Err(format!("{} is in the way of the snapshots", to.display()));
+        }
+        if from.exists() {
+            fs::rename(&from, &to).map_err(|e| {
+                format!(
+                    "Failed to move {} to {}: {}",
+                    from.display(),
+                    to.display(),
+                    e
+                )
+            })?;
+        }
+        let data_source = match &self.dataset.data_source {
+            DataSource::File(path) => match path.strip_prefix(&from) {
+                Ok(rest) => DataSource::File(to.join(rest)),
+                Err(_) => DataSource::File(path.clone()),
+            },
+            other => other.clone(),
+        };
+        Ok(SharedState {
+            dataset: Arc::new(Dataset {
+                name: Some(name.clone()),
+                data_source,
+                ..(*self.dataset).clone()
+            }),
+            audit: Arc::new(audit::AuditLog::new(CONFIG.audit_file.clone(), Some(name))),
+            ..self.clone()
+        })
+    }
+
+    /// Stops watching the dataset's source, once it's no longer served.
+    pub fn retire(&self) {
+        self.retired.store(true, Ordering::SeqCst);
+    }
+
+    fn is_retired(&self) -> bool {
+        self.retired.load(Ordering::SeqCst)
+    }
+
+    /// Where this dataset's snapshots are saved: the snapshot directory for the
+    /// main dataset, and a directory named after the dataset within it for others.
+    pub fn snapshot_dir(&self) -> PathBuf {
+        snapshot_dir(self.dataset.name.as_deref())
+    }
+
+    /// The snapshot new requests should use.
+    pub fn current(&self) -> Arc<AppState> {
+        self.current.load()
+    }
+
+    /// Makes `next` the snapshot for all requests from now on, remembering the
+    /// one it replaces for `roll_back`.
+    pub fn replace(&self, next: AppState) {
+        let previous = self.current.swap(Arc::new(next));
+        let mut history = self.history.lock().unwrap();
+        history.push_back(previous);
+        while history.len() > CONFIG.limits.max_rollback_steps {
+            history.pop_front();
+        }
+    }
+
+    /// Serves the snapshot that was current `steps` swaps ago again, forgetting
+    /// those in between, and reports what changed. Fails, changing nothing, when
+    /// fewer swaps are remembered.
+    pub async fn roll_back(&self, steps: usize) -> Result<ReloadSummary, String> {
+        let _guard = self.reloading.lock().await;
+        let target = {
+            let history = self.history.lock().unwrap();
+            if steps == 0 || steps > history.len() {
+                return Err(format!(
+                    "steps must be between 1 and {}, the number of changes that can be rolled back",
+                    history.len()
+                ));
+            }
+            Arc::clone(&history[history.len() - steps])
+        };
+        let dataset = Arc::clone(&self.dataset);
+        let saving = Arc::clone(&target);
+        let stored = tokio::task::spawn_blocking(move || persist(&dataset, &saving.data))
+            .await
+            .map_err(|e| format!("The rollback stopped unexpectedly: {}", e))?
+            .map_err(|e| format!("The data was not rolled back: {}", e))?;
+        {
+            let mut history = self.history.lock().unwrap();
+            let first = history.len() - steps;
+            history.truncate(first);
+        }
+        if let Some(hash) = stored {
+            self.source_hash.store(hash, Ordering::SeqCst);
+        }
+        let summary = ReloadSummary::between(&self.current(), &target);
+        self.current.swap(target);
+        Ok(summary)
+    }
+
+    /// Reads the dataset's source again and, if it loads, swaps it in and
+    /// reports what changed. On failure the current snapshot stays untouched.
+    pub async fn reload(&self) -> Result<ReloadSummary, String> {
+        let dataset = Arc::clone(&self.dataset);
+        let summary = self
+            .swap_in(
+                move || RawInput::read(&dataset, &dataset.data_source),
+                Swap::Reload,
+            )
+            .await?;
+        Ok(summary.expect("a forced reload always swaps"))
+    }
+
+    /// Like `reload`, but keeps the current snapshot (and its warm cache) when
+    /// the source's content is byte-for-byte the same as last read, returning
+    /// `None`.
+    pub async fn refresh(&self) -> Result<Option<ReloadSummary>, String> {
+        let dataset = Arc::clone(&self.dataset);
+        self.swap_in(
+            move || RawInput::read(&dataset, &dataset.data_source),
+            Swap::Refresh,
+        )
+        .await
+    }
+
+    /// Like `reload`, for a file other than the configured source: it's served
+    /// until the source changes or is reloaded.
+    pub async fn restore(&self, path: PathBuf) -> Result<ReloadSummary, String> {
+        let dataset = Arc::clone(&self.dataset);
+        let source = DataSource::File(path);
+        let summary = self
+            .swap_in(move || RawInput::read(&dataset, &source), Swap::Replace)
+            .await?;
+        Ok(summary.expect("a restore always swaps"))
+    }
+
+    /// Like `restore`, for data sent to the server as `file`; `source` says where
+    /// it came from.
+    pub async fn upload(
+        &self,
+        source: String,
+        file: data::RawFile,
+    ) -> Result<ReloadSummary, String> {
+        let dataset = Arc::clone(&self.dataset);
+        let summary = self
+            .swap_in(
+                move || RawInput::of(&dataset, source, vec![file]),
+                Swap::Replace,
+            )
+            .await?;
+        Ok(summary.expect("an upload always swaps"))
+    }
+
+    async fn swap_in(
+        &self,
+        read: impl FnOnce() -> Result<RawInput, String> + Send + 'static,
+        swap: Swap,
+    ) -> Result<Option<ReloadSummary>, String> {
+        let _guard = self.reloading.lock().await;
+        let last_read = self.source_hash.load(Ordering::SeqCst);
+        let dataset = Arc::clone(&self.dataset);
+
+        // Reading, parsing and indexing take a while; keep them off the async workers.
+        let next = tokio::task::spawn_blocking(move || {
+            let input = read()?;
+            if swap == Swap::Refresh && input.content_hash() == last_read {
+                return Ok(None);
+            }
+            let mut next = AppState::from_raw(&input)?;
+            let stored = match swap {
+                Swap::Replace => persist(&dataset, &next.data)
+                    .map_err(|e| format!("The data was not changed: {}", e))?,
+                Swap::Reload | Swap::Refresh => Some(next.content_hash),
+            };
+            if let Some(hash) = stored {
+                next.content_hash = hash;
+            }
+            Ok::<_, String>(Some((next, stored)))
+        })
+        .await
+        .map_err(|e| format!("Reload stopped unexpectedly: {}", e))??;
+
+        Ok(next.map(|(next, stored)| {
+            if let Some(hash) = stored {
+                self.source_hash.store(hash, Ordering::SeqCst);
+            }
+            let summary = ReloadSummary::between(&self.current(), &next);
+            self.replace(next);
+            summary
+        }))
+    }
+
+    /// Changes a copy of the current dataset with `change` and swaps it in, once
+    /// it passes the checks loaded data goes through; the data that comes out of
+    /// them must be exactly what went in. A database source is written before the
+    /// swap. Returns what `change` did, with the snapshots before and after. On
+    /// failure the current snapshot stays untouched.
+    pub async fn edit<T: Send + 'static>(
+        &self,
+        change: impl FnOnce(&mut data::BandData) -> Result<T, EditError> + Send + 'static,
+    ) -> Result<Edited<T>, EditError> {
+        // Another instance may have written the database since it was last read;
+        // editing what it holds now keeps that change rather than overwriting it.
+        if store::Location::of(&self.dataset.data_source).is_some() {
+            self.refresh().await.map_err(EditError::Unsaved)?;
+        }
+        let _guard = self.reloading.lock().await;
+        let before = self.current();
+        let current = Arc::clone(&before);
+        let dataset = Arc::clone(&self.dataset);
+
+        let next = tokio::task::spawn_blocking(move || {
+            let mut data = current.data.clone();
+            let outcome = change(&mut data)?;
+            validate::tidy(EDIT_ORIGIN, &mut data);
+            let problems = validate::check(EDIT_ORIGIN, &mut data);
+            if !problems.is_empty() {
+                let messages: Vec<String> = problems
+                    .iter()
+                    .map(|problem| format!("{}: {}", problem.context, problem.message))
+                    .collect();
+                return Err(EditError::Invalid(messages.join("; ")));
+            }
+            let bytes = serde_json::to_vec(&data).expect("the data serializes");
+            let content_hash = data::content_hash([&data::RawFile {
+                origin: EDIT_ORIGIN.to_string(),
+                bytes,
+            }]);
+            let mut next = AppState {
+                source: current.source.clone(),
+                checks: current.checks.clone(),
+                ..AppState::new(data, content_hash)
+            };
+            // Stored, it's the version every instance reading the database sees.
+            let stored = persist(&dataset, &next.data).map_err(EditError::Unsaved)?;
+            if let Some(hash) = stored {
+                next.content_hash = hash;
+            }
+            Ok((outcome, next, stored))
+        })
+        .await
+        .map_err(|e| EditError::Invalid(format!("The edit stopped unexpectedly: {}", e)))??;
+
+        let (outcome, next, stored) = next;
+        if let Some(hash) = stored {
+            self.source_hash.store(hash, Ordering::SeqCst);
+        }
+        self.replace(next);
+        Ok(Edited {
+            outcome,
+            before,
+            after: self.current(),
+        })
+    }
+}
+
+/// What `SharedState::edit` did.
+pub struct Edited<T> {
+    pub outcome: T,
+    pub before: Arc<AppState>,
+    pub after: Arc<AppState>,
+}
+
+// What problems found in edited data are reported as coming from.
+const EDIT_ORIGIN: &str = "the edit";
+
+/// Why an edit wasn't made.
+#[derive(Debug)]
+pub enum EditError {
+    // The performance to change isn't in the data.
+    NotFound,
+    // The change would leave the data invalid, or couldn't be made.
+    Invalid(String),
+    // The data source is a database, and writing the change to it failed.
+    Unsaved(String),
+}
+
+/// What a reload changed, counted per performance (artist, festival and year).
+#[derive(Debug, Serialize)]
+pub struct ReloadSummary {
+    // Performances in the new data.
+    pub performances: usize,
+    // The content hashes of the data served before and after.
+    #[serde(skip)]
+    pub content_hashes: (u64, u64),
+    pub added: Vec<ArtistPerformance>,
+    pub removed: Vec<ArtistPerformance>,
+}
+
+fn key(p: Performance<'_>) -> (&str, &str, u16) {
+    (p.name(), p.festival(), p.year())
+}
+
+impl ReloadSummary {
+    fn between(old: &AppState, new: &AppState) -> Self {
+        // A performance listed twice counts twice, so duplicates show up as changes too.
+        let mut balance: HashMap<(&str, &str, u16), isize> = HashMap::new();
+        for p in old.all_performances.iter() {
+            *balance.entry(key(p)).or_default() -= 1;
+        }
+        for p in new.all_performances.iter() {
+            *balance.entry(key(p)).or_default() += 1;
+        }
+
+        let mut added = Vec::new();
+        for p in new.all_performances.iter() {
+            if let Some(n) = balance.get_mut(&key(p)).filter(|n| **n > 0) {
+                *n -= 1;
+                added.push(p.to_owned());
+            }
+        }
+        let mut removed = Vec::new();
+        for p in old.all_performances.iter() {
+            if let Some(n) = balance.get_mut(&key(p)).filter(|n| **n < 0) {
+                *n += 1;
+                removed.push(p.to_owned());
+            }
+        }
+
+        ReloadSummary {
+            performances: new.all_performances.len(),
+            content_hashes: (old.content_hash, new.content_hash),
+            added,
+            removed,
+        }
+    }
+}
+
+impl FromRef<SharedState> for Arc<AppState> {
+    fn from_ref(shared: &SharedState) -> Self {
+        shared.current()
+    }
+}
+
+impl FromRef<SharedState> for Arc<Dataset> {
+    fn from_ref(shared: &SharedState) -> Self {
+        Arc::clone(&shared.dataset)
+    }
+}
+
+impl FromRef<SharedState> for Arc<audit::AuditLog> {
+    fn from_ref(shared: &SharedState) -> Self {
+        Arc::clone(&shared.audit)
+    }
+}
+
+impl FromRef<SharedState> for Arc<saved::SavedSearches> {
+    fn from_ref(shared: &SharedState) -> Self {
+        Arc::clone(&shared.saved_searches)
+    }
+}
+
+// --- Other Datasets ---
+
+/// The routes serving one dataset's API, in the halves that can be served on
+/// different listeners.
+#[derive(Debug, Clone)]
+pub(crate) struct Routes {
+    pub public: Router,
+    pub admin: Router,
+}
+
+/// The datasets served next to the main one, by name, each with the routes
+/// that serve its API.
+#[derive(Debug, Clone)]
+pub(crate) struct Datasets {
+    pub main: SharedState,
+    by_name: Arc<RwLock<BTreeMap<String, (SharedState, Routes)>>>,
+}
+
+impl Datasets {
+    pub fn new(main: SharedState) -> Self {
+        Datasets {
+            main,
+            by_name: Arc::default(),
+        }
+    }
+
+    /// Serves `shared`'s dataset, which has to have a name, with `routes`.
+    /// Returns false, changing nothing, when another dataset has the name.
+    pub fn insert(&self, shared: SharedState, routes: Routes) -> bool {
+        let name = shared
+            .dataset
+            .name
+            .clone()
+            .expect("other datasets are named");
+        match self.by_name.write().unwrap().entry(name) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert((shared, routes));
+                true
+            }
+        }
+    }
+
+    /// The dataset called `name` and its routes, if there is one.
+    pub fn get(&self, name: &str) -> Option<(SharedState, Routes)> {
+        self.by_name.read().unwrap().get(name).cloned()
+    }
+
+    /// Every dataset but the main one, by name.
+    pub fn list(&self) -> Vec<SharedState> {
+        let by_name = self.by_name.read().unwrap();
+        by_name.values().map(|(shared, _)| shared.clone()).collect()
+    }
+
+    /// Serves the dataset called `from` as `to` instead, with what `rename`
+    /// makes of it. Nothing changes when there's no `from`, there is a `to`
+    /// already, or `rename` fails.
+    pub fn rename(
+        &self,
+        from: &str,
+        to: &str,
+        rename: impl FnOnce(&SharedState) -> Result<(SharedState, Routes), String>,
+    ) -> Result<SharedState, RenameError> {
+        let mut by_name = self.by_name.write().unwrap();
+        if by_name.contains_key(to) {
+            return Err(RenameError::Taken);
+        }
+        let (shared, _) = by_name.get(from).ok_or(RenameError::NotFound)?;
+        let (renamed, routes) = rename(shared).map_err(RenameError::Failed)?;
+        by_name.remove(from);
+        by_name.insert(to.to_string(), (renamed.clone(), routes));
+        Ok(renamed)
+    }
+
+    /// Stops serving the dataset called `name`, returning it.
+    pub fn remove(&self, name: &str) -> Option<SharedState> {
+        let (shared, _) = self.by_name.write().unwrap().remove(name)?;
+        shared.retire();
+        Some(shared)
+    }
+}
+
+/// Why a dataset wasn't renamed.
+#[derive(Debug)]
+pub enum RenameError {
+    NotFound,
+    // Another dataset has the name already.
+    Taken,
+    Failed(String),
+}
+
+// --- Hot Reload ---
+
+// Identifies one version of the source's files: their paths, sizes and
+// modification times. Any write changes at least one of them.
+type Fingerprint = Vec<(std::path::PathBuf, u64, Option<SystemTime>)>;
+
+fn fingerprint(dataset: &Dataset) -> Fingerprint {
+    let mut paths = match &dataset.data_source {
+        DataSource::File(path) => vec![path.clone()],
+        DataSource::Directory(dir) => data::data_files(dir).unwrap_or_default(),
+        // There are no local files to watch.
+        DataSource::Url(_) => Vec::new(),
+    };
+    paths.extend(dataset.aliases_file.clone());
+    paths.extend(dataset.countries_file.clone());
+    paths
+        .into_iter()
+        .map(|path| {
+            let metadata = fs::metadata(&path).ok();
+            let len = metadata.as_ref().map_or(0, |m| m.len());
+            let modified = metadata.and_then(|m| m.modified().ok());
+            (path, len, modified)
+        })
+        .collect()
+}
+
+/// Polls the dataset's source every `interval` and swaps in freshly loaded data
+/// whenever its files change. Data that fails to load is reported and the
+/// current snapshot keeps serving; the next change is tried again.
+pub fn watch(shared: SharedState, interval: Duration) {
+    tokio::spawn(async move {
+        let mut last_seen = fingerprint(&shared.dataset);
+        loop {
+            tokio::time::sleep(interval).await;
+            if shared.is_retired() {
+                break;
+            }
+
+            let now = fingerprint(&shared.dataset);
+            if now == last_seen {
+                continue;
+            }
+            last_seen = now;
+
+            log_refresh(&shared.dataset, shared.refresh().await);
+        }
+    });
+}
+
+/// Re-reads the dataset's source every `interval`, for sources that can't be
+/// watched such as URLs, and swaps in the result whenever the content differs.
+pub fn refresh_periodically(shared: SharedState, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if shared.is_retired() {
+                break;
+            }
+            log_refresh(&shared.dataset, shared.refresh().await);
+        }
+    });
+}
+
+/// Logs what re-reading `dataset`'s source came to.
+pub fn log_refresh(dataset: &Dataset, result: Result<Option<ReloadSummary>, String>) {
+    let source = &dataset.data_source;
+    match result {
+        Ok(Some(summary)) => tracing::info!(
+            "->> Reloaded {}: {} total artist performances ({} added, {} removed).",
+            source,
+            summary.performances,
+            summary.added.len(),
+            summary.removed.len()
+        ),
+        // Touched or re-uploaded, but the same content.
+        Ok(None) => {}
+        Err(message) => tracing::warn!("->> Not reloading {}: {}", source, message),
+    }
+}
@@ -0,0 +1,52 @@
+// Statistics over the performances being served, such as how much of a lineup
+// comes from each country.
+
+use crate::artists::Artists;
+use crate::layout::Performance;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Serialize)]
+pub struct CountryCount<'a> {
+    // `None` for artists whose country isn't known.
+    pub country: Option<&'a str>,
+    // Distinct artists from the country, and their performances.
+    pub artists: usize,
+    pub performances: usize,
+    // The fraction of all counted performances, to three decimals.
+    pub share: f64,
+}
+
+/// How many artists and performances of `performances` come from each country,
+/// most performances first, with those of unknown origin last.
+pub fn countries<'a>(
+    artists: &Artists,
+    performances: impl Iterator<Item = Performance<'a>>,
+) -> Vec<CountryCount<'a>> {
+    let mut counts: HashMap<Option<&str>, (HashSet<u32>, usize)> = HashMap::new();
+    let mut total = 0;
+    for performance in performances {
+        let (ids, count) = counts.entry(performance.country()).or_default();
+        ids.insert(artists.of(performance.position()));
+        *count += 1;
+        total += 1;
+    }
+
+    let mut counts: Vec<CountryCount> = counts
+        .into_iter()
+        .map(|(country, (artists, performances))| CountryCount {
+            country,
+            artists: artists.len(),
+            performances,
+            share: (performances as f64 * 1000.0 / total as f64).round() / 1000.0,
+        })
+        .collect();
+    counts.sort_by(|a, b| {
+        (a.country.is_none(), b.performances, a.country).cmp(&(
+            b.country.is_none(),
+            a.performances,
+            b.country,
+        ))
+    });
+    counts
+}
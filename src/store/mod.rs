@@ -0,0 +1,191 @@
+// Persistent homes for the dataset that can be written to as well as read, as
+// opposed to the read-only sources in `data`: a SQLite database file (the
+// `sqlite` cargo feature) or a PostgreSQL database (the `postgres` feature),
+// which several server instances can share.
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use crate::data::{self, BandData, DataSource};
+use std::fmt;
+use std::path::Path;
+
+/// Somewhere the whole dataset can be saved and loaded again later.
+pub trait DataStore {
+    /// Reads the full dataset back, in the order it was saved.
+    fn load(&self) -> Result<BandData, String>;
+
+    /// Replaces everything stored with `data`, all at once: a failed save leaves
+    /// the previous dataset intact.
+    fn save(&self, data: &BandData) -> Result<(), String>;
+}
+
+/// Where a store lives.
+#[derive(Debug, Clone, Copy)]
+pub enum Location<'a> {
+    // A file ending in `.db`, `.sqlite` or `.sqlite3`.
+    Sqlite(&'a Path),
+    // A `postgres://` or `postgresql://` connection URL.
+    Postgres(&'a str),
+}
+
+impl<'a> Location<'a> {
+    /// The store `source` refers to, if it is one rather than plain JSON.
+    pub fn of(source: &'a DataSource) -> Option<Self> {
+        match source {
+            DataSource::File(path) if is_sqlite(path) => Some(Location::Sqlite(path)),
+            DataSource::Url(url) if is_postgres(url) => Some(Location::Postgres(url)),
+            _ => None,
+        }
+    }
+
+    /// Reads a path or URL given on the command line.
+    pub fn parse(text: &'a str) -> Option<Self> {
+        if is_postgres(text) {
+            Some(Location::Postgres(text))
+        } else if is_sqlite(Path::new(text)) {
+            Some(Location::Sqlite(Path::new(text)))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Sqlite(path) => write!(f, "{}", path.display()),
+            Location::Postgres(url) => f.write_str(&data::redact_password(url)),
+        }
+    }
+}
+
+fn is_sqlite(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["db", "sqlite", "sqlite3"].contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+fn is_postgres(url: &str) -> bool {
+    url.starts_with("postgres://") || url.starts_with("postgresql://")
+}
+
+/// Opens the store at `location`, or explains that this build can't.
+pub fn open(location: Location) -> Result<Box<dyn DataStore>, String> {
+    match location {
+        #[cfg(feature = "sqlite")]
+        Location::Sqlite(path) => Ok(Box::new(sqlite::SqliteStore::new(path))),
+        #[cfg(not(feature = "sqlite"))]
+        Location::Sqlite(_) => Err(unsupported(location, "SQLite", "sqlite")),
+        #[cfg(feature = "postgres")]
+        Location::Postgres(url) => Ok(Box::new(postgres::PostgresStore::new(url))),
+        #[cfg(not(feature = "postgres"))]
+        Location::Postgres(_) => Err(unsupported(location, "PostgreSQL", "postgres")),
+    }
+}
+
+#[cfg(not(all(feature = "sqlite", feature = "postgres")))]
+fn unsupported(location: Location, kind: &str, feature: &str) -> String {
+    format!(
+        "{} is a {} database, but this build has no {} support; rebuild with `--features {}`",
+        location, kind, kind, feature
+    )
+}
+
+/// Copies the dataset from `source` into `target`, replacing what's there, and
+/// reports how many performances it copied.
+pub fn import(source: &BandData, target: &dyn DataStore) -> Result<usize, String> {
+    target.save(source)?;
+    Ok(source
+        .festivals
+        .iter()
+        .flat_map(|festival| &festival.years)
+        .map(|year| year.artists.len())
+        .sum())
+}
+
+// What a record holds besides the fields its table has `columns` for (an act's
+// genres, a festival's country) as a JSON object, for the stores' `details`
+// columns; None when there's nothing.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn details(record: &impl serde::Serialize, columns: &[&str]) -> Option<String> {
+    let mut value = serde_json::to_value(record).expect("records serialize");
+    let fields = value.as_object_mut().expect("records serialize as objects");
+    fields.retain(|field, _| !columns.contains(&field.as_str()));
+    (!fields.is_empty()).then(|| value.to_string())
+}
+
+// The record with the given column values and the `details` saved for it, which
+// is called `label` in errors.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn record<T: serde::de::DeserializeOwned>(
+    origin: &str,
+    label: &str,
+    columns: serde_json::Value,
+    details: Option<String>,
+) -> Result<T, String> {
+    let invalid =
+        |e: serde_json::Error| format!("{} has invalid details for \"{}\": {}", origin, label, e);
+    let mut fields: serde_json::Map<String, serde_json::Value> = match details {
+        Some(details) => serde_json::from_str(&details).map_err(invalid)?,
+        None => serde_json::Map::new(),
+    };
+    if let serde_json::Value::Object(columns) = columns {
+        fields.extend(columns);
+    }
+    serde_json::from_value(fields.into()).map_err(invalid)
+}
+
+// One row of the festivals, editions and performances joined together.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+struct Row {
+    festival: String,
+    festival_details: Option<String>,
+    // Missing for festivals without editions and editions without artists, as a
+    // left join returns them.
+    year: Option<i64>,
+    edition_details: Option<String>,
+    artist: Option<String>,
+    details: Option<String>,
+}
+
+// Rebuilds the dataset from rows sorted by festival, edition and position.
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn from_rows(origin: &str, rows: impl IntoIterator<Item = Row>) -> Result<BandData, String> {
+    use crate::data::{Festival, FestivalYear};
+    use serde_json::json;
+
+    let mut data = BandData::default();
+    for row in rows {
+        if data.festivals.last().is_none_or(|f| f.name != row.festival) {
+            data.festivals.push(record::<Festival>(
+                origin,
+                &row.festival,
+                json!({ "name": row.festival, "years": [] }),
+                row.festival_details,
+            )?);
+        }
+        let festival = data.festivals.last_mut().unwrap();
+
+        let Some(year) = row.year else {
+            continue;
+        };
+        let year =
+            u16::try_from(year).map_err(|_| format!("{} has an invalid year {}", origin, year))?;
+        if festival.years.last().is_none_or(|y| y.year != year) {
+            festival.years.push(record::<FestivalYear>(
+                origin,
+                &format!("{} {}", festival.name, year),
+                json!({ "year": year, "artists": [] }),
+                row.edition_details,
+            )?);
+        }
+        if let Some(artist) = row.artist {
+            let act = record(origin, &artist, json!({ "name": artist }), row.details)?;
+            festival.years.last_mut().unwrap().artists.push(act);
+        }
+    }
+    Ok(data)
+}
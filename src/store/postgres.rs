@@ -0,0 +1,425 @@
+// A dataset kept in a PostgreSQL database, through a minimal binding to the
+// system's libpq. Several server instances can read the same database, and an
+// import replaces the dataset for all of them at once.
+//
+// The tables match the SQLite store's: festivals, their editions, and each
+// edition's artists in order, each with their `details`, plus `dataset_info`
+// recording the schema version. Databases from versions 1 and 2, with fewer
+// `details` columns, are read too and upgraded by the next import. Readers keep seeing the previous dataset
+// until an import commits.
+
+use super::DataStore;
+use crate::data::{self, BandData};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+const SCHEMA_VERSION: i64 = 3;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS dataset_info (
+        schema_version INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS festivals (
+        id BIGSERIAL PRIMARY KEY,
+        name TEXT NOT NULL,
+        details JSONB
+    );
+    CREATE UNIQUE INDEX IF NOT EXISTS festivals_by_name ON festivals (lower(name));
+    CREATE TABLE IF NOT EXISTS editions (
+        id BIGSERIAL PRIMARY KEY,
+        festival_id BIGINT NOT NULL REFERENCES festivals (id),
+        year INTEGER NOT NULL,
+        details JSONB,
+        UNIQUE (festival_id, year)
+    );
+    CREATE TABLE IF NOT EXISTS performances (
+        id BIGSERIAL PRIMARY KEY,
+        edition_id BIGINT NOT NULL REFERENCES editions (id),
+        position INTEGER NOT NULL,
+        artist TEXT NOT NULL,
+        details JSONB
+    );
+    ALTER TABLE festivals ADD COLUMN IF NOT EXISTS details JSONB;
+    ALTER TABLE editions ADD COLUMN IF NOT EXISTS details JSONB;
+    ALTER TABLE performances ADD COLUMN IF NOT EXISTS details JSONB;
+    CREATE INDEX IF NOT EXISTS performances_by_edition ON performances (edition_id, position);
+";
+
+// Held for the length of an import, so two instances importing at once take turns
+// (including creating the tables) instead of interleaving. Any constant will do.
+const IMPORT_LOCK: i64 = 0x006e_6f67_2065_656e;
+
+// Seconds to wait for the server, unless the URL says otherwise.
+const CONNECT_TIMEOUT: &CStr = c"10";
+
+/// A PostgreSQL database holding one dataset.
+#[derive(Debug, Clone)]
+pub struct PostgresStore {
+    url: String,
+}
+
+impl PostgresStore {
+    pub fn new(url: &str) -> Self {
+        PostgresStore {
+            url: url.to_string(),
+        }
+    }
+
+    fn connect(&self) -> Result<Connection, String> {
+        Connection::open(&self.url).map_err(|e| self.error(&e))
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{}: {}", data::redact_password(&self.url), message)
+    }
+}
+
+impl DataStore for PostgresStore {
+    fn load(&self) -> Result<BandData, String> {
+        let db = self.connect()?;
+        let exists = db.query("SELECT to_regclass('dataset_info') IS NOT NULL", &[])?;
+        let version = if exists.value(0, 0).as_deref() == Some("t") {
+            let version = db.query("SELECT schema_version FROM dataset_info", &[])?;
+            version.value(0, 0).and_then(|v| v.parse().ok())
+        } else {
+            None
+        };
+        let details = match version {
+            Some(1) => "NULL, NULL, NULL",
+            Some(2) => "NULL, NULL, p.details",
+            Some(SCHEMA_VERSION) => "f.details, e.details, p.details",
+            None => return Err(self.error("holds no dataset yet; import one with --import-into")),
+            Some(other) => {
+                return Err(self.error(&format!(
+                    "uses schema version {}, but this build only reads versions 1 to {}",
+                    other, SCHEMA_VERSION
+                )))
+            }
+        };
+
+        // Left joins keep festivals and editions that have no artists (yet).
+        let rows = db.query(
+            &format!(
+                "SELECT f.name, e.year, p.artist, {}
+                 FROM festivals f
+                 LEFT JOIN editions e ON e.festival_id = f.id
+                 LEFT JOIN performances p ON p.edition_id = e.id
+                 ORDER BY f.id, e.id, p.position",
+                details
+            ),
+            &[],
+        )?;
+        let all = (0..rows.len()).map(|row| super::Row {
+            festival: rows.value(row, 0).unwrap_or_default(),
+            year: rows.value(row, 1).and_then(|year| year.parse().ok()),
+            artist: rows.value(row, 2),
+            festival_details: rows.value(row, 3),
+            edition_details: rows.value(row, 4),
+            details: rows.value(row, 5),
+        });
+        super::from_rows(&data::redact_password(&self.url), all)
+    }
+
+    fn save(&self, data: &BandData) -> Result<(), String> {
+        let db = self.connect()?;
+
+        // Everything below happens in one transaction, rolled back if any of it fails.
+        db.execute("BEGIN")?;
+        match replace_contents(&db, data) {
+            Ok(()) => db.execute("COMMIT"),
+            Err(e) => {
+                let _ = db.execute("ROLLBACK");
+                Err(self.error(&e))
+            }
+        }
+    }
+}
+
+fn replace_contents(db: &Connection, data: &BandData) -> Result<(), String> {
+    db.query(
+        "SELECT pg_advisory_xact_lock($1)",
+        &[&IMPORT_LOCK.to_string()],
+    )?;
+    db.execute(SCHEMA)?;
+
+    let version = db.query("SELECT schema_version FROM dataset_info", &[])?;
+    match version.value(0, 0).and_then(|v| v.parse::<i64>().ok()) {
+        None => {
+            db.query(
+                "INSERT INTO dataset_info (schema_version) VALUES ($1)",
+                &[&SCHEMA_VERSION.to_string()],
+            )?;
+        }
+        Some(1 | 2) => {
+            // The columns were added above.
+            db.query(
+                "UPDATE dataset_info SET schema_version = $1",
+                &[&SCHEMA_VERSION.to_string()],
+            )?;
+        }
+        Some(SCHEMA_VERSION) => {}
+        Some(other) => {
+            return Err(format!(
+                "uses schema version {}; refusing to overwrite it",
+                other
+            ))
+        }
+    }
+    db.execute("DELETE FROM performances; DELETE FROM editions; DELETE FROM festivals;")?;
+
+    for festival in &data.festivals {
+        // Parameters can't be NULL, so no details are sent as an empty string.
+        let details = super::details(festival, &["name", "years"]);
+        let inserted = db.query(
+            "INSERT INTO festivals (name, details) VALUES ($1, NULLIF($2, '')::jsonb) RETURNING id",
+            &[&festival.name, details.as_deref().unwrap_or_default()],
+        )?;
+        let festival_id = inserted.value(0, 0).unwrap_or_default();
+        for year in &festival.years {
+            let details = super::details(year, &["year", "artists"]);
+            let inserted = db.query(
+                "INSERT INTO editions (festival_id, year, details)
+                 VALUES ($1, $2, NULLIF($3, '')::jsonb) RETURNING id",
+                &[
+                    &festival_id,
+                    &year.year.to_string(),
+                    details.as_deref().unwrap_or_default(),
+                ],
+            )?;
+            let edition_id = inserted.value(0, 0).unwrap_or_default();
+            if year.artists.is_empty() {
+                continue;
+            }
+            // One round trip per edition rather than per artist.
+            let details: Vec<Option<String>> = year
+                .artists
+                .iter()
+                .map(|act| super::details(act, &["name"]))
+                .collect();
+            db.query(
+                "INSERT INTO performances (edition_id, position, artist, details)
+                 SELECT $1, ordinality - 1, artist, details::jsonb
+                 FROM unnest($2::text[], $3::text[]) WITH ORDINALITY AS t (artist, details, ordinality)",
+                &[
+                    &edition_id,
+                    &text_array(year.artists.iter().map(|act| Some(act.name.as_str()))),
+                    &text_array(details.iter().map(Option::as_deref)),
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// A PostgreSQL array literal, `{"a","b",NULL}`, with quotes and backslashes escaped.
+fn text_array<'a>(items: impl Iterator<Item = Option<&'a str>>) -> String {
+    let quoted: Vec<String> = items
+        .map(|item| match item {
+            Some(item) => format!("\"{}\"", item.replace('\\', "\\\\").replace('"', "\\\"")),
+            None => "NULL".to_string(),
+        })
+        .collect();
+    format!("{{{}}}", quoted.join(","))
+}
+
+// --- libpq Binding ---
+
+// Just the parts of the C API used above.
+mod ffi {
+    use std::os::raw::{c_char, c_int};
+
+    #[repr(C)]
+    pub struct PGconn {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct PGresult {
+        _private: [u8; 0],
+    }
+
+    pub const CONNECTION_OK: c_int = 0;
+    pub const PGRES_COMMAND_OK: c_int = 1;
+    pub const PGRES_TUPLES_OK: c_int = 2;
+
+    #[link(name = "pq")]
+    extern "C" {
+        pub fn PQconnectdbParams(
+            keywords: *const *const c_char,
+            values: *const *const c_char,
+            expand_dbname: c_int,
+        ) -> *mut PGconn;
+        pub fn PQstatus(conn: *const PGconn) -> c_int;
+        pub fn PQerrorMessage(conn: *const PGconn) -> *const c_char;
+        pub fn PQfinish(conn: *mut PGconn);
+        pub fn PQexec(conn: *mut PGconn, command: *const c_char) -> *mut PGresult;
+        pub fn PQexecParams(
+            conn: *mut PGconn,
+            command: *const c_char,
+            n_params: c_int,
+            param_types: *const u32,
+            param_values: *const *const c_char,
+            param_lengths: *const c_int,
+            param_formats: *const c_int,
+            result_format: c_int,
+        ) -> *mut PGresult;
+        pub fn PQresultStatus(res: *const PGresult) -> c_int;
+        pub fn PQresultErrorMessage(res: *const PGresult) -> *const c_char;
+        pub fn PQntuples(res: *const PGresult) -> c_int;
+        pub fn PQgetisnull(res: *const PGresult, row: c_int, column: c_int) -> c_int;
+        pub fn PQgetvalue(res: *const PGresult, row: c_int, column: c_int) -> *const c_char;
+        pub fn PQclear(res: *mut PGresult);
+    }
+}
+
+fn c_string(text: &str) -> Result<CString, String> {
+    CString::new(text).map_err(|_| format!("{:?} contains a NUL byte", text))
+}
+
+// libpq's messages end in a newline and may span several lines.
+fn message(text: *const c_char) -> String {
+    if text.is_null() {
+        return "out of memory".to_string();
+    }
+    // SAFETY: libpq returns NUL-terminated strings that live as long as the
+    // connection or result they came from; this copies it right away.
+    let text = unsafe { CStr::from_ptr(text) };
+    text.to_string_lossy().trim().replace('\n', " ")
+}
+
+// An open connection, closed when dropped.
+struct Connection {
+    conn: *mut ffi::PGconn,
+}
+
+impl Connection {
+    fn open(url: &str) -> Result<Self, String> {
+        // Settings in the URL come later, so they override these defaults.
+        // Notices ("relation already exists, skipping") would go straight to stderr.
+        let keywords = [
+            c"connect_timeout",
+            c"application_name",
+            c"options",
+            c"dbname",
+        ];
+        let url = c_string(url)?;
+        let values = [
+            CONNECT_TIMEOUT.as_ptr(),
+            c"nog-een-bandje".as_ptr(),
+            c"-c client_min_messages=warning".as_ptr(),
+            url.as_ptr(),
+        ];
+        let keywords: Vec<*const c_char> = keywords
+            .iter()
+            .map(|k| k.as_ptr())
+            .chain([ptr::null()])
+            .collect();
+        let values: Vec<*const c_char> = values.into_iter().chain([ptr::null()]).collect();
+
+        // SAFETY: both arrays are NULL-terminated, and their strings outlive the call.
+        let conn = unsafe { ffi::PQconnectdbParams(keywords.as_ptr(), values.as_ptr(), 1) };
+        if conn.is_null() {
+            return Err("out of memory".to_string());
+        }
+        // Owned from here on, so it's finished even when connecting failed.
+        let connection = Connection { conn };
+        // SAFETY: a non-null handle, which libpq accepts whether or not it connected.
+        if unsafe { ffi::PQstatus(conn) } != ffi::CONNECTION_OK {
+            return Err(message(unsafe { ffi::PQerrorMessage(conn) }));
+        }
+        Ok(connection)
+    }
+
+    // Runs one or more statements that take no parameters.
+    fn execute(&self, sql: &str) -> Result<(), String> {
+        let sql = c_string(sql)?;
+        // SAFETY: a valid connection and a NUL-terminated command.
+        let result = QueryResult {
+            res: unsafe { ffi::PQexec(self.conn, sql.as_ptr()) },
+        };
+        self.check(result).map(|_| ())
+    }
+
+    // Runs a single statement with `params` as $1, $2, ..., all sent as text.
+    fn query(&self, sql: &str, params: &[&str]) -> Result<QueryResult, String> {
+        let sql = c_string(sql)?;
+        let params = params
+            .iter()
+            .map(|param| c_string(param))
+            .collect::<Result<Vec<_>, _>>()?;
+        let values: Vec<*const c_char> = params.iter().map(|p| p.as_ptr()).collect();
+
+        // SAFETY: a valid connection; `values` holds `params.len()` NUL-terminated
+        // strings that outlive the call. Null types, lengths and formats mean
+        // "infer the types, and everything is text".
+        let res = unsafe {
+            ffi::PQexecParams(
+                self.conn,
+                sql.as_ptr(),
+                values.len() as c_int,
+                ptr::null(),
+                values.as_ptr(),
+                ptr::null(),
+                ptr::null(),
+                0,
+            )
+        };
+        self.check(QueryResult { res })
+    }
+
+    fn check(&self, result: QueryResult) -> Result<QueryResult, String> {
+        if result.res.is_null() {
+            // SAFETY: a valid connection.
+            return Err(message(unsafe { ffi::PQerrorMessage(self.conn) }));
+        }
+        // SAFETY: a valid, non-null result.
+        match unsafe { ffi::PQresultStatus(result.res) } {
+            ffi::PGRES_COMMAND_OK | ffi::PGRES_TUPLES_OK => Ok(result),
+            _ => Err(message(unsafe { ffi::PQresultErrorMessage(result.res) })),
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from `PQconnectdbParams` and is finished only here.
+        unsafe { ffi::PQfinish(self.conn) };
+    }
+}
+
+// The rows a statement returned, freed when dropped.
+struct QueryResult {
+    res: *mut ffi::PGresult,
+}
+
+impl QueryResult {
+    fn len(&self) -> usize {
+        // SAFETY: a valid result.
+        unsafe { ffi::PQntuples(self.res) as usize }
+    }
+
+    // The text of one field, or `None` for NULL or a row that isn't there.
+    fn value(&self, row: usize, column: c_int) -> Option<String> {
+        if row >= self.len() {
+            return None;
+        }
+        let row = row as c_int;
+        // SAFETY: a valid result, and `row` is in range; the value is copied
+        // before the result can be freed.
+        unsafe {
+            if ffi::PQgetisnull(self.res, row, column) != 0 {
+                return None;
+            }
+            let value = CStr::from_ptr(ffi::PQgetvalue(self.res, row, column));
+            Some(value.to_string_lossy().into_owned())
+        }
+    }
+}
+
+impl Drop for QueryResult {
+    fn drop(&mut self) {
+        // SAFETY: freeing a null result is a no-op; otherwise it's freed only here.
+        unsafe { ffi::PQclear(self.res) };
+    }
+}
@@ -0,0 +1,554 @@
+// A dataset kept in a SQLite database file, through a minimal binding to the
+// system's libsqlite3.
+//
+// The schema mirrors the JSON format: festivals, their editions, and each
+// edition's artists in order, with whatever else a record holds (a festival's
+// country, a performance's genres) as JSON in `details`. `user_version` records
+// the schema version, so a database from a newer release is refused rather than
+// misread. Versions 1 (no `details`) and 2 (`details` on performances only) are
+// still read, and upgraded the next time the database is written.
+
+use super::DataStore;
+use crate::data::BandData;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+const SCHEMA_VERSION: i64 = 3;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS festivals (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        details TEXT
+    );
+    CREATE TABLE IF NOT EXISTS editions (
+        id INTEGER PRIMARY KEY,
+        festival_id INTEGER NOT NULL REFERENCES festivals (id),
+        year INTEGER NOT NULL,
+        details TEXT,
+        UNIQUE (festival_id, year)
+    );
+    CREATE TABLE IF NOT EXISTS performances (
+        id INTEGER PRIMARY KEY,
+        edition_id INTEGER NOT NULL REFERENCES editions (id),
+        position INTEGER NOT NULL,
+        artist TEXT NOT NULL,
+        details TEXT
+    );
+    CREATE INDEX IF NOT EXISTS performances_by_edition ON performances (edition_id, position);
+";
+
+// Another process (an import, say) may be writing; wait this long for it.
+const BUSY_TIMEOUT_MS: c_int = 5000;
+
+/// A SQLite database file holding one dataset.
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    path: PathBuf,
+}
+
+impl SqliteStore {
+    pub fn new(path: &Path) -> Self {
+        SqliteStore {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl DataStore for SqliteStore {
+    fn load(&self) -> Result<BandData, String> {
+        let db = Connection::open(&self.path, ffi::SQLITE_OPEN_READONLY)?;
+        let version = db.schema_version()?;
+        match version {
+            1..=SCHEMA_VERSION => {}
+            0 => {
+                return Err(format!(
+                    "{} holds no dataset yet; import one with --import-into",
+                    self.path.display()
+                ))
+            }
+            other => {
+                return Err(format!(
+                    "{} uses schema version {}, but this build only reads versions 1 to {}",
+                    self.path.display(),
+                    other,
+                    SCHEMA_VERSION
+                ))
+            }
+        }
+
+        // Left joins keep festivals and editions that have no artists (yet).
+        let details = match version {
+            1 => "NULL, NULL, NULL",
+            2 => "NULL, NULL, p.details",
+            _ => "f.details, e.details, p.details",
+        };
+        let mut rows = db.prepare(&format!(
+            "SELECT f.name, e.year, p.artist, {}
+             FROM festivals f
+             LEFT JOIN editions e ON e.festival_id = f.id
+             LEFT JOIN performances p ON p.edition_id = e.id
+             ORDER BY f.id, e.id, p.position",
+            details
+        ))?;
+        let mut all = Vec::new();
+        while rows.step()? {
+            all.push(super::Row {
+                festival: rows.text(0).unwrap_or_default(),
+                year: rows.integer(1),
+                artist: rows.text(2),
+                festival_details: rows.text(3),
+                edition_details: rows.text(4),
+                details: rows.text(5),
+            });
+        }
+        super::from_rows(&self.path.display().to_string(), all)
+    }
+
+    fn save(&self, data: &BandData) -> Result<(), String> {
+        let db = Connection::open(
+            &self.path,
+            ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+        )?;
+        let version = db.schema_version()?;
+        match version {
+            0..=SCHEMA_VERSION => {}
+            other => {
+                return Err(format!(
+                    "{} uses schema version {}; refusing to overwrite it",
+                    self.path.display(),
+                    other
+                ))
+            }
+        }
+
+        // Everything below happens in one transaction, rolled back if any of it fails.
+        db.execute_batch("BEGIN IMMEDIATE")?;
+        let result = replace_contents(&db, version, data);
+        match result {
+            Ok(()) => db.execute_batch("COMMIT"),
+            Err(e) => {
+                let _ = db.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+}
+
+fn replace_contents(db: &Connection, version: i64, data: &BandData) -> Result<(), String> {
+    db.execute_batch(SCHEMA)?;
+    if version == 1 {
+        db.execute_batch("ALTER TABLE performances ADD COLUMN details TEXT")?;
+    }
+    if (1..=2).contains(&version) {
+        db.execute_batch(
+            "ALTER TABLE festivals ADD COLUMN details TEXT;
+             ALTER TABLE editions ADD COLUMN details TEXT;",
+        )?;
+    }
+    db.execute_batch(&format!(
+        "DELETE FROM performances; DELETE FROM editions; DELETE FROM festivals;
+         PRAGMA user_version = {};",
+        SCHEMA_VERSION
+    ))?;
+
+    let mut insert_festival =
+        db.prepare("INSERT INTO festivals (name, details) VALUES (?1, ?2)")?;
+    let mut insert_edition =
+        db.prepare("INSERT INTO editions (festival_id, year, details) VALUES (?1, ?2, ?3)")?;
+    let mut insert_performance = db.prepare(
+        "INSERT INTO performances (edition_id, position, artist, details) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+
+    for festival in &data.festivals {
+        let details = super::details(festival, &["name", "years"]);
+        insert_festival.execute(&[Param::Text(&festival.name), Param::text(&details)])?;
+        let festival_id = db.last_insert_rowid();
+        for year in &festival.years {
+            let details = super::details(year, &["year", "artists"]);
+            insert_edition.execute(&[
+                Param::Integer(festival_id),
+                Param::Integer(year.year.into()),
+                Param::text(&details),
+            ])?;
+            let edition_id = db.last_insert_rowid();
+            for (position, act) in year.artists.iter().enumerate() {
+                let details = super::details(act, &["name"]);
+                insert_performance.execute(&[
+                    Param::Integer(edition_id),
+                    Param::Integer(position as i64),
+                    Param::Text(&act.name),
+                    Param::text(&details),
+                ])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// --- SQLite Binding ---
+
+// Just the parts of the C API used above.
+mod ffi {
+    use std::os::raw::{c_char, c_int, c_void};
+
+    #[repr(C)]
+    pub struct Sqlite3 {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct Stmt {
+        _private: [u8; 0],
+    }
+
+    pub const SQLITE_OK: c_int = 0;
+    pub const SQLITE_ROW: c_int = 100;
+    pub const SQLITE_DONE: c_int = 101;
+    pub const SQLITE_NULL: c_int = 5;
+    pub const SQLITE_OPEN_READONLY: c_int = 0x1;
+    pub const SQLITE_OPEN_READWRITE: c_int = 0x2;
+    pub const SQLITE_OPEN_CREATE: c_int = 0x4;
+    // Tells SQLite to copy bound text before the call returns.
+    pub const SQLITE_TRANSIENT: isize = -1;
+
+    #[link(name = "sqlite3")]
+    extern "C" {
+        pub fn sqlite3_open_v2(
+            filename: *const c_char,
+            db: *mut *mut Sqlite3,
+            flags: c_int,
+            vfs: *const c_char,
+        ) -> c_int;
+        pub fn sqlite3_close(db: *mut Sqlite3) -> c_int;
+        pub fn sqlite3_errmsg(db: *mut Sqlite3) -> *const c_char;
+        pub fn sqlite3_busy_timeout(db: *mut Sqlite3, ms: c_int) -> c_int;
+        pub fn sqlite3_exec(
+            db: *mut Sqlite3,
+            sql: *const c_char,
+            callback: *const c_void,
+            arg: *mut c_void,
+            errmsg: *mut *mut c_char,
+        ) -> c_int;
+        pub fn sqlite3_last_insert_rowid(db: *mut Sqlite3) -> i64;
+        pub fn sqlite3_prepare_v2(
+            db: *mut Sqlite3,
+            sql: *const c_char,
+            bytes: c_int,
+            stmt: *mut *mut Stmt,
+            tail: *mut *const c_char,
+        ) -> c_int;
+        pub fn sqlite3_bind_text(
+            stmt: *mut Stmt,
+            index: c_int,
+            text: *const c_char,
+            bytes: c_int,
+            destructor: isize,
+        ) -> c_int;
+        pub fn sqlite3_bind_int64(stmt: *mut Stmt, index: c_int, value: i64) -> c_int;
+        pub fn sqlite3_bind_null(stmt: *mut Stmt, index: c_int) -> c_int;
+        pub fn sqlite3_step(stmt: *mut Stmt) -> c_int;
+        pub fn sqlite3_reset(stmt: *mut Stmt) -> c_int;
+        pub fn sqlite3_finalize(stmt: *mut Stmt) -> c_int;
+        pub fn sqlite3_column_type(stmt: *mut Stmt, column: c_int) -> c_int;
+        pub fn sqlite3_column_int64(stmt: *mut Stmt, column: c_int) -> i64;
+        pub fn sqlite3_column_text(stmt: *mut Stmt, column: c_int) -> *const u8;
+        pub fn sqlite3_column_bytes(stmt: *mut Stmt, column: c_int) -> c_int;
+    }
+}
+
+// An open database, closed when dropped.
+struct Connection {
+    db: *mut ffi::Sqlite3,
+    path: PathBuf,
+}
+
+enum Param<'a> {
+    Text(&'a str),
+    Integer(i64),
+    Null,
+}
+
+impl<'a> Param<'a> {
+    // Text, or NULL for nothing.
+    fn text(text: &'a Option<String>) -> Self {
+        text.as_deref().map_or(Param::Null, Param::Text)
+    }
+}
+
+fn c_string(text: &str) -> Result<CString, String> {
+    CString::new(text).map_err(|_| "SQL text contains a NUL byte".to_string())
+}
+
+impl Connection {
+    fn open(path: &Path, flags: c_int) -> Result<Self, String> {
+        let filename = c_string(&path.to_string_lossy())?;
+        let mut db = ptr::null_mut();
+        // SAFETY: `filename` is NUL-terminated and outlives the call; SQLite
+        // writes a handle (even on failure) that `Connection` then owns and closes.
+        let code = unsafe { ffi::sqlite3_open_v2(filename.as_ptr(), &mut db, flags, ptr::null()) };
+        let connection = Connection {
+            db,
+            path: path.to_path_buf(),
+        };
+        if code != ffi::SQLITE_OK {
+            return Err(connection.error());
+        }
+        // SAFETY: `db` is a valid, open handle.
+        unsafe { ffi::sqlite3_busy_timeout(connection.db, BUSY_TIMEOUT_MS) };
+        Ok(connection)
+    }
+
+    // SQLite's description of the last failure on this connection.
+    fn error(&self) -> String {
+        if self.db.is_null() {
+            return format!("Failed to open {}: out of memory", self.path.display());
+        }
+        // SAFETY: `sqlite3_errmsg` returns a NUL-terminated string owned by SQLite,
+        // valid until the next call on this connection; it's copied right away.
+        let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.db)) };
+        format!("{}: {}", self.path.display(), message.to_string_lossy())
+    }
+
+    fn execute_batch(&self, sql: &str) -> Result<(), String> {
+        let sql = c_string(sql)?;
+        // SAFETY: a valid handle and a NUL-terminated string; no callback.
+        let code = unsafe {
+            ffi::sqlite3_exec(
+                self.db,
+                sql.as_ptr(),
+                ptr::null(),
+                ptr::null_mut::<c_void>(),
+                ptr::null_mut::<*mut c_char>(),
+            )
+        };
+        if code == ffi::SQLITE_OK {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn prepare(&self, sql: &str) -> Result<Statement<'_>, String> {
+        let sql = c_string(sql)?;
+        let mut stmt = ptr::null_mut();
+        // SAFETY: a valid handle and a NUL-terminated string (-1 means "up to the NUL").
+        let code = unsafe {
+            ffi::sqlite3_prepare_v2(self.db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut())
+        };
+        if code != ffi::SQLITE_OK {
+            return Err(self.error());
+        }
+        Ok(Statement {
+            connection: self,
+            stmt,
+        })
+    }
+
+    fn last_insert_rowid(&self) -> i64 {
+        // SAFETY: a valid handle.
+        unsafe { ffi::sqlite3_last_insert_rowid(self.db) }
+    }
+
+    fn schema_version(&self) -> Result<i64, String> {
+        let mut version = self.prepare("PRAGMA user_version")?;
+        version.step()?;
+        Ok(version.integer(0).unwrap_or(0))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // SAFETY: every statement borrows the connection, so all are finalized by
+        // now; closing a null handle is a no-op.
+        unsafe { ffi::sqlite3_close(self.db) };
+    }
+}
+
+// A prepared statement, finalized when dropped.
+struct Statement<'c> {
+    connection: &'c Connection,
+    stmt: *mut ffi::Stmt,
+}
+
+impl Statement<'_> {
+    // Runs the statement once with `params` bound to ?1, ?2, ..., ready to run again.
+    fn execute(&mut self, params: &[Param]) -> Result<(), String> {
+        for (i, param) in params.iter().enumerate() {
+            let index = i as c_int + 1;
+            // SAFETY: a valid statement; text is passed with its length and copied
+            // by SQLite (SQLITE_TRANSIENT), so it needn't outlive the call.
+            let code = unsafe {
+                match param {
+                    Param::Text(text) => ffi::sqlite3_bind_text(
+                        self.stmt,
+                        index,
+                        text.as_ptr() as *const c_char,
+                        text.len() as c_int,
+                        ffi::SQLITE_TRANSIENT,
+                    ),
+                    Param::Integer(value) => ffi::sqlite3_bind_int64(self.stmt, index, *value),
+                    Param::Null => ffi::sqlite3_bind_null(self.stmt, index),
+                }
+            };
+            if code != ffi::SQLITE_OK {
+                return Err(self.connection.error());
+            }
+        }
+        while self.step()? {}
+        // SAFETY: a valid statement.
+        unsafe { ffi::sqlite3_reset(self.stmt) };
+        Ok(())
+    }
+
+    // Advances to the next row, returning false once there are none left.
+    fn step(&mut self) -> Result<bool, String> {
+        // SAFETY: a valid statement.
+        match unsafe { ffi::sqlite3_step(self.stmt) } {
+            ffi::SQLITE_ROW => Ok(true),
+            ffi::SQLITE_DONE => Ok(false),
+            _ => Err(self.connection.error()),
+        }
+    }
+
+    fn is_null(&self, column: c_int) -> bool {
+        // SAFETY: a valid statement positioned on a row.
+        unsafe { ffi::sqlite3_column_type(self.stmt, column) == ffi::SQLITE_NULL }
+    }
+
+    fn integer(&self, column: c_int) -> Option<i64> {
+        // SAFETY: a valid statement positioned on a row.
+        (!self.is_null(column)).then(|| unsafe { ffi::sqlite3_column_int64(self.stmt, column) })
+    }
+
+    fn text(&self, column: c_int) -> Option<String> {
+        if self.is_null(column) {
+            return None;
+        }
+        // SAFETY: the pointer and length describe the column's UTF-8 text, valid
+        // until the next step; it's copied before then. `column_text` has to be
+        // called before `column_bytes` for the length to match.
+        unsafe {
+            let text = ffi::sqlite3_column_text(self.stmt, column);
+            let len = ffi::sqlite3_column_bytes(self.stmt, column) as usize;
+            if text.is_null() {
+                return Some(String::new());
+            }
+            let bytes = std::slice::from_raw_parts(text, len);
+            Some(String::from_utf8_lossy(bytes).into_owned())
+        }
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        // SAFETY: a valid statement, finalized exactly once.
+        unsafe { ffi::sqlite3_finalize(self.stmt) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, Festival, FestivalYear};
+    use crate::schema;
+
+    fn dataset(artists: &[&str]) -> BandData {
+        BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![
+                Festival {
+                    name: "Pinkpop".to_string(),
+                    years: vec![
+                        FestivalYear {
+                            year: 2008,
+                            artists: artists.iter().map(|a| Act::named(a.to_string())).collect(),
+                            ..FestivalYear::default()
+                        },
+                        // An edition without artists survives the round trip too.
+                        FestivalYear {
+                            year: 2009,
+                            ..FestivalYear::default()
+                        },
+                    ],
+                    ..Festival::default()
+                },
+                Festival {
+                    name: "Lowlands".to_string(),
+                    ..Festival::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn saves_and_loads_the_same_dataset() {
+        let path = std::env::temp_dir().join(format!("bands-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = SqliteStore::new(&path);
+
+        assert!(store.load().is_err());
+
+        let mut first = dataset(&[
+            "Kaiser Chiefs",
+            "Él Mató a un Policía Motorizado",
+            "the Kooks",
+        ]);
+        first.festivals[0].years[0].artists[1].genres = vec!["rock".to_string()];
+        first.festivals[0].country = Some("NL".to_string());
+        first.festivals[0].years[0].start_date = Some("2008-05-10".to_string());
+        store.save(&first).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(
+            serde_json::to_string(&loaded).unwrap(),
+            serde_json::to_string(&first).unwrap()
+        );
+
+        // Saving again replaces the dataset rather than adding to it.
+        let second = dataset(&["Editors"]);
+        store.save(&second).unwrap();
+        assert_eq!(
+            serde_json::to_string(&store.load().unwrap()).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn reads_and_upgrades_version_1_databases() {
+        let path = std::env::temp_dir().join(format!("bands-test-v1-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db =
+            Connection::open(&path, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE).unwrap();
+        db.execute_batch(
+            "CREATE TABLE festivals (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE COLLATE NOCASE);
+             CREATE TABLE editions (id INTEGER PRIMARY KEY, festival_id INTEGER NOT NULL, year INTEGER NOT NULL);
+             CREATE TABLE performances (id INTEGER PRIMARY KEY, edition_id INTEGER NOT NULL,
+                 position INTEGER NOT NULL, artist TEXT NOT NULL);
+             INSERT INTO festivals VALUES (1, 'Pinkpop');
+             INSERT INTO editions VALUES (1, 1, 2008);
+             INSERT INTO performances VALUES (1, 1, 0, 'Muse');
+             PRAGMA user_version = 1;",
+        )
+        .unwrap();
+        drop(db);
+
+        let store = SqliteStore::new(&path);
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.festivals[0].years[0].artists[0].name, "Muse");
+
+        let mut upgraded = dataset(&["Slayer"]);
+        upgraded.festivals[0].years[0].artists[0].genres = vec!["metal".to_string()];
+        upgraded.festivals[1].location = Some("Biddinghuizen".to_string());
+        store.save(&upgraded).unwrap();
+        assert_eq!(
+            serde_json::to_string(&store.load().unwrap()).unwrap(),
+            serde_json::to_string(&upgraded).unwrap()
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,72 @@
+// Time limits on handlers: one that takes longer than its route allows is
+// aborted, and the client gets a 503 instead. Most of the API answers from
+// memory, so the default is short; exporting a whole dataset and the admin API,
+// which reads sources and uploads, get longer.
+
+use crate::{ratelimit, CONFIG};
+use axum::extract::Request;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::{JoinError, JoinHandle};
+use tracing::{Instrument, Span};
+
+/// Middleware giving up on public requests after `limits.read_timeout`, or
+/// `limits.export_timeout` for exports.
+pub async fn public(request: Request, next: Next) -> Response {
+    let limit = if ratelimit::is_export(request.uri().path()) {
+        CONFIG.limits.export_timeout
+    } else {
+        CONFIG.limits.read_timeout
+    };
+    run(limit, request, next).await
+}
+
+/// Middleware giving up on admin requests after `limits.admin_timeout`.
+pub async fn admin(request: Request, next: Next) -> Response {
+    run(CONFIG.limits.admin_timeout, request, next).await
+}
+
+async fn run(limit: Option<Duration>, request: Request, next: Next) -> Response {
+    let Some(limit) = limit else {
+        return next.run(request).await;
+    };
+    // The handler runs as a task of its own, so the 503 goes out on time even
+    // while it's busy, and it's stopped at the next point where it waits. It
+    // logs in the request's span all the same.
+    let handler = next.run(request).instrument(Span::current());
+    let task = Abort(tokio::spawn(handler));
+    match tokio::time::timeout(limit, task).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(error)) => std::panic::resume_unwind(error.into_panic()),
+        Err(_) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "error": format!("The request took longer than {} seconds.", limit.as_secs()),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+// A handler's task, aborted when it's dropped: on a timeout, or when the client
+// goes away.
+struct Abort(JoinHandle<Response>);
+
+impl Future for Abort {
+    type Output = Result<Response, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl Drop for Abort {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
@@ -0,0 +1,565 @@
+// Just enough TOML for `config.toml` and for data files: `[table]` and
+// `[[array of tables]]` headers, `key = value` pairs, `#` comments, and values
+// that are strings (basic or literal), integers, booleans, arrays of those
+// (which may span lines) or inline tables. Dates, floats and multi-line strings
+// aren't supported and are reported as errors.
+//
+// `parse` keeps each table's keys with their lines, for the config file, whose
+// tables are all flat; `parse_tree` nests the tables, for data files.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// The TOML name of this value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+
+    /// The same value as JSON, for reading it as a dataset.
+    pub fn into_json(self) -> serde_json::Value {
+        match self {
+            Value::String(text) => serde_json::Value::String(text),
+            Value::Integer(number) => serde_json::Value::from(number),
+            Value::Boolean(flag) => serde_json::Value::Bool(flag),
+            Value::Array(items) => {
+                serde_json::Value::Array(items.into_iter().map(Value::into_json).collect())
+            }
+            Value::Table(entries) => serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into_json()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A value and the line it was defined on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub value: Value,
+    pub line: usize,
+}
+
+/// Top-level keys live in the table named "".
+pub type Document = BTreeMap<String, BTreeMap<String, Entry>>;
+
+/// Parses `text`, or describes the first problem as "line N: ...".
+pub fn parse(text: &str) -> Result<Document, String> {
+    let mut parser = Parser::new(text);
+    parser
+        .document()
+        .map_err(|message| format!("line {}: {}", parser.line, message))
+}
+
+/// Parses `text` into one table, with `[a.b]` as table `b` inside table `a` and
+/// `[[a]]` as the next table in array `a`.
+pub fn parse_tree(text: &str) -> Result<Value, String> {
+    let mut parser = Parser::new(text);
+    parser
+        .tree()
+        .map(Value::Table)
+        .map_err(|message| format!("line {}: {}", parser.line, message))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Self {
+        Parser {
+            chars: text.chars().collect(),
+            position: 0,
+            line: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Spaces and tabs only; newlines end statements.
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    // Blanks, comments and newlines, as allowed between statements and array items.
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.skip_blanks();
+            self.skip_comment();
+            if !(self.eat('\n') || self.eat('\r')) {
+                break;
+            }
+        }
+    }
+
+    // Whatever follows a statement on its line may only be a comment.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_blanks();
+        self.skip_comment();
+        self.eat('\r');
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(format!("unexpected '{}' after value", c)),
+        }
+    }
+
+    fn document(&mut self) -> Result<Document, String> {
+        let mut document = Document::new();
+        let mut table = String::new();
+        document.insert(table.clone(), BTreeMap::new());
+
+        loop {
+            self.skip_whitespace();
+            let Some(c) = self.peek() else {
+                return Ok(document);
+            };
+
+            if c == '[' {
+                self.bump();
+                self.skip_blanks();
+                table = self.key()?;
+                // Dotted headers are kept as one name, e.g. "server.tls".
+                while self.eat('.') {
+                    table = format!("{}.{}", table, self.key()?);
+                }
+                self.skip_blanks();
+                if !self.eat(']') {
+                    return Err("expected ']' after table name".to_string());
+                }
+                if document.contains_key(&table) {
+                    return Err(format!("table [{}] is defined twice", table));
+                }
+                document.insert(table.clone(), BTreeMap::new());
+            } else {
+                let line = self.line;
+                let key = self.key()?;
+                self.skip_blanks();
+                if !self.eat('=') {
+                    return Err(format!("expected '=' after '{}'", key));
+                }
+                self.skip_blanks();
+                let value = self.value()?;
+                let entries = document.get_mut(&table).expect("current table exists");
+                if entries.insert(key.clone(), Entry { value, line }).is_some() {
+                    return Err(format!("'{}' is defined twice", key));
+                }
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    fn tree(&mut self) -> Result<BTreeMap<String, Value>, String> {
+        let mut root = BTreeMap::new();
+        // The path of the current table; an array along it means its last table.
+        let mut path: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            let Some(c) = self.peek() else {
+                return Ok(root);
+            };
+
+            if c == '[' {
+                self.bump();
+                let array = self.eat('[');
+                self.skip_blanks();
+                path = vec![self.key()?];
+                while self.eat('.') {
+                    path.push(self.key()?);
+                }
+                self.skip_blanks();
+                if !self.eat(']') || (array && !self.eat(']')) {
+                    return Err(format!(
+                        "expected '{}' after table name",
+                        if array { "]]" } else { "]" }
+                    ));
+                }
+
+                let (name, parents) = path.split_last().expect("a header has a name");
+                let parent = table_at(&mut root, parents)?;
+                match (parent.get_mut(name), array) {
+                    (None, false) => {
+                        parent.insert(name.clone(), Value::Table(BTreeMap::new()));
+                    }
+                    (None, true) => {
+                        let tables = vec![Value::Table(BTreeMap::new())];
+                        parent.insert(name.clone(), Value::Array(tables));
+                    }
+                    (Some(Value::Array(tables)), true) => {
+                        tables.push(Value::Table(BTreeMap::new()));
+                    }
+                    _ => return Err(format!("'{}' is defined twice", path.join("."))),
+                }
+            } else {
+                let key = self.key()?;
+                self.skip_blanks();
+                if !self.eat('=') {
+                    return Err(format!("expected '=' after '{}'", key));
+                }
+                self.skip_blanks();
+                let value = self.value()?;
+                let table = table_at(&mut root, &path)?;
+                if table.insert(key.clone(), value).is_some() {
+                    return Err(format!("'{}' is defined twice", key));
+                }
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        if self.peek() == Some('"') {
+            return self.basic_string();
+        }
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                key.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        if key.is_empty() {
+            return Err(match self.peek() {
+                Some(c) => format!("expected a key, found '{}'", c),
+                None => "expected a key".to_string(),
+            });
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(c) if c.is_ascii_alphanumeric() || c == '-' || c == '+' => self.bare_value(),
+            Some(c) => Err(format!("expected a value, found '{}'", c)),
+            None => Err("expected a value".to_string()),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut text = String::new();
+        loop {
+            // Checked before consuming so the error points at the string's own line.
+            if matches!(self.peek(), None | Some('\n')) {
+                return Err("unterminated string".to_string());
+            }
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('u') => self.unicode_escape()?,
+                        Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+                        None => return Err("unterminated string".to_string()),
+                    };
+                    text.push(escaped);
+                }
+                Some(c) => text.push(c),
+                None => unreachable!("checked above"),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let digits: String = (0..4).filter_map(|_| self.bump()).collect();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid unicode escape '\\u{}'", digits))
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut text = String::new();
+        loop {
+            if matches!(self.peek(), None | Some('\n')) {
+                return Err("unterminated string".to_string());
+            }
+            match self.bump() {
+                Some('\'') => return Ok(text),
+                Some(c) => text.push(c),
+                None => unreachable!("checked above"),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_whitespace();
+            // A trailing comma before ']' is fine.
+            if !self.eat(',') {
+                self.skip_whitespace();
+                if self.eat(']') {
+                    return Ok(Value::Array(items));
+                }
+                return Err("expected ',' or ']' in array".to_string());
+            }
+        }
+    }
+
+    // `{ key = value, ... }`, all on one line.
+    fn inline_table(&mut self) -> Result<Value, String> {
+        self.bump();
+        let mut entries = BTreeMap::new();
+        self.skip_blanks();
+        if self.eat('}') {
+            return Ok(Value::Table(entries));
+        }
+        loop {
+            self.skip_blanks();
+            let key = self.key()?;
+            self.skip_blanks();
+            if !self.eat('=') {
+                return Err(format!("expected '=' after '{}'", key));
+            }
+            self.skip_blanks();
+            let value = self.value()?;
+            if entries.insert(key.clone(), value).is_some() {
+                return Err(format!("'{}' is defined twice", key));
+            }
+            self.skip_blanks();
+            if self.eat('}') {
+                return Ok(Value::Table(entries));
+            }
+            if !self.eat(',') {
+                return Err("expected ',' or '}' in inline table".to_string());
+            }
+        }
+    }
+
+    // Booleans and integers (with optional sign and `_` separators).
+    fn bare_value(&mut self) -> Result<Value, String> {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.' | ':') {
+                word.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        match word.as_str() {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            _ => word
+                .replace('_', "")
+                .parse::<i64>()
+                .map(Value::Integer)
+                .map_err(|_| format!("unsupported value '{}'", word)),
+        }
+    }
+}
+
+// The table at `path` under `root`, made on the way if need be.
+fn table_at<'a>(
+    root: &'a mut BTreeMap<String, Value>,
+    path: &[String],
+) -> Result<&'a mut BTreeMap<String, Value>, String> {
+    let mut table = root;
+    for (depth, name) in path.iter().enumerate() {
+        let next = table
+            .entry(name.clone())
+            .or_insert_with(|| Value::Table(BTreeMap::new()));
+        let next = match next {
+            Value::Array(items) => items.last_mut(),
+            other => Some(other),
+        };
+        table = match next {
+            Some(Value::Table(entries)) => entries,
+            _ => return Err(format!("'{}' is not a table", path[..=depth].join("."))),
+        };
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(document: &Document, table: &str, key: &str) -> Value {
+        document[table][key].value.clone()
+    }
+
+    #[test]
+    fn parses_tables_and_scalar_values() {
+        let document = parse(
+            "# comment\n\
+             name = 'top level'\n\
+             [server]\n\
+             address = \"127.0.0.1\" # trailing comment\n\
+             port = 8_080\n\
+             [features]\n\
+             regex_search = false\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            value(&document, "", "name"),
+            Value::String("top level".into())
+        );
+        assert_eq!(
+            value(&document, "server", "address"),
+            Value::String("127.0.0.1".into())
+        );
+        assert_eq!(value(&document, "server", "port"), Value::Integer(8080));
+        assert_eq!(
+            value(&document, "features", "regex_search"),
+            Value::Boolean(false)
+        );
+        assert_eq!(document["server"]["port"].line, 5);
+    }
+
+    #[test]
+    fn parses_multi_line_arrays_and_escapes() {
+        let document = parse(
+            "origins = [\n  \"https://a.example\", # first\n  'https://b.example',\n]\n\
+             quote = \"say \\\"hi\\\" \\u00e9\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            value(&document, "", "origins"),
+            Value::Array(vec![
+                Value::String("https://a.example".into()),
+                Value::String("https://b.example".into()),
+            ])
+        );
+        assert_eq!(
+            value(&document, "", "quote"),
+            Value::String("say \"hi\" é".into())
+        );
+    }
+
+    #[test]
+    fn nests_arrays_of_tables_and_inline_tables() {
+        let tree = parse_tree(
+            "schema_version = 2\n\
+             [[festivals]]\n\
+             name = \"Pinkpop\"\n\
+             [[festivals.years]]\n\
+             year = 2008\n\
+             artists = [{ name = \"Muse\" }, { name = 'Editors' }]\n\
+             [[festivals.years]]\n\
+             year = 2009\n\
+             artists = []\n\
+             [[festivals]]\n\
+             name = \"Lowlands\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            tree.into_json(),
+            serde_json::json!({
+                "schema_version": 2,
+                "festivals": [
+                    { "name": "Pinkpop", "years": [
+                        { "year": 2008, "artists": [{ "name": "Muse" }, { "name": "Editors" }] },
+                        { "year": 2009, "artists": [] },
+                    ] },
+                    { "name": "Lowlands" },
+                ],
+            })
+        );
+        assert_eq!(
+            parse_tree("[[festivals]]\nname = 'a'\n[festivals]\n").unwrap_err(),
+            "line 3: 'festivals' is defined twice"
+        );
+        assert_eq!(
+            parse_tree("name = 'a'\n[[name.years]]\n").unwrap_err(),
+            "line 2: 'name' is not a table"
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        assert_eq!(
+            parse("[server]\nport = 3000\nport = 3001\n").unwrap_err(),
+            "line 3: 'port' is defined twice"
+        );
+        assert_eq!(
+            parse("a = \"open\n").unwrap_err(),
+            "line 1: unterminated string"
+        );
+        assert_eq!(
+            parse("a = 1 2\n").unwrap_err(),
+            "line 1: unexpected '2' after value"
+        );
+        assert_eq!(
+            parse("a = 1.5\n").unwrap_err(),
+            "line 1: unsupported value '1.5'"
+        );
+        assert_eq!(
+            parse("a = { b = 1 c = 2 }\n").unwrap_err(),
+            "line 1: expected ',' or '}' in inline table"
+        );
+    }
+}
@@ -0,0 +1,714 @@
+// Checks on a freshly read dataset for records that are almost certainly
+// mistakes: festivals, artists, genres or stages without a name, implausible
+// years, dates, days and set times, country codes and source URLs that aren't,
+// an artist listed twice in one edition, an edition listed twice. In lenient mode those
+// records are left out and reported as warnings; in strict mode any of them
+// rejects the whole dataset, so a bad edit never gets served.
+//
+// Stray whitespace in names ("De Staat ", "Kaiser  Chiefs") is harmless enough
+// to fix on the spot; the fixes are kept so the source can be cleaned up too.
+// Names are also put in Unicode NFC, which changes how they're encoded but not
+// how they look, so that isn't reported.
+
+use crate::data::{Act, BandData, Festival, FestivalYear, Source};
+use crate::nfc::nfc;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Earlier than any edition in the data could really be.
+const MIN_YEAR: u16 = 1900;
+// Editions get announced a year or two ahead, not decades.
+const YEARS_AHEAD: u16 = 5;
+// Past this many, warnings are counted instead of listed.
+const MAX_LISTED: usize = 20;
+const DAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// What to do with a dataset that has problems.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    // Leave the bad records out and serve the rest.
+    Lenient,
+    // Refuse the dataset: at startup the server exits, on reload the old data stays.
+    Strict,
+}
+
+/// One record that failed a check or was tidied, and where to find it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    // The file or URL the record came from.
+    pub origin: String,
+    // The festival, usually with the edition's year, e.g. "Pinkpop 2019".
+    pub context: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.origin, self.context, self.message)
+    }
+}
+
+/// Everything checking a dataset found.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    // Invalid records, which were left out.
+    pub skipped: Vec<Problem>,
+    // Names whose whitespace was fixed.
+    pub tidied: Vec<Problem>,
+}
+
+/// Normalizes the names in the data read from `origin` to NFC, trims them and
+/// collapses runs of whitespace inside them, returning the whitespace fixes.
+/// Names that are nothing but whitespace are left for `check` to reject.
+pub fn tidy(origin: &str, data: &mut BandData) -> Vec<Problem> {
+    let mut tidied = Vec::new();
+    let mut fix = |name: &mut String, context: String, what: String| {
+        if let Cow::Owned(composed) = nfc(name) {
+            *name = composed;
+        }
+        let clean = collapse_whitespace(name);
+        if !clean.is_empty() && clean != *name {
+            tidied.push(Problem {
+                origin: origin.to_string(),
+                context,
+                message: format!("{} \"{}\" was read as \"{}\"", what, name, clean),
+            });
+            *name = clean;
+        }
+    };
+
+    for (index, festival) in data.festivals.iter_mut().enumerate() {
+        fix(
+            &mut festival.name,
+            format!("festival {}", index + 1),
+            "the name".to_string(),
+        );
+        let place = festival
+            .location
+            .as_mut()
+            .map(|location| (location, "the location"))
+            .into_iter()
+            .chain(
+                festival
+                    .country
+                    .as_mut()
+                    .map(|country| (country, "the country")),
+            )
+            .chain(source_fields(&mut festival.source));
+        for (value, what) in place {
+            fix(value, festival.name.clone(), what.to_string());
+        }
+        for edition in &mut festival.years {
+            let context = format!("{} {}", festival.name, edition.year);
+            let dates = edition
+                .start_date
+                .as_mut()
+                .map(|date| (date, "the start date"))
+                .into_iter()
+                .chain(edition.end_date.as_mut().map(|date| (date, "the end date")))
+                .chain(source_fields(&mut edition.source));
+            for (value, what) in dates {
+                fix(value, context.clone(), what.to_string());
+            }
+            for (position, act) in edition.artists.iter_mut().enumerate() {
+                fix(
+                    &mut act.name,
+                    context.clone(),
+                    format!("artist {}:", position + 1),
+                );
+                let details = act
+                    .genres
+                    .iter_mut()
+                    .map(|genre| (genre, "the genre"))
+                    .chain(act.country.as_mut().map(|country| (country, "the country")))
+                    .chain(act.stage.as_mut().map(|stage| (stage, "the stage")))
+                    .chain(act.day.as_mut().map(|day| (day, "the day")))
+                    .chain(act.start_time.as_mut().map(|time| (time, "the start time")));
+                for (value, what) in details {
+                    fix(
+                        value,
+                        context.clone(),
+                        format!("artist {}: {}", position + 1, what),
+                    );
+                }
+            }
+        }
+    }
+    tidied
+}
+
+// The parts of a source to tidy.
+fn source_fields(source: &mut Option<Source>) -> impl Iterator<Item = (&mut String, &'static str)> {
+    source.iter_mut().flat_map(|source| {
+        let url = source.url.as_mut().map(|url| (url, "the source URL"));
+        let retrieved = source
+            .retrieved
+            .as_mut()
+            .map(|day| (day, "the retrieval date"));
+        url.into_iter().chain(retrieved)
+    })
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Checks the data read from `origin`, removes every record with a problem and
+/// returns the problems. Duplicate editions are found when files are merged.
+pub fn check(origin: &str, data: &mut BandData) -> Vec<Problem> {
+    let max_year = current_year() + YEARS_AHEAD;
+    let mut problems = Vec::new();
+    let mut report = |context: String, message: String| {
+        problems.push(Problem {
+            origin: origin.to_string(),
+            context,
+            message,
+        })
+    };
+
+    let mut festival_number = 0;
+    data.festivals.retain_mut(|festival| {
+        festival_number += 1;
+        if festival.name.trim().is_empty() {
+            report(
+                format!("festival {}", festival_number),
+                blank(&festival.name, "the name"),
+            );
+            return false;
+        }
+        for message in check_place(festival)
+            .into_iter()
+            .chain(check_source(&mut festival.source))
+        {
+            report(festival.name.clone(), message);
+        }
+
+        festival.years.retain_mut(|edition| {
+            let context = format!("{} {}", festival.name, edition.year);
+            if !(MIN_YEAR..=max_year).contains(&edition.year) {
+                report(
+                    context,
+                    format!("the year is outside {}-{}", MIN_YEAR, max_year),
+                );
+                return false;
+            }
+            for message in check_edition(edition)
+                .into_iter()
+                .chain(check_source(&mut edition.source))
+            {
+                report(context.clone(), message);
+            }
+
+            // Positions count from 1 and include removed artists, so they match the file.
+            let mut first_seen: HashMap<String, usize> = HashMap::new();
+            let mut position = 0;
+            edition.artists.retain_mut(|act| {
+                let artist = &act.name;
+                position += 1;
+                if artist.trim().is_empty() {
+                    report(
+                        context.clone(),
+                        format!("artist {}: {}", position, blank(artist, "the name")),
+                    );
+                    return false;
+                }
+                // A blank genre only loses the genre; a repeated one is simply dropped.
+                let mut genres_seen: Vec<String> = Vec::new();
+                act.genres.retain(|genre| {
+                    if genre.trim().is_empty() {
+                        report(
+                            context.clone(),
+                            format!("artist {}: {}", position, blank(genre, "a genre")),
+                        );
+                        return false;
+                    }
+                    let key = genre.to_lowercase();
+                    let first = !genres_seen.contains(&key);
+                    genres_seen.push(key);
+                    first
+                });
+                for message in check_set(act) {
+                    report(context.clone(), format!("artist {}: {}", position, message));
+                }
+                let artist = &act.name;
+                match first_seen.entry(collapse_whitespace(artist).to_lowercase()) {
+                    Entry::Occupied(first) => {
+                        report(
+                            context.clone(),
+                            format!(
+                                "artist {}: \"{}\" is already listed as artist {}",
+                                position,
+                                artist,
+                                first.get()
+                            ),
+                        );
+                        false
+                    }
+                    Entry::Vacant(slot) => {
+                        slot.insert(position);
+                        true
+                    }
+                }
+            });
+            true
+        });
+        true
+    });
+
+    problems
+}
+
+// Clears the location or country of `festival` where it can't be right, saying
+// why. Country codes are kept in upper case.
+fn check_place(festival: &mut Festival) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(location) = festival
+        .location
+        .take_if(|location| location.trim().is_empty())
+    {
+        problems.push(blank(&location, "the location"));
+    }
+    if let Some(country) = festival.country.take_if(|country| !is_country(country)) {
+        problems.push(format!(
+            "the country \"{}\" isn't a two-letter code like NL",
+            country
+        ));
+    }
+    if let Some(country) = &mut festival.country {
+        country.make_ascii_uppercase();
+    }
+    problems
+}
+
+/// Whether `text` looks like an ISO 3166 country code: two letters, in any case.
+pub fn is_country(text: &str) -> bool {
+    text.len() == 2 && text.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+// Clears the dates and note of `edition` where they can't be right, saying why:
+// dates that aren't real, a start date in another year, an end before the start,
+// a blank note.
+fn check_edition(edition: &mut FestivalYear) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(note) = edition.note.take_if(|note| note.trim().is_empty()) {
+        problems.push(blank(&note, "the note"));
+    }
+    for (date, what) in [
+        (&mut edition.start_date, "start date"),
+        (&mut edition.end_date, "end date"),
+    ] {
+        if let Some(text) = date.take_if(|text| parse_date(text).is_none()) {
+            problems.push(format!(
+                "the {} \"{}\" isn't a date like 2008-05-31",
+                what, text
+            ));
+        }
+    }
+    let year = edition.year;
+    if let Some(start) = edition
+        .start_date
+        .take_if(|start| parse_date(start).is_some_and(|(y, _, _)| y != year))
+    {
+        problems.push(format!("the start date {} isn't in {}", start, year));
+    }
+    if let (Some(start), Some(end)) = (&edition.start_date, &edition.end_date) {
+        if end < start {
+            problems.push(format!(
+                "the end date {} is before the start date {}",
+                end, start
+            ));
+            edition.end_date = None;
+        }
+    }
+    problems
+}
+
+// Clears the parts of `source` that can't be right, saying why, and the whole
+// source once nothing is left of it.
+fn check_source(source: &mut Option<Source>) -> Vec<String> {
+    let mut problems = Vec::new();
+    let Some(inner) = source else {
+        return problems;
+    };
+    if let Some(url) = inner
+        .url
+        .take_if(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+    {
+        problems.push(format!(
+            "the source URL \"{}\" isn't an http:// or https:// address",
+            url
+        ));
+    }
+    if let Some(day) = inner.retrieved.take_if(|day| parse_date(day).is_none()) {
+        problems.push(format!(
+            "the retrieval date \"{}\" isn't a date like 2008-05-31",
+            day
+        ));
+    }
+    if *inner == Source::default() {
+        *source = None;
+    }
+    problems
+}
+
+/// The year, month and day of a "YYYY-MM-DD" date, if it is one.
+pub fn parse_date(text: &str) -> Option<(u16, u8, u8)> {
+    let mut parts = text.split('-');
+    let mut number = |digits: usize| {
+        let part = parts
+            .next()
+            .filter(|part| part.len() == digits && part.bytes().all(|b| b.is_ascii_digit()))?;
+        part.parse::<u16>().ok()
+    };
+    let (year, month, day) = (number(4)?, number(2)?, number(2)?);
+    if parts.next().is_some() {
+        return None;
+    }
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    (1..=days)
+        .contains(&day)
+        .then_some((year, month as u8, day as u8))
+}
+
+// Clears the country, stage, day or start time of `act` where it can't be
+// right, saying why; the performance itself is kept.
+fn check_set(act: &mut Act) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(country) = act.country.take_if(|country| !is_country(country)) {
+        problems.push(format!(
+            "the country \"{}\" isn't a two-letter code like NL",
+            country
+        ));
+    }
+    if let Some(country) = &mut act.country {
+        country.make_ascii_uppercase();
+    }
+    if let Some(stage) = act.stage.take_if(|stage| stage.trim().is_empty()) {
+        problems.push(blank(&stage, "the stage"));
+    }
+    if let Some(day) = act
+        .day
+        .take_if(|day| !DAYS.contains(&day.to_lowercase().as_str()))
+    {
+        problems.push(format!("the day \"{}\" isn't a day of the week", day));
+    }
+    if let Some(time) = act.start_time.take_if(|time| !is_time(time)) {
+        problems.push(format!(
+            "the start time \"{}\" isn't a time like 21:30",
+            time
+        ));
+    }
+    problems
+}
+
+// A 24-hour "H:MM" or "HH:MM" time.
+fn is_time(text: &str) -> bool {
+    let Some((hours, minutes)) = text.split_once(':') else {
+        return false;
+    };
+    let number = |digits: &str, max: u8| {
+        digits.bytes().all(|b| b.is_ascii_digit())
+            && digits.parse::<u8>().is_ok_and(|value| value <= max)
+    };
+    (1..=2).contains(&hours.len()) && minutes.len() == 2 && number(hours, 23) && number(minutes, 59)
+}
+
+fn blank(text: &str, what: &str) -> String {
+    if text.is_empty() {
+        format!("{} is empty", what)
+    } else {
+        format!("{} is only whitespace", what)
+    }
+}
+
+// Close enough for a plausibility check: at most a day off around New Year.
+fn current_year() -> u16 {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    (1970 + seconds / 31_556_952) as u16
+}
+
+/// Logs the records a lenient load left out, and how many names it tidied.
+pub fn log(report: &Report) {
+    if !report.tidied.is_empty() {
+        tracing::info!(
+            "->> Tidied the whitespace in {} name{} (see /api/admin/quality-report).",
+            report.tidied.len(),
+            if report.tidied.len() == 1 { "" } else { "s" }
+        );
+    }
+    let problems = &report.skipped;
+    if problems.is_empty() {
+        return;
+    }
+    tracing::warn!(
+        "->> Skipped {} invalid record{}:",
+        problems.len(),
+        if problems.len() == 1 { "" } else { "s" }
+    );
+    for problem in problems.iter().take(MAX_LISTED) {
+        tracing::warn!("->>   {}", problem);
+    }
+    if problems.len() > MAX_LISTED {
+        tracing::warn!("->>   ...and {} more", problems.len() - MAX_LISTED);
+    }
+}
+
+/// The error a strict load fails with.
+pub fn rejection(problems: &[Problem]) -> String {
+    let mut message = format!(
+        "the data has {} invalid record{} (validation is strict):",
+        problems.len(),
+        if problems.len() == 1 { "" } else { "s" }
+    );
+    for problem in problems.iter().take(MAX_LISTED) {
+        message.push_str(&format!("\n  {}", problem));
+    }
+    if problems.len() > MAX_LISTED {
+        message.push_str(&format!("\n  ...and {} more", problems.len() - MAX_LISTED));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Act, Festival, FestivalYear};
+    use crate::schema;
+
+    fn festival(name: &str, year: u16, artists: &[&str]) -> Festival {
+        Festival {
+            name: name.to_string(),
+            years: vec![FestivalYear {
+                year,
+                artists: artists.iter().map(|a| Act::named(a.to_string())).collect(),
+                ..FestivalYear::default()
+            }],
+            ..Festival::default()
+        }
+    }
+
+    fn names(artists: &[Act]) -> Vec<&str> {
+        artists.iter().map(|act| act.name.as_str()).collect()
+    }
+
+    #[test]
+    fn removes_invalid_records_and_says_where_they_were() {
+        let mut data = BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![
+                festival(
+                    "Lowlands",
+                    2011,
+                    &["Good Charlotte", "", "Muse", "good charlotte "],
+                ),
+                festival(" ", 2012, &["Muse"]),
+                festival("Pinkpop", 1019, &["Muse"]),
+                festival("Pinkpop", 2019, &["Muse", "  "]),
+            ],
+        };
+        let muse = &mut data.festivals[3].years[0].artists[0];
+        muse.stage = Some("Main".to_string());
+        muse.country = Some("United Kingdom".to_string());
+        muse.day = Some("Zaterdag".to_string());
+        muse.start_time = Some("24:00".to_string());
+        data.festivals[3].years[0].artists[0].genres = ["rock", "", "Rock", "alternative"]
+            .map(String::from)
+            .to_vec();
+
+        let problems: Vec<String> = check("bands.json", &mut data)
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "bands.json: Lowlands 2011: artist 2: the name is empty",
+                "bands.json: Lowlands 2011: artist 4: \"good charlotte \" is already listed as artist 1",
+                "bands.json: festival 2: the name is only whitespace",
+                &format!(
+                    "bands.json: Pinkpop 1019: the year is outside 1900-{}",
+                    current_year() + YEARS_AHEAD
+                ),
+                "bands.json: Pinkpop 2019: artist 1: a genre is empty",
+                "bands.json: Pinkpop 2019: artist 1: the country \"United Kingdom\" isn't a two-letter code like NL",
+                "bands.json: Pinkpop 2019: artist 1: the day \"Zaterdag\" isn't a day of the week",
+                "bands.json: Pinkpop 2019: artist 1: the start time \"24:00\" isn't a time like 21:30",
+                "bands.json: Pinkpop 2019: artist 2: the name is only whitespace",
+            ]
+        );
+        let muse = &data.festivals[2].years[0].artists[0];
+        assert_eq!(muse.genres, ["rock", "alternative"]);
+        assert_eq!(
+            (
+                muse.stage.as_deref(),
+                muse.day.as_deref(),
+                muse.start_time.as_deref()
+            ),
+            (Some("Main"), None, None)
+        );
+
+        let kept: Vec<(&str, u16, Vec<&str>)> = data
+            .festivals
+            .iter()
+            .flat_map(|f| {
+                f.years
+                    .iter()
+                    .map(|y| (f.name.as_str(), y.year, names(&y.artists)))
+            })
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("Lowlands", 2011, vec!["Good Charlotte", "Muse"]),
+                ("Pinkpop", 2019, vec!["Muse"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn clears_festival_details_that_cant_be_right() {
+        let mut pinkpop = festival("Pinkpop", 2008, &["Muse"]);
+        pinkpop.location = Some(" ".to_string());
+        pinkpop.country = Some("Nederland".to_string());
+        let mut lowlands = festival("Lowlands", 2009, &["Editors"]);
+        lowlands.country = Some("nl".to_string());
+        lowlands
+            .years
+            .extend([2010, 2011, 2012].map(|year| FestivalYear {
+                year,
+                ..FestivalYear::default()
+            }));
+        let dates = [
+            ("2009-08-21", "2009-08-23"),
+            ("2010-02-30", "2010-08-22"),
+            ("2012-08-20", "2011-08-22"),
+            ("2012-08-17", "2012-08-16"),
+        ];
+        for (edition, (start, end)) in lowlands.years.iter_mut().zip(dates) {
+            edition.start_date = Some(start.to_string());
+            edition.end_date = Some(end.to_string());
+        }
+        lowlands.years[0].note = Some(" ".to_string());
+        lowlands.source = Some(Source {
+            url: Some("www.lowlands.nl".to_string()),
+            retrieved: None,
+        });
+        lowlands.years[1].source = Some(Source {
+            url: Some("https://lowlands.nl/2010".to_string()),
+            retrieved: Some("yesterday".to_string()),
+        });
+        let mut data = BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![pinkpop, lowlands],
+        };
+
+        let problems: Vec<String> = check("bands.json", &mut data)
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "bands.json: Pinkpop: the location is only whitespace",
+                "bands.json: Pinkpop: the country \"Nederland\" isn't a two-letter code like NL",
+                "bands.json: Lowlands: the source URL \"www.lowlands.nl\" isn't an http:// or https:// address",
+                "bands.json: Lowlands 2009: the note is only whitespace",
+                "bands.json: Lowlands 2010: the start date \"2010-02-30\" isn't a date like 2008-05-31",
+                "bands.json: Lowlands 2010: the retrieval date \"yesterday\" isn't a date like 2008-05-31",
+                "bands.json: Lowlands 2011: the start date 2012-08-20 isn't in 2011",
+                "bands.json: Lowlands 2012: the end date 2012-08-16 is before the start date 2012-08-17",
+            ]
+        );
+        let [pinkpop, lowlands] = &data.festivals[..] else {
+            panic!("expected both festivals");
+        };
+        assert_eq!((&pinkpop.location, &pinkpop.country), (&None, &None));
+        assert_eq!(lowlands.country.as_deref(), Some("NL"));
+        assert_eq!(lowlands.source, None);
+        assert_eq!(
+            lowlands.years[1]
+                .source
+                .as_ref()
+                .and_then(|s| s.url.as_deref()),
+            Some("https://lowlands.nl/2010")
+        );
+        let kept: Vec<(Option<&str>, Option<&str>)> = lowlands
+            .years
+            .iter()
+            .map(|y| (y.start_date.as_deref(), y.end_date.as_deref()))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                (Some("2009-08-21"), Some("2009-08-23")),
+                (None, Some("2010-08-22")),
+                (None, Some("2011-08-22")),
+                (Some("2012-08-17"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn tidies_whitespace_and_composes_accents() {
+        let mut data = BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![festival(
+                "Pinkpop ",
+                2008,
+                &["De Staat ", "Sigur Ro\u{301}s", "Kaiser  Chiefs"],
+            )],
+        };
+
+        let tidied: Vec<String> = tidy("bands.json", &mut data)
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        assert_eq!(
+            tidied,
+            [
+                "bands.json: festival 1: the name \"Pinkpop \" was read as \"Pinkpop\"",
+                "bands.json: Pinkpop 2008: artist 1: \"De Staat \" was read as \"De Staat\"",
+                "bands.json: Pinkpop 2008: artist 3: \"Kaiser  Chiefs\" was read as \"Kaiser Chiefs\"",
+            ]
+        );
+        assert_eq!(
+            names(&data.festivals[0].years[0].artists),
+            ["De Staat", "Sigur R\u{f3}s", "Kaiser Chiefs"]
+        );
+    }
+
+    #[test]
+    fn valid_data_has_no_problems() {
+        let mut data = BandData {
+            schema_version: schema::CURRENT,
+            festivals: vec![festival(
+                "Pinkpop",
+                1970,
+                &["Golden Earring", "Fleetwood Mac"],
+            )],
+        };
+        assert!(check("bands.json", &mut data).is_empty());
+        assert_eq!(data.festivals[0].years[0].artists.len(), 2);
+    }
+}
@@ -0,0 +1,543 @@
+// Just enough YAML for data files kept by hand: block mappings and sequences
+// nested by indentation (including `- key: value` items), plain, 'single' and
+// "double" quoted scalars, one-line flow collections like `[Muse, Editors]` or
+// `{ name: Muse }`, `#` comments and a leading `---`. Plain scalars are typed
+// the YAML 1.2 way: integers, true/false and null/~ are those, anything else is
+// a string. Anchors, tags, block scalars (`|`, `>`), plain scalars continued on
+// the next line and multiple documents aren't supported and are reported as
+// errors.
+
+use serde_json::{Map, Value};
+
+/// Parses `text` into the value it describes, or describes the first problem
+/// as "line N: ...".
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        lines: lines(text)?,
+        next: 0,
+    };
+    if parser.lines.is_empty() {
+        return Ok(Value::Null);
+    }
+    let value = parser.block()?;
+    match parser.lines.get(parser.next) {
+        Some(line) => Err(at(line.number, "unexpected indentation")),
+        None => Ok(value),
+    }
+}
+
+fn at(number: usize, message: impl std::fmt::Display) -> String {
+    format!("line {}: {}", number, message)
+}
+
+// A line with content, without its indentation and comment.
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+fn lines(text: &str) -> Result<Vec<Line>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let number = index + 1;
+        let content = raw.trim_start_matches(' ');
+        if content.starts_with('\t') {
+            return Err(at(number, "YAML is indented with spaces, not tabs"));
+        }
+        let content = strip_comment(content).trim_end();
+        match content {
+            "" => continue,
+            // Directives and the document start may only come before the content.
+            _ if lines.is_empty() && (content == "---" || content.starts_with('%')) => continue,
+            "---" => return Err(at(number, "only one document per file is supported")),
+            "..." => break,
+            _ => lines.push(Line {
+                number,
+                indent: raw.len() - raw.trim_start_matches(' ').len(),
+                text: content.to_string(),
+            }),
+        }
+    }
+    Ok(lines)
+}
+
+// Cuts a `#` comment off the line. A `#` only starts one after whitespace and
+// outside quotes, and a quote only opens a string where a value can start, so
+// "Guns N' Roses # comment" loses just the comment.
+fn strip_comment(text: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut previous: Option<char> = None;
+    let mut last_visible: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some('"') if c == '\\' => {
+                chars.next();
+            }
+            Some(open) if c == open => {
+                if open == '\'' && chars.peek().map(|&(_, next)| next) == Some('\'') {
+                    chars.next();
+                } else {
+                    quote = None;
+                }
+            }
+            Some(_) => {}
+            None if c == '#' && previous.is_none_or(|p| p == ' ' || p == '\t') => {
+                return &text[..i];
+            }
+            None if (c == '"' || c == '\'')
+                && last_visible.is_none_or(|p| matches!(p, ':' | '-' | '[' | '{' | ',')) =>
+            {
+                quote = Some(c);
+            }
+            None => {}
+        }
+        previous = Some(c);
+        if c != ' ' {
+            last_visible = Some(c);
+        }
+    }
+    text
+}
+
+fn is_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Line> {
+        self.lines.get(self.next)
+    }
+
+    // The node starting on the next line: a sequence, a mapping or a scalar.
+    fn block(&mut self) -> Result<Value, String> {
+        let line = &self.lines[self.next];
+        let (number, indent) = (line.number, line.indent);
+        if is_item(&line.text) {
+            return self.sequence(indent);
+        }
+        if split_key(&line.text).map_err(|e| at(number, e))?.is_some() {
+            return self.mapping(indent);
+        }
+        let value = scalar(&line.text).map_err(|e| at(number, e))?;
+        self.next += 1;
+        Ok(value)
+    }
+
+    // The node indented under a line that ended without a value, if any.
+    fn nested(&mut self, indent: usize) -> Result<Value, String> {
+        match self.peek() {
+            Some(line) if line.indent > indent => self.block(),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<Value, String> {
+        let mut items = Vec::new();
+        while let Some(line) = self.lines.get_mut(self.next) {
+            if line.indent != indent || !is_item(&line.text) {
+                break;
+            }
+            let rest = line.text[1..].trim_start().to_string();
+            if rest.is_empty() {
+                self.next += 1;
+                items.push(self.nested(indent)?);
+            } else {
+                // What follows the dash is read as if it started its own line
+                // there, so `- name: Muse` begins a mapping at that column.
+                line.indent += line.text.len() - rest.len();
+                line.text = rest;
+                items.push(self.block()?);
+            }
+        }
+        self.check_dedent(indent)?;
+        Ok(Value::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<Value, String> {
+        let mut entries = Map::new();
+        while let Some(line) = self.peek() {
+            if line.indent != indent {
+                break;
+            }
+            let number = line.number;
+            let Some((key, rest)) = split_key(&line.text).map_err(|e| at(number, e))? else {
+                return Err(at(number, "expected 'key: value'"));
+            };
+            self.next += 1;
+
+            let value = if rest.is_empty() {
+                match self.peek() {
+                    // A sequence may sit at the same indentation as its key.
+                    Some(next) if next.indent == indent && is_item(&next.text) => {
+                        self.sequence(indent)?
+                    }
+                    _ => self.nested(indent)?,
+                }
+            } else {
+                scalar(&rest).map_err(|e| at(number, e))?
+            };
+            if entries.insert(key.clone(), value).is_some() {
+                return Err(at(number, format!("'{}' is defined twice", key)));
+            }
+        }
+        self.check_dedent(indent)?;
+        Ok(Value::Object(entries))
+    }
+
+    // After a collection at `indent`, the next line can't be indented deeper:
+    // nothing would own it.
+    fn check_dedent(&self, indent: usize) -> Result<(), String> {
+        match self.peek() {
+            Some(line) if line.indent > indent => Err(at(line.number, "unexpected indentation")),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Splits `key: value` into the key and whatever follows, which is empty when
+// the value is on the lines below. None when the line isn't a key at all.
+fn split_key(text: &str) -> Result<Option<(String, String)>, String> {
+    let mut cursor = Cursor::new(text);
+    let key = match cursor.peek() {
+        Some('"') => cursor.double_quoted()?,
+        Some('\'') => cursor.single_quoted()?,
+        None | Some('[' | '{') => return Ok(None),
+        _ => {
+            let colon = text
+                .match_indices(':')
+                .map(|(i, _)| i)
+                .find(|&i| matches!(text[i + 1..].chars().next(), None | Some(' ')));
+            let Some(colon) = colon else {
+                return Ok(None);
+            };
+            cursor.position = colon;
+            text[..colon].trim_end().to_string()
+        }
+    };
+    cursor.skip_spaces();
+    if !cursor.eat(':') || !matches!(cursor.peek(), None | Some(' ')) {
+        return Ok(None);
+    }
+    Ok(Some((key, cursor.rest().trim().to_string())))
+}
+
+// A whole value on one line.
+fn scalar(text: &str) -> Result<Value, String> {
+    let mut cursor = Cursor::new(text);
+    let value = cursor.value(false)?;
+    cursor.skip_spaces();
+    match cursor.peek() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{}' after the value", c)),
+    }
+}
+
+// Plain scalars that aren't strings.
+fn typed(plain: &str) -> Value {
+    match plain {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" => Value::Bool(true),
+        "false" | "False" | "FALSE" => Value::Bool(false),
+        _ => {
+            let digits = plain.strip_prefix(['-', '+']).unwrap_or(plain);
+            match plain.parse::<i64>() {
+                Ok(number) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+                    Value::from(number)
+                }
+                _ => Value::String(plain.to_string()),
+            }
+        }
+    }
+}
+
+struct Cursor<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Self {
+        Cursor { text, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.eat(' ') {}
+    }
+
+    // A value; inside `[...]` or `{...}` (`flow`), plain ones end at `,`, `]`
+    // and `}` too.
+    fn value(&mut self, flow: bool) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.double_quoted().map(Value::String),
+            Some('\'') => self.single_quoted().map(Value::String),
+            Some('[') => self.flow_sequence(),
+            Some('{') => self.flow_mapping(),
+            Some(c @ ('|' | '>')) => Err(format!(
+                "block scalars ('{}') aren't supported; put the text in quotes",
+                c
+            )),
+            Some(c @ ('&' | '*' | '!' | '%' | '@' | '`')) => Err(format!(
+                "a value can't start with '{}' here; put it in quotes",
+                c
+            )),
+            _ => {
+                let plain = self.plain(flow);
+                if flow && plain.is_empty() {
+                    return Err(match self.peek() {
+                        Some(c) => format!("expected a value, found '{}'", c),
+                        None => "expected a value".to_string(),
+                    });
+                }
+                Ok(typed(plain))
+            }
+        }
+    }
+
+    fn plain(&mut self, flow: bool) -> &'a str {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            let after = self.rest()[c.len_utf8()..].chars().next();
+            let ends = flow
+                && (matches!(c, ',' | ']' | '}')
+                    || (c == ':' && matches!(after, None | Some(' ' | ',' | ']' | '}'))));
+            if ends {
+                break;
+            }
+            self.bump();
+        }
+        self.text[start..self.position].trim_end()
+    }
+
+    fn double_quoted(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('u') => self.unicode_escape()?,
+                        Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+                        None => return Err("unterminated string".to_string()),
+                    };
+                    text.push(escaped);
+                }
+                Some(c) => text.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let digits: String = (0..4).filter_map(|_| self.bump()).collect();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid unicode escape '\\u{}'", digits))
+    }
+
+    // Quotes are doubled inside: 'Guns N'' Roses'.
+    fn single_quoted(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('\'') if self.eat('\'') => text.push('\''),
+                Some('\'') => return Ok(text),
+                Some(c) => text.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn flow_sequence(&mut self) -> Result<Value, String> {
+        self.bump();
+        let mut items = Vec::new();
+        loop {
+            self.skip_spaces();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value(true)?);
+            self.skip_spaces();
+            // A trailing comma before ']' is fine.
+            if !self.eat(',') {
+                if self.eat(']') {
+                    return Ok(Value::Array(items));
+                }
+                return Err("expected ',' or ']' in a list".to_string());
+            }
+        }
+    }
+
+    fn flow_mapping(&mut self) -> Result<Value, String> {
+        self.bump();
+        let mut entries = Map::new();
+        loop {
+            self.skip_spaces();
+            if self.eat('}') {
+                return Ok(Value::Object(entries));
+            }
+            let key = match self.value(true)? {
+                Value::String(key) => key,
+                Value::Null => return Err("expected a key".to_string()),
+                other => other.to_string(),
+            };
+            self.skip_spaces();
+            if !self.eat(':') {
+                return Err(format!("expected ':' after '{}'", key));
+            }
+            self.skip_spaces();
+            let value = self.value(true)?;
+            if entries.insert(key.clone(), value).is_some() {
+                return Err(format!("'{}' is defined twice", key));
+            }
+            self.skip_spaces();
+            if !self.eat(',') {
+                if self.eat('}') {
+                    return Ok(Value::Object(entries));
+                }
+                return Err("expected ',' or '}' in a mapping".to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_nested_blocks() {
+        let text = "\
+---
+# Lineups, one festival at a time.
+schema_version: 2
+festivals:
+- name: Pinkpop   # the oldest one
+  years:
+    - year: 2008
+      artists:
+        - name: Muse
+        - name: 'Guns N'' Roses'
+    - year: 2009
+      artists: []
+- name: \"Rock Werchter\"
+  years:
+  - year: 2010
+    artists:
+    -
+      name: Guns N' Roses # a trailing comment
+";
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "schema_version": 2,
+                "festivals": [
+                    { "name": "Pinkpop", "years": [
+                        { "year": 2008, "artists": [{ "name": "Muse" }, { "name": "Guns N' Roses" }] },
+                        { "year": 2009, "artists": [] },
+                    ] },
+                    { "name": "Rock Werchter", "years": [
+                        { "year": 2010, "artists": [{ "name": "Guns N' Roses" }] },
+                    ] },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn parses_flow_collections_and_typed_scalars() {
+        let text = "\
+artists: [Muse, \"Crosby, Stills & Nash\", 'Editors', ]
+act: { name: Muse, stage: Main }
+plain: [2008, -3, true, ~, 1.5, 2008a, http://example.com]
+escaped: \"caf\\u00e9 \\\"live\\\"\"
+";
+        assert_eq!(
+            parse(text).unwrap(),
+            json!({
+                "artists": ["Muse", "Crosby, Stills & Nash", "Editors"],
+                "act": { "name": "Muse", "stage": "Main" },
+                "plain": [2008, -3, true, null, "1.5", "2008a", "http://example.com"],
+                "escaped": "café \"live\"",
+            })
+        );
+        assert_eq!(
+            parse("- !!!\n- x\n").unwrap_err(),
+            "line 1: a value can't start with '!' here; put it in quotes"
+        );
+        assert_eq!(parse("'!!!'").unwrap(), json!("!!!"));
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        assert_eq!(
+            parse("name: Pinkpop\nname: Lowlands\n").unwrap_err(),
+            "line 2: 'name' is defined twice"
+        );
+        assert_eq!(
+            parse("years:\n  - 2008\n    - 2009\n").unwrap_err(),
+            "line 3: unexpected indentation"
+        );
+        assert_eq!(
+            parse("a:\n\tb: 1\n").unwrap_err(),
+            "line 2: YAML is indented with spaces, not tabs"
+        );
+        assert_eq!(
+            parse("a: 1\nb: \"open\n").unwrap_err(),
+            "line 2: unterminated string"
+        );
+        assert_eq!(
+            parse("a: [1, 2\n").unwrap_err(),
+            "line 1: expected ',' or ']' in a list"
+        );
+        assert_eq!(
+            parse("a: 1\n- b\n").unwrap_err(),
+            "line 2: expected 'key: value'"
+        );
+        assert_eq!(
+            parse("a: |\n  text\n").unwrap_err(),
+            "line 1: block scalars ('|') aren't supported; put the text in quotes"
+        );
+    }
+}